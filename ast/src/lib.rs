@@ -6,6 +6,14 @@ use cppl_error::{
 pub mod raw;
 pub mod refined;
 mod static_analysis;
+mod std_lib;
+
+
+pub use static_analysis::expr_type;
+pub use std_lib::{
+    BuiltinValue,
+    call_builtin,
+};
 
 
 #[derive(Default)]
@@ -25,15 +33,69 @@ pub fn analyze<'input>(filename:&'input str,mut stmts:Vec<raw::Statement<'input>
             return Err(ret);
         },
     }
-    match static_analysis::refine(stmts,filename) {
-        Ok(re)=>{
-            return Ok((re,ret));
+    // Refinement errors don't stop name resolution so more errors can be reported at once.
+    let (mut refined,mut errors)=static_analysis::refine(stmts,filename);
+    ret.errors.append(&mut errors);
+    match static_analysis::resolve(filename,&mut refined) {
+        Ok(mut w)=>ret.warnings.append(&mut w),
+        Err([mut e,mut w])=>{
+            ret.warnings.append(&mut w);
+            ret.errors.append(&mut e);
+        },
+    }
+    if !ret.errors.is_empty() {
+        ret.errors.sort_by_key(|e|e.start.index);
+        return Err(ret);
+    }
+    match static_analysis::infer(filename,&mut refined) {
+        Ok(mut w)=>ret.warnings.append(&mut w),
+        Err([mut e,mut w])=>{
+            ret.warnings.append(&mut w);
+            ret.errors.append(&mut e);
+            return Err(ret);
+        },
+    }
+    match static_analysis::resolve_methods(filename,&mut refined) {
+        Ok(mut w)=>ret.warnings.append(&mut w),
+        Err([mut e,mut w])=>{
+            ret.warnings.append(&mut w);
+            ret.errors.append(&mut e);
+            return Err(ret);
+        },
+    }
+    match static_analysis::type_check(filename,&refined) {
+        Ok(mut w)=>ret.warnings.append(&mut w),
+        Err([mut e,mut w])=>{
+            ret.warnings.append(&mut w);
+            ret.errors.append(&mut e);
+            return Err(ret);
+        },
+    }
+    match static_analysis::check_matches(filename,&refined) {
+        Ok(mut w)=>ret.warnings.append(&mut w),
+        Err([mut e,mut w])=>{
+            ret.warnings.append(&mut w);
+            ret.errors.append(&mut e);
+            return Err(ret);
         },
-        Err(e)=>{
-            ret.errors.push(e);
+    }
+    match static_analysis::check_access(filename,&refined) {
+        Ok(mut w)=>ret.warnings.append(&mut w),
+        Err([mut e,mut w])=>{
+            ret.warnings.append(&mut w);
+            ret.errors.append(&mut e);
+            return Err(ret);
+        },
+    }
+    match static_analysis::check_borrows(filename,&refined) {
+        Ok(mut w)=>ret.warnings.append(&mut w),
+        Err([mut e,mut w])=>{
+            ret.warnings.append(&mut w);
+            ret.errors.append(&mut e);
             return Err(ret);
         },
     }
+    return Ok((refined,ret));
 }
 fn is_camel_case(s:&str)->bool {
     let mut chars=s.chars();
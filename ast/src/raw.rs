@@ -39,6 +39,36 @@ pub enum Type<'input> {
         end:Location,
         inner:Box<AnonFunctionSignature<'input>>,
     },
+    /// `[T]`
+    Slice {
+        start:Location,
+        end:Location,
+        inner:Box<Self>,
+    },
+    /// `&T`
+    Ref {
+        start:Location,
+        end:Location,
+        inner:Box<Self>,
+    },
+    /// `&mut T`
+    RefMut {
+        start:Location,
+        end:Location,
+        inner:Box<Self>,
+    },
+    /// `*T`
+    Pointer {
+        start:Location,
+        end:Location,
+        inner:Box<Self>,
+    },
+    /// `*mut T`
+    PointerMut {
+        start:Location,
+        end:Location,
+        inner:Box<Self>,
+    },
     Uint{start:Location,end:Location},
     Int{start:Location,end:Location},
     Float{start:Location,end:Location},
@@ -60,6 +90,11 @@ impl<'input> Type<'input> {
             Union{start,..}=>start,
             Composite{start,..}=>start,
             FunctionSig{start,..}=>start,
+            Slice{start,..}=>start,
+            Ref{start,..}=>start,
+            RefMut{start,..}=>start,
+            Pointer{start,..}=>start,
+            PointerMut{start,..}=>start,
             Uint{start,..}=>start,
             Int{start,..}=>start,
             Float{start,..}=>start,
@@ -81,6 +116,11 @@ impl<'input> Type<'input> {
             Union{end,..}=>end,
             Composite{end,..}=>end,
             FunctionSig{end,..}=>end,
+            Slice{end,..}=>end,
+            Ref{end,..}=>end,
+            RefMut{end,..}=>end,
+            Pointer{end,..}=>end,
+            PointerMut{end,..}=>end,
             Uint{end,..}=>end,
             Int{end,..}=>end,
             Float{end,..}=>end,
@@ -105,6 +145,8 @@ pub enum Statement<'input> {
     StaticVarDef{start:Location,end:Location,inner:StaticVarDef<'input>},
     ConstVarDef{start:Location,end:Location,inner:ConstVarDef<'input>},
     VarAssign{start:Location,end:Location,inner:VarAssign<'input>},
+    PlaceAssign{start:Location,end:Location,inner:PlaceAssign<'input>},
+    CompoundAssign{start:Location,end:Location,inner:CompoundAssign<'input>},
     Expr{start:Location,end:Location,inner:Expr<'input>},
     Import{start:Location,end:Location,inner:Import<'input>},
     Return {
@@ -114,6 +156,13 @@ pub enum Statement<'input> {
         val:Option<Expr<'input>>,
     },
     Continue{start:Location,end:Location,inner:Option<&'input str>},
+    /// Leaves the innermost loop, or the loop with `label`. `loop` evaluates to `val`.
+    Break {
+        start:Location,
+        end:Location,
+        label:Option<&'input str>,
+        val:Option<Expr<'input>>,
+    },
     Enum{start:Location,end:Location,inner:Enum<'input>},
     Module{start:Location,end:Location,inner:&'input str},
     Impl{start:Location,end:Location,inner:Impl<'input>},
@@ -130,10 +179,13 @@ impl<'input> Statement<'input> {
             StaticVarDef{start,..}=>start,
             ConstVarDef{start,..}=>start,
             VarAssign{start,..}=>start,
+            PlaceAssign{start,..}=>start,
+            CompoundAssign{start,..}=>start,
             Expr{start,..}=>start,
             Import{start,..}=>start,
             Return{start,..}=>start,
             Continue{start,..}=>start,
+            Break{start,..}=>start,
             Enum{start,..}=>start,
             Module{start,..}=>start,
             Impl{start,..}=>start,
@@ -150,10 +202,13 @@ impl<'input> Statement<'input> {
             StaticVarDef{end,..}=>end,
             ConstVarDef{end,..}=>end,
             VarAssign{end,..}=>end,
+            PlaceAssign{end,..}=>end,
+            CompoundAssign{end,..}=>end,
             Expr{end,..}=>end,
             Import{end,..}=>end,
             Return{end,..}=>end,
             Continue{end,..}=>end,
+            Break{end,..}=>end,
             Enum{end,..}=>end,
             Module{end,..}=>end,
             Impl{end,..}=>end,
@@ -190,6 +245,11 @@ pub enum Expr<'input> {
     Mul{start:Location,end:Location,inner:Box<[Self;2]>},
     Div{start:Location,end:Location,inner:Box<[Self;2]>},
     Mod{start:Location,end:Location,inner:Box<[Self;2]>},
+    BitAnd{start:Location,end:Location,inner:Box<[Self;2]>},
+    BitOr{start:Location,end:Location,inner:Box<[Self;2]>},
+    BitXor{start:Location,end:Location,inner:Box<[Self;2]>},
+    ShiftLeft{start:Location,end:Location,inner:Box<[Self;2]>},
+    ShiftRight{start:Location,end:Location,inner:Box<[Self;2]>},
     Negate{start:Location,end:Location,inner:Box<Self>},
     Equal{start:Location,end:Location,inner:Box<[Self;2]>},
     NotEqual{start:Location,end:Location,inner:Box<[Self;2]>},
@@ -205,21 +265,50 @@ pub enum Expr<'input> {
     AnonFunction{start:Location,end:Location,inner:AnonFunction<'input>},
     Ref{start:Location,end:Location,inner:Box<Self>},
     RefMut{start:Location,end:Location,inner:Box<Self>},
-    ForeverLoop{start:Location,end:Location,inner:Block<'input>},
+    /// `*inner`. Dereferencing a pointer is only allowed in `unsafe` blocks.
+    Deref{start:Location,end:Location,inner:Box<Self>},
+    Unsafe{start:Location,end:Location,inner:Block<'input>},
+    ForeverLoop{start:Location,end:Location,label:Option<&'input str>,inner:Block<'input>},
     WhileLoop {
         start:Location,
         end:Location,
+        label:Option<&'input str>,
         condition:Box<Self>,
         block:Block<'input>,
     },
     ForLoop {
         start:Location,
         end:Location,
+        label:Option<&'input str>,
         var:&'input str,
         iterator:Box<Self>,
         block:Block<'input>,
     },
     Match{start:Location,end:Location,inner:Box<Match<'input>>},
+    /// `[a, b, c]`
+    List{start:Location,end:Location,inner:Vec<Self>},
+    /// `from[index]`
+    Index {
+        start:Location,
+        end:Location,
+        from:Box<Self>,
+        index:Box<Self>,
+    },
+    /// `from[range_start..range_end]`. Both ends of the range can be left out.
+    Slice {
+        start:Location,
+        end:Location,
+        from:Box<Self>,
+        range_start:Option<Box<Self>>,
+        range_end:Option<Box<Self>>,
+    },
+    /// Each branch is a condition and the block to run if it is true. `else if` adds a branch.
+    If {
+        start:Location,
+        end:Location,
+        branches:Vec<(Self,Block<'input>)>,
+        else_block:Option<Block<'input>>,
+    },
 }
 impl<'input> Expr<'input> {
     pub fn start(&self)->Location {
@@ -237,6 +326,11 @@ impl<'input> Expr<'input> {
             Mul{start,..}=>start,
             Div{start,..}=>start,
             Mod{start,..}=>start,
+            BitAnd{start,..}=>start,
+            BitOr{start,..}=>start,
+            BitXor{start,..}=>start,
+            ShiftLeft{start,..}=>start,
+            ShiftRight{start,..}=>start,
             Negate{start,..}=>start,
             Equal{start,..}=>start,
             NotEqual{start,..}=>start,
@@ -252,10 +346,16 @@ impl<'input> Expr<'input> {
             AnonFunction{start,..}=>start,
             Ref{start,..}=>start,
             RefMut{start,..}=>start,
+            Deref{start,..}=>start,
+            Unsafe{start,..}=>start,
             ForeverLoop{start,..}=>start,
             WhileLoop{start,..}=>start,
             ForLoop{start,..}=>start,
             Match{start,..}=>start,
+            List{start,..}=>start,
+            Index{start,..}=>start,
+            Slice{start,..}=>start,
+            If{start,..}=>start,
         }
     }
     pub fn end(&self)->Location {
@@ -273,6 +373,11 @@ impl<'input> Expr<'input> {
             Mul{end,..}=>end,
             Div{end,..}=>end,
             Mod{end,..}=>end,
+            BitAnd{end,..}=>end,
+            BitOr{end,..}=>end,
+            BitXor{end,..}=>end,
+            ShiftLeft{end,..}=>end,
+            ShiftRight{end,..}=>end,
             Negate{end,..}=>end,
             Equal{end,..}=>end,
             NotEqual{end,..}=>end,
@@ -288,10 +393,16 @@ impl<'input> Expr<'input> {
             AnonFunction{end,..}=>end,
             Ref{end,..}=>end,
             RefMut{end,..}=>end,
+            Deref{end,..}=>end,
+            Unsafe{end,..}=>end,
             ForeverLoop{end,..}=>end,
             WhileLoop{end,..}=>end,
             ForLoop{end,..}=>end,
             Match{end,..}=>end,
+            List{end,..}=>end,
+            Index{end,..}=>end,
+            Slice{end,..}=>end,
+            If{end,..}=>end,
         }
     }
 }
@@ -559,6 +670,31 @@ pub struct VarAssign<'input> {
     pub name:&'input str,
     pub data:Expr<'input>,
 }
+/// Assignment to something other than a variable, like a field.
+#[derive(Debug)]
+pub struct PlaceAssign<'input> {
+    pub start:Location,
+    pub end:Location,
+    pub place:Expr<'input>,
+    pub data:Expr<'input>,
+}
+/// `place op<- data`, like `x+<-1`. `place` is a variable or a field.
+#[derive(Debug)]
+pub struct CompoundAssign<'input> {
+    pub start:Location,
+    pub end:Location,
+    pub op:AssignOp,
+    pub place:Expr<'input>,
+    pub data:Expr<'input>,
+}
+#[derive(Debug,Copy,Clone)]
+pub enum AssignOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
 #[derive(Debug)]
 pub struct ObjectField<'input> {
     pub start:Location,
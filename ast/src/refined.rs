@@ -6,16 +6,20 @@ use std::{
 use cppl_error::Location;
 
 
+/// How deep type aliases and nested types are followed before giving up.
+const MAX_TYPE_DEPTH:usize=32;
+
+
 #[derive(Debug)]
 pub enum VarScopeItem<'input> {
     Root {
         modules:Vec<&'input str>,
-        imports:Vec<&'input str>,
+        imports:Vec<Import<'input>>,
         statements:Vec<Statement<'input>>,
         vars:HashMap<&'input str,Vec<Scope>>,
     },
     AnonFunction {
-        imports:Vec<&'input str>,
+        imports:Vec<Import<'input>>,
         parent_scope:Scope,
         def_start:Location,
         def_end:Location,
@@ -25,7 +29,7 @@ pub enum VarScopeItem<'input> {
         vars:HashMap<&'input str,Vec<Scope>>,
     },
     Function {
-        imports:Vec<&'input str>,
+        imports:Vec<Import<'input>>,
         parent_scope:Scope,
         def_start:Location,
         def_end:Location,
@@ -36,15 +40,17 @@ pub enum VarScopeItem<'input> {
         statements:Vec<Statement<'input>>,
         vars:HashMap<&'input str,Vec<Scope>>,
     },
+    /// A variable bound by the pattern of a `match` arm. Its parent is the scope of the arm and its
+    /// type comes from the type of what is matched on.
     MatchBlockVar {
         parent_scope:Scope,
         def_start:Location,
         def_end:Location,
         var:&'input str,
-        statements:Vec<Statement<'input>>,
+        ty:Type<'input>,
     },
     Block {
-        imports:Vec<&'input str>,
+        imports:Vec<Import<'input>>,
         parent_scope:Scope,
         def_start:Location,
         def_end:Location,
@@ -124,9 +130,41 @@ pub enum VarScopeItem<'input> {
         def_start:Location,
         def_end:Location,
         public:Option<Visibility>,
+        name:&'input str,
         params:Option<TypeParameters<'input>>,
         variants:Vec<Type<'input>>,
     },
+    /// A named enum variant. Named variants always declare a new variant and their generics are
+    /// the types they hold. Builtin types used as variants don't get one of these.
+    EnumVariant {
+        parent_scope:Scope,
+        def_start:Location,
+        def_end:Location,
+        name:&'input str,
+        fields:Vec<Type<'input>>,
+    },
+    /// A variable seen with a more specific type, like inside of `if x is T {}`. Reads and writes
+    /// go to `original`.
+    Narrowed {
+        parent_scope:Scope,
+        def_start:Location,
+        def_end:Location,
+        name:&'input str,
+        original:Scope,
+        ty:Type<'input>,
+    },
+    /// Only the standard library is a module for now since other files can't be loaded yet.
+    Module {
+        parent_scope:Scope,
+        name:&'input str,
+    },
+    /// A function provided by the compiler.
+    BuiltinFunction {
+        parent_scope:Scope,
+        name:&'input str,
+        params:Vec<Type<'input>>,
+        ret_type:Option<Type<'input>>,
+    },
 }
 impl<'input> VarScopeItem<'input> {
     pub fn add_var(&mut self,var:&'input str,scope:Scope) {
@@ -145,20 +183,155 @@ impl<'input> VarScopeItem<'input> {
             _=>{},
         }
     }
-    pub fn add_import(&mut self,import:&'input str) {
+    pub fn add_import(&mut self,import:Import<'input>) {
         use VarScopeItem::*;
         match self {
             Root{imports,..}|Function{imports,..}|AnonFunction{imports,..}|Block{imports,..}=>imports.push(import),
             _=>{},
         }
     }
-    pub fn get_var(&mut self,var:&'input str)->Option<Scope> {
+    pub fn get_var(&self,var:&'input str)->Option<Scope> {
         use VarScopeItem::*;
         match self {
             Root{vars,..}|Function{vars,..}|AnonFunction{vars,..}|Block{vars,..}=>vars.get(var).map(|v|v.last()).flatten().map(|s|*s),
             _=>None,
         }
     }
+    pub fn parent_scope(&self)->Option<Scope> {
+        use VarScopeItem::*;
+        match self {
+            Root{..}=>None,
+            AnonFunction{parent_scope,..}|
+                Function{parent_scope,..}|
+                MatchBlockVar{parent_scope,..}|
+                Block{parent_scope,..}|
+                Interface{parent_scope,..}|
+                Impl{parent_scope,..}|
+                Parameter{parent_scope,..}|
+                Var{parent_scope,..}|
+                Const{parent_scope,..}|
+                Static{parent_scope,..}|
+                Type{parent_scope,..}|
+                Enum{parent_scope,..}|
+                EnumVariant{parent_scope,..}|
+                Narrowed{parent_scope,..}|
+                Module{parent_scope,..}|
+                BuiltinFunction{parent_scope,..}=>Some(*parent_scope),
+        }
+    }
+    pub fn def_span(&self)->Option<(Location,Location)> {
+        use VarScopeItem::*;
+        match self {
+            Root{..}|Module{..}|BuiltinFunction{..}=>None,
+            AnonFunction{def_start,def_end,..}|
+                Function{def_start,def_end,..}|
+                MatchBlockVar{def_start,def_end,..}|
+                Block{def_start,def_end,..}|
+                Interface{def_start,def_end,..}|
+                Impl{def_start,def_end,..}|
+                Parameter{def_start,def_end,..}|
+                Var{def_start,def_end,..}|
+                Const{def_start,def_end,..}|
+                Static{def_start,def_end,..}|
+                Type{def_start,def_end,..}|
+                Enum{def_start,def_end,..}|
+                EnumVariant{def_start,def_end,..}|
+                Narrowed{def_start,def_end,..}=>Some((*def_start,*def_end)),
+        }
+    }
+    /// The name this item is declared with, if it has one.
+    pub fn name(&self)->Option<&'input str> {
+        use VarScopeItem::*;
+        match self {
+            Function{name,..}|
+                Interface{name,..}|
+                Parameter{name,..}|
+                Var{name,..}|
+                Const{name,..}|
+                Static{name,..}|
+                Type{name,..}|
+                Enum{name,..}|
+                EnumVariant{name,..}|
+                Narrowed{name,..}|
+                Module{name,..}|
+                BuiltinFunction{name,..}=>Some(name),
+            MatchBlockVar{var,..}=>Some(var),
+            _=>None,
+        }
+    }
+    /// The declared visibility of this item. `None` means it is private.
+    pub fn public(&self)->Option<Visibility> {
+        use VarScopeItem::*;
+        match self {
+            Function{public,..}|
+                Interface{public,..}|
+                Const{public,..}|
+                Static{public,..}|
+                Type{public,..}|
+                Enum{public,..}=>*public,
+            Module{..}|BuiltinFunction{..}|EnumVariant{..}=>Some(Visibility::Full),
+            _=>None,
+        }
+    }
+    /// The names of the type parameters declared on this item.
+    pub fn type_params(&self)->Vec<&'input str> {
+        use VarScopeItem::*;
+        match self {
+            Interface{params:Some(params),..}|
+                Impl{params:Some(params),..}|
+                Type{params:Some(params),..}|
+                Enum{params:Some(params),..}=>params.params.iter().map(|p|p.name).collect(),
+            _=>Vec::new(),
+        }
+    }
+    /// The type parameter `name` declared on this item.
+    pub fn type_param(&self,name:&str)->Option<&TypeParameter<'input>> {
+        use VarScopeItem::*;
+        match self {
+            Interface{params:Some(params),..}|
+                Impl{params:Some(params),..}|
+                Type{params:Some(params),..}|
+                Enum{params:Some(params),..}=>params.params.iter().find(|p|p.name==name),
+            _=>None,
+        }
+    }
+    pub fn statements(&self)->&[Statement<'input>] {
+        use VarScopeItem::*;
+        match self {
+            Root{statements,..}|
+                AnonFunction{statements,..}|
+                Function{statements,..}|
+                Block{statements,..}|
+                Interface{statements,..}|
+                Impl{statements,..}=>statements,
+            _=>&[],
+        }
+    }
+    pub fn statements_mut(&mut self)->&mut [Statement<'input>] {
+        use VarScopeItem::*;
+        match self {
+            Root{statements,..}|
+                AnonFunction{statements,..}|
+                Function{statements,..}|
+                Block{statements,..}|
+                Interface{statements,..}|
+                Impl{statements,..}=>statements,
+            _=>&mut [],
+        }
+    }
+    /// The declared type of a variable-like item.
+    pub fn var_type(&self)->Option<&Type<'input>> {
+        use VarScopeItem::*;
+        match self {
+            Parameter{ty,..}|
+                MatchBlockVar{ty,..}|
+                Var{ty,..}|
+                Const{ty,..}|
+                Static{ty,..}|
+                Narrowed{ty,..}=>Some(ty),
+            _=>None,
+        }
+    }
     pub fn add_stmt(&mut self,stmt:Statement<'input>) {
         use VarScopeItem::*;
         match self {
@@ -187,7 +360,18 @@ pub enum Visibility {
     Local,
     Full,
 }
-#[derive(Debug)]
+impl Visibility {
+    /// How widely something is visible. `None` is private.
+    pub fn rank(vis:Option<Self>)->u8 {
+        match vis {
+            None=>0,
+            Some(Visibility::Local)=>1,
+            Some(Visibility::Library)=>2,
+            Some(Visibility::Full)=>3,
+        }
+    }
+}
+#[derive(Debug,Clone)]
 pub enum Type<'input> {
     UnknownNamed {
         name:&'input str,
@@ -222,6 +406,44 @@ pub enum Type<'input> {
         end:Location,
         inner:Box<AnonFunctionSignature<'input>>,
     },
+    /// `[T]`
+    Slice {
+        start:Location,
+        end:Location,
+        inner:Box<Self>,
+    },
+    /// `&T`
+    Ref {
+        start:Location,
+        end:Location,
+        inner:Box<Self>,
+    },
+    /// `&mut T`
+    RefMut {
+        start:Location,
+        end:Location,
+        inner:Box<Self>,
+    },
+    /// `*T`
+    Pointer {
+        start:Location,
+        end:Location,
+        inner:Box<Self>,
+    },
+    /// `*mut T`
+    PointerMut {
+        start:Location,
+        end:Location,
+        inner:Box<Self>,
+    },
+    /// A reference to a type parameter of an enclosing item.
+    Generic {
+        start:Location,
+        end:Location,
+        name:&'input str,
+        /// The item the type parameter is declared on.
+        scope:Scope,
+    },
     Uint,
     Int,
     Float,
@@ -235,6 +457,71 @@ pub enum Type<'input> {
     GenericFloat,
     Unknown,
 }
+impl<'input> Type<'input> {
+    pub fn is_builtin(&self)->bool {
+        use Type::*;
+        matches!(self,Uint|Int|Float|DoubleFloat|Byte|Bool|Char|String|Never)
+    }
+    /// Whether this is the type of a number literal, or of a slice of them. These take on whatever
+    /// type they are used as.
+    pub fn is_literal(&self)->bool {
+        match self {
+            Type::GenericNumber|Type::GenericFloat=>true,
+            Type::Slice{inner,..}=>inner.is_literal(),
+            _=>false,
+        }
+    }
+    /// Where this type was written. Builtin types don't keep their location.
+    pub fn span(&self)->Option<(Location,Location)> {
+        use Type::*;
+        match self {
+            UnknownNamed{start,end,..}|
+                Named{start,end,..}|
+                Object{start,end,..}|
+                Union{start,end,..}|
+                Composite{start,end,..}|
+                FunctionSig{start,end,..}|
+                Slice{start,end,..}|
+                Ref{start,end,..}|
+                RefMut{start,end,..}|
+                Pointer{start,end,..}|
+                PointerMut{start,end,..}|
+                Generic{start,end,..}=>Some((*start,*end)),
+            _=>None,
+        }
+    }
+    /// This type with the type parameters in `bindings` replaced by what they are bound to.
+    pub fn substitute(&self,bindings:&[(&str,Type<'input>)])->Self {
+        let mut ty=self.clone();
+        ty.substitute_in_place(bindings);
+        return ty;
+    }
+    fn substitute_in_place(&mut self,bindings:&[(&str,Type<'input>)]) {
+        use Type::*;
+        match self {
+            Generic{name,..}=>if let Some((_,bound))=bindings.iter().find(|(n,_)|n==name) {
+                *self=bound.clone();
+            },
+            UnknownNamed{generics,..}|
+                Named{generics,..}=>generics.iter_mut().for_each(|g|g.substitute_in_place(bindings)),
+            Object{fields,..}=>fields.iter_mut().for_each(|f|f.ty.substitute_in_place(bindings)),
+            Union{items,..}|
+                Composite{items,..}=>items.iter_mut().for_each(|i|i.substitute_in_place(bindings)),
+            FunctionSig{inner,..}=>{
+                inner.params.normal.iter_mut().for_each(|p|p.ty.substitute_in_place(bindings));
+                if let Some(ret)=&mut inner.ret_type {
+                    ret.substitute_in_place(bindings);
+                }
+            },
+            Slice{inner,..}|
+                Ref{inner,..}|
+                RefMut{inner,..}|
+                Pointer{inner,..}|
+                PointerMut{inner,..}=>inner.substitute_in_place(bindings),
+            _=>{},
+        }
+    }
+}
 #[derive(Debug)]
 pub enum Statement<'input> {
     FunctionDef {
@@ -284,6 +571,21 @@ pub enum Statement<'input> {
         name:&'input str,
         data:Expr<'input>,
     },
+    /// Assignment to a field.
+    PlaceAssign {
+        start:Location,
+        end:Location,
+        place:Expr<'input>,
+        data:Expr<'input>,
+    },
+    /// `place op<- data`. `place` is only evaluated once.
+    CompoundAssign {
+        start:Location,
+        end:Location,
+        op:AssignOp,
+        place:Expr<'input>,
+        data:Expr<'input>,
+    },
     Expr {
         start:Location,
         end:Location,
@@ -295,10 +597,20 @@ pub enum Statement<'input> {
         label:Option<&'input str>,
         val:Option<Expr<'input>>,
     },
+    /// `target` is the block scope of the loop this continues.
     Continue {
         start:Location,
         end:Location,
         label:Option<&'input str>,
+        target:Scope,
+    },
+    /// `target` is the block scope of the loop this leaves.
+    Break {
+        start:Location,
+        end:Location,
+        label:Option<&'input str>,
+        target:Scope,
+        val:Option<Expr<'input>>,
     },
     Enum {
         start:Location,
@@ -325,6 +637,8 @@ pub enum Expr<'input> {
         from:Box<Self>,
         name:&'input str,
         args:Vec<Self>,
+        /// The function from an impl block this calls. `None` if this calls a field of `from`.
+        method:Option<Scope>,
     },
     UnknownFunctionCall{
         path:Vec<&'input str>,
@@ -393,6 +707,31 @@ pub enum Expr<'input> {
         end:Location,
         inner:Box<[Self;2]>,
     },
+    BitAnd {
+        start:Location,
+        end:Location,
+        inner:Box<[Self;2]>,
+    },
+    BitOr {
+        start:Location,
+        end:Location,
+        inner:Box<[Self;2]>,
+    },
+    BitXor {
+        start:Location,
+        end:Location,
+        inner:Box<[Self;2]>,
+    },
+    ShiftLeft {
+        start:Location,
+        end:Location,
+        inner:Box<[Self;2]>,
+    },
+    ShiftRight {
+        start:Location,
+        end:Location,
+        inner:Box<[Self;2]>,
+    },
     Negate {
         start:Location,
         end:Location,
@@ -469,20 +808,35 @@ pub enum Expr<'input> {
         end:Location,
         val:Box<Self>,
     },
+    /// Dereferencing a pointer is only allowed in `unsafe` blocks.
+    Deref {
+        start:Location,
+        end:Location,
+        val:Box<Self>,
+    },
+    Unsafe {
+        start:Location,
+        end:Location,
+        block:Scope,
+    },
     ForeverLoop {
         start:Location,
         end:Location,
+        label:Option<&'input str>,
         block:Scope,
     },
     WhileLoop {
         start:Location,
         end:Location,
+        label:Option<&'input str>,
         condition:Box<Self>,
         block:Scope,
     },
+    /// The loop variable is a parameter of `block`.
     ForLoop {
         start:Location,
         end:Location,
+        label:Option<&'input str>,
         var:&'input str,
         iterator:Box<Self>,
         block:Scope,
@@ -492,6 +846,238 @@ pub enum Expr<'input> {
         end:Location,
         block:Box<Match<'input>>,
     },
+    /// Each branch is a condition and the block to run if it is true.
+    If {
+        start:Location,
+        end:Location,
+        branches:Vec<(Self,Scope)>,
+        else_block:Option<Scope>,
+    },
+    List {
+        start:Location,
+        end:Location,
+        items:Vec<Self>,
+    },
+    /// Indexes that are out of bounds are caught at runtime.
+    Index {
+        start:Location,
+        end:Location,
+        from:Box<Self>,
+        index:Box<Self>,
+    },
+    /// `from[range_start..range_end]`. A missing start is the start of `from` and a missing end is
+    /// its end. Like indexes, the range is checked at runtime.
+    Slice {
+        start:Location,
+        end:Location,
+        from:Box<Self>,
+        range_start:Option<Box<Self>>,
+        range_end:Option<Box<Self>>,
+    },
+}
+impl<'input> Expr<'input> {
+    pub fn span(&self)->(Location,Location) {
+        use Expr::*;
+        match self {
+            FieldAccess{start,end,..}|
+                MethodCall{start,end,..}|
+                UnknownFunctionCall{start,end,..}|
+                FunctionCall{start,end,..}|
+                UnknownAssociatedPath{start,end,..}|
+                AssociatedPath{start,end,..}|
+                Var{start,end,..}|
+                UnknownVar{start,end,..}|
+                Block{start,end,..}|
+                Data{start,end,..}|
+                Add{start,end,..}|
+                Sub{start,end,..}|
+                Mul{start,end,..}|
+                Div{start,end,..}|
+                Mod{start,end,..}|
+                BitAnd{start,end,..}|
+                BitOr{start,end,..}|
+                BitXor{start,end,..}|
+                ShiftLeft{start,end,..}|
+                ShiftRight{start,end,..}|
+                Negate{start,end,..}|
+                Equal{start,end,..}|
+                NotEqual{start,end,..}|
+                GreaterEqual{start,end,..}|
+                LessEqual{start,end,..}|
+                Greater{start,end,..}|
+                Less{start,end,..}|
+                And{start,end,..}|
+                Or{start,end,..}|
+                Not{start,end,..}|
+                IsType{start,end,..}|
+                ObjectCreation{start,end,..}|
+                AnonFunction{start,end,..}|
+                Ref{start,end,..}|
+                RefMut{start,end,..}|
+                Deref{start,end,..}|
+                Unsafe{start,end,..}|
+                ForeverLoop{start,end,..}|
+                WhileLoop{start,end,..}|
+                ForLoop{start,end,..}|
+                Match{start,end,..}|
+                If{start,end,..}|
+                List{start,end,..}|
+                Index{start,end,..}|
+                Slice{start,end,..}=>(*start,*end),
+        }
+    }
+    /// The expressions directly contained in this one. Expressions in other scopes, like the
+    /// statements of a block, are not included.
+    pub fn children(&self)->Vec<&Self> {
+        use Expr::*;
+        match self {
+            FieldAccess{from,..}=>vec![&**from],
+            MethodCall{from,args,..}=>{
+                let mut ret=vec![&**from];
+                ret.extend(args.iter());
+                ret
+            },
+            UnknownFunctionCall{args,..}|FunctionCall{args,..}=>args.iter().collect(),
+            Add{inner,..}|
+                Sub{inner,..}|
+                Mul{inner,..}|
+                Div{inner,..}|
+                Mod{inner,..}|
+                BitAnd{inner,..}|
+                BitOr{inner,..}|
+                BitXor{inner,..}|
+                ShiftLeft{inner,..}|
+                ShiftRight{inner,..}|
+                Equal{inner,..}|
+                NotEqual{inner,..}|
+                GreaterEqual{inner,..}|
+                LessEqual{inner,..}|
+                Greater{inner,..}|
+                Less{inner,..}|
+                And{inner,..}|
+                Or{inner,..}=>inner.iter().collect(),
+            Negate{inner,..}|Not{inner,..}=>vec![&**inner],
+            IsType{to_test,..}=>vec![&**to_test],
+            ObjectCreation{fields,..}=>fields.iter().map(|f|&f.data).collect(),
+            Ref{val,..}|RefMut{val,..}|Deref{val,..}=>vec![&**val],
+            WhileLoop{condition,..}=>vec![&**condition],
+            ForLoop{iterator,..}=>vec![&**iterator],
+            If{branches,..}=>branches.iter().map(|(condition,_)|condition).collect(),
+            List{items,..}=>items.iter().collect(),
+            Index{from,index,..}=>vec![&**from,&**index],
+            Slice{from,range_start,range_end,..}=>{
+                let mut ret=vec![&**from];
+                ret.extend(range_start.as_deref());
+                ret.extend(range_end.as_deref());
+                ret
+            },
+            Match{block,..}=>{
+                let mut ret=vec![&block.to_match];
+                for (pattern,expr) in block.leafs.iter() {
+                    ret.extend(pattern.exprs());
+                    ret.push(expr);
+                }
+                ret
+            },
+            UnknownAssociatedPath{..}|
+                AssociatedPath{..}|
+                Var{..}|
+                UnknownVar{..}|
+                Block{..}|
+                Data{..}|
+                AnonFunction{..}|
+                Unsafe{..}|
+                ForeverLoop{..}=>Vec::new(),
+        }
+    }
+    /// Same as [`Expr::children`], but mutable.
+    pub fn children_mut(&mut self)->Vec<&mut Self> {
+        use Expr::*;
+        match self {
+            FieldAccess{from,..}=>vec![&mut **from],
+            MethodCall{from,args,..}=>{
+                let mut ret=vec![&mut **from];
+                ret.extend(args.iter_mut());
+                ret
+            },
+            UnknownFunctionCall{args,..}|FunctionCall{args,..}=>args.iter_mut().collect(),
+            Add{inner,..}|
+                Sub{inner,..}|
+                Mul{inner,..}|
+                Div{inner,..}|
+                Mod{inner,..}|
+                BitAnd{inner,..}|
+                BitOr{inner,..}|
+                BitXor{inner,..}|
+                ShiftLeft{inner,..}|
+                ShiftRight{inner,..}|
+                Equal{inner,..}|
+                NotEqual{inner,..}|
+                GreaterEqual{inner,..}|
+                LessEqual{inner,..}|
+                Greater{inner,..}|
+                Less{inner,..}|
+                And{inner,..}|
+                Or{inner,..}=>inner.iter_mut().collect(),
+            Negate{inner,..}|Not{inner,..}=>vec![&mut **inner],
+            IsType{to_test,..}=>vec![&mut **to_test],
+            ObjectCreation{fields,..}=>fields.iter_mut().map(|f|&mut f.data).collect(),
+            Ref{val,..}|RefMut{val,..}|Deref{val,..}=>vec![&mut **val],
+            WhileLoop{condition,..}=>vec![&mut **condition],
+            ForLoop{iterator,..}=>vec![&mut **iterator],
+            If{branches,..}=>branches.iter_mut().map(|(condition,_)|condition).collect(),
+            List{items,..}=>items.iter_mut().collect(),
+            Index{from,index,..}=>vec![&mut **from,&mut **index],
+            Slice{from,range_start,range_end,..}=>{
+                let mut ret=vec![&mut **from];
+                ret.extend(range_start.as_deref_mut());
+                ret.extend(range_end.as_deref_mut());
+                ret
+            },
+            Match{block,..}=>{
+                let self::Match{to_match,leafs,..}=&mut **block;
+                let mut ret=vec![to_match];
+                for (pattern,expr) in leafs.iter_mut() {
+                    ret.extend(pattern.exprs_mut());
+                    ret.push(expr);
+                }
+                ret
+            },
+            UnknownAssociatedPath{..}|
+                AssociatedPath{..}|
+                Var{..}|
+                UnknownVar{..}|
+                Block{..}|
+                Data{..}|
+                AnonFunction{..}|
+                Unsafe{..}|
+                ForeverLoop{..}=>Vec::new(),
+        }
+    }
+}
+impl<'input> Statement<'input> {
+    /// The expressions directly contained in this statement.
+    pub fn exprs(&self)->Vec<&Expr<'input>> {
+        use Statement::*;
+        match self {
+            VarAssign{data,..}|UnknownVarAssign{data,..}=>vec![data],
+            PlaceAssign{place,data,..}|CompoundAssign{place,data,..}=>vec![place,data],
+            Expr{expr,..}=>vec![expr],
+            Return{val:Some(val),..}|Break{val:Some(val),..}=>vec![val],
+            _=>Vec::new(),
+        }
+    }
+    /// Same as [`Statement::exprs`], but mutable.
+    pub fn exprs_mut(&mut self)->Vec<&mut Expr<'input>> {
+        use Statement::*;
+        match self {
+            VarAssign{data,..}|UnknownVarAssign{data,..}=>vec![data],
+            PlaceAssign{place,data,..}|CompoundAssign{place,data,..}=>vec![place,data],
+            Expr{expr,..}=>vec![expr],
+            Return{val:Some(val),..}|Break{val:Some(val),..}=>vec![val],
+            _=>Vec::new(),
+        }
+    }
 }
 #[derive(Debug)]
 pub enum Data<'input> {
@@ -543,12 +1129,20 @@ pub enum Data<'input> {
         data:bool,
     },
 }
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub enum MethodType {
     This,
     ThisMut,
     None,
 }
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum AssignOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
 #[derive(Debug)]
 pub enum MatchPattern<'input> {
     Data {
@@ -567,10 +1161,12 @@ pub enum MatchPattern<'input> {
         end:Location,
         structure:MatchPatternStructure<'input>,
     },
+    /// Matches anything and binds it to `var`.
     Var {
         start:Location,
         end:Location,
         name:&'input str,
+        var:Scope,
     },
     Equal {
         start:Location,
@@ -608,18 +1204,66 @@ pub enum MatchPattern<'input> {
         inner:Type<'input>,
     },
 }
+impl<'input> MatchPattern<'input> {
+    pub fn span(&self)->(Location,Location) {
+        use MatchPattern::*;
+        match self {
+            Data{start,end,..}|
+                MethodCall{start,end,..}|
+                Structure{start,end,..}|
+                Var{start,end,..}|
+                Equal{start,end,..}|
+                NotEqual{start,end,..}|
+                GreaterEqual{start,end,..}|
+                LessEqual{start,end,..}|
+                Greater{start,end,..}|
+                Less{start,end,..}|
+                IsType{start,end,..}=>(*start,*end),
+        }
+    }
+    pub fn exprs(&self)->Vec<&Expr<'input>> {
+        use MatchPattern::*;
+        match self {
+            MethodCall{args,..}=>args.iter().collect(),
+            Equal{inner,..}|
+                NotEqual{inner,..}|
+                GreaterEqual{inner,..}|
+                LessEqual{inner,..}|
+                Greater{inner,..}|
+                Less{inner,..}=>vec![inner],
+            _=>Vec::new(),
+        }
+    }
+    pub fn exprs_mut(&mut self)->Vec<&mut Expr<'input>> {
+        use MatchPattern::*;
+        match self {
+            MethodCall{args,..}=>args.iter_mut().collect(),
+            Equal{inner,..}|
+                NotEqual{inner,..}|
+                GreaterEqual{inner,..}|
+                LessEqual{inner,..}|
+                Greater{inner,..}|
+                Less{inner,..}=>vec![inner],
+            _=>Vec::new(),
+        }
+    }
+}
 #[derive(Debug)]
 pub enum MatchPatternStructureItem<'input> {
+    /// Binds the field `name` to a variable with the same name.
     Field {
         start:Location,
         end:Location,
         name:&'input str,
+        var:Scope,
     },
+    /// Binds the field `name` to the variable `rename`.
     NamedField {
         start:Location,
         end:Location,
         name:&'input str,
         rename:&'input str,
+        var:Scope,
     },
     NamedBlock {
         start:Location,
@@ -640,7 +1284,7 @@ pub enum MatchPatternStructure<'input> {
         start:Location,
         end:Location,
         exact:bool,
-        type_name:&'input str,
+        ty:Type<'input>,
         block:Vec<MatchPatternStructureItem<'input>>,
     },
 }
@@ -658,9 +1302,495 @@ pub struct Scopes<'input> {
     pub var_scopes:HashMap<Scope,VarScopeItem<'input>>,
 }
 impl<'input> Scopes<'input> {
+    pub fn get(&self,scope:Scope)->Option<&VarScopeItem<'input>> {
+        self.var_scopes.get(&scope)
+    }
     pub fn get_mut(&mut self,scope:Scope)->Option<&mut VarScopeItem<'input>> {
         self.var_scopes.get_mut(&scope)
     }
+    /// Splits the block of a branch of an `if` into the statements that run first and the
+    /// expression it ends with, which is the value of the `if` when the branch runs.
+    pub fn branch_value(&self,block:Scope)->(&[Statement<'input>],Option<&Expr<'input>>) {
+        let statements=self.get(block).map_or(&[][..],|b|b.statements());
+        match statements.split_last() {
+            Some((Statement::Expr{expr,..},rest))=>(rest,Some(expr)),
+            _=>(statements,None),
+        }
+    }
+    /// Look up a variable starting at `scope` and walking up the `parent_scope` chain.
+    pub fn get_var(&self,scope:Scope,var:&'input str)->Option<Scope> {
+        let mut current=Some(scope);
+        while let Some(scope)=current {
+            let item=self.get(scope)?;
+            if let Some(found)=item.get_var(var) {
+                return Some(found);
+            }
+            current=item.parent_scope();
+        }
+        return None;
+    }
+    /// Every impl block, in the order they were defined.
+    pub fn impls(&self)->Vec<Scope> {
+        let mut impls=self.var_scopes.iter()
+            .filter(|(_,item)|matches!(item,VarScopeItem::Impl{..}))
+            .map(|(scope,_)|*scope)
+            .collect::<Vec<_>>();
+        impls.sort_by_key(|s|s.0);
+        return impls;
+    }
+    /// Whether an impl for `for_ty` applies to values of type `ty`.
+    pub fn impl_applies(&self,for_ty:&Type<'input>,ty:&Type<'input>)->bool {
+        self.impl_applies_inner(for_ty,ty,0)
+    }
+    fn impl_applies_inner(&self,for_ty:&Type<'input>,ty:&Type<'input>,depth:usize)->bool {
+        match (for_ty,ty) {
+            (Type::Generic{..},_)=>true,
+            (Type::Named{path:a,..},Type::Named{path:b,..}) if a==b=>true,
+            (Type::Slice{inner:a,..},Type::Slice{inner:b,..})=>self.impl_applies_inner(a,b,depth+1),
+            // Impls for constraints apply to everything that fits the constraint.
+            (Type::Named{path,..},_)=>match self.get(*path) {
+                Some(VarScopeItem::Type{ty:Type::Object{exact:false,..},..})=>self.assignable(ty,for_ty,depth+1).is_ok(),
+                _=>false,
+            },
+            (a,b)=>a.is_builtin()&&std::mem::discriminant(a)==std::mem::discriminant(b),
+        }
+    }
+    /// Follows type aliases until something that isn't one is found.
+    pub fn expand_alias<'a>(&'a self,mut ty:&'a Type<'input>)->&'a Type<'input> {
+        // Guard against recursive aliases
+        for _ in 0..MAX_TYPE_DEPTH {
+            match ty {
+                Type::Named{path,..}=>match self.get(*path) {
+                    Some(VarScopeItem::Type{ty:inner,..})=>ty=inner,
+                    _=>break,
+                },
+                _=>break,
+            }
+        }
+        return ty;
+    }
+    /// The type the type alias `ty` stands for. The type parameters of generic aliases are
+    /// replaced by the generics `ty` uses, so `Box(Int)` is `{pub v:Int}` for `type Box[T]<-{pub v:T}`.
+    pub fn alias_target(&self,ty:&Type<'input>)->Option<Type<'input>> {
+        let Type::Named{path,generics,..}=ty else {return None};
+        let Some(VarScopeItem::Type{ty:target,params,..})=self.get(*path) else {return None};
+        return match params {
+            Some(params) if params.params.len()==generics.len()=>{
+                let bindings=params.params.iter().map(|p|p.name).zip(generics.iter().cloned()).collect::<Vec<_>>();
+                Some(target.substitute(&bindings))
+            },
+            _=>Some(target.clone()),
+        };
+    }
+    /// Like `auto_deref`, but generic type aliases have their type parameters filled in.
+    pub fn concrete(&self,ty:&Type<'input>)->Type<'input> {
+        let mut ty=self.auto_deref(ty).clone();
+        for _ in 0..MAX_TYPE_DEPTH {
+            match self.alias_target(&ty) {
+                Some(target)=>ty=self.auto_deref(&target).clone(),
+                None=>break,
+            }
+        }
+        return ty;
+    }
+    /// The constraint the type parameter `ty` was declared with, if it has one.
+    pub fn generic_bound(&self,ty:&Type<'input>)->Option<&Type<'input>> {
+        let Type::Generic{name,scope,..}=ty else {return None};
+        return self.get(*scope)?.type_param(name)?.ty.as_ref();
+    }
+    /// Binds the type parameters in `param` to the parts of `arg` they line up with. Parameters
+    /// that are already bound keep their first type.
+    pub fn bind_generics(&self,param:&Type<'input>,arg:&Type<'input>,bindings:&mut Vec<(&'input str,Type<'input>)>) {
+        use Type::*;
+        match (param,arg) {
+            (Generic{name,..},_) if bindings.iter().all(|(n,_)|n!=name)=>bindings.push((name,arg.clone())),
+            (Generic{..},_)=>{},
+            (Slice{inner:p,..},Slice{inner:a,..})|
+                (Ref{inner:p,..},Ref{inner:a,..}|RefMut{inner:a,..})|
+                (RefMut{inner:p,..},RefMut{inner:a,..})|
+                (Pointer{inner:p,..},Pointer{inner:a,..}|PointerMut{inner:a,..})|
+                (PointerMut{inner:p,..},PointerMut{inner:a,..})=>self.bind_generics(p,a,bindings),
+            (Named{path:p,generics:p_generics,..},Named{path:a,generics:a_generics,..}) if p==a=>{
+                for (p,a) in p_generics.iter().zip(a_generics.iter()) {
+                    self.bind_generics(p,a,bindings);
+                }
+            },
+            (Object{fields:p_fields,..},Object{fields:a_fields,..})=>for p in p_fields.iter() {
+                if let Some(a)=a_fields.iter().find(|a|a.name==p.name) {
+                    self.bind_generics(&p.ty,&a.ty,bindings);
+                }
+            },
+            (_,Ref{inner,..}|RefMut{inner,..})=>self.bind_generics(param,inner,bindings),
+            (Named{..},_)=>if let Some(target)=self.alias_target(param) {
+                self.bind_generics(&target,arg,bindings);
+            },
+            _=>{},
+        }
+    }
+    /// Follows type aliases and references until something that is neither is found. Fields and
+    /// methods are used through references as if they were the value.
+    pub fn auto_deref<'a>(&'a self,mut ty:&'a Type<'input>)->&'a Type<'input> {
+        for _ in 0..MAX_TYPE_DEPTH {
+            match self.expand_alias(ty) {
+                Type::Ref{inner,..}|Type::RefMut{inner,..}=>ty=inner,
+                _=>break,
+            }
+        }
+        return ty;
+    }
+    /// The type of the field `name` on values of type `ty`, if they have it. The field of a union or
+    /// enum is the field of the members that have it.
+    pub fn field_type(&self,ty:&Type<'input>,name:&str)->Option<Type<'input>> {
+        self.field_type_inner(ty,name,0)
+    }
+    fn field_type_inner(&self,ty:&Type<'input>,name:&str,depth:usize)->Option<Type<'input>> {
+        if depth>MAX_TYPE_DEPTH {
+            return Some(Type::Unknown);
+        }
+        let concrete=self.concrete(ty);
+        let members=match &concrete {
+            Type::Object{fields,..}=>return fields.iter().find(|f|f.name==name).map(|f|f.ty.clone()),
+            Type::Unknown|Type::UnknownNamed{..}=>return Some(Type::Unknown),
+            // Type parameters only have the fields of their constraint.
+            Type::Generic{..}=>return self.field_type_inner(self.generic_bound(&concrete)?,name,depth+1),
+            Type::Union{items,..}=>items,
+            Type::Named{path,..}=>match self.get(*path) {
+                Some(VarScopeItem::Enum{variants,..})=>variants,
+                _=>return None,
+            },
+            _=>return None,
+        };
+        let mut found=members.iter().filter_map(|m|self.field_type_inner(m,name,depth+1)).collect::<Vec<_>>();
+        if found.len()>1&&found.iter().all(|t|self.type_name(t)==self.type_name(&found[0])) {
+            found.truncate(1);
+        }
+        return match found.len() {
+            0=>None,
+            1=>found.pop(),
+            _=>{
+                let (start,end)=ty.span().unwrap_or_default();
+                Some(Type::Union{start,end,items:found})
+            },
+        };
+    }
+    /// The type of the items of `ty` if it is a slice.
+    pub fn item_type<'a>(&'a self,ty:&'a Type<'input>)->Option<&'a Type<'input>> {
+        match self.expand_alias(ty) {
+            Type::Slice{inner,..}=>Some(inner),
+            _=>None,
+        }
+    }
+    /// Checks whether a value of type `from` can be used where a `to` is expected. The error says
+    /// why it can't.
+    pub fn check_assignable(&self,from:&Type<'input>,to:&Type<'input>)->Result<(),String> {
+        self.assignable(from,to,0)
+    }
+    fn assignable(&self,from:&Type<'input>,to:&Type<'input>,depth:usize)->Result<(),String> {
+        use Type::*;
+        if depth>MAX_TYPE_DEPTH {
+            return Ok(());
+        }
+        let mismatch=||Err(format!("expected `{}`, found `{}`",self.type_name(to),self.type_name(from)));
+        match (from,to) {
+            (Never,_)=>Ok(()),
+            // Nothing is known about these, so nothing can be said to fit.
+            (Unknown|UnknownNamed{..},_)|(_,Unknown|UnknownNamed{..})=>mismatch(),
+            // A type parameter can be any type, so only the same parameter fits it. Its values can
+            // be used as its constraint.
+            (Generic{name:a,scope:a_scope,..},Generic{name:b,scope:b_scope,..}) if a==b&&a_scope==b_scope=>Ok(()),
+            (Generic{..},_)=>match self.generic_bound(from) {
+                Some(bound)=>self.assignable(bound,to,depth+1).or_else(|_|mismatch()),
+                None=>mismatch(),
+            },
+            (_,Generic{..})=>mismatch(),
+            (Union{items,..},_)=>items.iter().try_for_each(|item|self.assignable(item,to,depth+1)),
+            (_,Composite{items,..})=>items.iter().try_for_each(|item|self.assignable(from,item,depth+1)),
+            (_,Union{items,..})=>if items.iter().any(|item|self.assignable(from,item,depth+1).is_ok()) {
+                Ok(())
+            } else {
+                mismatch()
+            },
+            (Composite{items,..},_)=>if items.iter().any(|item|self.assignable(item,to,depth+1).is_ok()) {
+                Ok(())
+            } else {
+                mismatch()
+            },
+            (Named{path:a,generics:from_generics,..},Named{path:b,generics:to_generics,..}) if a==b=>{
+                if from_generics.len()!=to_generics.len() {
+                    return mismatch();
+                }
+                // Generics have to be the same type, except for the ones of values that don't
+                // decide them, like number literals or an empty list.
+                for (f,t) in from_generics.iter().zip(to_generics.iter()) {
+                    self.assignable(f,t,depth+1).or_else(|_|mismatch())?;
+                    if !f.is_literal()&&!matches!(f,Never) {
+                        self.assignable(t,f,depth+1).or_else(|_|mismatch())?;
+                    }
+                }
+                Ok(())
+            },
+            (_,Named{path,..})=>match self.get(*path) {
+                Some(VarScopeItem::Type{..})=>{
+                    let target=self.alias_target(to).unwrap_or(Unknown);
+                    match (self.expand_alias(from),self.expand_alias(&target)) {
+                        // Say which field is wrong when comparing objects.
+                        (Object{..},Object{..})=>self.assignable(from,&target,depth+1)
+                            .map_err(|e|format!("`{}` is not a `{}`: {}",self.type_name(from),self.type_name(to),e)),
+                        _=>self.assignable(from,&target,depth+1).or_else(|_|mismatch()),
+                    }
+                },
+                Some(VarScopeItem::Enum{variants,..})=>{
+                    // Variants made by calling them know the generics of their enum.
+                    if let Named{path:variant,start,end,generics}=from {
+                        let parent=self.get(*variant).and_then(|v|v.parent_scope());
+                        if !generics.is_empty()&&parent==Some(*path) {
+                            let from=Named{path:*path,start:*start,end:*end,generics:generics.clone()};
+                            return self.assignable(&from,to,depth+1).or_else(|_|mismatch());
+                        }
+                    }
+                    if variants.iter().any(|v|self.assignable(from,v,depth+1).is_ok()) {
+                        Ok(())
+                    } else {
+                        mismatch()
+                    }
+                },
+                Some(VarScopeItem::Interface{name,..})=>{
+                    let implemented=self.impls().into_iter().any(|scope|match self.get(scope) {
+                        Some(VarScopeItem::Impl{interface:Some(Named{path:i,..}),for_ty,..})=>i==path&&self.impl_applies_inner(for_ty,from,depth+1),
+                        _=>false,
+                    });
+                    if implemented {
+                        Ok(())
+                    } else {
+                        Err(format!("`{}` does not implement `{}`",self.type_name(from),name))
+                    }
+                },
+                _=>mismatch(),
+            },
+            (Named{path,..},_)=>match self.get(*path) {
+                Some(VarScopeItem::Type{..})=>self.assignable(&self.alias_target(from).unwrap_or(Unknown),to,depth+1),
+                _=>mismatch(),
+            },
+            (Object{fields:from_fields,exact:from_exact,..},Object{fields:to_fields,exact:to_exact,..})=>{
+                for to_field in to_fields.iter() {
+                    let Some(from_field)=from_fields.iter().find(|f|f.name==to_field.name) else {
+                        return Err(format!("`{}` is missing the field `{}`",self.type_name(from),to_field.name));
+                    };
+                    if Visibility::rank(from_field.public)<Visibility::rank(to_field.public) {
+                        return Err(format!("field `{}` needs to be at least as public",to_field.name));
+                    }
+                    if Visibility::rank(from_field.mutable)<Visibility::rank(to_field.mutable) {
+                        return Err(format!("field `{}` needs to be at least as mutable",to_field.name));
+                    }
+                    self.assignable(&from_field.ty,&to_field.ty,depth+1)
+                        .map_err(|e|format!("field `{}`: {}",to_field.name,e))?;
+                    // Writing through a mutable field has to keep the original type valid. Number
+                    // literals take on whatever type they are used as.
+                    if to_field.mutable.is_some()&&!from_field.ty.is_literal() {
+                        self.assignable(&to_field.ty,&from_field.ty,depth+1)
+                            .map_err(|e|format!("mutable field `{}`: {}",to_field.name,e))?;
+                    }
+                }
+                if *to_exact {
+                    if !from_exact {
+                        return Err(format!("`{}` may have more fields than the exact type `{}`",self.type_name(from),self.type_name(to)));
+                    }
+                    if let Some(extra)=from_fields.iter().find(|f|to_fields.iter().all(|t|t.name!=f.name)) {
+                        return Err(format!("`{}` has the field `{}` which the exact type `{}` does not",self.type_name(from),extra.name,self.type_name(to)));
+                    }
+                }
+                Ok(())
+            },
+            (FunctionSig{inner:from_sig,..},FunctionSig{inner:to_sig,..})=>{
+                if from_sig.params.normal.len()!=to_sig.params.normal.len() {
+                    return mismatch();
+                }
+                for (f,t) in from_sig.params.normal.iter().zip(to_sig.params.normal.iter()) {
+                    self.assignable(&t.ty,&f.ty,depth+1).or_else(|_|mismatch())?;
+                }
+                match (&from_sig.ret_type,&to_sig.ret_type) {
+                    (Some(f),Some(t))=>self.assignable(f,t,depth+1).or_else(|_|mismatch()),
+                    (None,Some(_))=>mismatch(),
+                    _=>Ok(()),
+                }
+            },
+            (Slice{inner:from_inner,..},Slice{inner:to_inner,..})=>{
+                self.assignable(from_inner,to_inner,depth+1).or_else(|_|mismatch())?;
+                // Items can be written through the slice, so the types have to be the same. Like
+                // with mutable fields, number literals take on the type they are used as, and so
+                // does an empty list.
+                if !from_inner.is_literal()&&!matches!(**from_inner,Never) {
+                    self.assignable(to_inner,from_inner,depth+1).or_else(|_|mismatch())?;
+                }
+                Ok(())
+            },
+            // Shared references and pointers can only be read through, so the value only has to
+            // fit. Mutable ones can be written through and need the same type.
+            (Ref{inner:from_inner,..}|RefMut{inner:from_inner,..},Ref{inner:to_inner,..})|
+                (Ref{inner:from_inner,..}|RefMut{inner:from_inner,..}|Pointer{inner:from_inner,..}|PointerMut{inner:from_inner,..},Pointer{inner:to_inner,..})=>{
+                self.assignable(from_inner,to_inner,depth+1).or_else(|_|mismatch())
+            },
+            (RefMut{inner:from_inner,..},RefMut{inner:to_inner,..})|
+                (RefMut{inner:from_inner,..}|PointerMut{inner:from_inner,..},PointerMut{inner:to_inner,..})=>{
+                self.assignable(from_inner,to_inner,depth+1).or_else(|_|mismatch())?;
+                if !from_inner.is_literal() {
+                    self.assignable(to_inner,from_inner,depth+1).or_else(|_|mismatch())?;
+                }
+                Ok(())
+            },
+            (GenericNumber,Uint|Int|Byte|Float|DoubleFloat|GenericNumber|GenericFloat)=>Ok(()),
+            (GenericFloat,Float|DoubleFloat|GenericFloat)=>Ok(()),
+            (a,b) if a.is_builtin()&&std::mem::discriminant(a)==std::mem::discriminant(b)=>Ok(()),
+            _=>mismatch(),
+        }
+    }
+    /// Finds the function defined directly in `statements` with the given name.
+    pub fn find_function(&self,statements:&[Statement<'input>],name:&str)->Option<Scope> {
+        statements.iter().find_map(|s|match s {
+            Statement::FunctionDef{def,..} if self.get(*def).and_then(|i|i.name())==Some(name)=>Some(*def),
+            _=>None,
+        })
+    }
+    /// Finds the method `name` for values of type `ty`. Inherent impls are searched first, then
+    /// interface impls and finally the default functions of those interfaces.
+    pub fn find_method(&self,ty:&Type<'input>,name:&str)->Option<Scope> {
+        let ty=self.auto_deref(ty);
+        let mut interface_impls=Vec::new();
+        for scope in self.impls() {
+            if let Some(VarScopeItem::Impl{interface,for_ty,statements,..})=self.get(scope) {
+                if !self.impl_applies(for_ty,ty) {
+                    continue;
+                }
+                match interface {
+                    None=>if let Some(method)=self.find_function(statements,name) {
+                        return Some(method);
+                    },
+                    Some(interface)=>interface_impls.push((statements,interface)),
+                }
+            }
+        }
+        for (statements,interface) in interface_impls {
+            if let Some(method)=self.find_function(statements,name) {
+                return Some(method);
+            }
+            if let Type::Named{path,..}=interface {
+                if let Some(method)=self.get(*path).and_then(|i|self.find_function(i.statements(),name)) {
+                    return Some(method);
+                }
+            }
+        }
+        return None;
+    }
+    /// The interface `ty` is, if it is one. Methods called on these are picked at runtime by the
+    /// impl of the value's type.
+    pub fn interface(&self,ty:&Type<'input>)->Option<Scope> {
+        match self.expand_alias(self.auto_deref(ty)) {
+            Type::Named{path,..} if matches!(self.get(*path),Some(VarScopeItem::Interface{..}))=>Some(*path),
+            _=>None,
+        }
+    }
+    /// The function `name` of the interface `interface`, either a default one or only the
+    /// signature every impl has to define.
+    pub fn interface_method(&self,interface:Scope,name:&str)->Option<InterfaceMethod<'_,'input>> {
+        let statements=self.get(interface)?.statements();
+        if let Some(def)=self.find_function(statements,name) {
+            return Some(InterfaceMethod::Default(def));
+        }
+        return statements.iter().find_map(|s|match s {
+            Statement::FunctionSig{sig,..} if sig.name==name=>Some(InterfaceMethod::Required(sig)),
+            _=>None,
+        });
+    }
+    /// The function a call of `name` on a value of the interface `interface` runs when the value
+    /// is a `ty`. This is the one from the first impl of the interface that applies to `ty`, or
+    /// the interface's default if the impl doesn't define it.
+    pub fn dispatch(&self,interface:Scope,ty:&Type<'input>,name:&str)->Option<Scope> {
+        for (for_ty,statements) in self.interface_impls(interface) {
+            let mut bindings=Vec::new();
+            self.bind_generics(for_ty,ty,&mut bindings);
+            if self.check_assignable(ty,&for_ty.substitute(&bindings)).is_ok() {
+                return self.find_function(statements,name)
+                    .or_else(||self.get(interface).and_then(|i|self.find_function(i.statements(),name)));
+            }
+        }
+        return None;
+    }
+    /// The type and statements of every impl of the interface `interface`, in the order they
+    /// were defined.
+    pub fn interface_impls(&self,interface:Scope)->Vec<(&Type<'input>,&[Statement<'input>])> {
+        self.impls().into_iter()
+            .filter_map(|scope|match self.get(scope) {
+                Some(VarScopeItem::Impl{interface:Some(Type::Named{path,..}),for_ty,statements,..}) if *path==interface=>Some((for_ty,statements.as_slice())),
+                _=>None,
+            })
+            .collect()
+    }
+    /// A human readable name for a type, used in errors.
+    pub fn type_name(&self,ty:&Type<'input>)->String {
+        use Type::*;
+        let list=|items:&[Type<'input>],sep:&str|items.iter().map(|t|self.type_name(t)).collect::<Vec<_>>().join(sep);
+        let generic_list=|name:&str,generics:&[Type<'input>]|if generics.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}({})",name,list(generics,","))
+        };
+        match ty {
+            UnknownNamed{name,generics,..}=>generic_list(name,generics),
+            Named{path,generics,..}=>generic_list(self.get(*path).and_then(|i|i.name()).unwrap_or("?"),generics),
+            Object{fields,exact,..}=>{
+                let mut out=std::string::String::from("{");
+                for (i,field) in fields.iter().enumerate() {
+                    if i>0 {
+                        out.push(',');
+                    }
+                    if field.public.is_some() {
+                        out.push_str("pub ");
+                    }
+                    if field.mutable.is_some() {
+                        out.push_str("mut ");
+                    }
+                    out.push_str(field.name);
+                    out.push(':');
+                    out.push_str(&self.type_name(&field.ty));
+                }
+                if !exact {
+                    if !fields.is_empty() {
+                        out.push(',');
+                    }
+                    out.push_str("...");
+                }
+                out.push('}');
+                out
+            },
+            Union{items,..}=>list(items,"|"),
+            Composite{items,..}=>list(items,"+"),
+            FunctionSig{inner,..}=>{
+                let params=inner.params.normal.iter().map(|p|self.type_name(&p.ty)).collect::<Vec<_>>().join(",");
+                match &inner.ret_type {
+                    Some(ret)=>format!("fn[{}]:{}",params,self.type_name(ret)),
+                    None=>format!("fn[{}]",params),
+                }
+            },
+            Slice{inner,..}=>format!("[{}]",self.type_name(inner)),
+            Ref{inner,..}=>format!("&{}",self.type_name(inner)),
+            RefMut{inner,..}=>format!("&mut {}",self.type_name(inner)),
+            Pointer{inner,..}=>format!("*{}",self.type_name(inner)),
+            PointerMut{inner,..}=>format!("*mut {}",self.type_name(inner)),
+            Generic{name,..}=>name.to_string(),
+            Uint=>"Uint".into(),
+            Int=>"Int".into(),
+            Float=>"Float".into(),
+            DoubleFloat=>"DoubleFloat".into(),
+            Byte=>"Byte".into(),
+            Bool=>"Bool".into(),
+            Char=>"Char".into(),
+            String=>"String".into(),
+            Never=>"Never".into(),
+            GenericNumber=>"{number}".into(),
+            GenericFloat=>"{float}".into(),
+            Unknown=>"{unknown}".into(),
+        }
+    }
     pub fn push(&mut self,item:VarScopeItem<'input>)->Scope {
         let scope=Scope(self.var_scope_count);
         self.var_scope_count+=1;
@@ -668,6 +1798,19 @@ impl<'input> Scopes<'input> {
         return scope;
     }
 }
+/// A single imported item. Import blocks are flattened into one of these per item.
+#[derive(Debug,Clone)]
+pub struct Import<'input> {
+    pub start:Location,
+    pub end:Location,
+    pub path:Vec<&'input str>,
+}
+impl<'input> Import<'input> {
+    /// The name the item is imported as.
+    pub fn name(&self)->&'input str {
+        self.path.last().expect("Internal compiler error: empty import path")
+    }
+}
 #[derive(Debug)]
 pub struct Match<'input> {
     pub start:Location,
@@ -675,13 +1818,21 @@ pub struct Match<'input> {
     pub to_match:Expr<'input>,
     pub leafs:Vec<(MatchPattern<'input>,Expr<'input>)>,
 }
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct AnonFunctionSignature<'input> {
     pub start:Location,
     pub end:Location,
     pub params:Parameters<'input>,
     pub ret_type:Option<Type<'input>>,
 }
+/// A function of an interface found by name.
+#[derive(Debug,Copy,Clone)]
+pub enum InterfaceMethod<'a,'input> {
+    /// A default function, which impls can replace.
+    Default(Scope),
+    /// A function every impl defines.
+    Required(&'a FunctionSignature<'input>),
+}
 #[derive(Debug)]
 pub struct FunctionSignature<'input> {
     pub start:Location,
@@ -691,7 +1842,7 @@ pub struct FunctionSignature<'input> {
     pub params:Parameters<'input>,
     pub ret_type:Option<Type<'input>>,
 }
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct Parameters<'input> {
     pub start:Location,
     pub end:Location,
@@ -711,7 +1862,7 @@ impl<'input> Parameters<'input> {
         return names;
     }
 }
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct Parameter<'input> {
     pub name:&'input str,
     pub start:Location,
@@ -719,7 +1870,7 @@ pub struct Parameter<'input> {
     pub mutable:bool,
     pub ty:Type<'input>,
 }
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct TypeObjectField<'input> {
     pub start:Location,
     pub end:Location,
@@ -728,13 +1879,13 @@ pub struct TypeObjectField<'input> {
     pub name:&'input str,
     pub ty:Type<'input>,
 }
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct TypeParameters<'input> {
     pub start:Location,
     pub end:Location,
     pub params:Vec<TypeParameter<'input>>,
 }
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct TypeParameter<'input> {
     pub name:&'input str,
     pub start:Location,
@@ -0,0 +1,240 @@
+//! Checks that fields are only read and changed where their `pub` and `mut` visibility allows.
+//!
+//! `local` means inside of the object: the scope its type is defined in and `impl` blocks for it.
+//! `lib` means inside of the library the type is defined in. Private fields are only visible
+//! locally, and fields without `mut` can't be changed at all. Values behind `&` references and `*`
+//! pointers can't be changed either, and neither can variables and parameters without `mut` or
+//! anything stored in them.
+use cppl_error::{
+    Location,
+    Error,
+};
+use crate::refined::*;
+use super::type_check::expr_type;
+
+
+/// Whether a field is being read or changed.
+#[derive(Copy,Clone)]
+enum Access {
+    Read,
+    Write,
+}
+struct State<'a,'input> {
+    filename:&'input str,
+    scopes:&'a Scopes<'input>,
+    errors:Vec<Error<'input,String>>,
+}
+impl<'a,'input> State<'a,'input> {
+    fn chain(&self,scope:Scope)->impl Iterator<Item=Scope>+'a {
+        let scopes=self.scopes;
+        let mut current=Some(scope);
+        std::iter::from_fn(move||{
+            let ret=current?;
+            current=scopes.get(ret).and_then(|i|i.parent_scope());
+            Some(ret)
+        })
+    }
+    /// The library a scope is in: the outermost module around it, or the root for the code being
+    /// compiled.
+    fn library(&self,scope:Scope)->Option<Scope> {
+        let mut library=None;
+        for s in self.chain(scope) {
+            match self.scopes.get(s) {
+                Some(VarScopeItem::Module{..})=>library=Some(s),
+                Some(VarScopeItem::Root{..}) if library.is_none()=>library=Some(s),
+                _=>{},
+            }
+        }
+        return library;
+    }
+    /// Whether `scope` is inside of the type `object` or an `impl` block for it.
+    fn inside_object(&self,scope:Scope,object:Scope)->bool {
+        self.chain(scope).any(|s|s==object||match self.scopes.get(s) {
+            Some(VarScopeItem::Impl{for_ty:Type::Named{path,..},..})=>*path==object,
+            _=>false,
+        })
+    }
+    /// Whether code in `scope` can use something with visibility `vis` on the object `object`.
+    fn allowed(&self,scope:Scope,object:Scope,vis:Option<Visibility>)->bool {
+        match vis {
+            Some(Visibility::Full)=>true,
+            Some(Visibility::Library)=>self.library(scope)==self.library(object),
+            Some(Visibility::Local)|None=>self.inside_object(scope,object),
+        }
+    }
+    /// The type that defines the fields of `ty`, if it is a named type.
+    fn object_of(&self,ty:&Type<'input>)->Option<Scope> {
+        let mut owner=None;
+        let mut ty=ty;
+        while let Type::Named{path,..}=ty {
+            match self.scopes.get(*path) {
+                Some(VarScopeItem::Type{ty:inner,..})=>{
+                    owner=Some(*path);
+                    ty=inner;
+                },
+                _=>break,
+            }
+        }
+        return owner;
+    }
+    fn check_field(&mut self,scope:Scope,from:&Expr<'input>,name:&str,(start,end):(Location,Location),access:Access) {
+        let from_ty=expr_type(self.filename,self.scopes,from);
+        let from_ty=self.scopes.auto_deref(&from_ty);
+        let Type::Object{fields,..}=self.scopes.expand_alias(from_ty) else {return};
+        let Some(field)=fields.iter().find(|f|f.name==name) else {return};
+        let object=self.object_of(from_ty);
+        let type_name=self.scopes.type_name(from_ty);
+        let mut reasons=Vec::new();
+        if let Some(object)=object {
+            if !self.allowed(scope,object,field.public) {
+                reasons.push(match field.public {
+                    None=>format!("field `{}` of `{}` is private",name,type_name),
+                    Some(Visibility::Library)=>format!("field `{}` of `{}` is only visible inside of its library",name,type_name),
+                    _=>format!("field `{}` of `{}` is only visible inside of `{}`",name,type_name,type_name),
+                });
+            }
+        }
+        if let Access::Write=access {
+            match (field.mutable,object) {
+                (None,_)=>reasons.push(format!("field `{}` is not mutable",name)),
+                (Some(vis),Some(object)) if !self.allowed(scope,object,Some(vis))=>reasons.push(match vis {
+                    Visibility::Library=>format!("field `{}` of `{}` can only be changed inside of its library",name,type_name),
+                    _=>format!("field `{}` of `{}` can only be changed inside of `{}`",name,type_name,type_name),
+                }),
+                _=>{},
+            }
+        }
+        for reason in reasons {
+            let err=Error::new_verif(self.filename,start,end,reason).with_note(field.start,field.end,"field defined here");
+            self.errors.push(err);
+        }
+    }
+    /// Only `&mut` references and `*mut` pointers can be written through. `place` changes what
+    /// `val` points to.
+    fn check_deref_write(&mut self,place:&Expr<'input>,val:&Expr<'input>) {
+        let ty=expr_type(self.filename,self.scopes,val);
+        let kind=match self.scopes.expand_alias(&ty) {
+            Type::Ref{..}=>"a `&` reference",
+            Type::Pointer{..}=>"a `*` pointer",
+            _=>return,
+        };
+        let (start,end)=place.span();
+        self.errors.push(Error::new_verif(self.filename,start,end,format!("can not change a value behind {}",kind)));
+    }
+    /// Changing `place` changes the variable it is stored in, unless it is behind a reference or
+    /// pointer, which [`State::check_deref_write`] checks instead.
+    fn check_binding_write(&mut self,scope:Scope,place:&Expr<'input>) {
+        let mut root=place;
+        loop {
+            match root {
+                Expr::FieldAccess{from,..}|Expr::Index{from,..}=>{
+                    let ty=expr_type(self.filename,self.scopes,from);
+                    if let Type::Ref{..}|Type::RefMut{..}|Type::Pointer{..}|Type::PointerMut{..}=self.scopes.expand_alias(&ty) {
+                        return;
+                    }
+                    root=from;
+                },
+                Expr::Var{path,..}=>break self.check_var_write(scope,*path,place.span()),
+                _=>return,
+            }
+        }
+    }
+    fn check_var_write(&mut self,scope:Scope,var:Scope,(start,end):(Location,Location)) {
+        let Some(item)=self.scopes.get(var) else {return};
+        let name=item.name().unwrap_or_default();
+        let (reason,note)=match item {
+            VarScopeItem::Narrowed{original,..}=>return self.check_var_write(scope,*original,(start,end)),
+            VarScopeItem::Var{mutable:Some(_),..}|VarScopeItem::Parameter{mutable:true,..}=>return,
+            VarScopeItem::Var{..}|VarScopeItem::MatchBlockVar{..}=>(format!("variable `{}` is not mutable",name),format!("declare `{}` with `mut` to change it",name)),
+            VarScopeItem::Parameter{..}=>(format!("parameter `{}` is not mutable",name),format!("declare `{}` with `mut` to change it",name)),
+            VarScopeItem::Const{..}=>(format!("can not change the constant `{}`",name),"constant defined here".to_string()),
+            VarScopeItem::Static{mutable:None,..}=>(format!("static `{}` is not mutable",name),"static defined here".to_string()),
+            VarScopeItem::Static{mutable:Some(vis),parent_scope,..}=>{
+                if self.allowed(scope,*parent_scope,Some(*vis)) {
+                    return;
+                }
+                let reason=match vis {
+                    Visibility::Library=>format!("static `{}` can only be changed inside of its library",name),
+                    _=>format!("static `{}` can only be changed inside of the scope it is defined in",name),
+                };
+                (reason,"static defined here".to_string())
+            },
+            _=>return,
+        };
+        let mut err=Error::new_verif(self.filename,start,end,reason);
+        if let Some((def_start,def_end))=item.def_span() {
+            err=err.with_note(def_start,def_end,note);
+        }
+        self.errors.push(err);
+    }
+    fn visit_expr(&mut self,scope:Scope,expr:&Expr<'input>,access:Access) {
+        match expr {
+            Expr::FieldAccess{from,name,..}=>{
+                self.check_field(scope,from,name,expr.span(),access);
+                if let Access::Write=access {
+                    self.check_deref_write(expr,from);
+                }
+                // Reading or changing `a.b.c` reads or changes `a.b` too.
+                self.visit_expr(scope,from,access);
+            },
+            Expr::Deref{val,..}=>{
+                if let Access::Write=access {
+                    self.check_deref_write(expr,val);
+                }
+                self.visit_expr(scope,val,Access::Read);
+            },
+            // Changing an item of a slice changes the slice.
+            Expr::Index{from,index,..}=>{
+                if let Access::Write=access {
+                    self.check_deref_write(expr,from);
+                }
+                self.visit_expr(scope,from,access);
+                self.visit_expr(scope,index,Access::Read);
+            },
+            _=>expr.children().into_iter().for_each(|e|self.visit_expr(scope,e,Access::Read)),
+        }
+    }
+    fn check_scope(&mut self,scope:Scope) {
+        let Some(item)=self.scopes.get(scope) else {return};
+        match item {
+            VarScopeItem::Var{data,..}|
+                VarScopeItem::Const{data,..}|
+                VarScopeItem::Static{data,..}=>self.visit_expr(scope,data,Access::Read),
+            _=>{},
+        }
+        for stmt in item.statements() {
+            match stmt {
+                Statement::PlaceAssign{place,data,..}|Statement::CompoundAssign{place,data,..}=>{
+                    self.check_binding_write(scope,place);
+                    self.visit_expr(scope,place,Access::Write);
+                    self.visit_expr(scope,data,Access::Read);
+                },
+                Statement::VarAssign{start,end,loc,data}=>{
+                    self.check_var_write(scope,*loc,(*start,*end));
+                    self.visit_expr(scope,data,Access::Read);
+                },
+                _=>stmt.exprs().into_iter().for_each(|e|self.visit_expr(scope,e,Access::Read)),
+            }
+        }
+    }
+}
+
+
+pub fn check_access<'input>(filename:&'input str,scopes:&Scopes<'input>)->Result<Vec<Error<'input,String>>,[Vec<Error<'input,String>>;2]> {
+    let mut state=State {
+        filename,
+        scopes,
+        errors:Vec::new(),
+    };
+    let mut keys=scopes.var_scopes.keys().copied().collect::<Vec<_>>();
+    keys.sort_by_key(|s|s.0);
+    for scope in keys {
+        state.check_scope(scope);
+    }
+    state.errors.sort_by_key(|e|e.start.index);
+    if state.errors.is_empty() {
+        return Ok(Vec::new());
+    } else {
+        return Err([state.errors,Vec::new()]);
+    }
+}
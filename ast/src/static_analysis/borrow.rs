@@ -0,0 +1,471 @@
+//! Checks that references follow the borrowing rules. A value can have any number of `&`
+//! references or a single `&mut` reference at a time, and it can't be used while a `&mut` reference
+//! to it is alive. A reference is alive until the last use of the variable holding it, or until the
+//! end of the statement if it isn't stored anywhere.
+//!
+//! `&mut` references are moved when they are used as a value, since there can only be one, so the
+//! variable they were moved out of can't be used after that. References also can't outlive the
+//! variable they borrow. Only `mut` variables can be borrowed as `&mut`.
+use std::collections::HashMap;
+use cppl_error::{
+    Location,
+    Error,
+};
+use crate::refined::*;
+use super::type_check::expr_type;
+
+
+#[derive(Copy,Clone,PartialEq)]
+enum BorrowKind {
+    Shared,
+    Mut,
+}
+/// How a value is used.
+#[derive(Copy,Clone)]
+enum Use {
+    /// Only read, like the operands of `+` or the object of a field access.
+    Copy,
+    /// Given away, like an argument or the value of a variable.
+    Move,
+}
+#[derive(Copy,Clone)]
+struct Borrow {
+    /// The variable that is borrowed.
+    var:Scope,
+    kind:BorrowKind,
+    start:Location,
+    end:Location,
+    /// The variable holding the reference. `None` if the reference is only used in the statement it
+    /// is created in.
+    holder:Option<Scope>,
+    /// The statement the borrow was made or stored in.
+    statement:usize,
+}
+struct State<'a,'input> {
+    filename:&'input str,
+    scopes:&'a Scopes<'input>,
+    /// Where each variable is used for the last time.
+    last_use:HashMap<Scope,(Location,Location)>,
+    /// The function being checked. `None` for the top level of the file.
+    function:Option<Scope>,
+    borrows:Vec<Borrow>,
+    statement_count:usize,
+    /// The statement being checked.
+    statement:usize,
+    /// Variables holding a `&mut` reference that has been moved out, and where it was moved.
+    moved:HashMap<Scope,(Location,Location)>,
+    errors:Vec<Error<'input,String>>,
+}
+impl<'a,'input> State<'a,'input> {
+    fn name(&self,var:Scope)->&'input str {
+        self.scopes.get(var).and_then(|v|v.name()).unwrap_or("?")
+    }
+    fn original(&self,var:Scope)->Scope {
+        original(self.scopes,var)
+    }
+    /// The variable a place like `a.b[c]` is part of. Places behind a dereference are not part of
+    /// any variable.
+    fn root(&self,place:&Expr<'input>)->Option<Scope> {
+        match place {
+            Expr::Var{path,..}=>Some(self.original(*path)),
+            Expr::FieldAccess{from,..}|Expr::Index{from,..}|Expr::Slice{from,..}=>self.root(from),
+            _=>None,
+        }
+    }
+    /// The variable that has to be `mut` to borrow `place` mutably. Places behind a reference or
+    /// pointer don't need one since they are changed through it.
+    fn mutable_root(&self,place:&Expr<'input>)->Option<Scope> {
+        let ty=expr_type(self.filename,self.scopes,place);
+        if let Type::Ref{..}|Type::RefMut{..}|Type::Pointer{..}|Type::PointerMut{..}=self.scopes.expand_alias(&ty) {
+            return None;
+        }
+        match place {
+            Expr::Var{path,..}=>Some(self.original(*path)),
+            Expr::FieldAccess{from,..}|Expr::Index{from,..}|Expr::Slice{from,..}=>self.mutable_root(from),
+            _=>None,
+        }
+    }
+    fn check_mutable(&mut self,place:&Expr<'input>,(start,end):(Location,Location)) {
+        let Some(var)=self.mutable_root(place) else {return};
+        let Some(item)=self.scopes.get(var) else {return};
+        let mutable=match item {
+            VarScopeItem::Var{mutable,..}|VarScopeItem::Static{mutable,..}=>mutable.is_some(),
+            VarScopeItem::Parameter{mutable,..}=>*mutable,
+            _=>false,
+        };
+        if !mutable {
+            let name=self.name(var);
+            let mut err=Error::new_verif(self.filename,start,end,format!("can not borrow `{}` as mutable because it is not mutable",name));
+            if let Some((def_start,def_end))=item.def_span() {
+                err=err.with_note(def_start,def_end,format!("declare `{}` with `mut` to borrow it as mutable",name));
+            }
+            self.errors.push(err);
+        }
+    }
+    /// Whether values of type `ty` can hold references.
+    fn holds_refs(&self,ty:&Type<'input>,depth:usize)->bool {
+        if depth>8 {
+            return false;
+        }
+        match self.scopes.expand_alias(ty) {
+            Type::Ref{..}|Type::RefMut{..}=>true,
+            Type::Slice{inner,..}=>self.holds_refs(inner,depth+1),
+            Type::Object{fields,..}=>fields.iter().any(|f|self.holds_refs(&f.ty,depth+1)),
+            Type::Union{items,..}|Type::Composite{items,..}=>items.iter().any(|t|self.holds_refs(t,depth+1)),
+            _=>false,
+        }
+    }
+    fn var_holds_refs(&self,var:Scope)->bool {
+        self.scopes.get(var).and_then(|v|v.var_type()).is_some_and(|ty|self.holds_refs(ty,0))
+    }
+    /// Whether a borrow is still alive at `index`.
+    fn is_live(&self,borrow:&Borrow,index:usize)->bool {
+        match borrow.holder {
+            None=>true,
+            Some(holder)=>self.last_use.get(&holder).is_some_and(|(start,_)|start.index>=index),
+        }
+    }
+    /// The first borrow of `var` alive at `index` that conflicts with a borrow of kind `kind`.
+    /// `None` for `kind` looks for anything that stops `var` from being changed.
+    fn conflict(&self,var:Scope,kind:Option<BorrowKind>,index:usize)->Option<Borrow> {
+        self.borrows.iter()
+            .filter(|b|b.var==var&&self.is_live(b,index))
+            .find(|b|kind!=Some(BorrowKind::Shared)||b.kind==BorrowKind::Mut)
+            .copied()
+    }
+    fn check_moved(&mut self,var:Scope,start:Location,end:Location) {
+        if let Some(&(move_start,move_end))=self.moved.get(&var) {
+            let name=self.name(var);
+            let err=Error::new_verif(self.filename,start,end,format!("use of moved value `{}`",name))
+                .with_note(move_start,move_end,format!("`{}` was moved here",name));
+            self.errors.push(err);
+        }
+    }
+    fn borrow(&mut self,place:&Expr<'input>,kind:BorrowKind,(start,end):(Location,Location),record:bool) {
+        if kind==BorrowKind::Mut {
+            self.check_mutable(place,(start,end));
+        }
+        let Some(var)=self.root(place) else {return};
+        if let Some(other)=self.conflict(var,Some(kind),start.index) {
+            let name=self.name(var);
+            let (reason,note)=match (kind,other.kind) {
+                (BorrowKind::Mut,BorrowKind::Mut)=>(
+                    format!("can not borrow `{}` as mutable more than once at a time",name),
+                    format!("first mutable borrow of `{}` is here",name),
+                ),
+                (BorrowKind::Mut,BorrowKind::Shared)=>(
+                    format!("can not borrow `{}` as mutable because it is also borrowed as immutable",name),
+                    format!("`{}` is borrowed as immutable here",name),
+                ),
+                (BorrowKind::Shared,_)=>(
+                    format!("can not borrow `{}` as immutable because it is also borrowed as mutable",name),
+                    format!("`{}` is borrowed as mutable here",name),
+                ),
+            };
+            self.errors.push(Error::new_verif(self.filename,start,end,reason).with_note(other.start,other.end,note));
+        }
+        if record {
+            self.borrows.push(Borrow{var,kind,start,end,holder:None,statement:self.statement});
+        }
+    }
+    /// Visits the parts of a place that is borrowed instead of read.
+    fn visit_place(&mut self,place:&Expr<'input>) {
+        match place {
+            Expr::Var{path,start,end}=>self.check_moved(self.original(*path),*start,*end),
+            Expr::FieldAccess{from,..}=>self.visit_place(from),
+            Expr::Index{from,index,..}=>{
+                self.visit_place(from);
+                self.visit_expr(index,Use::Copy);
+            },
+            Expr::Slice{from,range_start,range_end,..}=>{
+                self.visit_place(from);
+                range_start.iter().chain(range_end.iter()).for_each(|e|self.visit_expr(e,Use::Copy));
+            },
+            _=>self.visit_expr(place,Use::Copy),
+        }
+    }
+    /// Reports a write to `var` while it is borrowed. The write happens at `index`, after the value
+    /// being written is worked out.
+    fn check_write(&mut self,var:Scope,(start,end):(Location,Location),index:usize) {
+        if let Some(other)=self.conflict(var,None,index) {
+            let name=self.name(var);
+            let err=Error::new_verif(self.filename,start,end,format!("can not assign to `{}` while it is borrowed",name))
+                .with_note(other.start,other.end,format!("`{}` is borrowed here",name));
+            self.errors.push(err);
+        }
+    }
+    fn visit_expr(&mut self,expr:&Expr<'input>,how:Use) {
+        match expr {
+            Expr::Var{path,start,end}=>{
+                let var=self.original(*path);
+                self.check_moved(var,*start,*end);
+                if let Some(other)=self.conflict(var,Some(BorrowKind::Shared),start.index) {
+                    let name=self.name(var);
+                    let err=Error::new_verif(self.filename,*start,*end,format!("can not use `{}` while it is borrowed as mutable",name))
+                        .with_note(other.start,other.end,format!("`{}` is borrowed as mutable here",name));
+                    self.errors.push(err);
+                }
+                let is_ref_mut=self.scopes.get(var)
+                    .and_then(|v|v.var_type())
+                    .is_some_and(|ty|matches!(self.scopes.expand_alias(ty),Type::RefMut{..}));
+                if let (Use::Move,true)=(how,is_ref_mut) {
+                    self.moved.insert(var,(*start,*end));
+                }
+            },
+            Expr::Ref{val,..}=>{
+                self.visit_place(val);
+                self.borrow(val,BorrowKind::Shared,expr.span(),true);
+            },
+            Expr::RefMut{val,..}=>{
+                self.visit_place(val);
+                self.borrow(val,BorrowKind::Mut,expr.span(),true);
+            },
+            Expr::MethodCall{from,args,method,..}=>{
+                // Methods taking `mut this` borrow what they are called on mutably for the call.
+                let this_mut=method.and_then(|m|self.scopes.get(m)).is_some_and(|m|match m {
+                    VarScopeItem::Function{params,..}=>params.first()
+                        .and_then(|p|self.scopes.get(*p))
+                        .is_some_and(|p|matches!(p,VarScopeItem::Parameter{name:"this",mutable:true,..})),
+                    _=>false,
+                });
+                if this_mut {
+                    self.visit_place(from);
+                    self.borrow(from,BorrowKind::Mut,from.span(),false);
+                } else {
+                    self.visit_expr(from,Use::Copy);
+                }
+                args.iter().for_each(|a|self.visit_expr(a,Use::Move));
+            },
+            Expr::FunctionCall{args,..}|Expr::UnknownFunctionCall{args,..}=>args.iter().for_each(|a|self.visit_expr(a,Use::Move)),
+            Expr::List{items,..}=>items.iter().for_each(|i|self.visit_expr(i,Use::Move)),
+            Expr::ObjectCreation{fields,..}=>fields.iter().for_each(|f|self.visit_expr(&f.data,Use::Move)),
+            Expr::Block{block,..}|Expr::Unsafe{block,..}|Expr::ForeverLoop{block,..}=>self.visit_block(*block),
+            Expr::WhileLoop{condition,block,..}=>{
+                self.visit_expr(condition,Use::Copy);
+                self.visit_block(*block);
+            },
+            Expr::ForLoop{iterator,block,..}=>{
+                self.visit_expr(iterator,Use::Move);
+                self.visit_block(*block);
+            },
+            Expr::If{branches,else_block,..}=>{
+                for (condition,block) in branches {
+                    self.visit_expr(condition,Use::Copy);
+                    self.visit_block(*block);
+                }
+                if let Some(block)=else_block {
+                    self.visit_block(*block);
+                }
+            },
+            Expr::Match{block,..}=>{
+                self.visit_expr(&block.to_match,Use::Copy);
+                for (pattern,leaf) in block.leafs.iter() {
+                    pattern.exprs().into_iter().for_each(|e|self.visit_expr(e,Use::Copy));
+                    self.visit_expr(leaf,how);
+                }
+            },
+            // Anonymous functions are checked on their own.
+            Expr::AnonFunction{..}=>{},
+            _=>expr.children().into_iter().for_each(|e|self.visit_expr(e,Use::Copy)),
+        }
+    }
+    /// The variables whose references end up in the value of `expr`.
+    fn sources(&self,expr:&Expr<'input>,out:&mut Vec<Scope>) {
+        match expr {
+            Expr::Var{path,..}=>out.push(self.original(*path)),
+            _=>expr.children().into_iter().for_each(|e|self.sources(e,out)),
+        }
+    }
+    /// Gives the references made in this statement and the ones held by variables used in `data` to
+    /// `holder`.
+    fn store(&mut self,holder:Scope,data:&Expr<'input>) {
+        let statement=self.statement;
+        let mut sources=Vec::new();
+        self.sources(data,&mut sources);
+        let mut held=self.borrows.iter()
+            .filter(|b|b.holder.is_some_and(|h|sources.contains(&h)))
+            .map(|b|Borrow{holder:Some(holder),statement,..*b})
+            .collect::<Vec<_>>();
+        for borrow in self.borrows.iter_mut().filter(|b|b.holder.is_none()&&b.statement==statement) {
+            borrow.holder=Some(holder);
+        }
+        self.borrows.append(&mut held);
+        self.check_outlives(holder);
+    }
+    /// Reports references just given to `holder` that borrow variables in blocks that end before
+    /// `holder` is last used.
+    fn check_outlives(&mut self,holder:Scope) {
+        let Some(&(use_start,use_end))=self.last_use.get(&holder) else {return};
+        let Some(holder_block)=self.scopes.get(holder).and_then(|h|h.parent_scope()) else {return};
+        let mut errors=Vec::new();
+        for borrow in self.borrows.iter().filter(|b|b.holder==Some(holder)&&b.statement==self.statement) {
+            let Some(block)=self.scopes.get(borrow.var).and_then(|v|v.parent_scope()) else {continue};
+            let Some((_,block_end))=self.scopes.get(block).and_then(|b|b.def_span()) else {continue};
+            if block!=holder_block&&use_start.index>=block_end.index {
+                let name=self.name(borrow.var);
+                errors.push(Error::new_verif(self.filename,borrow.start,borrow.end,format!("`{}` does not live long enough",name))
+                    .with_note(use_start,use_end,format!("the reference is used here, after the block `{}` is in ends",name)));
+            }
+        }
+        self.errors.append(&mut errors);
+    }
+    /// Whether `var` is defined inside of the function being checked.
+    fn is_local(&self,var:Scope)->bool {
+        let Some(function)=self.function else {return false};
+        let mut current=self.scopes.get(var).and_then(|v|v.parent_scope());
+        while let Some(scope)=current {
+            if scope==function {
+                return true;
+            }
+            current=self.scopes.get(scope).and_then(|s|s.parent_scope());
+        }
+        return false;
+    }
+    /// Reports references to local variables returned from the function.
+    fn check_return(&mut self,val:&Expr<'input>) {
+        let returns_refs=match self.function.and_then(|f|self.scopes.get(f)) {
+            Some(VarScopeItem::Function{ret_type:Some(ty),..}|VarScopeItem::AnonFunction{ret_type:Some(ty),..})=>self.holds_refs(ty,0),
+            _=>false,
+        };
+        if !returns_refs {
+            return;
+        }
+        let mut sources=Vec::new();
+        self.sources(val,&mut sources);
+        let escaping=self.borrows.iter()
+            .filter(|b|match b.holder {
+                None=>b.statement==self.statement,
+                Some(holder)=>sources.contains(&holder),
+            })
+            .copied()
+            .filter(|b|self.is_local(b.var))
+            .collect::<Vec<_>>();
+        let (start,end)=val.span();
+        for borrow in escaping {
+            let name=self.name(borrow.var);
+            let mut err=Error::new_verif(self.filename,start,end,format!("can not return a reference to the local variable `{}`",name));
+            if let Some((def_start,def_end))=self.scopes.get(borrow.var).and_then(|v|v.def_span()) {
+                err=err.with_note(def_start,def_end,format!("`{}` is declared here and does not live after the function returns",name));
+            }
+            self.errors.push(err);
+        }
+    }
+    fn visit_stmt(&mut self,stmt:&Statement<'input>) {
+        let outer=self.statement;
+        self.statement_count+=1;
+        self.statement=self.statement_count;
+        match stmt {
+            Statement::VarDef{def,..}=>if let Some(VarScopeItem::Var{data,..})=self.scopes.get(*def) {
+                self.visit_expr(data,Use::Move);
+                if self.var_holds_refs(*def) {
+                    self.store(*def,data);
+                }
+            },
+            Statement::VarAssign{start,end,loc,data}=>{
+                self.visit_expr(data,Use::Move);
+                let var=self.original(*loc);
+                self.check_write(var,(*start,*end),end.index);
+                // The old value is gone, along with anything it borrowed.
+                self.moved.remove(&var);
+                self.borrows.retain(|b|b.holder!=Some(var));
+                if self.var_holds_refs(var) {
+                    self.store(var,data);
+                }
+            },
+            Statement::PlaceAssign{end,place,data,..}|Statement::CompoundAssign{end,place,data,..}=>{
+                self.visit_expr(data,Use::Move);
+                match self.root(place) {
+                    Some(var)=>{
+                        self.visit_place(place);
+                        self.check_write(var,place.span(),end.index);
+                        if self.var_holds_refs(var) {
+                            self.store(var,data);
+                        }
+                    },
+                    None=>self.visit_expr(place,Use::Copy),
+                }
+            },
+            Statement::Return{val:Some(val),..}=>{
+                self.visit_expr(val,Use::Move);
+                self.check_return(val);
+            },
+            _=>stmt.exprs().into_iter().for_each(|e|self.visit_expr(e,Use::Move)),
+        }
+        // References that weren't stored anywhere only live until the end of the statement.
+        let statement=self.statement;
+        self.borrows.retain(|b|b.holder.is_some()||b.statement!=statement);
+        self.statement=outer;
+    }
+    fn visit_block(&mut self,block:Scope) {
+        let Some(item)=self.scopes.get(block) else {return};
+        for stmt in item.statements() {
+            self.visit_stmt(stmt);
+        }
+        // The variables of the block are gone, so nothing can use their references anymore.
+        let scopes=self.scopes;
+        self.borrows.retain(|b|scopes.get(b.var).and_then(|v|v.parent_scope())!=Some(block));
+    }
+}
+
+
+/// The variable reads and writes of `var` go to.
+fn original(scopes:&Scopes,mut var:Scope)->Scope {
+    while let Some(&VarScopeItem::Narrowed{original,..})=scopes.get(var) {
+        var=original;
+    }
+    return var;
+}
+/// Where every variable is used for the last time.
+fn last_uses(scopes:&Scopes)->HashMap<Scope,(Location,Location)> {
+    fn visit(expr:&Expr,scopes:&Scopes,out:&mut HashMap<Scope,(Location,Location)>) {
+        if let Expr::Var{path,start,end}=expr {
+            let last=out.entry(original(scopes,*path)).or_insert((*start,*end));
+            if last.0.index<start.index {
+                *last=(*start,*end);
+            }
+        }
+        expr.children().into_iter().for_each(|e|visit(e,scopes,out));
+    }
+    let mut ret=HashMap::new();
+    for item in scopes.var_scopes.values() {
+        if let VarScopeItem::Var{data,..}|VarScopeItem::Const{data,..}|VarScopeItem::Static{data,..}=item {
+            visit(data,scopes,&mut ret);
+        }
+        for expr in item.statements().iter().flat_map(|s|s.exprs()) {
+            visit(expr,scopes,&mut ret);
+        }
+    }
+    return ret;
+}
+pub fn check_borrows<'input>(filename:&'input str,scopes:&Scopes<'input>)->Result<Vec<Error<'input,String>>,[Vec<Error<'input,String>>;2]> {
+    let mut state=State {
+        filename,
+        scopes,
+        last_use:last_uses(scopes),
+        function:None,
+        borrows:Vec::new(),
+        statement_count:0,
+        statement:0,
+        moved:HashMap::new(),
+        errors:Vec::new(),
+    };
+    let mut keys=scopes.var_scopes.keys().copied().collect::<Vec<_>>();
+    keys.sort_by_key(|s|s.0);
+    // Every function is checked on its own, starting with the top level of the file.
+    for scope in keys {
+        match scopes.get(scope) {
+            Some(VarScopeItem::Root{..})=>state.function=None,
+            Some(VarScopeItem::Function{..}|VarScopeItem::AnonFunction{..})=>state.function=Some(scope),
+            _=>continue,
+        }
+        state.borrows.clear();
+        state.moved.clear();
+        state.visit_block(scope);
+    }
+    state.errors.sort_by_key(|e|e.start.index);
+    if state.errors.is_empty() {
+        return Ok(Vec::new());
+    } else {
+        return Err([state.errors,Vec::new()]);
+    }
+}
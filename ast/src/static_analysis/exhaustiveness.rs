@@ -0,0 +1,246 @@
+//! Checks that every `match` covers every value of what it matches on, and warns about patterns
+//! that can never be reached.
+//!
+//! The values are split into cases: the variants of an enum, the members of a union and `true` and
+//! `false` for `Bool`. Any other type is a single case that only a pattern matching all of it
+//! covers, like a variable or `is` with the same type.
+use cppl_error::{
+    Location,
+    Error,
+};
+use crate::refined::*;
+use super::type_check::expr_type;
+
+
+/// A group of values a pattern can cover.
+#[derive(Clone)]
+enum Case<'input> {
+    Bool(bool),
+    Type(Type<'input>),
+}
+/// How much of a case a pattern matches.
+#[derive(Copy,Clone,PartialEq,Eq,PartialOrd,Ord)]
+enum Coverage {
+    Never,
+    Some,
+    All,
+}
+struct State<'a,'input> {
+    filename:&'input str,
+    scopes:&'a Scopes<'input>,
+    errors:Vec<Error<'input,String>>,
+    warnings:Vec<Error<'input,String>>,
+}
+impl<'a,'input> State<'a,'input> {
+    fn cases(&self,ty:&Type<'input>,depth:usize)->Vec<Case<'input>> {
+        if depth>8 {
+            return vec![Case::Type(ty.clone())];
+        }
+        match self.scopes.expand_alias(ty) {
+            Type::Bool=>vec![Case::Bool(true),Case::Bool(false)],
+            Type::Never=>Vec::new(),
+            Type::Union{items,..}=>items.iter().flat_map(|t|self.cases(t,depth+1)).collect(),
+            Type::Named{path,..}=>match self.scopes.get(*path) {
+                Some(VarScopeItem::Enum{variants,..})=>variants.iter().flat_map(|t|self.cases(t,depth+1)).collect(),
+                _=>vec![Case::Type(ty.clone())],
+            },
+            _=>vec![Case::Type(ty.clone())],
+        }
+    }
+    fn case_name(&self,case:&Case<'input>)->String {
+        match case {
+            Case::Bool(b)=>format!("`{}`",b),
+            Case::Type(ty)=>format!("`{}`",self.scopes.type_name(ty)),
+        }
+    }
+    /// How much of a value of type `ty` a pattern matching `pattern_ty` covers.
+    fn type_coverage(&self,ty:&Type<'input>,pattern_ty:&Type<'input>)->Coverage {
+        if self.scopes.check_assignable(ty,pattern_ty).is_ok() {
+            Coverage::All
+        } else if self.scopes.check_assignable(pattern_ty,ty).is_ok() {
+            Coverage::Some
+        } else {
+            Coverage::Never
+        }
+    }
+    fn coverage(&self,pattern:&MatchPattern<'input>,case:&Case<'input>)->Coverage {
+        match (pattern,case) {
+            (MatchPattern::Var{..},_)=>Coverage::All,
+            (MatchPattern::Data{inner:Data::Bool{data,..},..},Case::Bool(b))=>if data==b {
+                Coverage::All
+            } else {
+                Coverage::Never
+            },
+            (MatchPattern::Data{inner,..},Case::Type(ty))=>match self.type_coverage(&data_type(inner),ty) {
+                Coverage::Never=>Coverage::Never,
+                _=>Coverage::Some,
+            },
+            (MatchPattern::Data{..},Case::Bool(_))=>Coverage::Never,
+            (MatchPattern::IsType{inner,..},Case::Bool(_))=>self.type_coverage(&Type::Bool,inner),
+            (MatchPattern::IsType{inner,..},Case::Type(ty))=>self.type_coverage(ty,inner),
+            (MatchPattern::Structure{..},Case::Bool(_))=>Coverage::Never,
+            (MatchPattern::Structure{structure,..},Case::Type(ty))=>self.structure_coverage(structure,ty,0),
+            // Comparisons and method calls depend on the value.
+            _=>Coverage::Some,
+        }
+    }
+    fn structure_coverage(&self,structure:&MatchPatternStructure<'input>,ty:&Type<'input>,depth:usize)->Coverage {
+        match structure {
+            MatchPatternStructure::Block{exact,block,..}=>self.fields_coverage(*exact,block,ty,depth),
+            MatchPatternStructure::TypedBlock{exact,ty:pattern_ty,block,..}=>{
+                match self.type_coverage(ty,pattern_ty) {
+                    Coverage::Never=>Coverage::Never,
+                    Coverage::Some=>self.fields_coverage(*exact,block,pattern_ty,depth).min(Coverage::Some),
+                    Coverage::All=>self.fields_coverage(*exact,block,pattern_ty,depth),
+                }
+            },
+        }
+    }
+    fn fields_coverage(&self,exact:bool,items:&[MatchPatternStructureItem<'input>],ty:&Type<'input>,depth:usize)->Coverage {
+        if depth>8 {
+            return Coverage::Some;
+        }
+        let Type::Object{fields,exact:ty_exact,..}=self.scopes.expand_alias(ty) else {
+            return match self.scopes.expand_alias(ty) {
+                Type::Unknown|Type::UnknownNamed{..}|Type::Generic{..}=>Coverage::Some,
+                _=>Coverage::Never,
+            };
+        };
+        let mut coverage=Coverage::All;
+        for item in items {
+            let (name,block)=match item {
+                MatchPatternStructureItem::Field{name,..}|MatchPatternStructureItem::NamedField{name,..}=>(*name,None),
+                MatchPatternStructureItem::NamedBlock{name,block,..}=>(*name,Some(block)),
+            };
+            let Some(field)=fields.iter().find(|f|f.name==name) else {
+                // Values of an inexact type can have more fields than the type says.
+                if *ty_exact {
+                    return Coverage::Never;
+                }
+                coverage=Coverage::Some;
+                continue;
+            };
+            if let Some(block)=block {
+                coverage=coverage.min(self.structure_coverage(block,&field.ty,depth+1));
+            }
+        }
+        if exact {
+            let all_named=fields.iter().all(|f|items.iter().any(|i|item_name(i)==f.name));
+            match (ty_exact,all_named) {
+                (true,false)=>return Coverage::Never,
+                (false,_)=>coverage=coverage.min(Coverage::Some),
+                _=>{},
+            }
+        }
+        return coverage;
+    }
+    fn check_match(&mut self,block:&Match<'input>) {
+        let ty=expr_type(self.filename,self.scopes,&block.to_match);
+        if matches!(self.scopes.expand_alias(&ty),Type::Unknown|Type::UnknownNamed{..}) {
+            return;
+        }
+        let mut remaining=self.cases(&ty,0);
+        // Types that aren't split into cases are only covered by patterns matching all of them.
+        let whole=matches!(&remaining[..],[Case::Type(_)]);
+        // The cases matched so far and the pattern that matched them.
+        let mut matched:Vec<(Case<'input>,(Location,Location))>=Vec::new();
+        for (pattern,_) in block.leafs.iter() {
+            let coverage=remaining.iter().map(|c|self.coverage(pattern,c)).collect::<Vec<_>>();
+            let (start,end)=pattern.span();
+            if coverage.iter().all(|c|*c==Coverage::Never) {
+                // Every earlier pattern that matched some of the values this one would have.
+                let mut earlier:Vec<(Location,Location)>=Vec::new();
+                for (case,span) in matched.iter() {
+                    if self.coverage(pattern,case)!=Coverage::Never&&!earlier.contains(span) {
+                        earlier.push(*span);
+                    }
+                }
+                let warning=match &earlier[..] {
+                    []=>Error::new_warning(self.filename,start,end,format!("this pattern can never match a `{}`",self.scopes.type_name(&ty))),
+                    [(matched_start,matched_end)]=>Error::new_warning(self.filename,start,end,"unreachable pattern")
+                        .with_note(*matched_start,*matched_end,"everything it matches is already matched here"),
+                    _=>earlier.iter().fold(
+                        Error::new_warning(self.filename,start,end,"unreachable pattern"),
+                        |warning,(matched_start,matched_end)|warning.with_note(*matched_start,*matched_end,"some of what it matches is already matched here"),
+                    ),
+                };
+                self.warnings.push(warning);
+                continue;
+            }
+            let mut coverage=coverage.into_iter();
+            let (now_matched,rest)=remaining.into_iter().partition::<Vec<_>,_>(|_|coverage.next()==Some(Coverage::All));
+            matched.extend(now_matched.into_iter().map(|c|(c,(start,end))));
+            remaining=rest;
+        }
+        if remaining.is_empty() {
+            return;
+        }
+        let (start,end)=block.to_match.span();
+        let reason=if whole {
+            format!("non-exhaustive match: not every `{}` is matched, add a pattern that matches anything",self.scopes.type_name(&ty))
+        } else {
+            let names=remaining.iter().map(|c|self.case_name(c)).collect::<Vec<_>>();
+            let names=match names.split_last() {
+                Some((last,rest)) if !rest.is_empty()=>format!("{} and {}",rest.join(", "),last),
+                _=>names.join(""),
+            };
+            let verb=if remaining.len()==1 {"is"} else {"are"};
+            format!("non-exhaustive match: {} {} not matched",names,verb)
+        };
+        self.errors.push(Error::new_verif(self.filename,start,end,reason));
+    }
+    fn visit_expr(&mut self,expr:&Expr<'input>) {
+        if let Expr::Match{block,..}=expr {
+            self.check_match(block);
+        }
+        expr.children().into_iter().for_each(|e|self.visit_expr(e));
+    }
+}
+
+
+fn item_name<'input>(item:&MatchPatternStructureItem<'input>)->&'input str {
+    match item {
+        MatchPatternStructureItem::Field{name,..}|
+            MatchPatternStructureItem::NamedField{name,..}|
+            MatchPatternStructureItem::NamedBlock{name,..}=>name,
+    }
+}
+fn data_type<'input>(data:&Data<'input>)->Type<'input> {
+    match data {
+        Data::String{..}=>Type::String,
+        Data::GenericNumber{..}=>Type::GenericNumber,
+        Data::GenericFloat{..}=>Type::GenericFloat,
+        Data::UInt{..}=>Type::Uint,
+        Data::Int{..}=>Type::Int,
+        Data::Float{..}=>Type::Float,
+        Data::LargeFloat{..}=>Type::DoubleFloat,
+        Data::Char{..}=>Type::Char,
+        Data::Bool{..}=>Type::Bool,
+    }
+}
+pub fn check_matches<'input>(filename:&'input str,scopes:&Scopes<'input>)->Result<Vec<Error<'input,String>>,[Vec<Error<'input,String>>;2]> {
+    let mut state=State {
+        filename,
+        scopes,
+        errors:Vec::new(),
+        warnings:Vec::new(),
+    };
+    let mut keys=scopes.var_scopes.keys().copied().collect::<Vec<_>>();
+    keys.sort_by_key(|s|s.0);
+    for scope in keys {
+        let Some(item)=scopes.get(scope) else {continue};
+        if let VarScopeItem::Var{data,..}|VarScopeItem::Const{data,..}|VarScopeItem::Static{data,..}=item {
+            state.visit_expr(data);
+        }
+        for expr in item.statements().iter().flat_map(|s|s.exprs()) {
+            state.visit_expr(expr);
+        }
+    }
+    state.errors.sort_by_key(|e|e.start.index);
+    state.warnings.sort_by_key(|e|e.start.index);
+    if state.errors.is_empty() {
+        return Ok(state.warnings);
+    } else {
+        return Err([state.errors,state.warnings]);
+    }
+}
@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+use cppl_error::{
+    Location,
+    Error,
+};
+use crate::refined::*;
+use super::type_check::{
+    call_params,
+    method_call_params,
+    expr_type,
+};
+
+
+/// A place where a variable holding a number literal was used as a specific number type.
+#[derive(Copy,Clone)]
+struct Use<'input> {
+    start:Location,
+    end:Location,
+    var:&'input str,
+}
+struct State<'a,'input> {
+    filename:&'input str,
+    scopes:&'a Scopes<'input>,
+    /// Variables that still have a `{number}` or `{float}` type, and which other variable they
+    /// have to have the same type as.
+    links:HashMap<Scope,Scope>,
+    /// The concrete types each group of linked variables was used as.
+    uses:HashMap<Scope,Vec<(Type<'input>,Use<'input>)>>,
+    errors:Vec<Error<'input,String>>,
+}
+impl<'a,'input> State<'a,'input> {
+    fn find(&self,mut var:Scope)->Scope {
+        while let Some(&next)=self.links.get(&var) {
+            if next==var {
+                break;
+            }
+            var=next;
+        }
+        return var;
+    }
+    fn link(&mut self,a:Scope,b:Scope) {
+        let (a,b)=(self.find(a),self.find(b));
+        if a!=b {
+            self.links.insert(a,b);
+            let mut uses=self.uses.remove(&a).unwrap_or_default();
+            self.uses.entry(b).or_default().append(&mut uses);
+        }
+    }
+    /// The undecided variables whose value ends up in the value of `expr`.
+    fn sources(&self,expr:&Expr<'input>)->Vec<Scope> {
+        use Expr::*;
+        match expr {
+            Var{path,..} if self.links.contains_key(path)=>vec![*path],
+            Add{inner,..}|
+                Sub{inner,..}|
+                Mul{inner,..}|
+                Div{inner,..}|
+                Mod{inner,..}|
+                BitAnd{inner,..}|
+                BitOr{inner,..}|
+                BitXor{inner,..}=>inner.iter().flat_map(|e|self.sources(e)).collect(),
+            ShiftLeft{inner,..}|ShiftRight{inner,..}=>self.sources(&inner[0]),
+            Negate{inner,..}=>self.sources(inner),
+            _=>Vec::new(),
+        }
+    }
+    /// Records that `expr` is used where a `ty` is expected.
+    fn constrain(&mut self,expr:&Expr<'input>,ty:&Type<'input>) {
+        let ty=self.scopes.expand_alias(ty);
+        if !matches!(ty,Type::Uint|Type::Int|Type::Byte|Type::Float|Type::DoubleFloat) {
+            return;
+        }
+        let (start,end)=expr.span();
+        for var in self.sources(expr) {
+            let Some(name)=self.scopes.get(var).and_then(|v|v.name()) else {continue};
+            let group=self.find(var);
+            self.uses.entry(group).or_default().push((ty.clone(),Use{start,end,var:name}));
+        }
+    }
+    fn visit_expr(&mut self,expr:&Expr<'input>) {
+        use Expr::*;
+        for child in expr.children() {
+            self.visit_expr(child);
+        }
+        match expr {
+            FunctionCall{path,args,..}=>self.constrain_args(*path,args,false),
+            MethodCall{method:Some(method),args,..}=>self.constrain_args(*method,args,true),
+            MethodCall{method:None,from,name,args,..}=>{
+                let Some(params)=method_call_params(self.filename,self.scopes,from,name) else {return};
+                for (arg,(ty,_)) in args.iter().zip(params.iter()) {
+                    self.constrain(arg,ty);
+                }
+            },
+            Add{inner,..}|
+                Sub{inner,..}|
+                Mul{inner,..}|
+                Div{inner,..}|
+                Mod{inner,..}|
+                BitAnd{inner,..}|
+                BitOr{inner,..}|
+                BitXor{inner,..}|
+                Equal{inner,..}|
+                NotEqual{inner,..}|
+                GreaterEqual{inner,..}|
+                LessEqual{inner,..}|
+                Greater{inner,..}|
+                Less{inner,..}=>{
+                let [left,right]=&**inner;
+                self.constrain_operands(left,right);
+            },
+            _=>{},
+        }
+    }
+    /// Records that `left` and `right` are used together in an operation and so are the same type.
+    fn constrain_operands(&mut self,left:&Expr<'input>,right:&Expr<'input>) {
+        let left_ty=expr_type(self.filename,self.scopes,left);
+        let right_ty=expr_type(self.filename,self.scopes,right);
+        self.constrain(left,&right_ty);
+        self.constrain(right,&left_ty);
+        // Two undecided variables used together have to be the same type.
+        for a in self.sources(left) {
+            for b in self.sources(right) {
+                self.link(a,b);
+            }
+        }
+    }
+    fn constrain_args(&mut self,function:Scope,args:&[Expr<'input>],is_method:bool) {
+        let Some(params)=call_params(self.scopes,function,is_method) else {return};
+        for (arg,(ty,_)) in args.iter().zip(params.iter()) {
+            self.constrain(arg,ty);
+        }
+    }
+    fn visit_scope(&mut self,scope:Scope) {
+        let Some(item)=self.scopes.get(scope) else {return};
+        match item {
+            VarScopeItem::Var{ty,data,..}|
+                VarScopeItem::Const{ty,data,..}|
+                VarScopeItem::Static{ty,data,..}=>{
+                self.visit_expr(data);
+                self.constrain(data,ty);
+                if self.links.contains_key(&scope) {
+                    for source in self.sources(data) {
+                        self.link(scope,source);
+                    }
+                }
+            },
+            _=>{},
+        }
+        for stmt in item.statements() {
+            for expr in stmt.exprs() {
+                self.visit_expr(expr);
+            }
+            match stmt {
+                Statement::VarAssign{loc,data,..}=>{
+                    if let Some(ty)=self.scopes.get(*loc).and_then(|v|v.var_type()) {
+                        self.constrain(data,ty);
+                    }
+                    if self.links.contains_key(loc) {
+                        let data_ty=expr_type(self.filename,self.scopes,data);
+                        let (start,end)=data.span();
+                        let var=Expr::Var{start,end,path:*loc};
+                        self.constrain(&var,&data_ty);
+                        for source in self.sources(data) {
+                            self.link(*loc,source);
+                        }
+                    }
+                },
+                Statement::PlaceAssign{place,data,..}=>{
+                    let ty=expr_type(self.filename,self.scopes,place);
+                    self.constrain(data,&ty);
+                },
+                Statement::CompoundAssign{place,data,..}=>self.constrain_operands(place,data),
+                Statement::Return{val:Some(val),..}=>{
+                    let ret=self.enclosing_ret_type(scope);
+                    if let Some(ret)=ret {
+                        self.constrain(val,ret);
+                    }
+                },
+                _=>{},
+            }
+        }
+    }
+    fn enclosing_ret_type(&self,mut scope:Scope)->Option<&'a Type<'input>> {
+        loop {
+            match self.scopes.get(scope)? {
+                VarScopeItem::Function{ret_type,..}|VarScopeItem::AnonFunction{ret_type,..}=>return ret_type.as_ref(),
+                item=>scope=item.parent_scope()?,
+            }
+        }
+    }
+    /// Decides what type each group of variables is. Groups that were only ever used as a
+    /// `{number}` default to `Int` and groups with a float in them default to `Float`.
+    fn settle(&mut self)->HashMap<Scope,Type<'input>> {
+        let mut groups:HashMap<Scope,Vec<Scope>>=HashMap::new();
+        let mut vars=self.links.keys().copied().collect::<Vec<_>>();
+        vars.sort_by_key(|s|s.0);
+        for var in vars {
+            groups.entry(self.find(var)).or_default().push(var);
+        }
+        let mut groups=groups.into_iter().collect::<Vec<_>>();
+        groups.sort_by_key(|(group,_)|group.0);
+        let mut ret=HashMap::new();
+        for (group,vars) in groups {
+            let mut uses=self.uses.remove(&group).unwrap_or_default();
+            uses.sort_by_key(|(_,u)|u.start.index);
+            let ty=match uses.first() {
+                Some((first_ty,first_use))=>{
+                    let conflict=uses.iter().find(|(ty,_)|std::mem::discriminant(ty)!=std::mem::discriminant(first_ty));
+                    if let Some((ty,conflict))=conflict {
+                        let err=Error::new_verif(self.filename,conflict.start,conflict.end,format!(
+                            "the type of `{}` is ambiguous: it is used as both `{}` and `{}`",
+                            conflict.var,
+                            self.scopes.type_name(first_ty),
+                            self.scopes.type_name(ty),
+                        )).with_note(first_use.start,first_use.end,format!("`{}` is used as `{}` here",first_use.var,self.scopes.type_name(first_ty)));
+                        self.errors.push(err);
+                        continue;
+                    }
+                    first_ty.clone()
+                },
+                None=>{
+                    let float=vars.iter()
+                        .any(|v|matches!(self.scopes.get(*v).and_then(|v|v.var_type()),Some(Type::GenericFloat)));
+                    if float {Type::Float} else {Type::Int}
+                },
+            };
+            for var in vars {
+                ret.insert(var,ty.clone());
+            }
+        }
+        return ret;
+    }
+}
+
+
+fn set_var_type<'input>(scopes:&mut Scopes<'input>,var:Scope,new_ty:Type<'input>) {
+    if let Some(VarScopeItem::Var{ty,..}|VarScopeItem::Parameter{ty,..}|VarScopeItem::MatchBlockVar{ty,..})=scopes.get_mut(var) {
+        *ty=new_ty;
+    }
+}
+/// Finds the first expression `f` picks out in the parent of `scope`, which is where the expression
+/// that made `scope` is. This includes the initializers of the variables defined there.
+fn find_in_parent<'a,'input,T>(scopes:&'a Scopes<'input>,scope:Scope,f:&dyn Fn(&'a Expr<'input>)->Option<T>)->Option<T> {
+    fn find<'a,'input,T>(expr:&'a Expr<'input>,f:&dyn Fn(&'a Expr<'input>)->Option<T>)->Option<T> {
+        f(expr).or_else(||expr.children().into_iter().find_map(|e|find(e,f)))
+    }
+    let parent=scopes.get(scopes.get(scope)?.parent_scope()?)?;
+    parent.statements().iter().find_map(|stmt|match stmt {
+        Statement::VarDef{def,..}|
+            Statement::StaticVarDef{def,..}|
+            Statement::ConstVarDef{def,..}=>match scopes.get(*def) {
+            Some(VarScopeItem::Var{data,..}|VarScopeItem::Const{data,..}|VarScopeItem::Static{data,..})=>find(data,f),
+            _=>None,
+        },
+        stmt=>stmt.exprs().into_iter().find_map(|e|find(e,f)),
+    })
+}
+/// The iterator of the `for` loop with the block `block`.
+fn for_loop_iterator<'a,'input>(scopes:&'a Scopes<'input>,block:Scope)->Option<&'a Expr<'input>> {
+    find_in_parent(scopes,block,&|expr|match expr {
+        Expr::ForLoop{iterator,block:b,..} if *b==block=>Some(&**iterator),
+        _=>None,
+    })
+}
+/// What is matched on by the `match` arm `arm` and the pattern of the arm.
+fn match_arm<'a,'input>(scopes:&'a Scopes<'input>,arm:Scope,var:Scope)->Option<(&'a Expr<'input>,&'a MatchPattern<'input>)> {
+    find_in_parent(scopes,arm,&|expr|match expr {
+        Expr::Match{block,..}=>block.leafs.iter()
+            .find(|(pattern,_)|pattern_var_type(scopes,pattern,&Type::Unknown,var).is_some())
+            .map(|(pattern,_)|(&block.to_match,pattern)),
+        _=>None,
+    })
+}
+/// The type of the variable `var` bound by `pattern` when it matches a `ty`.
+fn pattern_var_type<'input>(scopes:&Scopes<'input>,pattern:&MatchPattern<'input>,ty:&Type<'input>,var:Scope)->Option<Type<'input>> {
+    fn structure<'input>(scopes:&Scopes<'input>,pattern:&MatchPatternStructure<'input>,ty:&Type<'input>,var:Scope)->Option<Type<'input>> {
+        let (ty,items)=match pattern {
+            MatchPatternStructure::Block{block,..}=>(ty,block),
+            MatchPatternStructure::TypedBlock{ty,block,..}=>(ty,block),
+        };
+        items.iter().find_map(|item|match item {
+            MatchPatternStructureItem::Field{name,var:v,..}|
+                MatchPatternStructureItem::NamedField{name,var:v,..} if *v==var=>Some(scopes.field_type(ty,name).unwrap_or(Type::Unknown)),
+            MatchPatternStructureItem::NamedBlock{name,block,..}=>{
+                structure(scopes,block,&scopes.field_type(ty,name).unwrap_or(Type::Unknown),var)
+            },
+            _=>None,
+        })
+    }
+    match pattern {
+        MatchPattern::Var{var:v,..} if *v==var=>Some(ty.clone()),
+        MatchPattern::Structure{structure:s,..}=>structure(scopes,s,ty,var),
+        _=>None,
+    }
+}
+/// Number literals inside other types, like in `[1, 2]` or `&5`, default to `Int` or `Float` right
+/// away. Only a variable that is a number itself has its type decided by how it is used.
+fn default_numbers<'input>(ty:Type<'input>)->Type<'input> {
+    fn inner(ty:Type)->Type {
+        match ty {
+            Type::GenericNumber=>Type::Int,
+            Type::GenericFloat=>Type::Float,
+            ty=>default_numbers(ty),
+        }
+    }
+    let boxed=|ty:Box<Type<'input>>|Box::new(inner(*ty));
+    match ty {
+        Type::Slice{start,end,inner}=>Type::Slice{start,end,inner:boxed(inner)},
+        Type::Ref{start,end,inner}=>Type::Ref{start,end,inner:boxed(inner)},
+        Type::RefMut{start,end,inner}=>Type::RefMut{start,end,inner:boxed(inner)},
+        Type::Pointer{start,end,inner}=>Type::Pointer{start,end,inner:boxed(inner)},
+        Type::PointerMut{start,end,inner}=>Type::PointerMut{start,end,inner:boxed(inner)},
+        Type::Named{start,end,path,generics}=>Type::Named{start,end,path,generics:generics.into_iter().map(inner).collect()},
+        Type::FunctionSig{start,end,inner:mut sig}=>{
+            for param in sig.params.normal.iter_mut() {
+                param.ty=inner(param.ty.clone());
+            }
+            sig.ret_type=sig.ret_type.map(inner);
+            Type::FunctionSig{start,end,inner:sig}
+        },
+        ty=>ty,
+    }
+}
+/// Whether there is a `{number}` or `{float}` somewhere inside of `ty`, but not `ty` itself.
+fn has_inner_numbers(ty:&Type)->bool {
+    fn has_numbers(ty:&Type)->bool {
+        matches!(ty,Type::GenericNumber|Type::GenericFloat)||has_inner_numbers(ty)
+    }
+    match ty {
+        Type::Slice{inner,..}|
+            Type::Ref{inner,..}|
+            Type::RefMut{inner,..}|
+            Type::Pointer{inner,..}|
+            Type::PointerMut{inner,..}=>has_numbers(inner),
+        Type::Named{generics,..}=>generics.iter().any(has_numbers),
+        Type::FunctionSig{inner,..}=>inner.params.normal.iter().any(|p|has_numbers(&p.ty))||inner.ret_type.as_ref().is_some_and(has_numbers),
+        _=>false,
+    }
+}
+/// The type `scope` gets from what defines it, if its type has to be inferred.
+fn defined_type<'input>(filename:&'input str,scopes:&Scopes<'input>,scope:Scope)->Option<Type<'input>> {
+    match scopes.get(scope)? {
+        VarScopeItem::Var{data,..}=>Some(expr_type(filename,scopes,data)),
+        // Variables bound by a pattern are whatever part of the matched value they are bound to.
+        VarScopeItem::MatchBlockVar{parent_scope,..}=>{
+            let (to_match,pattern)=match_arm(scopes,*parent_scope,scope)?;
+            let to_match_ty=expr_type(filename,scopes,to_match);
+            pattern_var_type(scopes,pattern,&to_match_ty,scope)
+        },
+        // The variable of a `for` loop is an item of what it loops over.
+        VarScopeItem::Parameter{parent_scope,..}=>{
+            let iterator=for_loop_iterator(scopes,*parent_scope)?;
+            let iterator_ty=expr_type(filename,scopes,iterator);
+            scopes.item_type(&iterator_ty).cloned()
+        },
+        _=>None,
+    }
+}
+/// Fills in the types of variables declared with `:=` from their initializers and decides which
+/// number type variables holding number literals are.
+pub fn infer<'input>(filename:&'input str,scopes:&mut Scopes<'input>)->Result<Vec<Error<'input,String>>,[Vec<Error<'input,String>>;2]> {
+    let mut inferred=scopes.var_scopes.iter()
+        .filter(|(_,item)|matches!(item,
+            VarScopeItem::Var{ty:Type::Unknown,..}|
+            VarScopeItem::MatchBlockVar{ty:Type::Unknown,..}|
+            VarScopeItem::Parameter{ty:Type::Unknown,..}
+        ))
+        .map(|(scope,item)|(*scope,item.def_span().map_or(0,|(start,_)|start.index)))
+        .collect::<Vec<_>>();
+    // Variables are inferred in the order they are written, so anything an initializer uses
+    // already has its type.
+    inferred.sort_by_key(|(scope,index)|(*index,scope.0));
+    let inferred=inferred.into_iter().map(|(scope,_)|scope).collect::<Vec<_>>();
+    let mut undecided=Vec::new();
+    // An initializer can use variables defined inside of it, like the ones bound by the arms of a
+    // `match`, so this goes on for as long as more types are found.
+    let mut pending=inferred.clone();
+    loop {
+        let count=pending.len();
+        let mut still_pending=Vec::new();
+        for scope in pending {
+            let data_ty=defined_type(filename,scopes,scope).unwrap_or(Type::Unknown);
+            if let Type::Unknown=data_ty {
+                still_pending.push(scope);
+                continue;
+            }
+            if matches!(data_ty,Type::GenericNumber|Type::GenericFloat) {
+                undecided.push(scope);
+            }
+            set_var_type(scopes,scope,data_ty);
+        }
+        pending=still_pending;
+        if pending.len()==count {
+            break;
+        }
+    }
+    let mut errors=Vec::new();
+    if !undecided.is_empty() {
+        let mut keys=scopes.var_scopes.keys().copied().collect::<Vec<_>>();
+        keys.sort_by_key(|s|s.0);
+        let mut state=State {
+            filename,
+            scopes,
+            links:undecided.iter().map(|s|(*s,*s)).collect(),
+            uses:HashMap::new(),
+            errors:Vec::new(),
+        };
+        for scope in keys {
+            state.visit_scope(scope);
+        }
+        let decided=state.settle();
+        errors=state.errors;
+        for (var,ty) in decided {
+            set_var_type(scopes,var,ty);
+        }
+    }
+    // Now that every variable holding a number has its type, the types with numbers inside of
+    // them, like `&mut x`, can use them.
+    for scope in inferred {
+        if !scopes.get(scope).and_then(|i|i.var_type()).is_some_and(has_inner_numbers) {
+            continue;
+        }
+        let Some(ty)=defined_type(filename,scopes,scope) else {continue};
+        set_var_type(scopes,scope,default_numbers(ty));
+    }
+    errors.sort_by_key(|e|e.start.index);
+    if errors.is_empty() {
+        return Ok(Vec::new());
+    } else {
+        return Err([errors,Vec::new()]);
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use cppl_error::Error;
+use crate::refined::*;
+use super::type_check::expr_type;
+
+
+struct State<'a,'input> {
+    filename:&'input str,
+    scopes:&'a Scopes<'input>,
+    /// The method each call resolved to, by the start and end index of the call.
+    methods:HashMap<(usize,usize),Scope>,
+    errors:Vec<Error<'input,String>>,
+}
+impl<'a,'input> State<'a,'input> {
+    fn visit(&mut self,expr:&Expr<'input>) {
+        for child in expr.children() {
+            self.visit(child);
+        }
+        let Expr::MethodCall{start,end,from,name,method:None,..}=expr else {return};
+        let ty=expr_type(self.filename,self.scopes,from);
+        // Calls on interfaces stay unresolved, the impl of the value decides what they call.
+        if let Some(interface)=self.scopes.interface(&ty) {
+            if self.scopes.interface_method(interface,name).is_none() {
+                self.push_unknown(expr,name,&ty);
+            }
+            return;
+        }
+        match self.scopes.find_method(&ty,name) {
+            Some(method)=>{
+                self.methods.insert((start.index,end.index),method);
+            },
+            // Anything else is a call of a function stored in a field.
+            None=>if !may_have_field(self.scopes,&ty,name,0) {
+                self.push_unknown(expr,name,&ty);
+            },
+        }
+    }
+    fn push_unknown(&mut self,expr:&Expr<'input>,name:&str,ty:&Type<'input>) {
+        let (start,end)=expr.span();
+        self.errors.push(Error::new_verif(self.filename,start,end,format!("no method named `{}` found for `{}`",name,self.scopes.type_name(ty))));
+    }
+}
+
+
+/// Whether `ty` could have a field named `name`. Anything we can't tell yet is assumed to.
+fn may_have_field<'input>(scopes:&Scopes<'input>,ty:&Type<'input>,name:&str,depth:usize)->bool {
+    // Guard against recursive type aliases.
+    if depth>32 {
+        return true;
+    }
+    match scopes.auto_deref(ty) {
+        Type::Object{fields,exact,..}=>!exact||fields.iter().any(|f|f.name==name),
+        Type::Named{path,..}=>match scopes.get(*path) {
+            Some(VarScopeItem::Type{ty,..})=>may_have_field(scopes,ty,name,depth+1),
+            Some(VarScopeItem::Enum{..}|VarScopeItem::EnumVariant{..})=>false,
+            _=>true,
+        },
+        ty=>!ty.is_builtin(),
+    }
+}
+fn set_methods<'input>(expr:&mut Expr<'input>,methods:&HashMap<(usize,usize),Scope>) {
+    for child in expr.children_mut() {
+        set_methods(child,methods);
+    }
+    if let Expr::MethodCall{start,end,method,..}=expr {
+        if let Some(found)=methods.get(&(start.index,end.index)) {
+            *method=Some(*found);
+        }
+    }
+}
+/// Resolves which function each method call calls from the type of what it is called on. This
+/// needs the types of every variable, so it is done after they are inferred.
+pub fn resolve_methods<'input>(filename:&'input str,scopes:&mut Scopes<'input>)->Result<Vec<Error<'input,String>>,[Vec<Error<'input,String>>;2]> {
+    let mut state=State {
+        filename,
+        scopes,
+        methods:HashMap::new(),
+        errors:Vec::new(),
+    };
+    for item in scopes.var_scopes.values() {
+        if let VarScopeItem::Var{data,..}|VarScopeItem::Const{data,..}|VarScopeItem::Static{data,..}=item {
+            state.visit(data);
+        }
+        for stmt in item.statements() {
+            for expr in stmt.exprs() {
+                state.visit(expr);
+            }
+        }
+    }
+    let State{methods,mut errors,..}=state;
+    for item in scopes.var_scopes.values_mut() {
+        if let VarScopeItem::Var{data,..}|VarScopeItem::Const{data,..}|VarScopeItem::Static{data,..}=item {
+            set_methods(data,&methods);
+        }
+        for stmt in item.statements_mut() {
+            for expr in stmt.exprs_mut() {
+                set_methods(expr,&methods);
+            }
+        }
+    }
+    errors.sort_by_key(|e|e.start.index);
+    if errors.is_empty() {
+        return Ok(Vec::new());
+    } else {
+        return Err([errors,Vec::new()]);
+    }
+}
@@ -1,7 +1,23 @@
 pub use verification::verify;
 pub use refine::refine;
+pub use name_res::resolve;
+pub use infer::infer;
+pub use methods::resolve_methods;
+pub use type_check::{
+    type_check,
+    expr_type,
+};
+pub use exhaustiveness::check_matches;
+pub use access::check_access;
+pub use borrow::check_borrows;
 
 
 mod verification;
 mod name_res;
 mod refine;
+mod infer;
+mod methods;
+mod type_check;
+mod exhaustiveness;
+mod access;
+mod borrow;
@@ -0,0 +1,645 @@
+use std::{
+    collections::HashMap,
+    mem,
+};
+use cppl_error::{
+    Location,
+    Error,
+};
+use crate::refined::*;
+
+
+#[derive(Debug,PartialEq,Copy,Clone)]
+enum Namespace {
+    Value,
+    Type,
+    Module,
+    /// Enum variants are both a type and a value.
+    Variant,
+}
+impl Namespace {
+    fn is_type(self)->bool {
+        self==Namespace::Type||self==Namespace::Variant
+    }
+    fn is_value(self)->bool {
+        self==Namespace::Value||self==Namespace::Variant
+    }
+}
+#[derive(Debug,Copy,Clone)]
+struct Item {
+    scope:Scope,
+    namespace:Namespace,
+    /// Can be used before it is defined, like functions, consts and types.
+    hoisted:bool,
+    public:Option<Visibility>,
+}
+impl Item {
+    fn new(scope:Scope,item:&VarScopeItem)->Option<Self> {
+        use VarScopeItem::*;
+        let (namespace,hoisted)=match item {
+            Function{..}|Const{..}|Static{..}|BuiltinFunction{..}=>(Namespace::Value,true),
+            Parameter{..}|Var{..}|MatchBlockVar{..}|Narrowed{..}=>(Namespace::Value,false),
+            Interface{..}|Type{..}|Enum{..}=>(Namespace::Type,true),
+            Module{..}=>(Namespace::Module,true),
+            EnumVariant{..}=>(Namespace::Variant,true),
+            _=>return None,
+        };
+        Some(Item {
+            scope,
+            namespace,
+            hoisted,
+            public:item.public(),
+        })
+    }
+}
+
+
+trait Resolve<'input> {
+    /// `scope` is the scope lookups start at.
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope);
+}
+impl<'input,T:Resolve<'input>> Resolve<'input> for Option<T> {
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope) {
+        if let Some(inner)=self {
+            inner.resolve(state,scope);
+        }
+    }
+}
+impl<'input,T:Resolve<'input>> Resolve<'input> for Vec<T> {
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope) {
+        self.iter_mut().for_each(|i|i.resolve(state,scope));
+    }
+}
+impl<'input> Resolve<'input> for VarScopeItem<'input> {
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope) {
+        use VarScopeItem::*;
+        match self {
+            Root{statements,..}|Block{statements,..}=>statements.resolve(state,scope),
+            Function{ret_type,statements,..}|AnonFunction{ret_type,statements,..}=>{
+                ret_type.resolve(state,scope);
+                statements.resolve(state,scope);
+            },
+            Interface{params,requirement,statements,..}=>{
+                params.resolve(state,scope);
+                requirement.resolve(state,scope);
+                statements.resolve(state,scope);
+            },
+            Impl{interface,params,for_ty,statements,..}=>{
+                params.resolve(state,scope);
+                interface.resolve(state,scope);
+                for_ty.resolve(state,scope);
+                statements.resolve(state,scope);
+            },
+            Parameter{ty,..}|MatchBlockVar{ty,..}|Narrowed{ty,..}=>ty.resolve(state,scope),
+            Var{ty,data,..}|Const{ty,data,..}|Static{ty,data,..}=>{
+                ty.resolve(state,scope);
+                data.resolve(state,scope);
+            },
+            Type{ty,params,..}=>{
+                params.resolve(state,scope);
+                ty.resolve(state,scope);
+            },
+            Enum{params,variants,..}=>{
+                params.resolve(state,scope);
+                variants.resolve(state,scope);
+            },
+            EnumVariant{fields,..}=>fields.resolve(state,scope),
+            Module{..}|BuiltinFunction{..}=>{},
+        }
+    }
+}
+impl<'input> Resolve<'input> for TypeParameters<'input> {
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope) {
+        self.params.iter_mut().for_each(|p|p.ty.resolve(state,scope));
+    }
+}
+impl<'input> Resolve<'input> for Parameters<'input> {
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope) {
+        self.normal.iter_mut().for_each(|p|p.ty.resolve(state,scope));
+        if let Some(var_arg)=&mut self.var_arg {
+            var_arg.ty.resolve(state,scope);
+        }
+    }
+}
+impl<'input> Resolve<'input> for Type<'input> {
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope) {
+        match self {
+            Type::UnknownNamed{name,start,end,generics}=>{
+                generics.resolve(state,scope);
+                let (name,start,end)=(*name,*start,*end);
+                if let Some((impl_scope,target))=state.self_impl(scope,name) {
+                    if let Type::UnknownNamed{name:"Self",..}=target {
+                        state.push(start,end,"impl blocks can not be for `Self`");
+                    } else {
+                        *self=target;
+                        self.resolve(state,impl_scope);
+                    }
+                } else if let Some(declared)=state.generic_scope(scope,name) {
+                    *self=Type::Generic{start,end,name,scope:declared};
+                } else if let Some(path)=state.lookup_type(scope,name) {
+                    let generics=mem::take(generics);
+                    *self=Type::Named{path,start,end,generics};
+                } else {
+                    state.push(start,end,format!("type `{}` is not defined",name));
+                }
+            },
+            Type::Named{generics,..}=>generics.resolve(state,scope),
+            Type::Object{fields,..}=>fields.iter_mut().for_each(|f|f.ty.resolve(state,scope)),
+            Type::Union{items,..}|Type::Composite{items,..}=>items.resolve(state,scope),
+            Type::Slice{inner,..}|
+                Type::Ref{inner,..}|
+                Type::RefMut{inner,..}|
+                Type::Pointer{inner,..}|
+                Type::PointerMut{inner,..}=>inner.resolve(state,scope),
+            Type::FunctionSig{inner,..}=>{
+                inner.params.resolve(state,scope);
+                inner.ret_type.resolve(state,scope);
+            },
+            _=>{},
+        }
+    }
+}
+impl<'input> Resolve<'input> for Statement<'input> {
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope) {
+        use Statement as St;
+        match self {
+            St::FunctionSig{sig,..}=>{
+                sig.params.resolve(state,scope);
+                sig.ret_type.resolve(state,scope);
+            },
+            St::VarAssign{data,..}=>data.resolve(state,scope),
+            St::UnknownVarAssign{start,end,name,data}=>{
+                data.resolve(state,scope);
+                let (start,end,name)=(*start,*end,*name);
+                match state.lookup_hoisted_value(scope,name) {
+                    Some(loc)=>{
+                        let data=mem::replace(data,Expr::UnknownVar{start,end,name});
+                        *self=St::VarAssign{start,end,loc,data};
+                    },
+                    None=>state.push(start,end,format!("variable `{}` is not defined",name)),
+                }
+            },
+            St::Expr{expr,..}=>expr.resolve(state,scope),
+            St::PlaceAssign{place,data,..}|St::CompoundAssign{place,data,..}=>{
+                place.resolve(state,scope);
+                data.resolve(state,scope);
+            },
+            St::Return{val,..}|St::Break{val,..}=>val.resolve(state,scope),
+            _=>{},
+        }
+    }
+}
+impl<'input> Resolve<'input> for Expr<'input> {
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope) {
+        use Expr as E;
+        match self {
+            E::FieldAccess{from,..}=>from.resolve(state,scope),
+            E::MethodCall{from,args,..}=>{
+                from.resolve(state,scope);
+                args.resolve(state,scope);
+            },
+            E::UnknownFunctionCall{path,start,end,args}=>{
+                args.resolve(state,scope);
+                let (start,end)=(*start,*end);
+                let found=match path.as_slice() {
+                    [name]=>{
+                        let found=state.lookup_hoisted_value(scope,name);
+                        if found.is_none() {
+                            state.push(start,end,format!("function `{}` is not defined",name));
+                        }
+                        found
+                    },
+                    path=>state.resolve_path(scope,path,start,end),
+                };
+                if let Some(path)=found {
+                    let args=mem::take(args);
+                    *self=E::FunctionCall{path,start,end,args};
+                }
+            },
+            E::FunctionCall{args,..}=>args.resolve(state,scope),
+            E::UnknownAssociatedPath{start,end,path}=>{
+                let (start,end)=(*start,*end);
+                if let Some(path)=state.resolve_path(scope,path,start,end) {
+                    *self=E::AssociatedPath{start,end,path};
+                }
+            },
+            E::UnknownVar{start,end,name}=>{
+                let (start,end,name)=(*start,*end,*name);
+                match state.lookup_hoisted_value(scope,name) {
+                    Some(path)=>*self=E::Var{start,end,path},
+                    None=>state.push(start,end,format!("variable `{}` is not defined",name)),
+                }
+            },
+            E::Add{inner,..}|
+                E::Sub{inner,..}|
+                E::Mul{inner,..}|
+                E::Div{inner,..}|
+                E::Mod{inner,..}|
+                E::BitAnd{inner,..}|
+                E::BitOr{inner,..}|
+                E::BitXor{inner,..}|
+                E::ShiftLeft{inner,..}|
+                E::ShiftRight{inner,..}|
+                E::Equal{inner,..}|
+                E::NotEqual{inner,..}|
+                E::GreaterEqual{inner,..}|
+                E::LessEqual{inner,..}|
+                E::Greater{inner,..}|
+                E::Less{inner,..}|
+                E::And{inner,..}|
+                E::Or{inner,..}=>inner.iter_mut().for_each(|e|e.resolve(state,scope)),
+            E::Negate{inner,..}|E::Not{inner,..}=>inner.resolve(state,scope),
+            E::IsType{to_test,ty,..}=>{
+                to_test.resolve(state,scope);
+                ty.resolve(state,scope);
+            },
+            E::ObjectCreation{fields,..}=>fields.iter_mut().for_each(|f|f.data.resolve(state,scope)),
+            E::Ref{val,..}|E::RefMut{val,..}|E::Deref{val,..}=>val.resolve(state,scope),
+            E::WhileLoop{condition,..}=>condition.resolve(state,scope),
+            E::ForLoop{iterator,..}=>iterator.resolve(state,scope),
+            E::Match{block,..}=>block.resolve(state,scope),
+            E::If{branches,..}=>branches.iter_mut().for_each(|(condition,_)|condition.resolve(state,scope)),
+            E::List{items,..}=>items.resolve(state,scope),
+            E::Index{from,index,..}=>{
+                from.resolve(state,scope);
+                index.resolve(state,scope);
+            },
+            E::Slice{from,range_start,range_end,..}=>{
+                from.resolve(state,scope);
+                range_start.iter_mut().for_each(|e|e.resolve(state,scope));
+                range_end.iter_mut().for_each(|e|e.resolve(state,scope));
+            },
+            _=>{},
+        }
+    }
+}
+impl<'input> Resolve<'input> for Box<Match<'input>> {
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope) {
+        self.to_match.resolve(state,scope);
+        for (pattern,expr) in self.leafs.iter_mut() {
+            pattern.resolve(state,scope);
+            expr.resolve(state,scope);
+        }
+    }
+}
+impl<'input> Resolve<'input> for MatchPattern<'input> {
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope) {
+        use MatchPattern as MP;
+        match self {
+            MP::MethodCall{args,..}=>args.resolve(state,scope),
+            MP::Equal{inner,..}|
+                MP::NotEqual{inner,..}|
+                MP::GreaterEqual{inner,..}|
+                MP::LessEqual{inner,..}|
+                MP::Greater{inner,..}|
+                MP::Less{inner,..}=>inner.resolve(state,scope),
+            MP::IsType{inner,..}=>inner.resolve(state,scope),
+            MP::Structure{structure,..}=>structure.resolve(state,scope),
+            MP::Var{start,end,name,..}=>{
+                // A name that refers to an enum variant checks for that variant instead of binding
+                // a variable.
+                let (start,end,name)=(*start,*end,*name);
+                if let Some(path)=state.lookup_variant(scope,name) {
+                    *self=MP::IsType{start,end,inner:Type::Named{path,start,end,generics:Vec::new()}};
+                }
+            },
+            _=>{},
+        }
+    }
+}
+impl<'input> Resolve<'input> for MatchPatternStructure<'input> {
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope) {
+        use MatchPatternStructure as MPS;
+        match self {
+            MPS::Block{block,..}=>block.resolve(state,scope),
+            MPS::TypedBlock{ty,block,..}=>{
+                ty.resolve(state,scope);
+                block.resolve(state,scope);
+            },
+        }
+    }
+}
+impl<'input> Resolve<'input> for MatchPatternStructureItem<'input> {
+    fn resolve(&mut self,state:&mut State<'input>,scope:Scope) {
+        if let MatchPatternStructureItem::NamedBlock{block,..}=self {
+            block.resolve(state,scope);
+        }
+    }
+}
+
+
+struct State<'input> {
+    filename:&'input str,
+    /// Every named item, keyed by the scope it is declared in.
+    items:HashMap<Scope,HashMap<&'input str,Vec<Item>>>,
+    item_by_scope:HashMap<Scope,Item>,
+    /// The resolved imports of each scope, keyed by the name they are imported as.
+    imports:HashMap<Scope,HashMap<&'input str,Scope>>,
+    /// Modules declared with `module` in the root scope.
+    declared_modules:Vec<&'input str>,
+    parents:HashMap<Scope,Scope>,
+    type_params:HashMap<Scope,Vec<&'input str>>,
+    /// The scopes that can have associated items accessed with `::`.
+    containers:HashMap<Scope,&'input str>,
+    /// The types impl blocks are for, so we can resolve `Self`.
+    impl_targets:HashMap<Scope,Type<'input>>,
+    /// The impl blocks for each named type.
+    impls_of:HashMap<Scope,Vec<Scope>>,
+    errors:Vec<Error<'input,String>>,
+    warnings:Vec<Error<'input,String>>,
+}
+impl<'input> State<'input> {
+    fn new(filename:&'input str,scopes:&Scopes<'input>)->Self {
+        let mut state=State {
+            filename,
+            items:HashMap::new(),
+            item_by_scope:HashMap::new(),
+            imports:HashMap::new(),
+            declared_modules:Vec::new(),
+            parents:HashMap::new(),
+            type_params:HashMap::new(),
+            containers:HashMap::new(),
+            impl_targets:HashMap::new(),
+            impls_of:HashMap::new(),
+            errors:Vec::new(),
+            warnings:Vec::new(),
+        };
+        for (scope,item) in scopes.var_scopes.iter() {
+            if let Some(parent)=item.parent_scope() {
+                state.parents.insert(*scope,parent);
+                if let (Some(name),Some(entry))=(item.name(),Item::new(*scope,item)) {
+                    state.item_by_scope.insert(*scope,entry);
+                    state.add_item(parent,name,entry);
+                    // Variants can also be used without the enum name, just like C.
+                    if let VarScopeItem::EnumVariant{..}=item {
+                        if let Some(enum_parent)=scopes.get(parent).and_then(|e|e.parent_scope()) {
+                            state.add_item(enum_parent,name,entry);
+                        }
+                    }
+                }
+            }
+            let params=item.type_params();
+            if !params.is_empty() {
+                state.type_params.insert(*scope,params);
+            }
+            match item {
+                VarScopeItem::Interface{name,..}|VarScopeItem::Module{name,..}|VarScopeItem::Enum{name,..}=>{
+                    state.containers.insert(*scope,name);
+                },
+                VarScopeItem::Root{modules,..}=>state.declared_modules=modules.clone(),
+                VarScopeItem::Impl{for_ty,..}=>{
+                    state.impl_targets.insert(*scope,for_ty.clone());
+                },
+                _=>{},
+            }
+        }
+        state.check_duplicates(scopes);
+        state.resolve_imports(scopes);
+        let mut impls=state.impl_targets.iter()
+            .filter_map(|(scope,ty)|match ty {
+                Type::UnknownNamed{name,..}=>Some((*scope,state.lookup_type(*scope,name)?)),
+                _=>None,
+            })
+            .collect::<Vec<_>>();
+        impls.sort_by_key(|(s,_)|s.0);
+        for (scope,target) in impls {
+            state.impls_of.entry(target).or_default().push(scope);
+        }
+        return state;
+    }
+    /// Imports are resolved in order, so an import can use the ones before it.
+    fn resolve_imports(&mut self,scopes:&Scopes<'input>) {
+        let mut keys=scopes.var_scopes.keys().copied().collect::<Vec<_>>();
+        keys.sort_by_key(|s|s.0);
+        for scope in keys {
+            use VarScopeItem::*;
+            let imports=match scopes.get(scope) {
+                Some(Root{imports,..}|Function{imports,..}|AnonFunction{imports,..}|Block{imports,..})=>imports,
+                _=>continue,
+            };
+            for import in imports {
+                if let Some(target)=self.resolve_path(scope,&import.path,import.start,import.end) {
+                    self.imports.entry(scope)
+                        .or_default()
+                        .insert(import.name(),target);
+                }
+            }
+        }
+    }
+    fn add_item(&mut self,parent:Scope,name:&'input str,item:Item) {
+        self.items.entry(parent)
+            .or_default()
+            .entry(name)
+            .or_default()
+            .push(item);
+    }
+    fn push<T:Into<String>>(&mut self,start:Location,end:Location,err:T) {
+        self.errors.push(Error::new_verif(self.filename,start,end,err.into()));
+    }
+    /// Impls of an interface must define every required function and nothing the interface
+    /// doesn't have.
+    fn check_interface_impl(&mut self,scopes:&Scopes<'input>,interface:&Type<'input>,statements:&[Statement<'input>]) {
+        let Type::Named{path,start,end,..}=interface else {return};
+        let Some(VarScopeItem::Interface{name:interface_name,statements:required,..})=scopes.get(*path) else {
+            self.push(*start,*end,format!("`{}` is not an interface",scopes.type_name(interface)));
+            return;
+        };
+        let mut names=Vec::new();
+        for stmt in required.iter() {
+            match stmt {
+                Statement::FunctionSig{sig,..}=>names.push((sig.name,true)),
+                Statement::FunctionDef{def,..}=>if let Some(name)=scopes.get(*def).and_then(|i|i.name()) {
+                    names.push((name,false));
+                },
+                _=>{},
+            }
+        }
+        for stmt in statements.iter() {
+            if let Statement::FunctionDef{start,end,def}=stmt {
+                let name=scopes.get(*def).and_then(|i|i.name()).unwrap_or("");
+                if !names.iter().any(|(n,_)|*n==name) {
+                    self.push(*start,*end,format!("`{}` is not a member of interface `{}`",name,interface_name));
+                }
+            }
+        }
+        for (name,_) in names.iter().filter(|(_,required)|*required) {
+            if scopes.find_function(statements,name).is_none() {
+                self.push(*start,*end,format!("missing `{}` from interface `{}`",name,interface_name));
+            }
+        }
+    }
+    /// Hoisted items can only be defined once per scope, unlike variables which can shadow each
+    /// other.
+    fn check_duplicates(&mut self,scopes:&Scopes<'input>) {
+        let mut duplicates=Vec::new();
+        for names in self.items.values() {
+            for (name,items) in names.iter() {
+                for namespace in [Namespace::Value,Namespace::Type,Namespace::Module,Namespace::Variant] {
+                    items.iter()
+                        .filter(|i|i.hoisted&&i.namespace==namespace)
+                        .skip(1)
+                        .for_each(|i|duplicates.push((*name,i.scope)));
+                }
+            }
+        }
+        for (name,scope) in duplicates {
+            let (start,end)=scopes.get(scope).and_then(|i|i.def_span()).unwrap_or_default();
+            self.push(start,end,format!("`{}` is defined multiple times in the same scope",name));
+        }
+    }
+    fn chain(&self,scope:Scope)->impl Iterator<Item=Scope>+'_ {
+        let mut current=Some(scope);
+        std::iter::from_fn(move||{
+            let ret=current?;
+            current=self.parents.get(&ret).copied();
+            Some(ret)
+        })
+    }
+    fn is_ancestor(&self,ancestor:Scope,scope:Scope)->bool {
+        self.chain(scope).any(|s|s==ancestor)
+    }
+    /// The library a scope is in: the outermost module around it, or the root for the code being
+    /// compiled.
+    fn library(&self,scope:Scope)->Scope {
+        let mut library=scope;
+        let mut module=None;
+        for s in self.chain(scope) {
+            if self.item_by_scope.get(&s).is_some_and(|i|i.namespace==Namespace::Module) {
+                module=Some(s);
+            }
+            library=s;
+        }
+        return module.unwrap_or(library);
+    }
+    /// The item that declares the type parameter `name` seen from `scope`.
+    fn generic_scope(&self,scope:Scope,name:&str)->Option<Scope> {
+        self.chain(scope)
+            .find(|s|self.type_params.get(s).is_some_and(|params|params.contains(&name)))
+    }
+    fn lookup(&self,scope:Scope,name:&str,filter:impl Fn(&Item)->bool)->Option<Scope> {
+        for s in self.chain(scope) {
+            let found=self.items.get(&s)
+                .and_then(|names|names.get(name))
+                .and_then(|items|items.iter().rev().find(|i|filter(i)));
+            if let Some(item)=found {
+                return Some(item.scope);
+            }
+            let imported=self.imports.get(&s)
+                .and_then(|imports|imports.get(name))
+                .and_then(|target|self.item_by_scope.get(target))
+                .filter(|i|filter(i));
+            if let Some(item)=imported {
+                return Some(item.scope);
+            }
+        }
+        return None;
+    }
+    /// Inside of impl blocks `Self` is the type the impl is for.
+    fn self_impl(&self,scope:Scope,name:&str)->Option<(Scope,Type<'input>)> {
+        if name!="Self" {
+            return None;
+        }
+        let found=self.chain(scope)
+            .find(|s|self.impl_targets.contains_key(s)||self.item_by_scope.get(s).is_some_and(|i|i.namespace==Namespace::Type))?;
+        let target=self.impl_targets.get(&found)?;
+        return Some((found,target.clone()));
+    }
+    fn lookup_type(&self,scope:Scope,name:&str)->Option<Scope> {
+        if name=="Self" {
+            return self.chain(scope).find(|s|self.item_by_scope.get(s).is_some_and(|i|i.namespace==Namespace::Type));
+        }
+        self.lookup(scope,name,|i|i.namespace.is_type())
+    }
+    fn lookup_variant(&self,scope:Scope,name:&str)->Option<Scope> {
+        self.lookup(scope,name,|i|i.namespace==Namespace::Variant)
+    }
+    /// Variables are resolved during refinement, so only items that can be used before their
+    /// definition are left for us.
+    fn lookup_hoisted_value(&self,scope:Scope,name:&str)->Option<Scope> {
+        self.lookup(scope,name,|i|i.namespace.is_value()&&i.hoisted)
+    }
+    fn resolve_path(&mut self,scope:Scope,path:&[&'input str],start:Location,end:Location)->Option<Scope> {
+        let (first,rest)=path.split_first()?;
+        let mut current=match self.lookup(scope,first,|i|i.hoisted) {
+            Some(s)=>s,
+            None if self.declared_modules.contains(first)=>{
+                self.push(start,end,format!("module `{}` can not be loaded yet, only `std` is available",first));
+                return None;
+            },
+            None=>{
+                self.push(start,end,format!("`{}` is not defined",first));
+                return None;
+            },
+        };
+        let mut current_name=first.to_string();
+        for name in rest {
+            let impls=self.impls_of.get(&current).map_or(&[][..],|i|i.as_slice());
+            if !self.containers.contains_key(&current)&&impls.is_empty() {
+                self.push(start,end,format!("`{}` does not have any associated items",current_name));
+                return None;
+            }
+            // Functions from impl blocks for a type are also associated with it.
+            let item=std::iter::once(current)
+                .chain(impls.iter().copied())
+                .find_map(|s|self.items.get(&s)
+                    .and_then(|names|names.get(name))
+                    .and_then(|items|items.iter().rev().find(|i|i.hoisted))
+                    .copied()
+                );
+            match item {
+                Some(item)=>{
+                    // Private and `pub(local)` items are only visible from inside the item that
+                    // contains them, and `pub(lib)` items from inside its library.
+                    let owner=self.parents.get(&item.scope).copied().unwrap_or(current);
+                    let reason=match item.public {
+                        Some(Visibility::Full)=>None,
+                        Some(Visibility::Library) if self.library(owner)==self.library(scope)=>None,
+                        Some(Visibility::Library)=>Some(format!("`{}` is only visible inside of the library of `{}`",name,current_name)),
+                        _ if self.is_ancestor(owner,scope)=>None,
+                        Some(Visibility::Local)=>Some(format!("`{}` is only visible inside of `{}`",name,current_name)),
+                        None=>Some(format!("`{}` is private in `{}`",name,current_name)),
+                    };
+                    if let Some(reason)=reason {
+                        self.push(start,end,reason);
+                        return None;
+                    }
+                    current=item.scope;
+                    current_name.push_str("::");
+                    current_name.push_str(name);
+                },
+                None=>{
+                    self.push(start,end,format!("`{}` is not defined in `{}`",name,current_name));
+                    return None;
+                },
+            }
+        }
+        return Some(current);
+    }
+}
+
+
+/// Impls can only be checked against their interface once every type is resolved.
+fn check_interface_impls<'input>(state:&mut State<'input>,scopes:&Scopes<'input>) {
+    for scope in scopes.impls() {
+        if let Some(VarScopeItem::Impl{interface:Some(interface),statements,..})=scopes.get(scope) {
+            state.check_interface_impl(scopes,interface,statements);
+        }
+    }
+}
+/// Resolves every `Unknown*` path, type and variable left behind by refinement into the `Scope`
+/// it refers to.
+pub fn resolve<'input>(filename:&'input str,scopes:&mut Scopes<'input>)->Result<Vec<Error<'input,String>>,[Vec<Error<'input,String>>;2]> {
+    let mut state=State::new(filename,scopes);
+    let mut keys=scopes.var_scopes.keys().copied().collect::<Vec<_>>();
+    keys.sort_by_key(|s|s.0);
+    for scope in keys {
+        scopes.get_mut(scope).unwrap().resolve(&mut state,scope);
+    }
+    check_interface_impls(&mut state,scopes);
+    state.errors.sort_by_key(|e|e.start.index);
+    if state.errors.is_empty() {
+        return Ok(state.warnings);
+    } else {
+        return Err([state.errors,state.warnings]);
+    }
+}
@@ -1,28 +1,65 @@
 #![allow(unused_variables)]
 use std::collections::HashMap;
-use cppl_error::Error;
+use cppl_error::{
+    Location,
+    Error,
+};
 use crate::{
     refined::*,
     raw,
 };
 
 
+/// A variable that a condition narrows: its name, the original variable, the type it narrows to
+/// and the span of the condition.
+type Narrowing<'input>=(&'input str,Scope,Type<'input>,Location,Location);
+/// A loop that `break` and `continue` can refer to.
+#[derive(Copy,Clone)]
+struct EnclosingLoop<'input> {
+    start:Location,
+    end:Location,
+    label:Option<&'input str>,
+    block:Scope,
+    /// Only `loop` can be left with a value. The other loops can end without a `break`.
+    forever:bool,
+}
+struct State<'input> {
+    filename:&'input str,
+    errors:Vec<Error<'input,String>>,
+    /// The loops around what is being converted, innermost last. Functions start with none.
+    loops:Vec<EnclosingLoop<'input>>,
+}
+impl<'input> State<'input> {
+    /// Finds the loop a `break` or `continue` refers to.
+    fn enclosing_loop(&self,keyword:&str,label:Option<&'input str>,start:Location,end:Location)->Result<EnclosingLoop<'input>,Error<'input,String>> {
+        match label {
+            Some(label)=>self.loops.iter()
+                .rev()
+                .find(|l|l.label==Some(label))
+                .copied()
+                .ok_or_else(||Error::new_verif(self.filename,start,end,format!("unknown label `'{}`",label))),
+            None=>self.loops.last()
+                .copied()
+                .ok_or_else(||Error::new_verif(self.filename,start,end,format!("`{}` outside of a loop",keyword))),
+        }
+    }
+}
 trait Convert<'input> {
     type Output;
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>>;
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>>;
 }
 impl<'input,T:Convert<'input>> Convert<'input> for Option<T> {
     type Output=Option<T::Output>;
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         Ok(match self {
-            Some(c)=>Some(c.convert(scopes,parent,filename)?),
+            Some(c)=>Some(c.convert(scopes,parent,state)?),
             None=>None,
         })
     }
 }
 impl<'input> Convert<'input> for raw::MethodType {
     type Output=MethodType;
-    fn convert(self,_:&mut Scopes<'input>,_:Scope,_:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,_:&mut Scopes<'input>,_:Scope,_:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         Ok(match self {
             raw::MethodType::This{..}=>MethodType::This,
             raw::MethodType::ThisMut{..}=>MethodType::ThisMut,
@@ -30,9 +67,21 @@ impl<'input> Convert<'input> for raw::MethodType {
         })
     }
 }
+impl<'input> Convert<'input> for raw::AssignOp {
+    type Output=AssignOp;
+    fn convert(self,_:&mut Scopes<'input>,_:Scope,_:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
+        Ok(match self {
+            raw::AssignOp::Add=>AssignOp::Add,
+            raw::AssignOp::Sub=>AssignOp::Sub,
+            raw::AssignOp::Mul=>AssignOp::Mul,
+            raw::AssignOp::Div=>AssignOp::Div,
+            raw::AssignOp::Mod=>AssignOp::Mod,
+        })
+    }
+}
 impl<'input> Convert<'input> for raw::Visibility {
     type Output=Visibility;
-    fn convert(self,_:&mut Scopes<'input>,_:Scope,_:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,_:&mut Scopes<'input>,_:Scope,_:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         Ok(match self {
             raw::Visibility::Full{..}=>Visibility::Full,
             raw::Visibility::Library{..}=>Visibility::Library,
@@ -42,96 +91,101 @@ impl<'input> Convert<'input> for raw::Visibility {
 }
 impl<'input> Convert<'input> for raw::Parameter<'input> {
     type Output=Parameter<'input>;
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         let raw::Parameter{start,end,mutable,name,ty}=self;
-        let ty=ty.convert(scopes,parent,filename)?;
+        let ty=ty.convert(scopes,parent,state)?;
         return Ok(Parameter{start,end,name,ty,mutable});
     }
 }
 impl<'input> Convert<'input> for raw::Parameters<'input> {
     type Output=Parameters<'input>;
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         let raw::Parameters{start,end,method_type,normal,var_arg}=self;
         let mut new_normal=Vec::new();
         for p in normal.into_iter() {
-            new_normal.push(p.convert(scopes,parent,filename)?);
+            new_normal.push(p.convert(scopes,parent,state)?);
         }
-        let var_arg=var_arg.convert(scopes,parent,filename)?;
-        let method_type=method_type.convert(scopes,parent,filename)?;
+        let var_arg=var_arg.convert(scopes,parent,state)?;
+        let method_type=method_type.convert(scopes,parent,state)?;
         return Ok(Parameters{start,end,method_type,normal:new_normal,var_arg});
     }
 }
 impl<'input> Convert<'input> for raw::TypeParameter<'input> {
     type Output=TypeParameter<'input>;
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         let raw::TypeParameter{start,end,name,ty}=self;
-        let ty=ty.convert(scopes,parent,filename)?;
+        let ty=ty.convert(scopes,parent,state)?;
         return Ok(TypeParameter{start,end,name,ty});
     }
 }
 impl<'input> Convert<'input> for raw::TypeParameters<'input> {
     type Output=TypeParameters<'input>;
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         let raw::TypeParameters{start,end,params}=self;
         let mut new_params=Vec::new();
         for p in params.into_iter() {
-            new_params.push(p.convert(scopes,parent,filename)?);
+            new_params.push(p.convert(scopes,parent,state)?);
         }
         return Ok(TypeParameters{start,end,params:new_params});
     }
 }
 impl<'input> Convert<'input> for raw::TypeObjectField<'input> {
     type Output=TypeObjectField<'input>;
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         let raw::TypeObjectField{start,end,public,mutable,name,ty}=self;
-        let public=public.convert(scopes,parent,filename)?;
-        let mutable=mutable.convert(scopes,parent,filename)?;
-        let ty=ty.convert(scopes,parent,filename)?;
+        let public=public.convert(scopes,parent,state)?;
+        let mutable=mutable.convert(scopes,parent,state)?;
+        let ty=ty.convert(scopes,parent,state)?;
         return Ok(TypeObjectField{start,end,public,mutable,name,ty});
     }
 }
 impl<'input> Convert<'input> for raw::AnonFunctionSignature<'input> {
     type Output=AnonFunctionSignature<'input>;
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         let raw::AnonFunctionSignature{start,end,params,ret_type}=self;
-        let params=params.convert(scopes,parent,filename)?;
-        let ret_type=ret_type.convert(scopes,parent,filename)?;
+        let params=params.convert(scopes,parent,state)?;
+        let ret_type=ret_type.convert(scopes,parent,state)?;
         return Ok(AnonFunctionSignature{start,end,params,ret_type});
     }
 }
 impl<'input> Convert<'input> for raw::Type<'input> {
     type Output=Type<'input>;
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         Ok(match self {
             raw::Type::Named{start,end,name,generics:old_generics}=>{
                 let mut generics=Vec::new();
                 for t in old_generics {
-                    generics.push(t.convert(scopes,parent,filename)?);
+                    generics.push(t.convert(scopes,parent,state)?);
                 }
                 Type::UnknownNamed{start,end,name,generics}
             },
             raw::Type::Object{start,end,fields:old_fields,exact}=>{
                 let mut fields=Vec::new();
                 for f in old_fields {
-                    fields.push(f.convert(scopes,parent,filename)?);
+                    fields.push(f.convert(scopes,parent,state)?);
                 }
                 Type::Object{start,end,fields,exact}
             },
             raw::Type::Union{start,end,inner}=>{
                 let mut items=Vec::new();
                 for i in inner {
-                    items.push(i.convert(scopes,parent,filename)?);
+                    items.push(i.convert(scopes,parent,state)?);
                 }
                 Type::Union{start,end,items}
             },
             raw::Type::Composite{start,end,inner}=>{
                 let mut items=Vec::new();
                 for i in inner {
-                    items.push(i.convert(scopes,parent,filename)?);
+                    items.push(i.convert(scopes,parent,state)?);
                 }
                 Type::Composite{start,end,items}
             },
-            raw::Type::FunctionSig{start,end,inner}=>Type::FunctionSig{start,end,inner:Box::new(inner.convert(scopes,parent,filename)?)},
+            raw::Type::FunctionSig{start,end,inner}=>Type::FunctionSig{start,end,inner:Box::new(inner.convert(scopes,parent,state)?)},
+            raw::Type::Slice{start,end,inner}=>Type::Slice{start,end,inner:Box::new(inner.convert(scopes,parent,state)?)},
+            raw::Type::Ref{start,end,inner}=>Type::Ref{start,end,inner:Box::new(inner.convert(scopes,parent,state)?)},
+            raw::Type::RefMut{start,end,inner}=>Type::RefMut{start,end,inner:Box::new(inner.convert(scopes,parent,state)?)},
+            raw::Type::Pointer{start,end,inner}=>Type::Pointer{start,end,inner:Box::new(inner.convert(scopes,parent,state)?)},
+            raw::Type::PointerMut{start,end,inner}=>Type::PointerMut{start,end,inner:Box::new(inner.convert(scopes,parent,state)?)},
             raw::Type::Uint{..}=>Type::Uint,
             raw::Type::Int{..}=>Type::Int,
             raw::Type::Float{..}=>Type::Float,
@@ -147,13 +201,13 @@ impl<'input> Convert<'input> for raw::Type<'input> {
 }
 impl<'input> Convert<'input> for raw::Statement<'input> {
     type Output=();
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         use raw::Statement as S;
         match self {
             S::FunctionDef{inner:raw::Function{start,end,public,name,params,ret_type,block},..}=>{
-                let ret_type=ret_type.convert(scopes,parent,filename)?;
-                let public=public.convert(scopes,parent,filename)?;
-                let params=params.convert(scopes,parent,filename)?;
+                let ret_type=ret_type.convert(scopes,parent,state)?;
+                let public=public.convert(scopes,parent,state)?;
+                let params=params.convert(scopes,parent,state)?;
                 let scope=scopes.push(VarScopeItem::Function {
                     imports:Vec::new(),
                     parent_scope:parent,
@@ -166,30 +220,18 @@ impl<'input> Convert<'input> for raw::Statement<'input> {
                     statements:Vec::new(),
                     vars:HashMap::new(),
                 });
-                for param in params.normal.into_iter() {
-                    let name=param.name;
-                    if scopes.get_mut(scope).unwrap().get_var(param.name).is_some() {
-                        return Err(Error::new_verif(filename,start,end,format!("parameter {} is specified twice",name)));
-                    }
-                    let param_scope=scopes.push(VarScopeItem::Parameter {
-                        parent_scope:parent,
-                        def_start:param.start,
-                        def_end:param.end,
-                        mutable:param.mutable,
-                        name,
-                        ty:param.ty,
-                    });
-                    scopes.get_mut(scope).unwrap().add_var(name,param_scope);
-                }
+                add_params(scopes,scope,params,state);
                 scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::FunctionDef{start,end,def:scope});
+                let loops=std::mem::take(&mut state.loops);
                 for s in block.inner {
-                    s.convert(scopes,scope,filename)?;
+                    s.convert(scopes,scope,state).unwrap_or_else(|e|state.errors.push(e));
                 }
+                state.loops=loops;
             },
             S::FunctionSig{inner:raw::FunctionSignature{start,end,public,name,params,ret_type},..}=>{
-                let ret_type=ret_type.convert(scopes,parent,filename)?;
-                let public=public.convert(scopes,parent,filename)?;
-                let params=params.convert(scopes,parent,filename)?;
+                let ret_type=ret_type.convert(scopes,parent,state)?;
+                let public=public.convert(scopes,parent,state)?;
+                let params=params.convert(scopes,parent,state)?;
                 scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::FunctionSig{
                     start,
                     end,
@@ -204,9 +246,9 @@ impl<'input> Convert<'input> for raw::Statement<'input> {
                 });
             },
             S::InterfaceDef{inner:raw::Interface{start,end,public,name,params,requirement,block},..}=>{
-                let public=public.convert(scopes,parent,filename)?;
-                let params=params.convert(scopes,parent,filename)?;
-                let requirement=requirement.convert(scopes,parent,filename)?;
+                let public=public.convert(scopes,parent,state)?;
+                let params=params.convert(scopes,parent,state)?;
+                let requirement=requirement.convert(scopes,parent,state)?;
                 let scope=scopes.push(VarScopeItem::Interface {
                     parent_scope:parent,
                     def_start:start,
@@ -220,14 +262,14 @@ impl<'input> Convert<'input> for raw::Statement<'input> {
                     required_functions:Vec::new(),
                 });
                 scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::InterfaceDef{start,end,def:scope});
-                for i in block.inner.into_iter().map(|s|s.convert(scopes,scope,filename)) {
-                    i?;
+                for s in block.inner {
+                    s.convert(scopes,scope,state).unwrap_or_else(|e|state.errors.push(e));
                 }
             },
             S::TypeDef{inner:raw::TypeDef{start,end,public,name,params,ty},..}=>{
-                let public=public.convert(scopes,parent,filename)?;
-                let ty=ty.convert(scopes,parent,filename)?;
-                let params=params.convert(scopes,parent,filename)?;
+                let public=public.convert(scopes,parent,state)?;
+                let ty=ty.convert(scopes,parent,state)?;
+                let params=params.convert(scopes,parent,state)?;
                 let scope=scopes.push(VarScopeItem::Type {
                     parent_scope:parent,
                     def_start:start,
@@ -240,9 +282,9 @@ impl<'input> Convert<'input> for raw::Statement<'input> {
                 scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::TypeDef{start,end,def:scope});
             },
             S::VarDef{inner:raw::VarDef{start,end,mutable,name,ty,data},..}=>{
-                let mutable=mutable.convert(scopes,parent,filename)?;
-                let ty=ty.convert(scopes,parent,filename)?.unwrap_or(Type::Unknown);
-                let data=data.convert(scopes,parent,filename)?;
+                let mutable=mutable.convert(scopes,parent,state)?;
+                let ty=ty.convert(scopes,parent,state)?.unwrap_or(Type::Unknown);
+                let data=data.convert(scopes,parent,state)?;
                 let scope=scopes.push(VarScopeItem::Var {
                     parent_scope:parent,
                     def_start:start,
@@ -256,10 +298,10 @@ impl<'input> Convert<'input> for raw::Statement<'input> {
                 scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_var(name,scope);
             },
             S::StaticVarDef{inner:raw::StaticVarDef{start,end,public,mutable,name,ty,data},..}=>{
-                let public=public.convert(scopes,parent,filename)?;
-                let mutable=mutable.convert(scopes,parent,filename)?;
-                let ty=ty.convert(scopes,parent,filename)?;
-                let data=data.convert(scopes,parent,filename)?;
+                let public=public.convert(scopes,parent,state)?;
+                let mutable=mutable.convert(scopes,parent,state)?;
+                let ty=ty.convert(scopes,parent,state)?;
+                let data=data.convert(scopes,parent,state)?;
                 let scope=scopes.push(VarScopeItem::Static {
                     parent_scope:parent,
                     def_start:start,
@@ -274,9 +316,9 @@ impl<'input> Convert<'input> for raw::Statement<'input> {
                 scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_var(name,scope);
             },
             S::ConstVarDef{inner:raw::ConstVarDef{start,end,public,name,ty,data},..}=>{
-                let public=public.convert(scopes,parent,filename)?;
-                let ty=ty.convert(scopes,parent,filename)?;
-                let data=data.convert(scopes,parent,filename)?;
+                let public=public.convert(scopes,parent,state)?;
+                let ty=ty.convert(scopes,parent,state)?;
+                let data=data.convert(scopes,parent,state)?;
                 let scope=scopes.push(VarScopeItem::Const {
                     parent_scope:parent,
                     def_start:start,
@@ -290,31 +332,45 @@ impl<'input> Convert<'input> for raw::Statement<'input> {
                 scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_var(name,scope);
             },
             S::VarAssign{inner:raw::VarAssign{start,end,name,data},..}=>{
-                let data=data.convert(scopes,parent,filename)?;
-                if let Some(loc)=scopes.get_mut(parent).expect("Internal compiler error: invalid scope").get_var(name) {
-                    scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::VarAssign {
-                        start,
-                        end,
-                        data,
-                        loc,
-                    });
+                let data=data.convert(scopes,parent,state)?;
+                // Anything not defined yet is left for name resolution, since it could be a static
+                // defined further down.
+                let stmt=if let Some(loc)=scopes.get_var(parent,name) {
+                    Statement::VarAssign{start,end,data,loc}
                 } else {
-                    return Err(Error::new_verif(filename,start,end,format!("variable {} is not defined",name)));
-                }
+                    Statement::UnknownVarAssign{start,end,name,data}
+                };
+                scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(stmt);
+            },
+            S::PlaceAssign{inner:raw::PlaceAssign{start,end,place,data},..}=>{
+                let place=place.convert(scopes,parent,state)?;
+                let data=data.convert(scopes,parent,state)?;
+                scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::PlaceAssign{start,end,place,data});
+            },
+            S::CompoundAssign{inner:raw::CompoundAssign{start,end,op,place,data},..}=>{
+                let op=op.convert(scopes,parent,state)?;
+                let place=place.convert(scopes,parent,state)?;
+                let data=data.convert(scopes,parent,state)?;
+                scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::CompoundAssign{start,end,op,place,data});
             },
             S::Expr{start,end,inner}=>{
-                let expr=inner.convert(scopes,parent,filename)?;
+                let expr=inner.convert(scopes,parent,state)?;
                 scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::Expr {
                     start,
                     end,
                     expr,
                 });
             },
-            S::Import{start:_,end:_,inner:_}=>{
-                todo!();
+            S::Import{inner,..}=>{
+                let mut imports=Vec::new();
+                flatten_import(inner,&[],&mut imports);
+                let parent=scopes.get_mut(parent).expect("Internal compiler error: invalid scope");
+                for import in imports {
+                    parent.add_import(import);
+                }
             },
             S::Return{start,end,label,val}=>{
-                let val=val.convert(scopes,parent,filename)?;
+                let val=val.convert(scopes,parent,state)?;
                 scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::Return {
                     start,
                     end,
@@ -322,13 +378,84 @@ impl<'input> Convert<'input> for raw::Statement<'input> {
                     val,
                 });
             },
-            S::Continue{start,end,inner}=>scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::Continue{start,end,label:inner}),
-            S::Enum{start:_,end:_,inner:_}=>{
-                todo!();
+            S::Continue{start,end,inner}=>{
+                let target=state.enclosing_loop("continue",inner,start,end)?.block;
+                scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::Continue{start,end,label:inner,target});
+            },
+            S::Break{start,end,label,val}=>{
+                let target=state.enclosing_loop("break",label,start,end)?;
+                if let Some(val)=&val {
+                    if !target.forever {
+                        let (val_start,val_end)=(val.start(),val.end());
+                        return Err(Error::new_verif(state.filename,val_start,val_end,"only `loop` can be left with a value".to_string())
+                            .with_note(target.start,target.end,"this loop can end without a `break`"));
+                    }
+                }
+                let val=val.convert(scopes,parent,state)?;
+                scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::Break {
+                    start,
+                    end,
+                    label,
+                    target:target.block,
+                    val,
+                });
+            },
+            S::Enum{inner:raw::Enum{start,end,public,name,params,variants},..}=>{
+                let public=public.convert(scopes,parent,state)?;
+                let params=params.convert(scopes,parent,state)?;
+                let scope=scopes.push(VarScopeItem::Enum {
+                    parent_scope:parent,
+                    def_start:start,
+                    def_end:end,
+                    public,
+                    name,
+                    params,
+                    variants:Vec::new(),
+                });
+                let mut new_variants=Vec::with_capacity(variants.len());
+                for variant in variants {
+                    let ty=match variant {
+                        raw::Type::Named{start,end,name,generics}=>{
+                            let mut fields=Vec::new();
+                            for t in generics {
+                                fields.push(t.convert(scopes,scope,state)?);
+                            }
+                            let path=scopes.push(VarScopeItem::EnumVariant {
+                                parent_scope:scope,
+                                def_start:start,
+                                def_end:end,
+                                name,
+                                fields,
+                            });
+                            Type::Named{path,start,end,generics:Vec::new()}
+                        },
+                        ty=>ty.convert(scopes,scope,state)?,
+                    };
+                    new_variants.push(ty);
+                }
+                if let Some(VarScopeItem::Enum{variants,..})=scopes.get_mut(scope) {
+                    *variants=new_variants;
+                }
+                scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::Enum{start,end,e:scope});
             },
             S::Module{inner,..}=>scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_module(inner),
-            S::Impl{start:_,end:_,inner:_}=>{
-                todo!();
+            S::Impl{inner:raw::Impl{start,end,params,interface,for_ty,block},..}=>{
+                let params=params.convert(scopes,parent,state)?;
+                let interface=interface.convert(scopes,parent,state)?;
+                let for_ty=for_ty.convert(scopes,parent,state)?;
+                let scope=scopes.push(VarScopeItem::Impl {
+                    parent_scope:parent,
+                    def_start:start,
+                    def_end:end,
+                    interface,
+                    params,
+                    for_ty,
+                    statements:Vec::new(),
+                });
+                scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::Impl{start,end,i:scope});
+                for s in block.inner {
+                    s.convert(scopes,scope,state).unwrap_or_else(|e|state.errors.push(e));
+                }
             },
         }
         return Ok(());
@@ -336,133 +463,164 @@ impl<'input> Convert<'input> for raw::Statement<'input> {
 }
 impl<'input> Convert<'input> for raw::Expr<'input> {
     type Output=Expr<'input>;
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         Ok(match self {
             raw::Expr::FieldAccess{start,end,from,name}=>{
-                let from=Box::new(from.convert(scopes,parent,filename)?);
+                let from=Box::new(from.convert(scopes,parent,state)?);
                 Expr::FieldAccess{start,end,from,name}
             },
             raw::Expr::MethodCall{start,end,from,name,args:old_args}=>{
-                let from=Box::new(from.convert(scopes,parent,filename)?);
+                let from=Box::new(from.convert(scopes,parent,state)?);
                 let mut args=Vec::with_capacity(old_args.len());
                 for arg in old_args {
-                    args.push(arg.convert(scopes,parent,filename)?);
+                    args.push(arg.convert(scopes,parent,state)?);
                 }
-                Expr::MethodCall{start,end,from,name,args}
+                Expr::MethodCall{start,end,from,name,args,method:None}
             },
             raw::Expr::FunctionCall{start,end,path,args:old_args}=>{
                 let mut args=Vec::with_capacity(old_args.len());
                 for arg in old_args {
-                    args.push(arg.convert(scopes,parent,filename)?);
+                    args.push(arg.convert(scopes,parent,state)?);
+                }
+                match path.as_slice() {
+                    [name]=>match scopes.get_var(parent,name) {
+                        Some(path)=>Expr::FunctionCall{start,end,path,args},
+                        None=>Expr::UnknownFunctionCall{start,end,path,args},
+                    },
+                    _=>Expr::UnknownFunctionCall{start,end,path,args},
                 }
-                Expr::UnknownFunctionCall{start,end,path,args}
             },
             raw::Expr::AssociatedPath{start,end,inner}=>{
                 Expr::UnknownAssociatedPath{start,end,path:inner}
             },
             raw::Expr::Var{start,end,inner}=>{
-                if let Some(path)=scopes.get_mut(parent).expect("Internal compiler error: invalid scope").get_var(inner) {
+                if let Some(path)=scopes.get_var(parent,inner) {
                     Expr::Var{start,end,path}
                 } else {
-                    return Err(Error::new_verif(filename,start,end,format!("variable {} is not defined",inner)));
+                    Expr::UnknownVar{start,end,name:inner}
                 }
             },
             raw::Expr::Block{start,end,inner}=>{
-                let block=inner.convert(scopes,parent,filename)?;
+                let block=inner.convert(scopes,parent,state)?;
                 Expr::Block{start,end,block}
             },
             raw::Expr::Data{start,end,inner}=>{
-                let data=inner.convert(scopes,parent,filename)?;
+                let data=inner.convert(scopes,parent,state)?;
                 Expr::Data{start,end,data}
             },
             raw::Expr::Add{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::Add{start,end,inner}
             },
             raw::Expr::Sub{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::Sub{start,end,inner}
             },
             raw::Expr::Mul{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::Mul{start,end,inner}
             },
             raw::Expr::Div{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::Div{start,end,inner}
             },
             raw::Expr::Mod{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::Mod{start,end,inner}
             },
+            raw::Expr::BitAnd{start,end,inner}=>{
+                let [inner0,inner1]=*inner;
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
+                Expr::BitAnd{start,end,inner}
+            },
+            raw::Expr::BitOr{start,end,inner}=>{
+                let [inner0,inner1]=*inner;
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
+                Expr::BitOr{start,end,inner}
+            },
+            raw::Expr::BitXor{start,end,inner}=>{
+                let [inner0,inner1]=*inner;
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
+                Expr::BitXor{start,end,inner}
+            },
+            raw::Expr::ShiftLeft{start,end,inner}=>{
+                let [inner0,inner1]=*inner;
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
+                Expr::ShiftLeft{start,end,inner}
+            },
+            raw::Expr::ShiftRight{start,end,inner}=>{
+                let [inner0,inner1]=*inner;
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
+                Expr::ShiftRight{start,end,inner}
+            },
             raw::Expr::Negate{start,end,inner}=>{
-                let inner=Box::new(inner.convert(scopes,parent,filename)?);
+                let inner=Box::new(inner.convert(scopes,parent,state)?);
                 Expr::Negate{start,end,inner}
             },
             raw::Expr::Equal{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::Equal{start,end,inner}
             },
             raw::Expr::NotEqual{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::NotEqual{start,end,inner}
             },
             raw::Expr::GreaterEqual{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::GreaterEqual{start,end,inner}
             },
             raw::Expr::LessEqual{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::LessEqual{start,end,inner}
             },
             raw::Expr::Greater{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::Greater{start,end,inner}
             },
             raw::Expr::Less{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::Less{start,end,inner}
             },
             raw::Expr::And{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::And{start,end,inner}
             },
             raw::Expr::Or{start,end,inner}=>{
                 let [inner0,inner1]=*inner;
-                let inner=Box::new([inner0.convert(scopes,parent,filename)?,inner1.convert(scopes,parent,filename)?]);
+                let inner=Box::new([inner0.convert(scopes,parent,state)?,inner1.convert(scopes,parent,state)?]);
                 Expr::Or{start,end,inner}
             },
             raw::Expr::Not{start,end,inner}=>{
-                let inner=Box::new(inner.convert(scopes,parent,filename)?);
+                let inner=Box::new(inner.convert(scopes,parent,state)?);
                 Expr::Not{start,end,inner}
             },
             raw::Expr::IsType{start,end,inner,ty}=>{
-                let to_test=Box::new(inner.convert(scopes,parent,filename)?);
-                let ty=ty.convert(scopes,parent,filename)?;
+                let to_test=Box::new(inner.convert(scopes,parent,state)?);
+                let ty=ty.convert(scopes,parent,state)?;
                 Expr::IsType{start,end,to_test,ty}
             },
             raw::Expr::ObjectCreation{start,end,inner}=>{
                 let mut fields=Vec::new();
                 for field in inner {
-                    fields.push(field.convert(scopes,parent,filename)?);
+                    fields.push(field.convert(scopes,parent,state)?);
                 }
                 Expr::ObjectCreation{start,end,fields}
             },
             raw::Expr::AnonFunction{inner:raw::AnonFunction{start,end,params,ret_type,block},..}=>{
-                let ret_type=ret_type.convert(scopes,parent,filename)?;
-                let params=params.convert(scopes,parent,filename)?;
+                let ret_type=ret_type.convert(scopes,parent,state)?;
+                let params=params.convert(scopes,parent,state)?;
                 let scope=scopes.push(VarScopeItem::AnonFunction {
                     imports:Vec::new(),
                     parent_scope:parent,
@@ -473,68 +631,177 @@ impl<'input> Convert<'input> for raw::Expr<'input> {
                     statements:Vec::new(),
                     vars:HashMap::new(),
                 });
-                for param in params.normal.into_iter() {
-                    let name=param.name;
-                    if scopes.get_mut(scope).unwrap().get_var(param.name).is_some() {
-                        return Err(Error::new_verif(filename,start,end,format!("parameter {} is specified twice",name)));
-                    }
-                    let param_scope=scopes.push(VarScopeItem::Parameter {
-                        parent_scope:parent,
-                        def_start:param.start,
-                        def_end:param.end,
-                        mutable:param.mutable,
-                        name,
-                        ty:param.ty,
-                    });
-                    scopes.get_mut(scope).unwrap().add_var(name,param_scope);
-                }
+                add_params(scopes,scope,params,state);
                 scopes.get_mut(parent).expect("Internal compiler error: invalid scope").add_stmt(Statement::FunctionDef{start,end,def:scope});
+                let loops=std::mem::take(&mut state.loops);
                 for s in block.inner {
-                    s.convert(scopes,scope,filename)?;
+                    s.convert(scopes,scope,state).unwrap_or_else(|e|state.errors.push(e));
                 }
+                state.loops=loops;
                 Expr::AnonFunction{start,end,function:scope}
             },
             raw::Expr::Ref{start,end,inner}=>{
-                todo!();
+                let val=Box::new(inner.convert(scopes,parent,state)?);
+                Expr::Ref{start,end,val}
             },
             raw::Expr::RefMut{start,end,inner}=>{
-                todo!();
+                let val=Box::new(inner.convert(scopes,parent,state)?);
+                Expr::RefMut{start,end,val}
+            },
+            raw::Expr::Deref{start,end,inner}=>{
+                let val=Box::new(inner.convert(scopes,parent,state)?);
+                Expr::Deref{start,end,val}
+            },
+            raw::Expr::Unsafe{start,end,inner}=>{
+                let block=inner.convert(scopes,parent,state)?;
+                Expr::Unsafe{start,end,block}
             },
-            raw::Expr::ForeverLoop{start,end,inner}=>{
-                todo!();
+            raw::Expr::ForeverLoop{start,end,label,inner}=>{
+                let block=convert_loop_block(inner,(start,end),label,true,None,scopes,parent,state);
+                Expr::ForeverLoop{start,end,label,block}
             },
-            raw::Expr::WhileLoop{start,end,condition,block}=>{
-                todo!();
+            raw::Expr::WhileLoop{start,end,label,condition,block}=>{
+                let condition=Box::new(condition.convert(scopes,parent,state)?);
+                let block=convert_loop_block(block,(start,end),label,false,None,scopes,parent,state);
+                Expr::WhileLoop{start,end,label,condition,block}
             },
-            raw::Expr::ForLoop{start,end,var,iterator,block}=>{
-                todo!();
+            raw::Expr::ForLoop{start,end,label,var,iterator,block}=>{
+                let iterator=Box::new(iterator.convert(scopes,parent,state)?);
+                let block=convert_loop_block(block,(start,end),label,false,Some(var),scopes,parent,state);
+                Expr::ForLoop{start,end,label,var,iterator,block}
             },
             raw::Expr::Match{start,end,inner}=>{
-                todo!();
+                let block=Box::new(inner.convert(scopes,parent,state)?);
+                Expr::Match{start,end,block}
+            },
+            raw::Expr::If{start,end,branches:old_branches,else_block}=>{
+                let mut branches=Vec::with_capacity(old_branches.len());
+                for (condition,block) in old_branches {
+                    let condition=condition.convert(scopes,parent,state)?;
+                    let narrowed=narrowed_var(scopes,&condition);
+                    let block=convert_narrowed_block(block,narrowed,scopes,parent,state)?;
+                    branches.push((condition,block));
+                }
+                let else_block=else_block.convert(scopes,parent,state)?;
+                Expr::If{start,end,branches,else_block}
+            },
+            raw::Expr::List{start,end,inner}=>{
+                let mut items=Vec::with_capacity(inner.len());
+                for item in inner {
+                    items.push(item.convert(scopes,parent,state)?);
+                }
+                Expr::List{start,end,items}
+            },
+            raw::Expr::Index{start,end,from,index}=>{
+                let from=Box::new(from.convert(scopes,parent,state)?);
+                let index=Box::new(index.convert(scopes,parent,state)?);
+                Expr::Index{start,end,from,index}
+            },
+            raw::Expr::Slice{start,end,from,range_start,range_end}=>{
+                let from=Box::new(from.convert(scopes,parent,state)?);
+                let range_start=range_start.map(|e|e.convert(scopes,parent,state).map(Box::new)).transpose()?;
+                let range_end=range_end.map(|e|e.convert(scopes,parent,state).map(Box::new)).transpose()?;
+                Expr::Slice{start,end,from,range_start,range_end}
+            },
+        })
+    }
+}
+impl<'input> Convert<'input> for Box<raw::Match<'input>> {
+    type Output=Match<'input>;
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
+        let raw::Match{start,end,to_match,leafs:old_leafs}=*self;
+        let to_match=to_match.convert(scopes,parent,state)?;
+        let mut leafs=Vec::with_capacity(old_leafs.len());
+        for (pattern,expr) in old_leafs {
+            // Every arm gets its own scope for the variables its pattern binds.
+            let arm=block_scope(scopes,parent,expr.start(),expr.end());
+            let pattern=pattern.convert(scopes,arm,state)?;
+            let expr=expr.convert(scopes,arm,state)?;
+            leafs.push((pattern,expr));
+        }
+        return Ok(Match{start,end,to_match,leafs});
+    }
+}
+impl<'input> Convert<'input> for raw::MatchPattern<'input> {
+    type Output=MatchPattern<'input>;
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
+        use raw::MatchPattern as MP;
+        Ok(match self {
+            MP::Data{start,end,inner}=>MatchPattern::Data{start,end,inner:inner.convert(scopes,parent,state)?},
+            MP::MethodCall{start,end,name,args:old_args}=>{
+                let mut args=Vec::with_capacity(old_args.len());
+                for arg in old_args {
+                    args.push(arg.convert(scopes,parent,state)?);
+                }
+                MatchPattern::MethodCall{start,end,name,args}
+            },
+            MP::Structure{start,end,inner}=>MatchPattern::Structure{start,end,structure:inner.convert(scopes,parent,state)?},
+            MP::Var{start,end,inner}=>MatchPattern::Var{start,end,name:inner,var:bind_pattern_var(scopes,parent,start,end,inner,state)},
+            MP::Equal{start,end,inner}=>MatchPattern::Equal{start,end,inner:inner.convert(scopes,parent,state)?},
+            MP::NotEqual{start,end,inner}=>MatchPattern::NotEqual{start,end,inner:inner.convert(scopes,parent,state)?},
+            MP::GreaterEqual{start,end,inner}=>MatchPattern::GreaterEqual{start,end,inner:inner.convert(scopes,parent,state)?},
+            MP::LessEqual{start,end,inner}=>MatchPattern::LessEqual{start,end,inner:inner.convert(scopes,parent,state)?},
+            MP::Greater{start,end,inner}=>MatchPattern::Greater{start,end,inner:inner.convert(scopes,parent,state)?},
+            MP::Less{start,end,inner}=>MatchPattern::Less{start,end,inner:inner.convert(scopes,parent,state)?},
+            MP::IsType{start,end,inner}=>MatchPattern::IsType{start,end,inner:inner.convert(scopes,parent,state)?},
+        })
+    }
+}
+impl<'input> Convert<'input> for raw::MatchPatternStructure<'input> {
+    type Output=MatchPatternStructure<'input>;
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
+        Ok(match self {
+            raw::MatchPatternStructure::Block{start,end,exact,block:old_block}=>{
+                let mut block=Vec::with_capacity(old_block.len());
+                for item in old_block {
+                    block.push(item.convert(scopes,parent,state)?);
+                }
+                MatchPatternStructure::Block{start,end,exact,block}
+            },
+            raw::MatchPatternStructure::TypedBlock{start,end,exact,type_name,block:old_block}=>{
+                let mut block=Vec::with_capacity(old_block.len());
+                for item in old_block {
+                    block.push(item.convert(scopes,parent,state)?);
+                }
+                let ty=raw::Type::Named{start,end,name:type_name,generics:Vec::new()}.convert(scopes,parent,state)?;
+                MatchPatternStructure::TypedBlock{start,end,exact,ty,block}
+            },
+        })
+    }
+}
+impl<'input> Convert<'input> for raw::MatchPatternStructureItem<'input> {
+    type Output=MatchPatternStructureItem<'input>;
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
+        use raw::MatchPatternStructureItem as MPSI;
+        Ok(match self {
+            MPSI::Field{start,end,inner}=>{
+                let var=bind_pattern_var(scopes,parent,start,end,inner,state);
+                MatchPatternStructureItem::Field{start,end,name:inner,var}
+            },
+            MPSI::NamedField{start,end,name,rename}=>{
+                let var=bind_pattern_var(scopes,parent,start,end,rename,state);
+                MatchPatternStructureItem::NamedField{start,end,name,rename,var}
+            },
+            MPSI::NamedBlock{start,end,name,block}=>{
+                let block=block.convert(scopes,parent,state)?;
+                MatchPatternStructureItem::NamedBlock{start,end,name,block}
             },
         })
     }
 }
 impl<'input> Convert<'input> for raw::Block<'input> {
     type Output=Scope;
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>> {
-        let scope=scopes.push(VarScopeItem::Block {
-            imports:Vec::new(),
-            parent_scope:parent,
-            def_start:self.start,
-            def_end:self.end,
-            statements:Vec::new(),
-            vars:Default::default(),
-        });
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
+        let scope=block_scope(scopes,parent,self.start,self.end);
         for s in self.inner.into_iter() {
-            s.convert(scopes,scope,filename)?;
+            s.convert(scopes,scope,state).unwrap_or_else(|e|state.errors.push(e));
         }
         return Ok(scope);
     }
 }
 impl<'input> Convert<'input> for raw::Data<'input> {
     type Output=Data<'input>;
-    fn convert(self,_:&mut Scopes<'input>,_:Scope,_:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,_:&mut Scopes<'input>,_:Scope,_:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         Ok(match self {
             raw::Data::String{start,end,inner}=>Data::String{start,end,s:inner},
             raw::Data::GenericNumber{start,end,negative,inner}=>Data::GenericNumber{start,end,negative,data:inner},
@@ -547,21 +814,165 @@ impl<'input> Convert<'input> for raw::Data<'input> {
 }
 impl<'input> Convert<'input> for raw::ObjectField<'input> {
     type Output=ObjectField<'input>;
-    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,filename:&'input str)->Result<Self::Output,Error<'input,String>> {
+    fn convert(self,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Self::Output,Error<'input,String>> {
         let raw::ObjectField{start,end,public,mutable,name,data}=self;
-        let public=public.convert(scopes,parent,filename)?;
-        let mutable=mutable.convert(scopes,parent,filename)?;
-        let data=data.convert(scopes,parent,filename)?;
+        let public=public.convert(scopes,parent,state)?;
+        let mutable=mutable.convert(scopes,parent,state)?;
+        let data=data.convert(scopes,parent,state)?;
         return Ok(ObjectField{start,end,public,mutable,name,data});
     }
 }
 
 
-pub fn refine<'input>(statements:Vec<raw::Statement<'input>>,filename:&'input str)->Result<Scopes<'input>,Error<'input,String>> {
+/// Turns `a::{b,c::{d,e}}` into `a::b`, `a::c::d` and `a::c::e`.
+fn flatten_import<'input>(import:raw::Import<'input>,prefix:&[&'input str],out:&mut Vec<Import<'input>>) {
+    match import {
+        raw::Import::Path{start,end,inner}=>{
+            let mut path=prefix.to_vec();
+            path.extend(inner);
+            out.push(Import{start,end,path});
+        },
+        raw::Import::PathBlock{path,block,..}=>{
+            let mut prefix=prefix.to_vec();
+            prefix.extend(path);
+            for import in block {
+                flatten_import(import,&prefix,out);
+            }
+        },
+    }
+}
+fn block_scope<'input>(scopes:&mut Scopes<'input>,parent:Scope,def_start:Location,def_end:Location)->Scope {
+    return scopes.push(VarScopeItem::Block {
+        imports:Vec::new(),
+        parent_scope:parent,
+        def_start,
+        def_end,
+        statements:Vec::new(),
+        vars:Default::default(),
+    });
+}
+/// Adds a variable bound by a pattern to the scope of the match arm `arm`. Binding the same name
+/// twice is reported, and the second one isn't visible in the arm.
+fn bind_pattern_var<'input>(scopes:&mut Scopes<'input>,arm:Scope,start:Location,end:Location,name:&'input str,state:&mut State<'input>)->Scope {
+    let existing=scopes.get(arm).and_then(|a|a.get_var(name));
+    let var=scopes.push(VarScopeItem::MatchBlockVar {
+        parent_scope:arm,
+        def_start:start,
+        def_end:end,
+        var:name,
+        ty:Type::Unknown,
+    });
+    match existing.and_then(|e|scopes.get(e)).and_then(|e|e.def_span()) {
+        Some((first_start,first_end))=>{
+            let err=Error::new_verif(state.filename,start,end,format!("`{}` is bound more than once in the same pattern",name))
+                .with_note(first_start,first_end,"first bound here");
+            state.errors.push(err);
+        },
+        None=>scopes.get_mut(arm).expect("Internal compiler error: invalid scope").add_var(name,var),
+    }
+    return var;
+}
+/// Converts the block of a loop. `break` and `continue` inside of it refer to the block's scope.
+/// `var` is the variable of a `for` loop, which is a parameter of the block.
+#[allow(clippy::too_many_arguments)]
+fn convert_loop_block<'input>(block:raw::Block<'input>,(start,end):(Location,Location),label:Option<&'input str>,forever:bool,var:Option<&'input str>,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Scope {
+    let scope=block_scope(scopes,parent,block.start,block.end);
+    if let Some(name)=var {
+        let param=scopes.push(VarScopeItem::Parameter {
+            parent_scope:scope,
+            def_start:start,
+            def_end:end,
+            mutable:false,
+            name,
+            ty:Type::Unknown,
+        });
+        scopes.get_mut(scope).unwrap().add_var(name,param);
+    }
+    state.loops.push(EnclosingLoop{start,end,label,block:scope,forever});
+    for s in block.inner {
+        s.convert(scopes,scope,state).unwrap_or_else(|e|state.errors.push(e));
+    }
+    state.loops.pop();
+    return scope;
+}
+/// The variable and type a condition like `x is T` narrows to.
+fn narrowed_var<'input>(scopes:&Scopes<'input>,condition:&Expr<'input>)->Option<Narrowing<'input>> {
+    match condition {
+        Expr::IsType{start,end,to_test,ty}=>match &**to_test {
+            Expr::Var{path,..}=>{
+                let name=scopes.get(*path)?.name()?;
+                Some((name,*path,ty.clone(),*start,*end))
+            },
+            _=>None,
+        },
+        _=>None,
+    }
+}
+/// Converts a block, shadowing the narrowed variable inside of it with its more specific type.
+fn convert_narrowed_block<'input>(block:raw::Block<'input>,narrowed:Option<Narrowing<'input>>,scopes:&mut Scopes<'input>,parent:Scope,state:&mut State<'input>)->Result<Scope,Error<'input,String>> {
+    let scope=block_scope(scopes,parent,block.start,block.end);
+    if let Some((name,original,ty,def_start,def_end))=narrowed {
+        let var=scopes.push(VarScopeItem::Narrowed{parent_scope:scope,def_start,def_end,name,original,ty});
+        scopes.get_mut(scope).unwrap().add_var(name,var);
+    }
+    for s in block.inner {
+        s.convert(scopes,scope,state).unwrap_or_else(|e|state.errors.push(e));
+    }
+    return Ok(scope);
+}
+/// Adds the parameters to a function scope. Duplicate parameters are reported and skipped.
+fn add_params<'input>(scopes:&mut Scopes<'input>,scope:Scope,params:Parameters<'input>,state:&mut State<'input>) {
+    let mut normal=params.normal;
+    let mutable=match params.method_type {
+        MethodType::This=>Some(false),
+        MethodType::ThisMut=>Some(true),
+        MethodType::None=>None,
+    };
+    if let Some(mutable)=mutable {
+        // `this` is only typed inside of impl blocks and interfaces, where we know what `Self` is.
+        let in_impl=scopes.get(scope)
+            .and_then(|i|i.parent_scope())
+            .and_then(|p|scopes.get(p))
+            .is_some_and(|p|matches!(p,VarScopeItem::Impl{..}|VarScopeItem::Interface{..}));
+        let ty=if in_impl {
+            Type::UnknownNamed{name:"Self",start:params.start,end:params.end,generics:Vec::new()}
+        } else {
+            Type::Unknown
+        };
+        normal.insert(0,Parameter{name:"this",start:params.start,end:params.end,mutable,ty});
+    }
+    for param in normal.into_iter() {
+        let name=param.name;
+        if scopes.get(scope).unwrap().get_var(name).is_some() {
+            state.errors.push(Error::new_verif(state.filename,param.start,param.end,format!("parameter {} is specified twice",name)));
+            continue;
+        }
+        let param_scope=scopes.push(VarScopeItem::Parameter {
+            parent_scope:scope,
+            def_start:param.start,
+            def_end:param.end,
+            mutable:param.mutable,
+            name,
+            ty:param.ty,
+        });
+        match scopes.get_mut(scope).unwrap() {
+            VarScopeItem::Function{params,..}|VarScopeItem::AnonFunction{params,..}=>params.push(param_scope),
+            _=>{},
+        }
+        scopes.get_mut(scope).unwrap().add_var(name,param_scope);
+    }
+}
+
+
+/// Refines the raw AST into scopes. Errors don't stop refinement, so the scopes are always returned
+/// along with every error found.
+pub fn refine<'input>(statements:Vec<raw::Statement<'input>>,filename:&'input str)->(Scopes<'input>,Vec<Error<'input,String>>) {
     let mut scopes=Scopes::default();
     let root_scope=scopes.push(VarScopeItem::Root{modules:Vec::new(),imports:Vec::new(),statements:Vec::new(),vars:Default::default()});
+    crate::std_lib::add_std(&mut scopes,root_scope);
+    let mut state=State{filename,errors:Vec::new(),loops:Vec::new()};
     for s in statements {
-        s.convert(&mut scopes,root_scope,filename)?;
+        s.convert(&mut scopes,root_scope,&mut state).unwrap_or_else(|e|state.errors.push(e));
     }
-    return Ok(scopes);
+    return (scopes,state.errors);
 }
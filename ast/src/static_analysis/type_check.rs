@@ -0,0 +1,912 @@
+use std::collections::HashSet;
+use cppl_error::{
+    Location,
+    Error,
+};
+use crate::refined::*;
+
+
+/// Parameter types along with where they were defined.
+type CallParams<'input>=Vec<(Type<'input>,Option<(Location,Location)>)>;
+/// What to point at when a value doesn't have the type it is expected to have.
+type Note<'a>=Option<(Location,Location,&'a str)>;
+
+
+struct State<'a,'input> {
+    filename:&'input str,
+    scopes:&'a Scopes<'input>,
+    errors:Vec<Error<'input,String>>,
+    warnings:Vec<Error<'input,String>>,
+    /// The blocks of every `unsafe` expression.
+    unsafe_blocks:HashSet<Scope>,
+    /// The blocks of every branch of an `if`. The expression they end with is checked as the value
+    /// of the `if`.
+    branch_blocks:HashSet<Scope>,
+}
+impl<'a,'input> State<'a,'input> {
+    fn push<T:Into<String>>(&mut self,start:Location,end:Location,err:T) {
+        self.errors.push(Error::new_verif(self.filename,start,end,err.into()));
+    }
+    /// Reports an error if `expr` can't be used as `ty`. `note` points at whatever decided the
+    /// expected type.
+    fn expect(&mut self,expr:&Expr<'input>,ty:&Type<'input>,note:Note) {
+        // What the type is expected because of was already reported.
+        if let Type::Unknown=ty {
+            self.value_type(expr);
+            return;
+        }
+        // Every arm of a `match` can be its value, so each of them has to fit.
+        if let Expr::Match{block,..}=expr {
+            self.check_match_patterns(block);
+            for (_,leaf) in block.leafs.iter() {
+                self.expect(leaf,ty,note);
+            }
+            return;
+        }
+        // The same goes for every branch of an `if`.
+        if let Expr::If{..}=expr {
+            self.if_value(expr,Some((ty,note)));
+            return;
+        }
+        let before=self.errors.len();
+        let found=self.type_of(expr);
+        // Don't pile a mismatch on top of the errors inside of the expression.
+        if self.errors.len()>before {
+            return;
+        }
+        if let Type::Unknown=found {
+            if can_lack_value(expr) {
+                let (start,end)=expr.span();
+                self.push(start,end,format!("expected a value of type `{}`",self.scopes.type_name(ty)));
+            }
+            return;
+        }
+        if let Err(reason)=self.scopes.check_assignable(&found,ty) {
+            let (start,end)=expr.span();
+            let mut err=Error::new_verif(self.filename,start,end,format!("mismatched types: {}",reason));
+            if let Some((start,end,note))=note {
+                err=err.with_note(start,end,note);
+            }
+            self.errors.push(err);
+        }
+    }
+    /// The type of `expr`, which has to have a value. Expressions without one, like calls of
+    /// functions that don't return anything, are reported.
+    fn value_type(&mut self,expr:&Expr<'input>)->Type<'input> {
+        if let Expr::If{..}=expr {
+            return self.if_value(expr,None);
+        }
+        let before=self.errors.len();
+        let ty=self.type_of(expr);
+        if let Type::Unknown=ty {
+            if self.errors.len()==before&&can_lack_value(expr) {
+                let (start,end)=expr.span();
+                self.push(start,end,"expected a value");
+            }
+        }
+        return ty;
+    }
+    /// The type of an `if` used as a value, which needs an `else`. Every branch has to end with a
+    /// value unless it never finishes, and the values have to be `expected` if it is given, or the
+    /// same type otherwise.
+    fn if_value(&mut self,expr:&Expr<'input>,expected:Option<(&Type<'input>,Note)>)->Type<'input> {
+        let Expr::If{start,end,branches,else_block}=expr else {
+            return self.type_of(expr);
+        };
+        for (condition,_) in branches.iter() {
+            self.expect(condition,&Type::Bool,None);
+        }
+        let mut ty=Type::Never;
+        let mut first=None;
+        for block in branches.iter().map(|(_,block)|*block).chain(*else_block) {
+            let Some(value)=self.scopes.branch_value(block).1 else {
+                if !self.block_returns(block) {
+                    let (start,end)=self.scopes.get(block).and_then(|b|b.def_span()).unwrap_or((*start,*end));
+                    self.push(start,end,"expected this branch to end with a value");
+                }
+                continue;
+            };
+            if let Some((expected,note))=expected {
+                self.expect(value,expected,note);
+                continue;
+            }
+            let value_ty=self.value_type(value);
+            if matches!(value_ty,Type::Unknown)||matches!(ty,Type::Unknown) {
+                ty=Type::Unknown;
+                continue;
+            }
+            if let Some(common)=self.common_type(ty.clone(),value_ty.clone()) {
+                ty=common;
+                first=first.or(Some(value.span()));
+                continue;
+            }
+            let (value_start,value_end)=value.span();
+            let mut err=Error::new_verif(self.filename,value_start,value_end,format!(
+                "mismatched types: expected `{}`, found `{}`",
+                self.scopes.type_name(&ty),
+                self.scopes.type_name(&value_ty),
+            ));
+            if let Some((first_start,first_end))=first {
+                err=err.with_note(first_start,first_end,"expected because of the first branch");
+            }
+            self.errors.push(err);
+            ty=Type::Unknown;
+        }
+        if else_block.is_none() {
+            self.push(*start,*end,"`if` without `else` does not have a value");
+            return Type::Unknown;
+        }
+        return match expected {
+            Some((expected,_))=>expected.clone(),
+            None=>ty,
+        };
+    }
+    /// The type of a function as a value.
+    fn function_type(&self,scope:Scope)->Type<'input> {
+        let (params,ret_type,start,end)=match self.scopes.get(scope) {
+            Some(VarScopeItem::Function{params,ret_type,def_start,def_end,..})|
+                Some(VarScopeItem::AnonFunction{params,ret_type,def_start,def_end,..})=>(params,ret_type,*def_start,*def_end),
+            _=>return Type::Unknown,
+        };
+        let normal=params.iter()
+            .filter_map(|p|match self.scopes.get(*p) {
+                Some(VarScopeItem::Parameter{def_start,def_end,mutable,name,ty,..})=>Some(Parameter {
+                    start:*def_start,
+                    end:*def_end,
+                    mutable:*mutable,
+                    name,
+                    ty:ty.clone(),
+                }),
+                _=>None,
+            })
+            .collect();
+        return Type::FunctionSig {
+            start,
+            end,
+            inner:Box::new(AnonFunctionSignature {
+                start,
+                end,
+                params:Parameters{start,end,method_type:MethodType::None,normal,var_arg:None},
+                ret_type:ret_type.clone(),
+            }),
+        };
+    }
+    fn ret_type(&self,scope:Scope)->Type<'input> {
+        match self.scopes.get(scope) {
+            Some(VarScopeItem::Function{ret_type,..})|
+                Some(VarScopeItem::AnonFunction{ret_type,..})|
+                Some(VarScopeItem::BuiltinFunction{ret_type,..})=>ret_type.clone().unwrap_or(Type::Unknown),
+            Some(item)=>match item.var_type().map(|t|self.scopes.expand_alias(t)) {
+                Some(Type::FunctionSig{inner,..})=>inner.ret_type.clone().unwrap_or(Type::Unknown),
+                _=>Type::Unknown,
+            },
+            None=>Type::Unknown,
+        }
+    }
+    /// Checks the arguments of a call of `function`. `bindings` are the types its type parameters
+    /// have in this call.
+    fn check_call(&mut self,start:Location,end:Location,function:Scope,args:&[Expr<'input>],is_method:bool,bindings:&[(&str,Type<'input>)]) {
+        let Some(params)=call_params(self.scopes,function,is_method) else {return};
+        let params=params.into_iter().map(|(ty,span)|(ty.substitute(bindings),span)).collect();
+        self.check_args(start,end,&params,args);
+    }
+    /// The type a call of `method` on a `from_ty` returns, after checking its arguments.
+    fn method_call(&mut self,start:Location,end:Location,method:Scope,from_ty:&Type<'input>,args:&[Expr<'input>])->Type<'input> {
+        let bindings=impl_bindings(self.scopes,method,from_ty);
+        self.check_call(start,end,method,args,true,&bindings);
+        return self.ret_type(method).substitute(&bindings);
+    }
+    fn check_args(&mut self,start:Location,end:Location,params:&CallParams<'input>,args:&[Expr<'input>]) {
+        if params.len()!=args.len() {
+            self.push(start,end,format!("expected {} arguments, found {}",params.len(),args.len()));
+            return;
+        }
+        for (arg,(ty,span)) in args.iter().zip(params.iter()) {
+            self.expect(arg,ty,span.map(|(s,e)|(s,e,"parameter defined here")));
+        }
+    }
+    fn field_type(&mut self,from:&Expr<'input>,name:&str,start:Location,end:Location)->Type<'input> {
+        let from_ty=self.value_type(from);
+        return self.field_of(&from_ty,name,start,end);
+    }
+    fn field_of(&mut self,from_ty:&Type<'input>,name:&str,start:Location,end:Location)->Type<'input> {
+        match self.scopes.field_type(from_ty,name) {
+            Some(ty)=>ty,
+            None=>{
+                self.push(start,end,format!("no field `{}` on type `{}`",name,self.scopes.type_name(from_ty)));
+                Type::Unknown
+            },
+        }
+    }
+    /// The type both operands of a binary operator are used as, which have to be the same type.
+    /// Number literals take the type of the other side.
+    fn operand_type(&mut self,start:Location,end:Location,left:Type<'input>,right:Type<'input>)->Type<'input> {
+        // A side without a type has already been reported.
+        if matches!(left,Type::Unknown)||matches!(right,Type::Unknown) {
+            return Type::Unknown;
+        }
+        if self.scopes.check_assignable(&right,&left).is_ok() {
+            match left {
+                Type::GenericNumber|Type::GenericFloat=>right,
+                _=>left,
+            }
+        } else if self.scopes.check_assignable(&left,&right).is_ok() {
+            right
+        } else {
+            self.push(start,end,format!(
+                "mismatched types: `{}` and `{}`",
+                self.scopes.type_name(&left),
+                self.scopes.type_name(&right),
+            ));
+            Type::Unknown
+        }
+    }
+    /// The type of an arithmetic operation with the operator `op` on operands of the type `ty`.
+    /// Only numbers can be used, and strings with `+`.
+    fn arithmetic_type(&mut self,start:Location,end:Location,op:&str,ty:Type<'input>)->Type<'input> {
+        match self.scopes.expand_alias(&ty) {
+            Type::Byte|Type::Int|Type::Uint|Type::Float|Type::DoubleFloat|Type::GenericNumber|Type::GenericFloat|Type::Never|Type::Unknown=>ty,
+            Type::String if op=="+"=>ty,
+            _=>{
+                let allowed=if op=="+" {"number types and `String`"} else {"number types"};
+                self.push(start,end,format!("`{}` can only be used on {}, found `{}`",op,allowed,self.scopes.type_name(&ty)));
+                Type::Unknown
+            },
+        }
+    }
+    /// The type of an operand of a bitwise operator, which has to be an integer.
+    fn integer_type(&mut self,expr:&Expr<'input>,op:&str)->Type<'input> {
+        let ty=self.value_type(expr);
+        match self.scopes.expand_alias(&ty) {
+            Type::Byte|Type::Int|Type::Uint|Type::GenericNumber|Type::Never|Type::Unknown=>ty,
+            _=>{
+                let (start,end)=expr.span();
+                self.push(start,end,format!("`{}` can only be used on integer types, found `{}`",op,self.scopes.type_name(&ty)));
+                Type::Unknown
+            },
+        }
+    }
+    /// The type of the items of `expr`, which has to be a slice. `what` says what is being done
+    /// with it for the error.
+    fn items_of(&mut self,expr:&Expr<'input>,what:&str)->Type<'input> {
+        let ty=self.value_type(expr);
+        if let Some(item)=self.scopes.item_type(&ty) {
+            return item.clone();
+        }
+        if !matches!(self.scopes.expand_alias(&ty),Type::Never|Type::Unknown) {
+            let (start,end)=expr.span();
+            self.push(start,end,format!("`{}` {}",self.scopes.type_name(&ty),what));
+        }
+        return Type::Unknown;
+    }
+    /// Checks that an index or an end of a range is an integer.
+    fn check_index(&mut self,index:&Expr<'input>) {
+        let ty=self.value_type(index);
+        if !matches!(self.scopes.expand_alias(&ty),Type::Byte|Type::Int|Type::Uint|Type::GenericNumber|Type::Never|Type::Unknown) {
+            let (start,end)=index.span();
+            self.push(start,end,format!("indexes have to be integers, found `{}`",self.scopes.type_name(&ty)));
+        }
+    }
+    /// The type both `a` and `b` fit, if there is one. Number literals take the type of the other
+    /// side and `Never` fits anything.
+    fn common_type(&self,a:Type<'input>,b:Type<'input>)->Option<Type<'input>> {
+        let takes_other=matches!(a,Type::GenericNumber|Type::GenericFloat|Type::Never);
+        if self.scopes.check_assignable(&b,&a).is_ok() {
+            return Some(if takes_other {b} else {a});
+        } else if takes_other&&self.scopes.check_assignable(&a,&b).is_ok() {
+            return Some(b);
+        }
+        return None;
+    }
+    /// The type of a list literal. Every item has to be the same type as the first, except that
+    /// number literals take the type of the other items. Empty lists can be any list.
+    fn list_type(&mut self,start:Location,end:Location,items:&[Expr<'input>])->Type<'input> {
+        let Some((first,rest))=items.split_first() else {
+            return Type::Slice{start,end,inner:Box::new(Type::Never)};
+        };
+        let mut item_ty=self.value_type(first);
+        for item in rest {
+            let ty=self.value_type(item);
+            if matches!(ty,Type::Unknown)||matches!(item_ty,Type::Unknown) {
+                continue;
+            }
+            if let Some(common)=self.common_type(item_ty.clone(),ty.clone()) {
+                item_ty=common;
+            } else {
+                let (item_start,item_end)=item.span();
+                let (first_start,first_end)=first.span();
+                let err=Error::new_verif(self.filename,item_start,item_end,format!(
+                    "mismatched types: expected `{}`, found `{}`",
+                    self.scopes.type_name(&item_ty),
+                    self.scopes.type_name(&ty),
+                )).with_note(first_start,first_end,"expected because of the first item");
+                self.errors.push(err);
+            }
+        }
+        return Type::Slice{start,end,inner:Box::new(item_ty)};
+    }
+    /// Checks what a `match` matches on and its patterns.
+    fn check_match_patterns(&mut self,block:&Match<'input>) {
+        let ty=self.value_type(&block.to_match);
+        for (pattern,_) in block.leafs.iter() {
+            pattern.exprs().into_iter().for_each(|e|{self.type_of(e);});
+            if let MatchPattern::Structure{structure,..}=pattern {
+                self.check_structure_pattern(structure,&ty);
+            }
+        }
+    }
+    /// Checks that the fields a structure pattern names exist on what it matches.
+    fn check_structure_pattern(&mut self,pattern:&MatchPatternStructure<'input>,ty:&Type<'input>) {
+        let (ty,items)=match pattern {
+            MatchPatternStructure::Block{block,..}=>(ty,block),
+            MatchPatternStructure::TypedBlock{ty,block,..}=>(ty,block),
+        };
+        for item in items {
+            let (start,end,name)=match item {
+                MatchPatternStructureItem::Field{start,end,name,..}|
+                    MatchPatternStructureItem::NamedField{start,end,name,..}|
+                    MatchPatternStructureItem::NamedBlock{start,end,name,..}=>(*start,*end,*name),
+            };
+            let Some(field_ty)=self.scopes.field_type(ty,name) else {
+                self.push(start,end,format!("no field `{}` on type `{}`",name,self.scopes.type_name(ty)));
+                continue;
+            };
+            if let MatchPatternStructureItem::NamedBlock{block,..}=item {
+                self.check_structure_pattern(block,&field_ty);
+            }
+        }
+    }
+    /// Finds the type of an expression and checks everything inside of it.
+    fn type_of(&mut self,expr:&Expr<'input>)->Type<'input> {
+        use Expr::*;
+        match expr {
+            Data{data,..}=>match data {
+                self::Data::String{..}=>Type::String,
+                self::Data::GenericNumber{..}=>Type::GenericNumber,
+                self::Data::GenericFloat{..}=>Type::GenericFloat,
+                self::Data::UInt{..}=>Type::Uint,
+                self::Data::Int{..}=>Type::Int,
+                self::Data::Float{..}=>Type::Float,
+                self::Data::LargeFloat{..}=>Type::DoubleFloat,
+                self::Data::Char{..}=>Type::Char,
+                self::Data::Bool{..}=>Type::Bool,
+            },
+            Var{path,..}|AssociatedPath{path,..}=>match self.scopes.get(*path) {
+                Some(VarScopeItem::Function{..})=>self.function_type(*path),
+                Some(VarScopeItem::EnumVariant{fields,..}) if fields.is_empty()=>{
+                    let (start,end)=expr.span();
+                    Type::Named{path:*path,start,end,generics:Vec::new()}
+                },
+                Some(item)=>item.var_type().cloned().unwrap_or(Type::Unknown),
+                None=>Type::Unknown,
+            },
+            FunctionCall{path,start,end,args}=>match self.scopes.get(*path) {
+                // The generics of the enum are whatever the arguments make its type parameters.
+                Some(VarScopeItem::EnumVariant{parent_scope,fields,..})=>{
+                    let mut bindings=Vec::new();
+                    for (field,arg) in fields.iter().zip(args.iter()) {
+                        self.scopes.bind_generics(field,&expr_type(self.filename,self.scopes,arg),&mut bindings);
+                    }
+                    self.check_call(*start,*end,*path,args,false,&bindings);
+                    let generics=self.scopes.get(*parent_scope).map(|e|e.type_params()).unwrap_or_default()
+                        .into_iter()
+                        .map(|param|bindings.iter().find(|(n,_)|*n==param).map_or(Type::Never,|(_,ty)|ty.clone()))
+                        .collect();
+                    Type::Named{path:*path,start:*start,end:*end,generics}
+                },
+                _=>{
+                    self.check_call(*start,*end,*path,args,false,&[]);
+                    self.ret_type(*path)
+                },
+            },
+            MethodCall{from,name,args,method,start,end}=>match method {
+                Some(method)=>{
+                    let from_ty=self.value_type(from);
+                    self.method_call(*start,*end,*method,&from_ty,args)
+                },
+                None=>{
+                    let from_ty=self.value_type(from);
+                    if let Some(interface)=self.scopes.interface(&from_ty) {
+                        return match self.scopes.interface_method(interface,name) {
+                            Some(InterfaceMethod::Default(def))=>{
+                                self.check_call(*start,*end,def,args,true,&[]);
+                                self.ret_type(def)
+                            },
+                            Some(InterfaceMethod::Required(sig))=>{
+                                let params=sig.params.normal.iter().map(|p|(p.ty.clone(),Some((p.start,p.end)))).collect();
+                                self.check_args(*start,*end,&params,args);
+                                sig.ret_type.clone().unwrap_or(Type::Unknown)
+                            },
+                            None=>{
+                                self.push(*start,*end,format!("no method named `{}` found for `{}`",name,self.scopes.type_name(&from_ty)));
+                                Type::Unknown
+                            },
+                        };
+                    }
+                    // Methods are only resolved once the types of variables are known, which
+                    // needs the types of the calls before that.
+                    if let Some(method)=self.scopes.find_method(&from_ty,name) {
+                        return self.method_call(*start,*end,method,&from_ty,args);
+                    }
+                    let ty=self.field_of(&from_ty,name,*start,*end);
+                    match self.scopes.expand_alias(&ty) {
+                        Type::FunctionSig{inner,..}=>{
+                            let params=inner.params.normal.iter().map(|p|(p.ty.clone(),Some((p.start,p.end)))).collect();
+                            self.check_args(*start,*end,&params,args);
+                            inner.ret_type.clone().unwrap_or(Type::Unknown)
+                        },
+                        Type::Unknown=>{
+                            args.iter().for_each(|arg|{self.type_of(arg);});
+                            Type::Unknown
+                        },
+                        _=>{
+                            self.push(*start,*end,format!("field `{}` of `{}` is not a function",name,self.scopes.type_name(&from_ty)));
+                            args.iter().for_each(|arg|{self.type_of(arg);});
+                            Type::Unknown
+                        },
+                    }
+                },
+            },
+            FieldAccess{from,name,start,end}=>self.field_type(from,name,*start,*end),
+            Add{start,end,inner}|
+                Sub{start,end,inner}|
+                Mul{start,end,inner}|
+                Div{start,end,inner}|
+                Mod{start,end,inner}=>{
+                let op=match expr {
+                    Add{..}=>"+",
+                    Sub{..}=>"-",
+                    Mul{..}=>"*",
+                    Div{..}=>"/",
+                    _=>"%",
+                };
+                let left=self.value_type(&inner[0]);
+                let right=self.value_type(&inner[1]);
+                let ty=self.operand_type(*start,*end,left,right);
+                self.arithmetic_type(*start,*end,op,ty)
+            },
+            BitAnd{start,end,inner}|
+                BitOr{start,end,inner}|
+                BitXor{start,end,inner}=>{
+                let op=match expr {
+                    BitAnd{..}=>"&",
+                    BitOr{..}=>"|",
+                    _=>"^",
+                };
+                let left=self.integer_type(&inner[0],op);
+                let right=self.integer_type(&inner[1],op);
+                self.operand_type(*start,*end,left,right)
+            },
+            // The amount to shift by doesn't have to be the same type as what is shifted.
+            ShiftLeft{inner,..}|ShiftRight{inner,..}=>{
+                let op=if let ShiftLeft{..}=expr {"<<"} else {">>"};
+                self.integer_type(&inner[1],op);
+                self.integer_type(&inner[0],op)
+            },
+            Negate{inner,..}=>self.value_type(inner),
+            // Both sides are compared as the same type.
+            Equal{start,end,inner}|
+                NotEqual{start,end,inner}|
+                GreaterEqual{start,end,inner}|
+                LessEqual{start,end,inner}|
+                Greater{start,end,inner}|
+                Less{start,end,inner}=>{
+                let left=self.value_type(&inner[0]);
+                let right=self.value_type(&inner[1]);
+                self.operand_type(*start,*end,left,right);
+                Type::Bool
+            },
+            And{inner,..}|Or{inner,..}=>{
+                self.expect(&inner[0],&Type::Bool,None);
+                self.expect(&inner[1],&Type::Bool,None);
+                Type::Bool
+            },
+            Not{inner,..}=>{
+                self.expect(inner,&Type::Bool,None);
+                Type::Bool
+            },
+            IsType{to_test,..}=>{
+                self.value_type(to_test);
+                Type::Bool
+            },
+            ObjectCreation{start,end,fields}=>{
+                let fields=fields.iter()
+                    .map(|f|TypeObjectField {
+                        start:f.start,
+                        end:f.end,
+                        public:f.public,
+                        mutable:f.mutable,
+                        name:f.name,
+                        ty:self.value_type(&f.data),
+                    })
+                    .collect();
+                Type::Object{start:*start,end:*end,fields,exact:true}
+            },
+            AnonFunction{function,..}=>self.function_type(*function),
+            Ref{start,end,val}=>Type::Ref{start:*start,end:*end,inner:Box::new(self.type_of(val))},
+            RefMut{start,end,val}=>Type::RefMut{start:*start,end:*end,inner:Box::new(self.type_of(val))},
+            Deref{val,..}=>{
+                let ty=self.value_type(val);
+                match self.scopes.expand_alias(&ty) {
+                    Type::Ref{inner,..}|
+                        Type::RefMut{inner,..}|
+                        Type::Pointer{inner,..}|
+                        Type::PointerMut{inner,..}=>(**inner).clone(),
+                    Type::Never=>Type::Never,
+                    Type::Unknown=>Type::Unknown,
+                    _=>{
+                        let (start,end)=expr.span();
+                        self.push(start,end,format!("`{}` can not be dereferenced",self.scopes.type_name(&ty)));
+                        Type::Unknown
+                    },
+                }
+            },
+            If{branches,else_block,..}=>{
+                for (condition,_) in branches.iter() {
+                    self.expect(condition,&Type::Bool,None);
+                }
+                // Branches with different types or without values leave the `if` without one,
+                // and so does a missing `else`.
+                let mut ty=else_block.map(|_|Type::Never);
+                for block in branches.iter().map(|(_,block)|*block).chain(*else_block) {
+                    let value_ty=match self.scopes.branch_value(block).1 {
+                        Some(value)=>self.type_of(value),
+                        None if self.block_returns(block)=>Type::Never,
+                        None=>Type::Unknown,
+                    };
+                    ty=ty.and_then(|ty|self.common_type(ty,value_ty));
+                }
+                ty.unwrap_or(Type::Unknown)
+            },
+            ForeverLoop{block,..}=>self.loop_type(*block),
+            WhileLoop{condition,..}=>{
+                self.expect(condition,&Type::Bool,None);
+                Type::Unknown
+            },
+            ForLoop{iterator,..}=>{
+                self.items_of(iterator,"can not be iterated over");
+                Type::Unknown
+            },
+            Match{block,..}=>{
+                self.check_match_patterns(block);
+                let mut ty=Some(Type::Never);
+                for (_,leaf) in block.leafs.iter() {
+                    let leaf_ty=self.type_of(leaf);
+                    ty=ty.and_then(|ty|self.common_type(ty,leaf_ty));
+                }
+                // Arms with different types or without values leave the `match` without one.
+                ty.unwrap_or(Type::Unknown)
+            },
+            List{start,end,items}=>self.list_type(*start,*end,items),
+            Index{from,index,..}=>{
+                self.check_index(index);
+                self.items_of(from,"can not be indexed")
+            },
+            Slice{start,end,from,range_start,range_end}=>{
+                for bound in range_start.iter().chain(range_end.iter()) {
+                    self.check_index(bound);
+                }
+                match self.items_of(from,"can not be sliced") {
+                    Type::Unknown=>Type::Unknown,
+                    item=>Type::Slice{start:*start,end:*end,inner:Box::new(item)},
+                }
+            },
+            _=>{
+                expr.children().into_iter().for_each(|e|{self.type_of(e);});
+                Type::Unknown
+            },
+        }
+    }
+    /// The type of the place being assigned to and a note pointing at where it was defined.
+    /// Whether the place can be changed is checked by the access pass.
+    fn place_type(&mut self,place:&Expr<'input>)->(Type<'input>,Option<(Location,Location,&'static str)>) {
+        let Expr::FieldAccess{start,end,from,name}=place else {
+            let note=match place {
+                Expr::Var{path,..}=>self.scopes.get(*path)
+                    .and_then(|v|v.def_span())
+                    .map(|(s,e)|(s,e,"variable defined here")),
+                _=>None,
+            };
+            return (self.type_of(place),note);
+        };
+        let from_ty=match &**from {
+            Expr::FieldAccess{..}=>self.place_type(from).0,
+            _=>self.type_of(from),
+        };
+        let scopes=self.scopes;
+        let Type::Object{fields,..}=scopes.concrete(&from_ty) else {
+            return (Type::Unknown,None);
+        };
+        let Some(field)=fields.iter().find(|f|f.name==*name) else {
+            self.push(*start,*end,format!("no field `{}` on type `{}`",name,scopes.type_name(&from_ty)));
+            return (Type::Unknown,None);
+        };
+        return (field.ty.clone(),Some((field.start,field.end,"field defined here")));
+    }
+    /// The first value a `loop` with the block `target` is left with. The other `break`s have to
+    /// match its type.
+    fn first_break(&self,target:Scope)->Option<&'a Expr<'input>> {
+        self.scopes.var_scopes.values()
+            .flat_map(|item|item.statements())
+            .filter_map(|stmt|match stmt {
+                Statement::Break{target:t,val:Some(val),..} if *t==target=>Some(val),
+                _=>None,
+            })
+            .min_by_key(|val|val.span().0.index)
+    }
+    fn loop_type(&self,block:Scope)->Type<'input> {
+        if let Some(val)=self.first_break(block) {
+            return expr_type(self.filename,self.scopes,val);
+        }
+        let has_break=self.scopes.var_scopes.values()
+            .flat_map(|item|item.statements())
+            .any(|stmt|matches!(stmt,Statement::Break{target,..} if *target==block));
+        return if has_break {Type::Unknown} else {Type::Never};
+    }
+    /// Whether running `statements` always ends with a `return` or never finishes.
+    fn always_returns(&self,statements:&[Statement<'input>])->bool {
+        statements.iter().any(|stmt|match stmt {
+            Statement::Return{..}=>true,
+            Statement::Expr{expr,..}=>self.expr_returns(expr),
+            _=>false,
+        })
+    }
+    fn block_returns(&self,block:Scope)->bool {
+        self.scopes.get(block).is_some_and(|b|self.always_returns(b.statements()))
+    }
+    /// Whether running `expr` always ends with a `return` or never finishes. Loops other than
+    /// `loop` can run their body zero times.
+    fn expr_returns(&self,expr:&Expr<'input>)->bool {
+        use Expr::*;
+        match expr {
+            Block{block,..}|Unsafe{block,..}=>self.block_returns(*block),
+            If{branches,else_block:Some(else_block),..}=>{
+                branches.iter().all(|(_,block)|self.block_returns(*block))&&self.block_returns(*else_block)
+            },
+            Match{block,..}=>block.leafs.iter().all(|(_,leaf)|self.expr_returns(leaf)),
+            // A `loop` that is never left with `break` can only end by returning.
+            ForeverLoop{block,..}=>matches!(self.loop_type(*block),Type::Never),
+            FunctionCall{path,..}=>matches!(self.ret_type(*path),Type::Never),
+            _=>false,
+        }
+    }
+    /// The function a `return` in `scope` returns from.
+    fn enclosing_function(&self,mut scope:Scope)->Option<Scope> {
+        loop {
+            let item=self.scopes.get(scope)?;
+            if matches!(item,VarScopeItem::Function{..}|VarScopeItem::AnonFunction{..}) {
+                return Some(scope);
+            }
+            scope=item.parent_scope()?;
+        }
+    }
+    /// Whether `scope` is inside of an `unsafe` block.
+    fn is_unsafe(&self,mut scope:Scope)->bool {
+        loop {
+            if self.unsafe_blocks.contains(&scope) {
+                return true;
+            }
+            match self.scopes.get(scope).and_then(|i|i.parent_scope()) {
+                Some(parent)=>scope=parent,
+                None=>return false,
+            }
+        }
+    }
+    /// Reports pointers that are dereferenced outside of `unsafe` blocks in `expr`.
+    fn check_derefs(&mut self,scope:Scope,expr:&Expr<'input>) {
+        if let Expr::Deref{start,end,val}=expr {
+            let ty=expr_type(self.filename,self.scopes,val);
+            let is_pointer=matches!(self.scopes.expand_alias(&ty),Type::Pointer{..}|Type::PointerMut{..});
+            if is_pointer&&!self.is_unsafe(scope) {
+                self.push(*start,*end,"dereferencing a pointer is only allowed in `unsafe` blocks");
+            }
+        }
+        expr.children().into_iter().for_each(|e|self.check_derefs(scope,e));
+    }
+    fn check_scope(&mut self,scope:Scope) {
+        let Some(item)=self.scopes.get(scope) else {return};
+        let exprs=match item {
+            VarScopeItem::Var{data,..}|
+                VarScopeItem::Const{data,..}|
+                VarScopeItem::Static{data,..}=>vec![data],
+            _=>Vec::new(),
+        };
+        for expr in exprs.iter() {
+            self.check_derefs(scope,expr);
+        }
+        for stmt in item.statements() {
+            for expr in stmt.exprs() {
+                self.check_derefs(scope,expr);
+            }
+        }
+        // The value a branch of an `if` ends with is checked with the `if`.
+        let branch_value=match self.branch_blocks.contains(&scope) {
+            true=>self.scopes.branch_value(scope).1,
+            false=>None,
+        };
+        match item {
+            VarScopeItem::Var{ty,data,def_start,def_end,..}|
+                VarScopeItem::Const{ty,data,def_start,def_end,..}|
+                VarScopeItem::Static{ty,data,def_start,def_end,..}=>{
+                let note=ty.span().unwrap_or((*def_start,*def_end));
+                self.expect(data,ty,Some((note.0,note.1,"expected because of this type")));
+            },
+            VarScopeItem::Function{ret_type:Some(ret),statements,def_start,def_end,..}|
+                VarScopeItem::AnonFunction{ret_type:Some(ret),statements,def_start,def_end,..} if !self.always_returns(statements)=>{
+                let mut err=Error::new_verif(self.filename,*def_start,*def_end,format!("not every path returns a `{}`",self.scopes.type_name(ret)));
+                if let Some((start,end))=ret.span() {
+                    err=err.with_note(start,end,"expected because of this return type");
+                }
+                self.errors.push(err);
+            },
+            _=>{},
+        }
+        for stmt in item.statements() {
+            match stmt {
+                Statement::VarAssign{loc,data,..}=>{
+                    let var=self.scopes.get(*loc);
+                    match (var.and_then(|v|v.var_type()),var.and_then(|v|v.def_span())) {
+                        (Some(ty),Some((start,end)))=>self.expect(data,ty,Some((start,end,"variable defined here"))),
+                        _=>{self.type_of(data);},
+                    }
+                },
+                Statement::Return{start,end,val,..}=>{
+                    let ret=self.enclosing_function(scope).and_then(|f|match self.scopes.get(f) {
+                        Some(VarScopeItem::Function{ret_type,..})|
+                            Some(VarScopeItem::AnonFunction{ret_type,..})=>ret_type.as_ref(),
+                        _=>None,
+                    });
+                    match (val,ret) {
+                        (Some(val),Some(ret))=>{
+                            let note=ret.span().map(|(s,e)|(s,e,"expected because of this return type"));
+                            self.expect(val,ret,note);
+                        },
+                        (Some(val),None)=>{self.type_of(val);},
+                        (None,Some(ret))=>self.push(*start,*end,format!("expected a value of type `{}`",self.scopes.type_name(ret))),
+                        (None,None)=>{},
+                    }
+                },
+                Statement::Break{target,val:Some(val),..}=>match self.first_break(*target) {
+                    Some(first) if !std::ptr::eq(first,val)=>{
+                        let ty=expr_type(self.filename,self.scopes,first);
+                        let (start,end)=first.span();
+                        self.expect(val,&ty,Some((start,end,"expected because of this `break`")));
+                    },
+                    _=>{self.type_of(val);},
+                },
+                Statement::PlaceAssign{place,data,..}=>{
+                    let (ty,note)=self.place_type(place);
+                    self.expect(data,&ty,note);
+                },
+                Statement::CompoundAssign{start,end,op,place,data}=>{
+                    let (ty,note)=self.place_type(place);
+                    let op=match op {
+                        AssignOp::Add=>"+",
+                        AssignOp::Sub=>"-",
+                        AssignOp::Mul=>"*",
+                        AssignOp::Div=>"/",
+                        AssignOp::Mod=>"%",
+                    };
+                    self.arithmetic_type(*start,*end,op,ty.clone());
+                    self.expect(data,&ty,note);
+                },
+                Statement::Expr{expr,..} if branch_value.is_some_and(|value|std::ptr::eq(value,expr))=>{},
+                Statement::UnknownVarAssign{data,..}|Statement::Expr{expr:data,..}=>{self.type_of(data);},
+                _=>{},
+            }
+        }
+    }
+}
+
+
+/// The parameter types a call to `function` has to match along with where each was defined.
+pub(super) fn call_params<'input>(scopes:&Scopes<'input>,function:Scope,is_method:bool)->Option<CallParams<'input>> {
+    let mut params=match scopes.get(function)? {
+        VarScopeItem::Function{params,..}|VarScopeItem::AnonFunction{params,..}=>{
+            params.iter()
+                .filter_map(|p|scopes.get(*p))
+                .filter_map(|p|Some((p.var_type()?.clone(),p.def_span())))
+                .collect::<Vec<_>>()
+        },
+        VarScopeItem::BuiltinFunction{params,..}=>params.iter().map(|t|(t.clone(),None)).collect(),
+        VarScopeItem::EnumVariant{fields,..}=>fields.iter().map(|t|(t.clone(),t.span())).collect(),
+        item=>match scopes.expand_alias(item.var_type()?) {
+            Type::FunctionSig{inner,..}=>inner.params.normal.iter().map(|p|(p.ty.clone(),Some((p.start,p.end)))).collect(),
+            _=>return None,
+        },
+    };
+    // The receiver of a method call is passed as `this`.
+    if is_method {
+        let this=match scopes.get(function) {
+            Some(VarScopeItem::Function{params,..})=>params.first().and_then(|p|scopes.get(*p)),
+            _=>None,
+        };
+        if this.and_then(|p|p.name())==Some("this") {
+            params.remove(0);
+        }
+    }
+    return Some(params);
+}
+/// The types the type parameters of the impl `method` is defined in have when it is called on a
+/// `from`.
+fn impl_bindings<'input>(scopes:&Scopes<'input>,method:Scope,from:&Type<'input>)->Vec<(&'input str,Type<'input>)> {
+    let mut bindings=Vec::new();
+    if let Some(VarScopeItem::Impl{for_ty,params:Some(_),..})=scopes.get(method).and_then(|m|m.parent_scope()).and_then(|s|scopes.get(s)) {
+        scopes.bind_generics(for_ty,from,&mut bindings);
+    }
+    return bindings;
+}
+/// Whether `expr` can be without a value, like a call of a function that doesn't return anything.
+/// Other expressions only have an unknown type because of an error that was already reported.
+fn can_lack_value(expr:&Expr)->bool {
+    use Expr::*;
+    matches!(expr,FunctionCall{..}|MethodCall{..}|Block{..}|Unsafe{..}|ForeverLoop{..}|WhileLoop{..}|ForLoop{..}|Match{..})
+}
+/// The parameters of the method `name` called on `from` before the call is resolved, including the
+/// ones only an interface's signature has.
+pub(super) fn method_call_params<'input>(filename:&'input str,scopes:&Scopes<'input>,from:&Expr<'input>,name:&str)->Option<CallParams<'input>> {
+    let from_ty=expr_type(filename,scopes,from);
+    let method=match scopes.interface(&from_ty) {
+        Some(interface)=>match scopes.interface_method(interface,name)? {
+            InterfaceMethod::Default(def)=>def,
+            InterfaceMethod::Required(sig)=>return Some(sig.params.normal.iter().map(|p|(p.ty.clone(),Some((p.start,p.end)))).collect()),
+        },
+        None=>scopes.find_method(&from_ty,name)?,
+    };
+    let bindings=impl_bindings(scopes,method,&from_ty);
+    return Some(call_params(scopes,method,true)?.into_iter().map(|(ty,span)|(ty.substitute(&bindings),span)).collect());
+}
+/// The type of an expression without reporting any errors in it. Backends use this to find the
+/// types analysis doesn't store in the refined tree.
+pub fn expr_type<'input>(filename:&'input str,scopes:&Scopes<'input>,expr:&Expr<'input>)->Type<'input> {
+    let mut state=State {
+        filename,
+        scopes,
+        errors:Vec::new(),
+        warnings:Vec::new(),
+        unsafe_blocks:HashSet::new(),
+        branch_blocks:HashSet::new(),
+    };
+    return state.type_of(expr);
+}
+/// Adds the block of every `unsafe` expression in `expr` to `unsafe_blocks`, and the blocks of
+/// every branch of an `if` to `branch_blocks`.
+fn find_blocks(expr:&Expr,unsafe_blocks:&mut HashSet<Scope>,branch_blocks:&mut HashSet<Scope>) {
+    match expr {
+        Expr::Unsafe{block,..}=>{unsafe_blocks.insert(*block);},
+        Expr::If{branches,else_block,..}=>branch_blocks.extend(branches.iter().map(|(_,block)|*block).chain(*else_block)),
+        _=>{},
+    }
+    expr.children().into_iter().for_each(|e|find_blocks(e,unsafe_blocks,branch_blocks));
+}
+pub fn type_check<'input>(filename:&'input str,scopes:&Scopes<'input>)->Result<Vec<Error<'input,String>>,[Vec<Error<'input,String>>;2]> {
+    let mut unsafe_blocks=HashSet::new();
+    let mut branch_blocks=HashSet::new();
+    for item in scopes.var_scopes.values() {
+        if let VarScopeItem::Var{data,..}|VarScopeItem::Const{data,..}|VarScopeItem::Static{data,..}=item {
+            find_blocks(data,&mut unsafe_blocks,&mut branch_blocks);
+        }
+        for expr in item.statements().iter().flat_map(|s|s.exprs()) {
+            find_blocks(expr,&mut unsafe_blocks,&mut branch_blocks);
+        }
+    }
+    let mut state=State {
+        filename,
+        scopes,
+        errors:Vec::new(),
+        warnings:Vec::new(),
+        unsafe_blocks,
+        branch_blocks,
+    };
+    let mut keys=scopes.var_scopes.keys().copied().collect::<Vec<_>>();
+    keys.sort_by_key(|s|s.0);
+    for scope in keys {
+        state.check_scope(scope);
+    }
+    state.errors.sort_by_key(|e|e.start.index);
+    if state.errors.is_empty() {
+        return Ok(state.warnings);
+    } else {
+        return Err([state.errors,state.warnings]);
+    }
+}
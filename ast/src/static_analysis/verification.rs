@@ -91,12 +91,35 @@ impl<'input> VerifyAst<'input> for Statement<'input> {
             St::VarAssign{start,end,..}=>if state.is_root() {
                 state.push(*start,*end,"assigning to variables is not allowed in the root scope");
             },
+            St::PlaceAssign{start,end,inner:PlaceAssign{place,data,..}}=>if state.is_root() {
+                state.push(*start,*end,"assigning to fields is not allowed in the root scope");
+            } else {
+                match place {
+                    Expr::FieldAccess{..}|Expr::Index{..}|Expr::Deref{..}=>place.verify(state),
+                    _=>state.push(place.start(),place.end(),"can only assign to variables, fields, indexes and dereferences"),
+                }
+                data.verify(state);
+            },
+            St::CompoundAssign{start,end,inner:CompoundAssign{place,data,..}}=>if state.is_root() {
+                state.push(*start,*end,"assigning to variables is not allowed in the root scope");
+            } else {
+                match place {
+                    Expr::Var{..}|Expr::FieldAccess{..}|Expr::Index{..}|Expr::Deref{..}=>place.verify(state),
+                    _=>state.push(place.start(),place.end(),"can only assign to variables, fields, indexes and dereferences"),
+                }
+                data.verify(state);
+            },
             St::Return{start,end,..}=>if state.is_root() {
                 state.push(*start,*end,"return statements are not allowed in the root scope");
             },
             St::Continue{start,end,..}=>if state.is_root() {
                 state.push(*start,*end,"continue statements are not allowed in the root scope");
             },
+            St::Break{start,end,val,..}=>if state.is_root() {
+                state.push(*start,*end,"break statements are not allowed in the root scope");
+            } else if let Some(val)=val {
+                val.verify(state);
+            },
             St::InterfaceDef{inner:Interface{start,end,name,params,block:Block{inner,..},..},..}=>{
                 if let Some(TypeParameters{params,..})=params {
                     params.iter_mut().for_each(|TypeParameter{start,end,name,..}|{
@@ -176,6 +199,11 @@ impl<'input> VerifyAst<'input> for Expr<'input> {
             E::Mul{inner,..}=>inner.iter_mut().for_each(|e|e.verify(state)),
             E::Div{inner,..}=>inner.iter_mut().for_each(|e|e.verify(state)),
             E::Mod{inner,..}=>inner.iter_mut().for_each(|e|e.verify(state)),
+            E::BitAnd{inner,..}=>inner.iter_mut().for_each(|e|e.verify(state)),
+            E::BitOr{inner,..}=>inner.iter_mut().for_each(|e|e.verify(state)),
+            E::BitXor{inner,..}=>inner.iter_mut().for_each(|e|e.verify(state)),
+            E::ShiftLeft{inner,..}=>inner.iter_mut().for_each(|e|e.verify(state)),
+            E::ShiftRight{inner,..}=>inner.iter_mut().for_each(|e|e.verify(state)),
             E::Negate{inner,..}=>inner.verify(state),
             E::Equal{inner,..}=>inner.iter_mut().for_each(|e|e.verify(state)),
             E::NotEqual{inner,..}=>inner.iter_mut().for_each(|e|e.verify(state)),
@@ -195,23 +223,50 @@ impl<'input> VerifyAst<'input> for Expr<'input> {
             },
             E::Ref{inner,..}=>inner.verify(state),
             E::RefMut{inner,..}=>inner.verify(state),
-            E::ForeverLoop{inner,..}=>inner.verify(state),
-            E::WhileLoop{condition,block,..}=>{
+            E::Deref{inner,..}=>inner.verify(state),
+            E::Unsafe{inner,..}=>inner.verify(state),
+            E::ForeverLoop{start,end,label,inner}=>{
+                verify_label(state,*start,*end,*label);
+                inner.verify(state);
+            },
+            E::WhileLoop{start,end,label,condition,block}=>{
+                verify_label(state,*start,*end,*label);
                 condition.verify(state);
                 block.verify(state);
             },
-            E::ForLoop{iterator,block,..}=>{
+            E::ForLoop{start,end,label,iterator,block,..}=>{
+                verify_label(state,*start,*end,*label);
                 iterator.verify(state);
                 block.verify(state);
             },
             E::Match{inner,..}=>inner.verify(state),
+            E::If{branches,else_block,..}=>{
+                for (condition,block) in branches.iter_mut() {
+                    condition.verify(state);
+                    block.verify(state);
+                }
+                if let Some(block)=else_block {
+                    block.verify(state);
+                }
+            },
+            E::List{inner,..}=>inner.iter_mut().for_each(|e|e.verify(state)),
+            E::Index{from,index,..}=>{
+                from.verify(state);
+                index.verify(state);
+            },
+            E::Slice{from,range_start,range_end,..}=>{
+                from.verify(state);
+                range_start.iter_mut().for_each(|e|e.verify(state));
+                range_end.iter_mut().for_each(|e|e.verify(state));
+            },
             _=>{},
         }
     }
 }
 impl<'input> VerifyAst<'input> for Block<'input> {
     fn verify(&mut self,state:&mut State<'input>) {
-        self.inner.iter_mut().for_each(|s|s.verify(state.scoped()));
+        state.scoped();
+        self.inner.iter_mut().for_each(|s|s.verify(state));
         state.drop_scope();
     }
 }
@@ -322,6 +377,13 @@ impl<'input> State<'input> {
 }
 
 
+fn verify_label<'input>(state:&mut State<'input>,start:Location,end:Location,label:Option<&'input str>) {
+    if let Some(label)=label {
+        if !is_snake_case(label) {
+            state.push_warn(start,end,"labels should have a snake_case name");
+        }
+    }
+}
 pub fn verify<'input>(filename:&'input str,statements:&mut [Statement<'input>])->Result<Vec<Error<'input,String>>,[Vec<Error<'input,String>>;2]> {
     let mut state=State::new(filename);
     statements.into_iter().for_each(|s|s.verify(&mut state));
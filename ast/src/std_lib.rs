@@ -0,0 +1,84 @@
+//! The parts of the standard library that are built into the compiler.
+use std::io::{
+    stdin,
+    stdout,
+    stderr,
+    Write,
+};
+use crate::refined::*;
+
+
+/// (module path, function name, parameters, return type)
+type BuiltinDef=(&'static [&'static str],&'static str,fn()->Vec<Type<'static>>,fn()->Option<Type<'static>>);
+
+
+const BUILTINS:&[BuiltinDef]=&[
+    (&["io"],"print",||vec![Type::String],||None),
+    (&["io"],"println",||vec![Type::String],||None),
+    (&["io"],"eprint",||vec![Type::String],||None),
+    (&["io"],"eprintln",||vec![Type::String],||None),
+    (&["io"],"read_line",Vec::new,||Some(Type::String)),
+    (&["env"],"Arguments",Vec::new,||Some(Type::Slice{start:Default::default(),end:Default::default(),inner:Box::new(Type::String)})),
+];
+
+
+/// Adds the `std` module and everything in it as children of `root`.
+pub(crate) fn add_std<'input>(scopes:&mut Scopes<'input>,root:Scope) {
+    let std=scopes.push(VarScopeItem::Module{parent_scope:root,name:"std"});
+    let mut modules:Vec<(&'static [&'static str],Scope)>=Vec::new();
+    for (path,name,params,ret_type) in BUILTINS {
+        let mut parent_scope=std;
+        for i in 0..path.len() {
+            parent_scope=match modules.iter().find(|(p,_)|*p==&path[..=i]) {
+                Some((_,scope))=>*scope,
+                None=>{
+                    let scope=scopes.push(VarScopeItem::Module{parent_scope,name:path[i]});
+                    modules.push((&path[..=i],scope));
+                    scope
+                },
+            };
+        }
+        scopes.push(VarScopeItem::BuiltinFunction {
+            parent_scope,
+            name,
+            params:params(),
+            ret_type:ret_type(),
+        });
+    }
+}
+
+/// The values of a backend that runs builtin functions itself.
+pub trait BuiltinValue:Sized {
+    /// What a function without a return type returns.
+    fn none()->Self;
+    fn string(s:String)->Self;
+    fn slice(items:Vec<Self>)->Self;
+    fn as_str(&self)->Option<&str>;
+}
+/// Runs the builtin function `name` for the backends that don't compile it. `program_args` are the
+/// arguments passed to the program. The error says why the call failed.
+pub fn call_builtin<V:BuiltinValue>(name:&str,args:Vec<V>,program_args:&[String])->Result<V,String> {
+    let text=args.first().and_then(|a|a.as_str()).unwrap_or("");
+    match name {
+        "print"=>{
+            print!("{}",text);
+            stdout().flush().map_err(|e|format!("could not write to stdout: {}",e))?;
+        },
+        "println"=>println!("{}",text),
+        "eprint"=>{
+            eprint!("{}",text);
+            stderr().flush().map_err(|e|format!("could not write to stderr: {}",e))?;
+        },
+        "eprintln"=>eprintln!("{}",text),
+        "read_line"=>{
+            let mut line=String::new();
+            stdin().read_line(&mut line).map_err(|e|format!("could not read from stdin: {}",e))?;
+            let len=line.trim_end_matches(['\n','\r']).len();
+            line.truncate(len);
+            return Ok(V::string(line));
+        },
+        "Arguments"=>return Ok(V::slice(program_args.iter().map(|a|V::string(a.clone())).collect())),
+        _=>return Err(format!("the builtin function `{}` can not be run yet",name)),
+    }
+    return Ok(V::none());
+}
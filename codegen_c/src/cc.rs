@@ -0,0 +1,40 @@
+//! Compiling the translated C with the system's C compiler.
+use std::{
+    env,
+    fs,
+    path::Path,
+    process::Command,
+};
+use cppl_error::{
+    Location,
+    Error,
+};
+use cppl_ast::refined::Scopes;
+use crate::translate::translate;
+
+
+/// What every translated program starts with: the runtime, written in C99.
+pub const PRELUDE:&str=include_str!("prelude.c");
+
+
+/// Translates a program to C at `c_file`, then compiles it into an executable at `output` using
+/// the C compiler in `CC`, or `cc`.
+pub fn build<'input>(filename:&'input str,source:&str,scopes:&Scopes<'input>,c_file:&Path,output:&Path)->Result<(),Error<'input,String>> {
+    let error=|reason:String|Error::new_compile(filename,Location::default(),Location::default(),reason);
+    let code=translate(filename,source,scopes)?;
+    fs::write(c_file,code).map_err(|e|error(format!("could not write `{}`: {}",c_file.display(),e)))?;
+    let cc=env::var("CC").unwrap_or_else(|_|"cc".to_string());
+    let compiled=Command::new(&cc)
+        .arg("-std=c99")
+        .arg("-O2")
+        .arg("-o")
+        .arg(output)
+        .arg(c_file)
+        .arg("-lm")
+        .output()
+        .map_err(|e|error(format!("could not run the C compiler `{}`: {}",cc,e)))?;
+    if !compiled.status.success() {
+        return Err(error(format!("compiling the C failed: {}",String::from_utf8_lossy(&compiled.stderr).trim_end())));
+    }
+    return Ok(());
+}
@@ -0,0 +1,17 @@
+//! Portable C for the refined AST.
+//!
+//! `translate` turns `main` and every function it calls into one C99 file, which only needs the C
+//! standard library, and `build` compiles it with the system's C compiler. Objects become structs
+//! laid out like `compiled_idea.md` describes, interfaces become boxes with a vtable and anonymous
+//! functions become environments holding the variables they capture. Anything that can't be
+//! translated yet is a compile error saying what isn't supported.
+pub use translate::translate;
+pub use cc::{
+    build,
+    PRELUDE,
+};
+
+
+mod cc;
+mod translate;
+mod types;
@@ -0,0 +1,197 @@
+/* What every translated program starts with.
+ *
+ * Strings are pointers to their length and bytes, slices are pointers to their length followed by
+ * the items. Nothing is freed yet, programs only keep what they allocate until they end. */
+#include <math.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+
+typedef struct {
+    uint64_t len;
+    const char *bytes;
+} cppl_string;
+typedef struct {
+    uint64_t len;
+    cppl_string *items[];
+} cppl_slice_string;
+/* The header of every field of an object, see `compiled_idea.md`. The id is made from the name of
+ * the field and the type holds the item type in bits 0..5 and the flags in bits 5..8. */
+typedef struct {
+    uint8_t id[7];
+    uint8_t type;
+} cppl_field;
+
+
+/* How many calls are nested. `main` itself is the first one. */
+static uint32_t cppl_depth = 1;
+/* The program's arguments, without its name. */
+static cppl_slice_string *cppl_args;
+
+
+/* Stops the program with a runtime error, as the interpreter prints it. */
+static void cppl_fail(const char *error) {
+    fflush(stdout);
+    fprintf(stderr, "%s\n", error);
+    exit(101);
+}
+static void *cppl_allocate(size_t size) {
+    void *ptr = malloc(size == 0 ? 1 : size);
+    if (ptr == NULL) {
+        cppl_fail("out of memory");
+    }
+    return ptr;
+}
+/* Called before every call to a function of the program, `cppl_leave` after it. */
+static inline void cppl_enter(const char *error) {
+    if (cppl_depth >= 1000) {
+        cppl_fail(error);
+    }
+    cppl_depth++;
+}
+static inline void cppl_leave(void) {
+    cppl_depth--;
+}
+/* A string of `len` bytes, stored right after it. */
+static cppl_string *cppl_string_of_len(uint64_t len, char **bytes) {
+    cppl_string *s = cppl_allocate(sizeof *s + len);
+    *bytes = (char *)(s + 1);
+    s->len = len;
+    s->bytes = *bytes;
+    return s;
+}
+static cppl_string *cppl_new_string(const char *bytes, uint64_t len) {
+    char *copy;
+    cppl_string *s = cppl_string_of_len(len, &copy);
+    memcpy(copy, bytes, len);
+    return s;
+}
+static inline cppl_string *cppl_concat(const cppl_string *a, const cppl_string *b) {
+    char *bytes;
+    cppl_string *s = cppl_string_of_len(a->len + b->len, &bytes);
+    memcpy(bytes, a->bytes, a->len);
+    memcpy(bytes + a->len, b->bytes, b->len);
+    return s;
+}
+/* -1, 0 or 1 like `Ordering`. */
+static inline int cppl_string_compare(const cppl_string *a, const cppl_string *b) {
+    uint64_t len = a->len < b->len ? a->len : b->len;
+    int order = len == 0 ? 0 : memcmp(a->bytes, b->bytes, len);
+    if (order == 0) {
+        order = (a->len > b->len) - (a->len < b->len);
+    }
+    return (order > 0) - (order < 0);
+}
+static inline bool cppl_string_equal(const cppl_string *a, const cppl_string *b) {
+    return cppl_string_compare(a, b) == 0;
+}
+static inline void cppl_print(const cppl_string *s) {
+    fwrite(s->bytes, 1, s->len, stdout);
+    fflush(stdout);
+}
+static inline void cppl_println(const cppl_string *s) {
+    fwrite(s->bytes, 1, s->len, stdout);
+    fputc('\n', stdout);
+}
+static inline void cppl_eprint(const cppl_string *s) {
+    fwrite(s->bytes, 1, s->len, stderr);
+    fflush(stderr);
+}
+static inline void cppl_eprintln(const cppl_string *s) {
+    fwrite(s->bytes, 1, s->len, stderr);
+    fputc('\n', stderr);
+}
+static inline cppl_string *cppl_read_line(void) {
+    uint64_t len = 0, cap = 64;
+    char *line = cppl_allocate(cap);
+    int c;
+    /* `stdout` is flushed first, like Rust's `stdin` does. */
+    fflush(stdout);
+    while ((c = getchar()) != EOF && c != '\n') {
+        if (len == cap) {
+            cap *= 2;
+            char *bigger = cppl_allocate(cap);
+            memcpy(bigger, line, len);
+            free(line);
+            line = bigger;
+        }
+        line[len++] = (char)c;
+    }
+    while (len > 0 && line[len - 1] == '\r') {
+        len--;
+    }
+    cppl_string *s = cppl_new_string(line, len);
+    free(line);
+    return s;
+}
+/* `Arguments()`, a copy so changing it doesn't change what later calls return. */
+static inline cppl_slice_string *cppl_arguments(void) {
+    size_t size = sizeof *cppl_args + cppl_args->len * sizeof cppl_args->items[0];
+    cppl_slice_string *args = cppl_allocate(size);
+    memcpy(args, cppl_args, size);
+    return args;
+}
+/* `Int`s wrap like they do in the interpreter, which signed integers can't do in C. */
+static inline int64_t cppl_int_add(int64_t a, int64_t b) {
+    return (int64_t)((uint64_t)a + (uint64_t)b);
+}
+static inline int64_t cppl_int_sub(int64_t a, int64_t b) {
+    return (int64_t)((uint64_t)a - (uint64_t)b);
+}
+static inline int64_t cppl_int_mul(int64_t a, int64_t b) {
+    return (int64_t)((uint64_t)a * (uint64_t)b);
+}
+static inline int64_t cppl_int_neg(int64_t a) {
+    return (int64_t)(0 - (uint64_t)a);
+}
+/* The divisor is never 0, that is checked before. Dividing the smallest `Int` by -1 wraps. */
+static inline int64_t cppl_int_div(int64_t a, int64_t b) {
+    return b == -1 ? cppl_int_neg(a) : a / b;
+}
+static inline int64_t cppl_int_mod(int64_t a, int64_t b) {
+    return b == -1 ? 0 : a % b;
+}
+/* The amount is wrapped to the size of the integer. */
+static inline int64_t cppl_int_shl(int64_t a, uint64_t amount) {
+    return (int64_t)((uint64_t)a << (amount & 63));
+}
+static inline int64_t cppl_int_shr(int64_t a, uint64_t amount) {
+    amount &= 63;
+    return a < 0 ? ~(~a >> amount) : a >> amount;
+}
+/* Floats converted to integers saturate and NaN becomes 0, like Rust's `as`. */
+static inline int64_t cppl_float_to_int(double f) {
+    if (f != f) {
+        return 0;
+    }
+    if (f <= -9223372036854775808.0) {
+        return INT64_MIN;
+    }
+    return f >= 9223372036854775808.0 ? INT64_MAX : (int64_t)f;
+}
+static inline uint64_t cppl_float_to_uint(double f) {
+    if (f != f || f <= 0) {
+        return 0;
+    }
+    return f >= 18446744073709551616.0 ? UINT64_MAX : (uint64_t)f;
+}
+static inline uint8_t cppl_float_to_byte(double f) {
+    if (f != f || f <= 0) {
+        return 0;
+    }
+    return f >= 255 ? 255 : (uint8_t)f;
+}
+static void cppl_start(int argc, char **argv) {
+    /* Lines are written right away, like Rust's `stdout`. */
+    setvbuf(stdout, NULL, _IOLBF, 0);
+    /* The program's name isn't one of its arguments. */
+    uint64_t len = argc > 0 ? (uint64_t)argc - 1 : 0;
+    cppl_args = cppl_allocate(sizeof *cppl_args + len * sizeof cppl_args->items[0]);
+    cppl_args->len = len;
+    for (uint64_t i = 0; i < len; i++) {
+        cppl_args->items[i] = cppl_new_string(argv[i + 1], strlen(argv[i + 1]));
+    }
+}
@@ -0,0 +1,2418 @@
+//! Translates the refined AST to C.
+//!
+//! Expressions become C expressions where they can. Anything that needs statements, like calls,
+//! which check how deep calls are nested, or `match`es, is written out as statements before the
+//! expression that uses it, and values that could change in the meantime are moved to
+//! temporaries so everything still runs in the order the interpreter runs it.
+//!
+//! Variables that anonymous functions capture or that references point to live on the heap, so
+//! they outlive the function that made them like they do in the interpreter.
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+use cppl_error::{
+    Location,
+    Error,
+    ContextualError,
+};
+use cppl_ast::{
+    refined::*,
+    expr_type,
+};
+use crate::{
+    cc::PRELUDE,
+    types::*,
+};
+
+
+/// How many calls can be nested before the program is stopped, the same as the interpreter.
+/// `cppl_enter` in the prelude checks it.
+const MAX_CALL_DEPTH:usize=1_000;
+
+
+pub(crate) type TranslateResult<'input,T=()>=Result<T,Error<'input,String>>;
+#[derive(Copy,Clone)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+impl Op {
+    fn symbol(&self)->&'static str {
+        match self {
+            Op::Add=>"+",
+            Op::Sub=>"-",
+            Op::Mul=>"*",
+            Op::Div=>"/",
+            Op::Mod=>"%",
+            Op::BitAnd=>"&",
+            Op::BitOr=>"|",
+            Op::BitXor=>"^",
+            Op::ShiftLeft=>"<<",
+            Op::ShiftRight=>">>",
+        }
+    }
+}
+#[derive(Copy,Clone)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    LessEqual,
+    Greater,
+    Less,
+}
+impl Comparison {
+    fn symbol(&self)->&'static str {
+        match self {
+            Comparison::Equal=>"==",
+            Comparison::NotEqual=>"!=",
+            Comparison::GreaterEqual=>">=",
+            Comparison::LessEqual=>"<=",
+            Comparison::Greater=>">",
+            Comparison::Less=>"<",
+        }
+    }
+}
+/// A number literal, which can be written as any number type.
+#[derive(Debug,Copy,Clone)]
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+/// A value as a C expression.
+#[derive(Debug,Clone)]
+struct Value {
+    code:String,
+    ty:CType,
+    literal:Option<Number>,
+    /// Whether statements written after `code` can't change what it is, like they can for
+    /// variables.
+    stable:bool,
+    /// Whether `code` is where a value is stored, which can be assigned to and has to be copied
+    /// to get a value of its own.
+    place:bool,
+}
+impl Value {
+    fn new(code:String,ty:CType)->Self {
+        Value{code,ty,literal:None,stable:false,place:false}
+    }
+    /// A temporary or anything else that doesn't change.
+    fn temp(code:String,ty:CType)->Self {
+        Value{code,ty,literal:None,stable:true,place:false}
+    }
+    fn place(code:String,ty:CType)->Self {
+        Value{code,ty,literal:None,stable:false,place:true}
+    }
+}
+/// What the value of an expression is needed for.
+#[derive(Debug,Clone,PartialEq)]
+enum Want {
+    /// Nothing, it is a statement.
+    Nothing,
+    Value,
+    /// A value of this type.
+    As(CType),
+}
+/// Where a variable is in C.
+#[derive(Debug,Clone)]
+pub(crate) struct Local {
+    pub code:String,
+    pub ty:CType,
+}
+/// A function of the program in C.
+#[derive(Debug,Clone)]
+pub(crate) struct Signature {
+    pub name:String,
+    /// The type of `this` and whether it is `mut this`, which is a pointer, if it is a method.
+    pub this:Option<(CType,bool)>,
+    pub params:Vec<CType>,
+    pub ret:Option<CType>,
+}
+/// Translates the program into C. Functions are translated the first time they are used.
+pub(crate) struct Translator<'a,'input> {
+    pub filename:&'input str,
+    pub scopes:&'a Scopes<'input>,
+    /// Every name at the top level of the C file.
+    pub names:Names,
+    pub types:Types<'input>,
+    pub typedefs:Vec<String>,
+    pub prototypes:Vec<String>,
+    /// Static variables, after the string literals and `cppl_errors`.
+    pub data:Vec<String>,
+    pub definitions:Vec<String>,
+    /// The runtime errors the program can stop with, `cppl_errors` in C.
+    errors:Vec<(Location,Location,String)>,
+    error_ids:HashMap<(usize,usize,String),usize>,
+    /// String literals and the static strings holding them.
+    strings:HashMap<String,String>,
+    string_data:Vec<String>,
+    functions:HashMap<Scope,Signature>,
+    pending:Vec<Scope>,
+    /// Variables of the root scope, `static`s and `const`s.
+    globals:HashMap<Scope,Local>,
+    /// The variables each anonymous function uses from the functions around it.
+    captures:HashMap<Scope,Vec<Scope>>,
+    /// Variables stored on the heap, because anonymous functions or references share them.
+    cells:HashSet<Scope>,
+    /// Named functions used as values, by the function.
+    function_values:HashMap<Scope,String>,
+}
+impl<'a,'input> Translator<'a,'input> {
+    pub fn new(filename:&'input str,scopes:&'a Scopes<'input>)->Self {
+        // Everything the prelude names starts with `cppl_`, which variables of the program never
+        // do in C.
+        let mut taken=PRELUDE.split(|c:char|!c.is_ascii_alphanumeric()&&c!='_')
+            .filter(|word|word.starts_with("cppl_"))
+            .collect::<Vec<_>>();
+        taken.extend(["main","cppl_errors","cppl_root"]);
+        Translator {
+            filename,
+            scopes,
+            names:Names::new(taken),
+            types:Types::default(),
+            typedefs:Vec::new(),
+            prototypes:Vec::new(),
+            data:Vec::new(),
+            definitions:Vec::new(),
+            errors:Vec::new(),
+            error_ids:HashMap::new(),
+            strings:HashMap::new(),
+            string_data:Vec::new(),
+            functions:HashMap::new(),
+            pending:Vec::new(),
+            globals:HashMap::new(),
+            captures:HashMap::new(),
+            cells:HashSet::new(),
+            function_values:HashMap::new(),
+        }
+    }
+    pub fn error<T>(&self,(start,end):(Location,Location),reason:impl Into<String>)->TranslateResult<'input,T> {
+        Err(Error::new_compile(self.filename,start,end,reason.into()))
+    }
+    pub fn unsupported<T>(&self,span:(Location,Location),what:impl std::fmt::Display)->TranslateResult<'input,T> {
+        self.error(span,format!("{} can't be compiled to C yet",what))
+    }
+    /// The index of a runtime error in `cppl_errors`.
+    fn error_id(&mut self,(start,end):(Location,Location),reason:impl Into<String>)->usize {
+        let reason=reason.into();
+        let key=(start.index,end.index,reason.clone());
+        if let Some(id)=self.error_ids.get(&key) {
+            return *id;
+        }
+        self.errors.push((start,end,reason));
+        self.error_ids.insert(key,self.errors.len()-1);
+        return self.errors.len()-1;
+    }
+    /// A pointer to a static string holding `s`.
+    fn string(&mut self,s:&str)->String {
+        if let Some(name)=self.strings.get(s) {
+            return format!("&{}",name);
+        }
+        let mut base=s.chars()
+            .take(16)
+            .map(|c|if c.is_ascii_alphanumeric() {c.to_ascii_lowercase()} else {'_'})
+            .collect::<String>();
+        base=base.trim_matches('_').to_string();
+        let name=if base.is_empty() {
+            self.names.unique("cppl_string")
+        } else {
+            self.names.unique(&format!("cppl_string_{}",base))
+        };
+        self.string_data.push(format!("static cppl_string {} = {{{}, {}}};",name,s.len(),c_string(s)));
+        self.strings.insert(s.to_string(),name.clone());
+        return format!("&{}",name);
+    }
+    /// Declares `function` if it hasn't been, and queues it to be translated.
+    pub fn function(&mut self,function:Scope)->TranslateResult<'input,Signature> {
+        if let Some(signature)=self.functions.get(&function) {
+            return Ok(signature.clone());
+        }
+        let scopes=self.scopes;
+        let Some(item@VarScopeItem::Function{name,parent_scope,..})=scopes.get(function) else {
+            return self.error(Default::default(),"Internal compiler error: not a function");
+        };
+        let span=item.def_span().unwrap_or_default();
+        let (this,params,ret)=self.method_signature(function)?;
+        // `Self` is the type of the impl or the interface the method is in.
+        let (owner,this_ty)=match scopes.get(*parent_scope) {
+            Some(VarScopeItem::Impl{for_ty,..})=>(Some(match scopes.expand_alias(for_ty) {
+                Type::Named{path,..}=>scopes.get(*path).and_then(|i|i.name()).unwrap_or("type").to_string(),
+                _=>match for_ty {
+                    Type::Named{path,..}=>scopes.get(*path).and_then(|i|i.name()).unwrap_or("type").to_string(),
+                    _=>"type".to_string(),
+                },
+            }),this.map(|_|self.ctype(span,for_ty)).transpose()?),
+            Some(VarScopeItem::Interface{name,..})=>{
+                let interface=Type::Named{start:span.0,end:span.1,path:*parent_scope,generics:Vec::new()};
+                (Some(name.to_string()),this.map(|_|self.ctype(span,&interface)).transpose()?)
+            },
+            _=>(None,None),
+        };
+        let c_name=match owner {
+            Some(owner)=>self.names.unique(&format!("cppl_{}_{}",identifier(&owner),identifier(name))),
+            None=>self.names.unique(&format!("cppl_{}",identifier(name))),
+        };
+        let signature=Signature {
+            name:c_name,
+            this:this_ty.zip(this),
+            params,
+            ret,
+        };
+        self.functions.insert(function,signature.clone());
+        self.pending.push(function);
+        return Ok(signature);
+    }
+    /// A variable of the root scope, a `static` or a `const`. `static`s and `const`s are
+    /// initialized the first time they are used, like in the interpreter.
+    fn global(&mut self,span:(Location,Location),var:Scope)->TranslateResult<'input,Local> {
+        if let Some(global)=self.globals.get(&var) {
+            return Ok(global.clone());
+        }
+        let scopes=self.scopes;
+        let (name,ty,data,constant)=match scopes.get(var) {
+            Some(VarScopeItem::Static{name,ty,data,..})=>(name,ty,data,false),
+            Some(VarScopeItem::Const{name,ty,data,..})=>(name,ty,data,true),
+            Some(item)=>return self.error(span,format!("Internal compiler error: `{}` is used before it is defined",item.name().unwrap_or("?"))),
+            None=>return self.error(span,"Internal compiler error: undefined variable"),
+        };
+        let ctype=self.ctype(span,ty)?;
+        let c_name=self.names.unique(&format!("cppl_{}",identifier(name)));
+        // Literals don't need to be initialized later.
+        if let Expr::Data{data,..}=data {
+            if let Some(value)=literal(data) {
+                let code=match &value.literal {
+                    Some(number)=>convert_literal(number,&ctype),
+                    None=>Some(value.code).filter(|_|value.ty==ctype),
+                };
+                if let Some(code)=code {
+                    let qualifier=if constant {"const "} else {""};
+                    self.data.push(format!("static {}{} = {};",qualifier,self.declare(&ctype,&c_name),code));
+                    let global=Local{code:c_name,ty:ctype};
+                    self.globals.insert(var,global.clone());
+                    return Ok(global);
+                }
+            }
+        }
+        let global=Local{code:format!("(*{}())",c_name),ty:ctype.clone()};
+        self.globals.insert(var,global.clone());
+        let mut translator=FunctionTranslator::new(self,None,false);
+        translator.indent=2;
+        let value=translator.value_as(data,&ctype)?;
+        translator.line(format!("value = {};",bare(&value.code)));
+        let lines=translator.finish();
+        let mut body=vec![
+            "    static bool ready;".to_string(),
+            format!("    static {};",self.declare(&ctype,"value")),
+            "    if (!ready) {".to_string(),
+        ];
+        body.extend(lines);
+        body.push("        ready = true;".into());
+        body.push("    }".into());
+        body.push("    return &value;".into());
+        let pointer=CType::Ref{inner:Box::new(ctype),mutable:true};
+        let signature=format!("static {}",self.declare(&pointer,&format!("{}(void)",c_name)));
+        self.define_function(signature,&body);
+        return Ok(global);
+    }
+    /// A named function as a value, which calls it through a function taking the environment
+    /// like anonymous functions do.
+    fn function_value(&mut self,span:(Location,Location),function:Scope)->TranslateResult<'input,Value> {
+        let signature=self.function(function)?;
+        if signature.this.is_some() {
+            return self.unsupported(span,"methods used as values");
+        }
+        let ty=self.function_type(signature.params.clone(),signature.ret.clone());
+        if let Some(name)=self.function_values.get(&function) {
+            return Ok(Value::temp(format!("&{}",name),ty));
+        }
+        let CType::Function(i)=ty else {
+            return self.error(span,"Internal compiler error: not a function type");
+        };
+        let type_name=self.types.functions[i].name.clone();
+        let thunk=self.names.unique(&format!("{}_thunk",signature.name));
+        let value=self.names.unique(&format!("{}_value",signature.name));
+        let mut params=vec![format!("{} *self",type_name)];
+        let mut args=Vec::with_capacity(signature.params.len());
+        for (i,param) in signature.params.iter().enumerate() {
+            params.push(self.declare(param,&format!("arg_{}",i)));
+            args.push(format!("arg_{}",i));
+        }
+        let call=format!("{}({})",signature.name,args.join(", "));
+        let body=[
+            "    (void)self;".to_string(),
+            match signature.ret {
+                Some(_)=>format!("    return {};",call),
+                None=>format!("    {};",call),
+            },
+        ];
+        let declarator=format!("{}({})",thunk,params.join(", "));
+        self.define_function(format!("static {}",self.declare_function(signature.ret.as_ref(),&declarator)),&body);
+        self.data.push(format!("static {} {} = {{{}}};",type_name,value,thunk));
+        self.function_values.insert(function,value.clone());
+        return Ok(Value::temp(format!("&{}",value),ty));
+    }
+    /// The function, anonymous function or root scope `scope` is in, or `scope` itself if it is
+    /// one.
+    fn enclosing_function(&self,mut scope:Scope)->Scope {
+        loop {
+            match self.scopes.get(scope) {
+                Some(VarScopeItem::Function{..}|VarScopeItem::AnonFunction{..}|VarScopeItem::Root{..})|None=>return scope,
+                Some(item)=>match item.parent_scope() {
+                    Some(parent)=>scope=parent,
+                    None=>return scope,
+                },
+            }
+        }
+    }
+    fn is_root(&self,scope:Scope)->bool {
+        matches!(self.scopes.get(scope),Some(VarScopeItem::Root{..}))
+    }
+    /// Notes that `var` is used in `function`. Variables used by anonymous functions inside of
+    /// the function defining them are captured by all of them in between.
+    fn use_var(&mut self,var:Scope,function:Scope) {
+        let scopes=self.scopes;
+        let var=original(scopes,var);
+        let parent=match scopes.get(var) {
+            Some(VarScopeItem::Var{parent_scope,..}|VarScopeItem::Parameter{parent_scope,..}|VarScopeItem::MatchBlockVar{parent_scope,..})=>*parent_scope,
+            _=>return,
+        };
+        let declared=self.enclosing_function(parent);
+        if declared==function||self.is_root(declared) {
+            return;
+        }
+        self.cells.insert(var);
+        let mut current=function;
+        while current!=declared {
+            let Some(VarScopeItem::AnonFunction{parent_scope,..})=scopes.get(current) else {break};
+            let captured=self.captures.entry(current).or_default();
+            if !captured.contains(&var) {
+                captured.push(var);
+            }
+            current=self.enclosing_function(*parent_scope);
+        }
+    }
+    /// Finds which variables anonymous functions capture and which have to live on the heap.
+    fn find_cells(&mut self) {
+        let scopes=self.scopes;
+        let mut all=scopes.var_scopes.keys().copied().collect::<Vec<_>>();
+        all.sort_by_key(|s|s.0);
+        for scope in all {
+            let Some(item)=scopes.get(scope) else {continue};
+            let mut exprs=Vec::new();
+            let mut assigned=Vec::new();
+            let function=match item {
+                VarScopeItem::Var{data,parent_scope,..}|
+                    VarScopeItem::Const{data,parent_scope,..}|
+                    VarScopeItem::Static{data,parent_scope,..}=>{
+                    exprs.push(data);
+                    self.enclosing_function(*parent_scope)
+                },
+                _=>self.enclosing_function(scope),
+            };
+            for stmt in item.statements() {
+                exprs.extend(stmt.exprs());
+                if let Statement::VarAssign{loc,..}=stmt {
+                    assigned.push(*loc);
+                }
+            }
+            for var in assigned {
+                self.use_var(var,function);
+            }
+            while let Some(expr)=exprs.pop() {
+                match expr {
+                    Expr::Var{path,..}|Expr::AssociatedPath{path,..}|Expr::FunctionCall{path,..}=>self.use_var(*path,function),
+                    Expr::Ref{val,..}|Expr::RefMut{val,..}=>if let Some(var)=place_root(val) {
+                        let var=original(scopes,var);
+                        let local=match scopes.get(var) {
+                            Some(VarScopeItem::Var{parent_scope,..}|VarScopeItem::Parameter{parent_scope,..}|VarScopeItem::MatchBlockVar{parent_scope,..})=>!self.is_root(self.enclosing_function(*parent_scope)),
+                            _=>false,
+                        };
+                        if local {
+                            self.cells.insert(var);
+                        }
+                    },
+                    _=>{},
+                }
+                exprs.extend(expr.children());
+            }
+        }
+    }
+    /// Translates the root scope, `main` and every function they use.
+    pub fn translate_program(&mut self)->TranslateResult<'input> {
+        let root=Scope(0);
+        let scopes=self.scopes;
+        let statements=scopes.get(root).map(|r|r.statements()).unwrap_or_default();
+        self.find_cells();
+        // Named object types get their name before anonymous ones with the same fields do.
+        let mut all=scopes.var_scopes.keys().copied().collect::<Vec<_>>();
+        all.sort_by_key(|s|s.0);
+        for scope in all {
+            if let Some(VarScopeItem::Type{params:None,ty:Type::Object{..},..})=scopes.get(scope) {
+                let named=Type::Named{start:Location::default(),end:Location::default(),path:scope,generics:Vec::new()};
+                let _=self.ctype(Default::default(),&named);
+            }
+        }
+        let Some(main)=scopes.find_function(statements,"main") else {
+            return Err(Error::new_runtime(self.filename,Location::default(),Location::default(),"there is no `main` function to run"));
+        };
+        let signature=self.function(main)?;
+        let takes_args=match &signature.params[..] {
+            []=>false,
+            [CType::Slice(item)] if **item==CType::String=>true,
+            _=>{
+                let span=scopes.get(main).and_then(|m|m.def_span()).unwrap_or_default();
+                return self.unsupported(span,"a `main` function with these parameters");
+            },
+        };
+        let mut translator=FunctionTranslator::new(self,None,true);
+        translator.statements(statements)?;
+        let body=translator.finish();
+        let has_root=!body.is_empty();
+        if has_root {
+            self.define_function("static void cppl_root(void)".into(),&body);
+        }
+        while let Some(function)=self.pending.pop() {
+            self.translate_function(function)?;
+        }
+        let args=if takes_args {"cppl_arguments()"} else {""};
+        let mut body=vec!["    cppl_start(argc, argv);".to_string()];
+        if has_root {
+            body.push("    cppl_root();".into());
+        }
+        // Returning anything but an integer gives the exit code 0.
+        match &signature.ret {
+            Some(ty) if ty.is_integer()=>body.push(format!("    return (int){}({});",signature.name,args)),
+            _=>{
+                body.push(format!("    {}({});",signature.name,args));
+                body.push("    return 0;".into());
+            },
+        }
+        let mut out="int main(int argc, char **argv) {\n".to_string();
+        for line in body {
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out.push('}');
+        self.definitions.push(out);
+        return Ok(());
+    }
+    fn translate_function(&mut self,function:Scope)->TranslateResult<'input> {
+        let signature=self.functions[&function].clone();
+        let scopes=self.scopes;
+        let Some(item@VarScopeItem::Function{params,..})=scopes.get(function) else {
+            return self.error(Default::default(),"Internal compiler error: not a function");
+        };
+        let mut translator=FunctionTranslator::new(self,signature.ret.clone(),false);
+        let mut c_params=Vec::with_capacity(params.len());
+        let mut params=params.iter();
+        if let Some((ty,mutable))=&signature.this {
+            let Some(this)=params.next() else {
+                return translator.shared.error(Default::default(),"Internal compiler error: method without `this`");
+            };
+            translator.names.unique("this");
+            match (mutable,ty) {
+                // The box of an interface is already shared.
+                (true,CType::Interface(_))|(false,_)=>{
+                    if translator.shared.cells.contains(this) {
+                        c_params.push(translator.param(*this,"this",ty));
+                    } else {
+                        c_params.push(translator.shared.declare(ty,"this"));
+                        translator.vars.insert(*this,Local{code:"this".into(),ty:ty.clone()});
+                    }
+                },
+                (true,_)=>{
+                    let pointer=CType::Ref{inner:Box::new(ty.clone()),mutable:true};
+                    c_params.push(translator.shared.declare(&pointer,"this"));
+                    translator.vars.insert(*this,Local{code:"(*this)".into(),ty:ty.clone()});
+                },
+            }
+        }
+        for (param,ty) in params.zip(signature.params.iter()) {
+            let name=scopes.get(*param).and_then(|p|p.name()).unwrap_or("arg");
+            c_params.push(translator.param(*param,name,ty));
+        }
+        translator.statements(item.statements())?;
+        translator.ret_zero();
+        let body=translator.finish();
+        let params=if c_params.is_empty() {"void".to_string()} else {c_params.join(", ")};
+        let declarator=format!("{}({})",signature.name,params);
+        let signature=format!("static {}",self.declare_function(signature.ret.as_ref(),&declarator));
+        self.define_function(signature,&body);
+        return Ok(());
+    }
+    /// The whole C file.
+    fn output(&self,source:&str)->String {
+        let mut out=format!("/* Generated by cppl from `{}`. */\n{}",self.filename,PRELUDE);
+        for section in [&self.typedefs,&self.prototypes] {
+            if !section.is_empty() {
+                out.push_str("\n\n");
+                out.push_str(&section.join("\n"));
+            }
+        }
+        let mut data=self.string_data.clone();
+        if !self.errors.is_empty() {
+            // Errors are stored as they are printed, with the source line they happened on.
+            let mut errors="static const char *const cppl_errors[] = {".to_string();
+            for (i,(start,end,reason)) in self.errors.iter().enumerate() {
+                let err=Error::<String>::new_runtime(self.filename,*start,*end,reason.clone());
+                let message=ContextualError::from((source,err)).to_string();
+                errors.push_str(&format!("\n    /* {} */ {},",i,c_string(&message)));
+            }
+            errors.push_str("\n};");
+            data.push(errors);
+        }
+        data.extend(self.data.iter().cloned());
+        if !data.is_empty() {
+            out.push_str("\n\n");
+            out.push_str(&data.join("\n"));
+        }
+        out.push_str("\n\n");
+        out.push_str(&self.definitions.join("\n"));
+        out.push('\n');
+        return out;
+    }
+}
+/// Translates `main`, every function it calls and the root scope into a C99 program. The runtime
+/// errors are stored in it as they are printed, which is why it needs the source.
+pub fn translate<'input>(filename:&'input str,source:&str,scopes:&Scopes<'input>)->Result<String,Error<'input,String>> {
+    let mut translator=Translator::new(filename,scopes);
+    translator.translate_program()?;
+    return Ok(translator.output(source));
+}
+
+
+enum LineKind {
+    Text(String),
+    /// Filled in once the code it is waiting for is known, like the type of a `loop`.
+    Hole(usize),
+}
+struct Line {
+    indent:usize,
+    kind:LineKind,
+}
+/// The value of a `loop`, `match` or `if`, which is assigned by each of its arms.
+struct Joined {
+    name:String,
+    /// Where the variable is declared.
+    declaration:usize,
+    /// The assignments, filled in once the type is known.
+    arms:Vec<(usize,Value)>,
+    ty:Option<CType>,
+}
+struct Loop {
+    block:Scope,
+    /// The label after the loop, for `break`s from loops inside of it.
+    exit:Option<String>,
+    /// The label at the end of the body, for `continue`s from loops inside of it.
+    next:Option<String>,
+    value:Option<Joined>,
+}
+/// Translates the body of one function.
+struct FunctionTranslator<'t,'a,'input> {
+    shared:&'t mut Translator<'a,'input>,
+    lines:Vec<Line>,
+    holes:Vec<Option<String>>,
+    indent:usize,
+    names:Names,
+    vars:HashMap<Scope,Local>,
+    loops:Vec<Loop>,
+    ret:Option<CType>,
+    /// Whether this is the root scope, whose variables are global.
+    root:bool,
+}
+impl<'t,'a,'input> FunctionTranslator<'t,'a,'input> {
+    fn new(shared:&'t mut Translator<'a,'input>,ret:Option<CType>,root:bool)->Self {
+        FunctionTranslator {
+            shared,
+            lines:Vec::new(),
+            holes:Vec::new(),
+            indent:1,
+            names:Names::locals(),
+            vars:HashMap::new(),
+            loops:Vec::new(),
+            ret,
+            root,
+        }
+    }
+    /// The lines of the body.
+    fn finish(self)->Vec<String> {
+        let mut out=Vec::with_capacity(self.lines.len());
+        for line in self.lines {
+            let text=match line.kind {
+                LineKind::Text(text)=>text,
+                LineKind::Hole(hole)=>match &self.holes[hole] {
+                    Some(text)=>text.clone(),
+                    None=>continue,
+                },
+            };
+            out.push(format!("{}{}","    ".repeat(line.indent),text));
+        }
+        return out;
+    }
+    fn line(&mut self,text:impl Into<String>) {
+        self.lines.push(Line{indent:self.indent,kind:LineKind::Text(text.into())});
+    }
+    fn open(&mut self,text:impl Into<String>) {
+        self.line(text);
+        self.indent+=1;
+    }
+    fn close(&mut self) {
+        self.indent-=1;
+        self.line("}");
+    }
+    fn hole(&mut self)->usize {
+        self.holes.push(None);
+        self.lines.push(Line{indent:self.indent,kind:LineKind::Hole(self.holes.len()-1)});
+        return self.holes.len()-1;
+    }
+    /// Lines written by `f` into a buffer of their own, indented from 0, to be added later.
+    fn detached<T>(&mut self,f:impl FnOnce(&mut Self)->TranslateResult<'input,T>)->TranslateResult<'input,(T,Vec<Line>)> {
+        let lines=std::mem::take(&mut self.lines);
+        let indent=std::mem::replace(&mut self.indent,0);
+        let result=f(self);
+        let detached=std::mem::replace(&mut self.lines,lines);
+        self.indent=indent;
+        return Ok((result?,detached));
+    }
+    fn append(&mut self,lines:Vec<Line>) {
+        for mut line in lines {
+            line.indent+=self.indent;
+            self.lines.push(line);
+        }
+    }
+    /// Stops the program with a runtime error.
+    fn fail(&mut self,span:(Location,Location),reason:impl Into<String>) {
+        let id=self.shared.error_id(span,reason);
+        self.line(format!("cppl_fail(cppl_errors[{}]);",id));
+    }
+    /// Stops the program with a runtime error if `condition` is true.
+    fn check(&mut self,condition:&str,span:(Location,Location),reason:impl Into<String>) {
+        self.open(format!("if ({}) {{",bare(condition)));
+        self.fail(span,reason);
+        self.close();
+    }
+    /// A new variable holding `code`.
+    fn temp(&mut self,name:&str,ty:CType,code:&str)->Value {
+        let name=self.names.unique(name);
+        self.line(format!("{} = {};",self.shared.declare(&ty,&name),bare(code)));
+        return Value::temp(name,ty);
+    }
+    /// `value`, moved to a variable if using its code twice would do more than reading it.
+    fn simple(&mut self,value:Value,name:&str)->Value {
+        if is_simple(&value.code) {
+            return value;
+        }
+        return self.temp(name,value.ty.clone(),&value.code);
+    }
+    /// `value`, moved to a variable if statements could change it.
+    fn stable(&mut self,value:Value,name:&str)->Value {
+        if value.stable {
+            return value;
+        }
+        return self.temp(name,value.ty.clone(),&value.code);
+    }
+    /// Moves `value` to a variable declared at line `at`, the place it was evaluated.
+    fn spill_at(&mut self,at:usize,value:Value)->Value {
+        let (text,value)=if value.place {
+            let name=self.names.unique("place");
+            let pointer=CType::Ref{inner:Box::new(value.ty.clone()),mutable:true};
+            let text=format!("{} = {};",self.shared.declare(&pointer,&name),address_of(&value.code));
+            (text,Value{code:format!("(*{})",name),stable:true,..value})
+        } else {
+            let name=self.names.unique("tmp");
+            let text=format!("{} = {};",self.shared.declare(&value.ty,&name),bare(&value.code));
+            (text,Value::temp(name,value.ty))
+        };
+        self.lines.insert(at,Line{indent:self.indent,kind:LineKind::Text(text)});
+        return value;
+    }
+    /// Values evaluated one after the other, each with the number of lines there were right after
+    /// it. Values that statements written after them could change are moved to variables at the
+    /// point they were evaluated.
+    fn settle(&mut self,values:Vec<(Value,usize)>)->Vec<Value> {
+        let end=self.lines.len();
+        let mut out=Vec::with_capacity(values.len());
+        for (value,at) in values.into_iter().rev() {
+            if value.stable||at>=end {
+                out.push(value);
+            } else {
+                let value=self.spill_at(at,value);
+                out.push(value);
+            }
+        }
+        out.reverse();
+        return out;
+    }
+    /// A parameter, which is copied to the heap if it is captured.
+    fn param(&mut self,param:Scope,name:&str,ty:&CType)->String {
+        let c_name=self.names.unique(&identifier(name));
+        if self.shared.cells.contains(&param) {
+            let arg=self.names.unique(&format!("{}_arg",c_name));
+            let pointer=CType::Ref{inner:Box::new(ty.clone()),mutable:true};
+            self.line(format!("{0} = cppl_allocate(sizeof *{1});",self.shared.declare(&pointer,&c_name),c_name));
+            self.line(format!("*{} = {};",c_name,arg));
+            self.vars.insert(param,Local{code:format!("(*{})",c_name),ty:ty.clone()});
+            return self.shared.declare(ty,&arg);
+        }
+        self.vars.insert(param,Local{code:c_name.clone(),ty:ty.clone()});
+        return self.shared.declare(ty,&c_name);
+    }
+    /// Defines the variable `var` holding `code`.
+    fn define(&mut self,var:Scope,name:&str,ty:CType,code:&str) {
+        if self.root {
+            let c_name=self.shared.names.unique(&format!("cppl_{}",identifier(name)));
+            self.shared.data.push(format!("static {};",self.shared.declare(&ty,&c_name)));
+            self.line(format!("{} = {};",c_name,bare(code)));
+            self.shared.globals.insert(var,Local{code:c_name,ty});
+            return;
+        }
+        let c_name=self.names.unique(&identifier(name));
+        if self.shared.cells.contains(&var) {
+            let pointer=CType::Ref{inner:Box::new(ty.clone()),mutable:true};
+            self.line(format!("{0} = cppl_allocate(sizeof *{1});",self.shared.declare(&pointer,&c_name),c_name));
+            self.line(format!("*{} = {};",c_name,bare(code)));
+            self.vars.insert(var,Local{code:format!("(*{})",c_name),ty});
+        } else {
+            self.line(format!("{} = {};",self.shared.declare(&ty,&c_name),bare(code)));
+            self.vars.insert(var,Local{code:c_name,ty});
+        }
+    }
+    /// Returns a zero at the end of a function that returns something, for when it falls off the
+    /// end.
+    fn ret_zero(&mut self) {
+        let Some(ret)=self.ret.clone() else {return};
+        if let Some(Line{kind:LineKind::Text(text),indent:1})=self.lines.last() {
+            if text.starts_with("return ") {
+                return;
+            }
+        }
+        match ret {
+            CType::Object(_)=>{
+                let name=self.names.unique("zero");
+                self.line(format!("{} = {{0}};",self.shared.declare(&ret,&name)));
+                self.line(format!("return {};",name));
+            },
+            _=>self.line(format!("return {};",self.shared.zero(&ret))),
+        }
+    }
+    fn statements(&mut self,statements:&[Statement<'input>])->TranslateResult<'input> {
+        for stmt in statements {
+            self.stmt(stmt)?;
+        }
+        return Ok(());
+    }
+    fn block(&mut self,block:Scope)->TranslateResult<'input> {
+        let scopes=self.shared.scopes;
+        if let Some(item)=scopes.get(block) {
+            self.statements(item.statements())?;
+        }
+        return Ok(());
+    }
+    /// Where the variable `var` is.
+    fn var(&mut self,span:(Location,Location),var:Scope)->TranslateResult<'input,Local> {
+        let scopes=self.shared.scopes;
+        let original=original(scopes,var);
+        let local=match self.vars.get(&original) {
+            Some(local)=>local.clone(),
+            None=>self.shared.global(span,original)?,
+        };
+        if original==var {
+            return Ok(local);
+        }
+        // Narrowed variables are the same variable, seen as a more specific type.
+        let Some(ty)=scopes.get(var).and_then(|v|v.var_type()) else {return Ok(local)};
+        let Ok(narrowed)=self.shared.ctype(span,ty) else {return Ok(local)};
+        if narrowed==local.ty {
+            return Ok(local);
+        }
+        match &local.ty {
+            CType::Interface(interface)=>{
+                let boxed=self.shared.boxed(span,*interface,&narrowed)?;
+                Ok(Local{code:format!("(({} *){})->value",boxed.name,local.code),ty:narrowed})
+            },
+            _=>Ok(local),
+        }
+    }
+    fn stmt(&mut self,stmt:&Statement<'input>)->TranslateResult<'input> {
+        let scopes=self.shared.scopes;
+        match stmt {
+            Statement::VarDef{start,end,def}=>{
+                if let Some(VarScopeItem::Var{ty,data,name,..})=scopes.get(*def) {
+                    // Analysis doesn't find the type of everything yet, like loops and matches.
+                    let value=match ty {
+                        Type::Unknown=>self.value(data)?,
+                        _=>{
+                            let ctype=self.shared.ctype((*start,*end),ty)?;
+                            self.value_as(data,&ctype)?
+                        },
+                    };
+                    let ty=match value.literal {
+                        // Literals on their own are an `Int` or a `DoubleFloat`.
+                        Some(Number::Int(_)) if value.ty==CType::Uint=>CType::Uint,
+                        _=>value.ty.clone(),
+                    };
+                    self.define(*def,name,ty,&value.code);
+                }
+            },
+            Statement::VarAssign{start,end,loc,data}=>{
+                let var=self.var((*start,*end),*loc)?;
+                let value=self.value_as(data,&var.ty)?;
+                self.line(format!("{} = {};",var.code,bare(&value.code)));
+            },
+            Statement::UnknownVarAssign{start,end,name,..}=>return self.shared.error((*start,*end),format!("`{}` is not defined",name)),
+            Statement::PlaceAssign{start,end,place,data}=>{
+                let ty=expr_type(self.shared.filename,scopes,place);
+                let ty=self.shared.ctype((*start,*end),&ty)?;
+                let value=self.value_as(data,&ty)?;
+                let at=self.lines.len();
+                let target=self.place(place)?;
+                let mut values=self.settle(vec![(value,at),(target,self.lines.len())]);
+                let target=values.pop().unwrap_or_else(||Value::new(String::new(),CType::Int));
+                let value=values.pop().unwrap_or_else(||Value::new(String::new(),CType::Int));
+                let value=self.convert((*start,*end),value,&target.ty)?;
+                self.line(format!("{} = {};",target.code,bare(&value.code)));
+            },
+            Statement::CompoundAssign{start,end,op,place,data}=>{
+                let span=(*start,*end);
+                let op=match op {
+                    AssignOp::Add=>Op::Add,
+                    AssignOp::Sub=>Op::Sub,
+                    AssignOp::Mul=>Op::Mul,
+                    AssignOp::Div=>Op::Div,
+                    AssignOp::Mod=>Op::Mod,
+                };
+                let mut target=self.place(place)?;
+                let at=self.lines.len();
+                let value=self.value(data)?;
+                // The old value is read before `data` runs.
+                let old=if self.lines.len()>at&&!target.stable {
+                    target=self.spill_at(at,target);
+                    let old=Value{place:false,..target.clone()};
+                    let name=self.names.unique("old");
+                    let text=format!("{} = {};",self.shared.declare(&old.ty,&name),bare(&old.code));
+                    self.lines.insert(at+1,Line{indent:self.indent,kind:LineKind::Text(text)});
+                    Value::temp(name,old.ty)
+                } else {
+                    Value{place:false,..target.clone()}
+                };
+                let old=self.deref(old);
+                let result=self.arithmetic(op,old,value,span)?;
+                let result=self.convert(span,result,&target.ty)?;
+                self.line(format!("{} = {};",target.code,bare(&result.code)));
+            },
+            Statement::Expr{expr,..}=>{
+                self.expr(expr,Want::Nothing)?;
+            },
+            Statement::Return{val,..}=>{
+                match (self.ret.clone(),val) {
+                    (Some(ty),Some(val))=>{
+                        let value=self.value_as(val,&ty)?;
+                        self.line(format!("return {};",bare(&value.code)));
+                    },
+                    (None,Some(val))=>{
+                        self.expr(val,Want::Nothing)?;
+                        self.line("return;");
+                    },
+                    (Some(ty),None)=>match ty {
+                        CType::Object(_)=>{
+                            let name=self.names.unique("zero");
+                            self.line(format!("{} = {{0}};",self.shared.declare(&ty,&name)));
+                            self.line(format!("return {};",name));
+                        },
+                        _=>self.line(format!("return {};",self.shared.zero(&ty))),
+                    },
+                    (None,None)=>self.line("return;"),
+                }
+            },
+            Statement::Continue{target,..}=>{
+                let Some(i)=self.loops.iter().rposition(|l|l.block==*target) else {
+                    return self.shared.error(stmt_span(stmt),"Internal compiler error: `continue` outside of a loop");
+                };
+                if i+1==self.loops.len() {
+                    self.line("continue;");
+                } else {
+                    let label=match &self.loops[i].next {
+                        Some(label)=>label.clone(),
+                        None=>{
+                            let label=self.names.unique("next");
+                            self.loops[i].next=Some(label.clone());
+                            label
+                        },
+                    };
+                    self.line(format!("goto {};",label));
+                }
+            },
+            Statement::Break{target,val,..}=>{
+                let Some(i)=self.loops.iter().rposition(|l|l.block==*target) else {
+                    return self.shared.error(stmt_span(stmt),"Internal compiler error: `break` outside of a loop");
+                };
+                if let Some(val)=val {
+                    match self.loops[i].value.as_ref().map(|v|v.ty.clone()) {
+                        Some(ty)=>{
+                            let value=match ty {
+                                Some(ty)=>self.value_as(val,&ty)?,
+                                None=>self.value(val)?,
+                            };
+                            let hole=self.hole();
+                            if let Some(joined)=&mut self.loops[i].value {
+                                joined.arms.push((hole,value));
+                            }
+                        },
+                        None=>{
+                            self.expr(val,Want::Nothing)?;
+                        },
+                    }
+                }
+                if i+1==self.loops.len() {
+                    self.line("break;");
+                } else {
+                    let label=match &self.loops[i].exit {
+                        Some(label)=>label.clone(),
+                        None=>{
+                            let label=self.names.unique("exit");
+                            self.loops[i].exit=Some(label.clone());
+                            label
+                        },
+                    };
+                    self.line(format!("goto {};",label));
+                }
+            },
+            _=>{},
+        }
+        return Ok(());
+    }
+    /// Follows references to what they point to.
+    fn deref(&self,mut value:Value)->Value {
+        while let CType::Ref{inner,..}=value.ty {
+            let code=match value.code.strip_prefix('&') {
+                Some(inner) if is_simple(inner)=>inner.to_string(),
+                _=>format!("(*{})",value.code),
+            };
+            value=Value::place(code,*inner);
+        }
+        return value;
+    }
+    /// The value of `expr`, copied if it is stored somewhere so it is a value of its own.
+    fn value(&mut self,expr:&Expr<'input>)->TranslateResult<'input,Value> {
+        let value=self.operand(expr)?;
+        return Ok(self.own(value));
+    }
+    /// `value` as a value of its own.
+    fn own(&mut self,value:Value)->Value {
+        if !value.place {
+            return value;
+        }
+        if self.shared.needs_copy(&value.ty) {
+            let code=self.shared.copy(&value.ty,&value.code);
+            return Value::new(code,value.ty);
+        }
+        return Value{place:false,..value};
+    }
+    /// The value of `expr`, which can be where it is stored.
+    fn operand(&mut self,expr:&Expr<'input>)->TranslateResult<'input,Value> {
+        match self.expr(expr,Want::Value)? {
+            Some(value)=>Ok(value),
+            None=>self.shared.unsupported(expr.span(),"using this as a value"),
+        }
+    }
+    /// The value of `expr` without copying it, for operators that only read it.
+    fn read(&mut self,expr:&Expr<'input>)->TranslateResult<'input,Value> {
+        let value=self.operand(expr)?;
+        return Ok(Value{place:false,..value});
+    }
+    /// The value of `expr` as a value of type `ty`.
+    fn value_as(&mut self,expr:&Expr<'input>,ty:&CType)->TranslateResult<'input,Value> {
+        let span=expr.span();
+        let value=match (expr,ty) {
+            (Expr::ObjectCreation{..},CType::Object(i))=>self.object(expr,Some(*i))?,
+            (Expr::List{..},CType::Slice(_))=>self.list(expr,Some(ty))?,
+            (Expr::Match{..}|Expr::ForeverLoop{..},_)=>match self.expr(expr,Want::As(ty.clone()))? {
+                Some(value)=>value,
+                None=>return self.shared.unsupported(span,"using this as a value"),
+            },
+            _=>self.value(expr)?,
+        };
+        return self.convert(span,value,ty);
+    }
+    /// `value` converted to `ty`, like the interpreter converts numbers and values given to
+    /// interfaces.
+    fn convert(&mut self,span:(Location,Location),value:Value,ty:&CType)->TranslateResult<'input,Value> {
+        if value.ty==*ty {
+            return Ok(value);
+        }
+        if let Some(literal)=value.literal {
+            if let Some(code)=convert_literal(&literal,ty) {
+                return Ok(Value{code,ty:ty.clone(),literal:Some(literal),stable:true,place:false});
+            }
+        }
+        match self.shared.convert(span,&value.code,&value.ty,ty)? {
+            Some(code)=>Ok(Value{code,ty:ty.clone(),literal:None,stable:value.stable,place:false}),
+            None=>self.shared.unsupported(span,format_args!("converting `{}` to `{}`",self.shared.type_name(&value.ty),self.shared.type_name(ty))),
+        }
+    }
+    /// Where `expr` is stored, for assignments and references. Values that aren't stored
+    /// anywhere are stored in a new variable.
+    fn place(&mut self,expr:&Expr<'input>)->TranslateResult<'input,Value> {
+        let span=expr.span();
+        match expr {
+            Expr::Var{path,..}=>{
+                let var=self.var(span,*path)?;
+                Ok(Value::place(var.code,var.ty))
+            },
+            Expr::FieldAccess{from,name,..}=>{
+                let from=self.place(from)?;
+                self.field(span,from,name)
+            },
+            Expr::Index{from,index,..}=>{
+                let from=self.place(from)?;
+                self.index(span,from,index)
+            },
+            Expr::Deref{val,..}=>{
+                let value=self.value(val)?;
+                match value.ty {
+                    CType::Ref{inner,..}=>Ok(Value::place(format!("(*{})",value.code),*inner)),
+                    _=>self.shared.error(span,"only references and pointers can be dereferenced"),
+                }
+            },
+            _=>{
+                let value=self.value(expr)?;
+                let value=self.temp("tmp",value.ty.clone(),&value.code);
+                Ok(Value::place(value.code,value.ty))
+            },
+        }
+    }
+    /// The field `name` of `from`.
+    fn field(&mut self,span:(Location,Location),from:Value,name:&str)->TranslateResult<'input,Value> {
+        let from=self.deref(from);
+        let CType::Object(i)=from.ty else {
+            return self.shared.unsupported(span,format_args!("fields of `{}`",self.shared.type_name(&from.ty)));
+        };
+        let Some(field)=self.shared.types.objects[i].fields.iter().find(|f|f.name==name) else {
+            return self.shared.error(span,format!("there is no field `{}`",name));
+        };
+        let code=member(&from.code,&field.member);
+        return Ok(Value{code,ty:field.ty.clone(),literal:None,stable:from.stable,place:from.place});
+    }
+    /// Item `index` of the slice `from`, checking that it is in bounds.
+    fn index(&mut self,span:(Location,Location),from:Value,index:&Expr<'input>)->TranslateResult<'input,Value> {
+        let at=self.lines.len();
+        let index_span=index.span();
+        let index=self.value(index)?;
+        let mut values=self.settle(vec![(from,at),(index,self.lines.len())]);
+        let (Some(index),Some(from))=(values.pop(),values.pop()) else {
+            return self.shared.error(span,"Internal compiler error: missing values");
+        };
+        let from=self.deref(from);
+        let CType::Slice(item)=from.ty.clone() else {
+            return self.shared.unsupported(span,format_args!("indexing `{}`",self.shared.type_name(&from.ty)));
+        };
+        let index=self.deref(index);
+        if !index.ty.is_integer() {
+            return self.shared.error(index_span,"indexes have to be integers");
+        }
+        let from=self.simple(from,"items");
+        let index=self.simple(index,"index");
+        let condition=match index.ty {
+            CType::Int=>format!("{0} < 0 || (uint64_t){0} >= {1}->len",index.code,from.code),
+            _=>format!("{} >= {}->len",index.code,from.code),
+        };
+        self.check(&condition,index_span,"index out of bounds");
+        return Ok(Value{code:format!("{}->items[{}]",from.code,index.code),ty:*item,literal:None,stable:false,place:true});
+    }
+    /// `from[start..end]`, checking that the range is in bounds.
+    fn slice(&mut self,span:(Location,Location),from:&Expr<'input>,start:Option<&Expr<'input>>,end:Option<&Expr<'input>>)->TranslateResult<'input,Value> {
+        let mut values=vec![];
+        let from=self.operand(from)?;
+        values.push((from,self.lines.len()));
+        for bound in [start,end].into_iter().flatten() {
+            let value=self.value(bound)?;
+            if !value.ty.is_integer() {
+                return self.shared.error(bound.span(),"indexes have to be integers");
+            }
+            values.push((value,self.lines.len()));
+        }
+        let mut values=self.settle(values).into_iter();
+        let Some(from)=values.next() else {
+            return self.shared.error(span,"Internal compiler error: missing values");
+        };
+        let from=self.deref(from);
+        if !matches!(from.ty,CType::Slice(_)) {
+            return self.shared.unsupported(span,format_args!("slicing `{}`",self.shared.type_name(&from.ty)));
+        }
+        let from=self.simple(from,"items");
+        let mut bounds=Vec::with_capacity(2);
+        let mut checks=Vec::new();
+        for (bound,default) in [(start,"0".to_string()),(end,format!("{}->len",from.code))] {
+            if bound.is_none() {
+                bounds.push(default);
+                continue;
+            }
+            let Some(value)=values.next() else {continue};
+            let value=self.simple(value,"bound");
+            if value.ty==CType::Int {
+                checks.push(format!("{} < 0",value.code));
+            }
+            bounds.push(format!("(uint64_t){}",value.code));
+        }
+        checks.push(format!("{} > {}",bounds[0],bounds[1]));
+        checks.push(format!("{} > {}->len",bounds[1],from.code));
+        self.check(&checks.join(" || "),span,"range is out of bounds for the slice");
+        let range=self.shared.slice_range(&from.ty);
+        return Ok(Value::new(format!("{}({}, {}, {})",range,from.code,bare(&bounds[0]),bare(&bounds[1])),from.ty));
+    }
+    fn literal(&mut self,data:&Data<'input>)->Value {
+        match data {
+            Data::String{s,..}=>Value::temp(self.shared.string(s),CType::String),
+            _=>literal(data).unwrap_or_else(||Value::temp("0".into(),CType::Int)),
+        }
+    }
+    fn expr(&mut self,expr:&Expr<'input>,want:Want)->TranslateResult<'input,Option<Value>> {
+        use Expr::*;
+        let span=expr.span();
+        let scopes=self.shared.scopes;
+        Ok(Some(match expr {
+            Data{data,..}=>self.literal(data),
+            Var{path,..}|AssociatedPath{path,..}=>match scopes.get(*path) {
+                Some(VarScopeItem::Function{..})=>self.shared.function_value(span,*path)?,
+                Some(VarScopeItem::BuiltinFunction{name,..})=>return self.shared.unsupported(span,format_args!("the builtin function `{}` used as a value",name)),
+                Some(VarScopeItem::EnumVariant{fields,..}) if fields.is_empty()=>self.variant(span,*path,&[])?,
+                Some(VarScopeItem::EnumVariant{..})=>return self.shared.unsupported(span,"enum variants used as functions"),
+                _=>{
+                    let var=self.var(span,*path)?;
+                    Value::place(var.code,var.ty)
+                },
+            },
+            FunctionCall{path,args,..}=>match scopes.get(*path) {
+                Some(VarScopeItem::Function{name,..})=>{
+                    let signature=self.shared.function(*path)?;
+                    return self.call(span,&signature,name,None,args,want);
+                },
+                Some(VarScopeItem::BuiltinFunction{name,..})=>return self.builtin(span,name,args,want),
+                Some(VarScopeItem::EnumVariant{..})=>self.variant(span,*path,args)?,
+                _=>{
+                    let var=self.var(span,*path)?;
+                    let function=Value::place(var.code,var.ty);
+                    let name=scopes.get(*path).and_then(|v|v.name()).unwrap_or("result");
+                    return self.call_value(span,function,name,args,want);
+                },
+            },
+            MethodCall{from,name,args,method,..}=>return self.method_call(span,from,name,args,*method,want),
+            FieldAccess{from,name,..}=>{
+                let from=self.operand(from)?;
+                self.field(span,from,name)?
+            },
+            Add{inner,..}=>self.binary(Op::Add,inner,span)?,
+            Sub{inner,..}=>self.binary(Op::Sub,inner,span)?,
+            Mul{inner,..}=>self.binary(Op::Mul,inner,span)?,
+            Div{inner,..}=>self.binary(Op::Div,inner,span)?,
+            Mod{inner,..}=>self.binary(Op::Mod,inner,span)?,
+            BitAnd{inner,..}=>self.binary(Op::BitAnd,inner,span)?,
+            BitOr{inner,..}=>self.binary(Op::BitOr,inner,span)?,
+            BitXor{inner,..}=>self.binary(Op::BitXor,inner,span)?,
+            ShiftLeft{inner,..}=>self.binary(Op::ShiftLeft,inner,span)?,
+            ShiftRight{inner,..}=>self.binary(Op::ShiftRight,inner,span)?,
+            Negate{inner,..}=>{
+                let value=self.operand(inner)?;
+                let value=self.deref(value);
+                if let Some(literal)=value.literal {
+                    let negated=match literal {
+                        Number::Int(n)=>Number::Int(-n),
+                        Number::Float(f)=>Number::Float(-f),
+                    };
+                    let code=convert_literal(&negated,&value.ty).unwrap_or_default();
+                    Value{code,ty:value.ty,literal:Some(negated),stable:true,place:false}
+                } else {
+                    let code=match value.ty {
+                        CType::Int=>format!("cppl_int_neg({})",bare(&value.code)),
+                        CType::Uint=>format!("(0 - {})",value.code),
+                        CType::Byte=>format!("(uint8_t)(0 - {})",value.code),
+                        CType::Float|CType::DoubleFloat=>format!("(-{})",paren(&value.code)),
+                        _=>return self.shared.error(span,"only numbers can be negated"),
+                    };
+                    Value::new(code,value.ty)
+                }
+            },
+            Equal{inner,..}|
+                NotEqual{inner,..}|
+                GreaterEqual{inner,..}|
+                LessEqual{inner,..}|
+                Greater{inner,..}|
+                Less{inner,..}=>{
+                let cc=match expr {
+                    Equal{..}=>Comparison::Equal,
+                    NotEqual{..}=>Comparison::NotEqual,
+                    GreaterEqual{..}=>Comparison::GreaterEqual,
+                    LessEqual{..}=>Comparison::LessEqual,
+                    Greater{..}=>Comparison::Greater,
+                    _=>Comparison::Less,
+                };
+                // Only the values are needed, so they are read before anything after them runs.
+                let left=self.read(&inner[0])?;
+                let at=self.lines.len();
+                let right=self.read(&inner[1])?;
+                let mut values=self.settle(vec![(left,at),(right,self.lines.len())]);
+                let (Some(right),Some(left))=(values.pop(),values.pop()) else {
+                    return self.shared.error(span,"Internal compiler error: missing values");
+                };
+                Value::new(self.compare(cc,left,right,span)?,CType::Bool)
+            },
+            And{inner,..}|Or{inner,..}=>{
+                let and=matches!(expr,And{..});
+                let first=self.value_as(&inner[0],&CType::Bool)?;
+                let (second,lines)=self.detached(|t|t.value_as(&inner[1],&CType::Bool))?;
+                if lines.is_empty() {
+                    let op=if and {"&&"} else {"||"};
+                    Value::new(format!("({} {} {})",first.code,op,second.code),CType::Bool)
+                } else {
+                    // `a and b` is `if a {b} else {false}` and `a or b` is `if a {true} else {b}`.
+                    let result=self.temp(if and {"and"} else {"or"},CType::Bool,&first.code);
+                    if and {
+                        self.open(format!("if ({}) {{",result.code));
+                    } else {
+                        self.open(format!("if (!{}) {{",result.code));
+                    }
+                    self.append(lines);
+                    self.line(format!("{} = {};",result.code,bare(&second.code)));
+                    self.close();
+                    result
+                }
+            },
+            Not{inner,..}=>{
+                let value=self.value_as(inner,&CType::Bool)?;
+                Value::new(format!("!{}",paren(&value.code)),CType::Bool)
+            },
+            IsType{to_test,ty,..}=>{
+                let value=self.operand(to_test)?;
+                let value=self.simple(value,"value");
+                let static_ty=expr_type(self.shared.filename,scopes,to_test);
+                Value::new(self.is_type(span,&value,&static_ty,ty)?,CType::Bool)
+            },
+            ObjectCreation{..}=>self.object(expr,None)?,
+            AnonFunction{function,..}=>self.closure(span,*function)?,
+            Ref{val,..}|RefMut{val,..}=>{
+                let place=self.place(val)?;
+                let mutable=matches!(expr,RefMut{..});
+                Value::new(address_of(&place.code),CType::Ref{inner:Box::new(place.ty),mutable})
+            },
+            Deref{val,..}=>{
+                let value=self.operand(val)?;
+                match value.ty {
+                    CType::Ref{inner,..}=>Value::place(format!("(*{})",value.code),*inner),
+                    _=>return self.shared.error(span,"only references and pointers can be dereferenced"),
+                }
+            },
+            Block{block,..}|Unsafe{block,..}=>{
+                self.open("{");
+                self.block(*block)?;
+                self.close();
+                return Ok(None);
+            },
+            ForeverLoop{block,..}=>{
+                let joined=match &want {
+                    Want::Nothing=>None,
+                    _=>{
+                        let name=self.names.unique("loop");
+                        let declaration=self.hole();
+                        let ty=match &want {
+                            Want::As(ty)=>Some(ty.clone()),
+                            _=>None,
+                        };
+                        Some(Joined{name,declaration,arms:Vec::new(),ty})
+                    },
+                };
+                self.open("for (;;) {");
+                self.enter_loop(*block,joined)?;
+                let joined=self.leave_loop();
+                return match joined {
+                    Some(joined)=>self.join(span,joined),
+                    None=>Ok(None),
+                };
+            },
+            WhileLoop{condition,block,..}=>{
+                let (condition,lines)=self.detached(|t|t.value_as(condition,&CType::Bool))?;
+                if lines.is_empty() {
+                    self.open(format!("while ({}) {{",bare(&condition.code)));
+                } else {
+                    self.open("for (;;) {");
+                    self.append(lines);
+                    self.open(format!("if (!{}) {{",paren(&condition.code)));
+                    self.line("break;");
+                    self.close();
+                }
+                self.enter_loop(*block,None)?;
+                self.leave_loop();
+                return Ok(None);
+            },
+            ForLoop{var,iterator,block,..}=>{
+                let items=self.operand(iterator)?;
+                let items=self.deref(items);
+                // The loop goes over a copy, so changing what it loops over doesn't change it.
+                let items=self.own(Value{place:true,..items});
+                let items=self.stable(items,"items");
+                let CType::Slice(item)=items.ty.clone() else {
+                    return self.shared.unsupported(span,format_args!("iterating over `{}`",self.shared.type_name(&items.ty)));
+                };
+                let i=self.names.unique("i");
+                self.open(format!("for (uint64_t {0} = 0; {0} < {1}->len; {0}++) {{",i,items.code));
+                if let Some(param)=scopes.get(*block).and_then(|b|b.get_var(var)) {
+                    self.define(param,var,*item,&format!("{}->items[{}]",items.code,i));
+                }
+                self.enter_loop(*block,None)?;
+                self.leave_loop();
+                return Ok(None);
+            },
+            Match{block,..}=>return self.translate_match(expr,block,want),
+            If{branches,else_block,..}=>{
+                // Only an `if` with an `else` has a value.
+                let mut joined=match (&want,else_block) {
+                    (Want::Nothing,_)|(_,None)=>None,
+                    _=>{
+                        let name=self.names.unique("if");
+                        let declaration=self.hole();
+                        let ty=match &want {
+                            Want::As(ty)=>Some(ty.clone()),
+                            _=>None,
+                        };
+                        Some(Joined{name,declaration,arms:Vec::new(),ty})
+                    },
+                };
+                let mut chain=Chain::default();
+                let blocks=branches.iter().map(|(condition,block)|(Some(condition),*block)).chain(else_block.map(|block|(None,block)));
+                for (condition,block) in blocks {
+                    match condition {
+                        Some(condition)=>{
+                            let (condition,lines)=self.detached(|t|t.value_as(condition,&CType::Bool))?;
+                            self.branch(&mut chain,Some(condition.code),lines);
+                        },
+                        None=>self.branch(&mut chain,None,Vec::new()),
+                    }
+                    let Some(joined)=&mut joined else {
+                        self.block(block)?;
+                        continue;
+                    };
+                    // The branch's value is the expression it ends with.
+                    let (statements,value)=scopes.branch_value(block);
+                    self.statements(statements)?;
+                    let Some(value)=value else {continue};
+                    let value=match joined.ty.clone() {
+                        Some(ty)=>Some(self.value_as(value,&ty)?),
+                        None=>self.expr(value,Want::Value)?.map(|v|self.own(v)),
+                    };
+                    if let Some(value)=value {
+                        let hole=self.hole();
+                        joined.arms.push((hole,value));
+                    }
+                }
+                self.end_chain(chain);
+                return match joined {
+                    Some(joined)=>self.join(span,joined),
+                    None=>Ok(None),
+                };
+            },
+            List{..}=>self.list(expr,None)?,
+            Index{from,index,..}=>{
+                let from=self.operand(from)?;
+                self.index(span,from,index)?
+            },
+            Slice{from,range_start,range_end,..}=>self.slice(span,from,range_start.as_deref(),range_end.as_deref())?,
+            UnknownFunctionCall{path,..}|UnknownAssociatedPath{path,..}=>return self.shared.error(span,format!("`{}` is not defined",path.join("::"))),
+            UnknownVar{name,..}=>return self.shared.error(span,format!("`{}` is not defined",name)),
+        }))
+    }
+    /// Writes the body of a loop that was just opened, then closes it.
+    fn enter_loop(&mut self,block:Scope,value:Option<Joined>)->TranslateResult<'input> {
+        self.loops.push(Loop{block,exit:None,next:None,value});
+        self.block(block)?;
+        if let Some(Loop{next:Some(label),..})=self.loops.last() {
+            let label=label.clone();
+            self.line(format!("{}: ;",label));
+        }
+        self.close();
+        return Ok(());
+    }
+    fn leave_loop(&mut self)->Option<Joined> {
+        let Loop{exit,value,..}=self.loops.pop()?;
+        if let Some(label)=exit {
+            self.line(format!("{}: ;",label));
+        }
+        return value;
+    }
+    /// Declares the variable holding the value of a `loop`, `match` or `if` and assigns it in each
+    /// arm, now that they are all known.
+    fn join(&mut self,span:(Location,Location),joined:Joined)->TranslateResult<'input,Option<Value>> {
+        let Joined{name,declaration,arms,ty}=joined;
+        if arms.is_empty() {
+            return Ok(None);
+        }
+        let ty=match ty {
+            Some(ty)=>ty,
+            // Literals take the type of the other arms.
+            None=>match arms.iter().find(|(_,v)|v.literal.is_none()) {
+                Some((_,value))=>value.ty.clone(),
+                None if arms.iter().any(|(_,v)|v.ty.is_float())=>CType::DoubleFloat,
+                None=>arms[0].1.ty.clone(),
+            },
+        };
+        self.holes[declaration]=Some(format!("{};",self.shared.declare(&ty,&name)));
+        for (hole,value) in arms {
+            let value=self.convert(span,value,&ty)?;
+            self.holes[hole]=Some(format!("{} = {};",name,bare(&value.code)));
+        }
+        return Ok(Some(Value::temp(name,ty)));
+    }
+    /// Starts the next branch of an `if`/`else` chain. Conditions that need statements of their
+    /// own go in the `else` of the branch before.
+    fn branch(&mut self,chain:&mut Chain,condition:Option<String>,lines:Vec<Line>) {
+        if chain.started {
+            self.indent-=1;
+            if lines.is_empty() {
+                match &condition {
+                    Some(condition)=>self.line(format!("}} else if ({}) {{",bare(condition))),
+                    None=>self.line("} else {"),
+                }
+                self.indent+=1;
+                return;
+            }
+            self.line("} else {");
+            self.indent+=1;
+            chain.nested+=1;
+        }
+        self.append(lines);
+        match condition {
+            Some(condition)=>self.open(format!("if ({}) {{",bare(&condition))),
+            None=>self.open("{"),
+        }
+        chain.started=true;
+    }
+    fn end_chain(&mut self,chain:Chain) {
+        if !chain.started {
+            return;
+        }
+        self.close();
+        for _ in 0..chain.nested {
+            self.close();
+        }
+    }
+    fn binary(&mut self,op:Op,inner:&[Expr<'input>;2],span:(Location,Location))->TranslateResult<'input,Value> {
+        let left=self.read(&inner[0])?;
+        let at=self.lines.len();
+        let right=self.read(&inner[1])?;
+        let mut values=self.settle(vec![(left,at),(right,self.lines.len())]);
+        let (Some(right),Some(left))=(values.pop(),values.pop()) else {
+            return self.shared.error(span,"Internal compiler error: missing values");
+        };
+        let (left,right)=(self.deref(left),self.deref(right));
+        return self.arithmetic(op,left,right,span);
+    }
+    fn arithmetic(&mut self,op:Op,left:Value,right:Value,span:(Location,Location))->TranslateResult<'input,Value> {
+        if let Op::ShiftLeft|Op::ShiftRight=op {
+            if !left.ty.is_integer()||!right.ty.is_integer() {
+                return self.shared.error(span,format!("`{}` can only be used on integers",op.symbol()));
+            }
+            let amount=match right.ty {
+                CType::Uint=>right.code.clone(),
+                _=>format!("(uint64_t){}",right.code),
+            };
+            let code=match (op,&left.ty) {
+                (Op::ShiftLeft,CType::Int)=>format!("cppl_int_shl({}, {})",bare(&left.code),amount),
+                (_,CType::Int)=>format!("cppl_int_shr({}, {})",bare(&left.code),amount),
+                (_,CType::Uint)=>format!("((uint64_t){} {} ({} & 63))",left.code,op.symbol(),amount),
+                _=>format!("(uint8_t)({} {} ({} & 7))",left.code,op.symbol(),amount),
+            };
+            return Ok(Value::new(code,left.ty));
+        }
+        if left.ty==CType::String&&right.ty==CType::String {
+            if let Op::Add=op {
+                return Ok(Value::new(format!("cppl_concat({}, {})",bare(&left.code),bare(&right.code)),CType::String));
+            }
+        }
+        if !left.ty.is_number()||!right.ty.is_number() {
+            return self.shared.error(span,format!("`{}` can not be used on these values",op.symbol()));
+        }
+        let ty=unify(&left,&right);
+        let left=self.convert(span,left,&ty)?;
+        let mut right=self.convert(span,right,&ty)?;
+        if let Op::Div|Op::Mod=op {
+            if ty.is_integer() {
+                right=self.simple(right,"divisor");
+                if !matches!(right.literal,Some(Number::Int(n)) if n!=0) {
+                    self.check(&format!("{} == 0",right.code),span,"attempt to divide by zero");
+                }
+            }
+        }
+        let code=match (&ty,op) {
+            (CType::Int,Op::Add|Op::Sub|Op::Mul|Op::Div|Op::Mod)=>{
+                let function=match op {
+                    Op::Add=>"cppl_int_add",
+                    Op::Sub=>"cppl_int_sub",
+                    Op::Mul=>"cppl_int_mul",
+                    Op::Div=>"cppl_int_div",
+                    _=>"cppl_int_mod",
+                };
+                format!("{}({}, {})",function,bare(&left.code),bare(&right.code))
+            },
+            (CType::Float|CType::DoubleFloat,Op::Mod)=>{
+                let function=if ty==CType::Float {"fmodf"} else {"fmod"};
+                format!("{}({}, {})",function,bare(&left.code),bare(&right.code))
+            },
+            (CType::Float|CType::DoubleFloat,Op::BitAnd|Op::BitOr|Op::BitXor)=>{
+                return self.shared.error(span,format!("`{}` can only be used on integers",op.symbol()));
+            },
+            // Both could be literals, which C would calculate as an `int`.
+            (CType::Uint,_) if left.literal.is_some()=>format!("((uint64_t){} {} {})",left.code,op.symbol(),right.code),
+            (CType::Byte,_)=>format!("(uint8_t)({} {} {})",left.code,op.symbol(),right.code),
+            _=>format!("({} {} {})",left.code,op.symbol(),right.code),
+        };
+        return Ok(Value::new(code,ty));
+    }
+    /// Compares two values like the interpreter does.
+    fn compare(&mut self,cc:Comparison,left:Value,right:Value,span:(Location,Location))->TranslateResult<'input,String> {
+        let (left,right)=(self.deref(left),self.deref(right));
+        if let Comparison::Equal|Comparison::NotEqual=cc {
+            let equal=self.equal(span,left,right)?;
+            return Ok(match cc {
+                Comparison::Equal=>equal,
+                _=>format!("!{}",paren(&equal)),
+            });
+        }
+        if left.ty.is_number()&&right.ty.is_number() {
+            let ty=unify(&left,&right);
+            let left=self.convert(span,left,&ty)?;
+            let right=self.convert(span,right,&ty)?;
+            if ty.is_float() {
+                let left=self.simple(left,"left");
+                let right=self.simple(right,"right");
+                self.check(&format!("isnan({}) || isnan({})",bare(&left.code),bare(&right.code)),span,"these values can not be compared");
+                return Ok(format!("({} {} {})",left.code,cc.symbol(),right.code));
+            }
+            return Ok(format!("({} {} {})",left.code,cc.symbol(),right.code));
+        }
+        match (&left.ty,&right.ty) {
+            (CType::String,CType::String)=>Ok(format!("(cppl_string_compare({}, {}) {} 0)",bare(&left.code),bare(&right.code),cc.symbol())),
+            (CType::Char,CType::Char)|(CType::Bool,CType::Bool)=>Ok(format!("({} {} {})",left.code,cc.symbol(),right.code)),
+            _=>{
+                self.fail(span,"these values can not be compared");
+                Ok("false".into())
+            },
+        }
+    }
+    /// Whether two values are equal, like `=`. Values of different types never are.
+    fn equal(&mut self,span:(Location,Location),left:Value,right:Value)->TranslateResult<'input,String> {
+        if left.ty.is_number()&&right.ty.is_number() {
+            let ty=unify(&left,&right);
+            let left=self.convert(span,left,&ty)?;
+            let right=self.convert(span,right,&ty)?;
+            return Ok(format!("({} == {})",left.code,right.code));
+        }
+        let right=if left.ty==right.ty {
+            right
+        } else {
+            match (&left.ty,&right.ty) {
+                (CType::Object(_),CType::Object(_))|(CType::Slice(_),CType::Slice(_))=>match self.shared.convert(span,&right.code,&right.ty,&left.ty)? {
+                    Some(code)=>Value::new(code,left.ty.clone()),
+                    None=>return Ok("false".into()),
+                },
+                _=>return Ok("false".into()),
+            }
+        };
+        match self.shared.equal(&left.ty,bare(&left.code),bare(&right.code)) {
+            Ok(code) if code.contains(" == ")=>Ok(format!("({})",code)),
+            Ok(code)=>Ok(code),
+            Err(what)=>self.shared.unsupported(span,what),
+        }
+    }
+    /// The condition for `value`, whose static type is `static_ty`, being of type `ty`.
+    fn is_type(&mut self,span:(Location,Location),value:&Value,static_ty:&Type<'input>,ty:&Type<'input>)->TranslateResult<'input,String> {
+        let scopes=self.shared.scopes;
+        let value=self.deref(value.clone());
+        let terms=match &value.ty {
+            // Values of enums and interfaces know what they are at runtime.
+            CType::Enum(e)=>{
+                let variants=self.shared.types.enums[e].variants.iter().map(|v|(v.scope,v.id.clone())).collect::<Vec<_>>();
+                let count=variants.len();
+                let mut terms=Vec::new();
+                for (scope,id) in variants {
+                    let variant=Type::Named{start:span.0,end:span.1,path:scope,generics:Vec::new()};
+                    if scopes.check_assignable(&variant,ty).is_ok() {
+                        terms.push(format!("{}->variant == {}",value.code,id));
+                    }
+                }
+                if terms.len()==count&&count>0 {
+                    return Ok("true".into());
+                }
+                terms
+            },
+            CType::Interface(interface)=>{
+                let mut terms=Vec::new();
+                for scope in scopes.impls() {
+                    let Some(VarScopeItem::Impl{interface:Some(Type::Named{path,..}),for_ty,params:None,..})=scopes.get(scope) else {
+                        continue;
+                    };
+                    if path!=interface||scopes.check_assignable(for_ty,ty).is_err() {
+                        continue;
+                    }
+                    let Ok(ctype)=self.shared.ctype(span,for_ty) else {continue};
+                    let boxed=self.shared.boxed(span,*interface,&ctype)?;
+                    terms.push(format!("{}->vtable == &{}",value.code,boxed.vtable));
+                }
+                terms
+            },
+            _=>return Ok(scopes.check_assignable(static_ty,ty).is_ok().to_string()),
+        };
+        if terms.is_empty() {
+            return Ok("false".into());
+        }
+        return Ok(format!("({})",terms.join(" || ")));
+    }
+    /// An enum variant holding the values of `args`.
+    fn variant(&mut self,span:(Location,Location),variant:Scope,args:&[Expr<'input>])->TranslateResult<'input,Value> {
+        let ty=Type::Named{start:span.0,end:span.1,path:variant,generics:Vec::new()};
+        let ctype=self.shared.ctype(span,&ty)?;
+        let CType::Enum(e)=&ctype else {
+            return self.shared.error(span,"Internal compiler error: not an enum");
+        };
+        let Some(found)=self.shared.types.enums[e].variants.iter().find(|v|v.scope==variant) else {
+            return self.shared.error(span,"Internal compiler error: unknown variant");
+        };
+        let (name,fields,constructor)=(found.name.clone(),found.fields.clone(),found.constructor.clone());
+        let Some(constructor)=constructor else {
+            return Ok(Value::temp(format!("&{}",name),ctype));
+        };
+        let args=self.args(args,&fields)?;
+        return Ok(Value::new(format!("{}({})",constructor,args.join(", ")),ctype));
+    }
+    /// The values of `args` as the types of the parameters, in order.
+    fn args(&mut self,args:&[Expr<'input>],params:&[CType])->TranslateResult<'input,Vec<String>> {
+        let mut values=Vec::with_capacity(args.len());
+        for (arg,ty) in args.iter().zip(params.iter()) {
+            let value=self.value_as(arg,ty)?;
+            values.push((value,self.lines.len()));
+        }
+        return Ok(self.settle(values).into_iter().map(|v|bare(&v.code).to_string()).collect());
+    }
+    /// Calls `code`, checking how deep calls are nested first. `name` names the result.
+    fn invoke(&mut self,span:(Location,Location),code:String,ret:Option<&CType>,name:&str,want:Want)->Option<Value> {
+        let id=self.shared.error_id(span,format!("stack overflow: more than {} calls are nested",MAX_CALL_DEPTH));
+        self.line(format!("cppl_enter(cppl_errors[{}]);",id));
+        let result=match (ret,want) {
+            (Some(ty),Want::Value|Want::As(_))=>Some(self.temp(&identifier(name),ty.clone(),&code)),
+            _=>{
+                self.line(format!("{};",code));
+                None
+            },
+        };
+        self.line("cppl_leave();");
+        return result;
+    }
+    /// Calls a function of the program.
+    fn call(&mut self,span:(Location,Location),signature:&Signature,name:&str,this:Option<Value>,args:&[Expr<'input>],want:Want)->TranslateResult<'input,Option<Value>> {
+        let mut values=Vec::with_capacity(args.len()+1);
+        if let Some(this)=this {
+            values.push((this,self.lines.len()));
+        }
+        for (arg,ty) in args.iter().zip(signature.params.iter()) {
+            let value=self.value_as(arg,ty)?;
+            values.push((value,self.lines.len()));
+        }
+        let args=self.settle(values).into_iter().map(|v|bare(&v.code).to_string()).collect::<Vec<_>>();
+        let code=format!("{}({})",signature.name,args.join(", "));
+        return Ok(self.invoke(span,code,signature.ret.as_ref(),name,want));
+    }
+    /// Calls a function value.
+    fn call_value(&mut self,span:(Location,Location),function:Value,name:&str,args:&[Expr<'input>],want:Want)->TranslateResult<'input,Option<Value>> {
+        let function=self.deref(function);
+        let CType::Function(i)=function.ty else {
+            return self.shared.error(span,"only functions can be called");
+        };
+        let (params,ret)={
+            let ty=&self.shared.types.functions[i];
+            (ty.params.clone(),ty.ret.clone())
+        };
+        let mut values=vec![(function,self.lines.len())];
+        for (arg,ty) in args.iter().zip(params.iter()) {
+            let value=self.value_as(arg,ty)?;
+            values.push((value,self.lines.len()));
+        }
+        let mut values=self.settle(values).into_iter();
+        let Some(function)=values.next() else {
+            return self.shared.error(span,"Internal compiler error: missing values");
+        };
+        let function=self.simple(function,"function");
+        let mut args=vec![function.code.clone()];
+        args.extend(values.map(|v|bare(&v.code).to_string()));
+        let code=format!("{}->call({})",function.code,args.join(", "));
+        return Ok(self.invoke(span,code,ret.as_ref(),name,want));
+    }
+    fn method_call(&mut self,span:(Location,Location),from:&Expr<'input>,name:&str,args:&[Expr<'input>],method:Option<Scope>,want:Want)->TranslateResult<'input,Option<Value>> {
+        let Some(method)=method else {
+            let from=self.operand(from)?;
+            let from=self.deref(from);
+            return match &from.ty {
+                CType::Interface(interface)=>{
+                    let interface=*interface;
+                    let Some(found)=self.shared.types.interfaces[&interface].methods.iter().find(|m|m.name==name) else {
+                        return self.shared.error(span,format!("no method `{}` found",name));
+                    };
+                    let (member,params,ret)=(found.member.clone(),found.params.clone(),found.ret.clone());
+                    let mut values=vec![(from,self.lines.len())];
+                    for (arg,ty) in args.iter().zip(params.iter()) {
+                        let value=self.value_as(arg,ty)?;
+                        values.push((value,self.lines.len()));
+                    }
+                    let mut values=self.settle(values).into_iter();
+                    let Some(this)=values.next() else {
+                        return self.shared.error(span,"Internal compiler error: missing values");
+                    };
+                    let this=self.simple(Value{place:false,..this},"this");
+                    let mut args=vec![this.code.clone()];
+                    args.extend(values.map(|v|bare(&v.code).to_string()));
+                    let code=format!("{}->vtable->{}({})",this.code,member,args.join(", "));
+                    Ok(self.invoke(span,code,ret.as_ref(),name,want))
+                },
+                CType::Object(_)=>{
+                    let function=self.field(span,from,name)?;
+                    self.call_value(span,function,name,args,want)
+                },
+                _=>self.shared.unsupported(span,format_args!("calling `{}` on `{}`",name,self.shared.type_name(&from.ty))),
+            };
+        };
+        let signature=self.shared.function(method)?;
+        let this=match &signature.this {
+            None=>None,
+            Some((ty,false))=>{
+                let value=self.operand(from)?;
+                let value=self.deref(value);
+                let value=self.own(value);
+                Some(self.convert(span,value,ty)?)
+            },
+            Some((ty,true))=>{
+                let place=self.place(from)?;
+                let place=self.deref(place);
+                match (&place.ty,ty) {
+                    (CType::Interface(_),CType::Interface(_))=>Some(self.convert(span,Value{place:false,..place},ty)?),
+                    (from,to) if from==to=>Some(Value::new(address_of(&place.code),CType::Ref{inner:Box::new(place.ty.clone()),mutable:true})),
+                    _=>return self.shared.unsupported(span,format_args!("calling `{}` on `{}`",name,self.shared.type_name(&place.ty))),
+                }
+            },
+        };
+        return self.call(span,&signature,name,this,args,want);
+    }
+    /// Calls a builtin function.
+    fn builtin(&mut self,span:(Location,Location),name:&str,args:&[Expr<'input>],want:Want)->TranslateResult<'input,Option<Value>> {
+        match name {
+            "print"|"println"|"eprint"|"eprintln"=>{
+                let text=match args.first() {
+                    Some(arg)=>self.value_as(arg,&CType::String)?,
+                    None=>Value::temp(self.shared.string(""),CType::String),
+                };
+                self.line(format!("cppl_{}({});",name,bare(&text.code)));
+                return Ok(None);
+            },
+            "read_line"=>{
+                if want==Want::Nothing {
+                    self.line("cppl_read_line();");
+                    return Ok(None);
+                }
+                return Ok(Some(self.temp("line",CType::String,"cppl_read_line()")));
+            },
+            "Arguments"=>{
+                let ty=self.shared.slice_type(CType::String);
+                return Ok(Some(Value::new("cppl_arguments()".into(),ty)));
+            },
+            _=>return self.shared.unsupported(span,format_args!("the builtin function `{}`",name)),
+        }
+    }
+    /// A new object. `object` is the type to make, otherwise the type is made from the fields.
+    fn object(&mut self,expr:&Expr<'input>,object:Option<usize>)->TranslateResult<'input,Value> {
+        let span=expr.span();
+        let Expr::ObjectCreation{fields,..}=expr else {
+            return self.shared.error(span,"Internal compiler error: not an object");
+        };
+        // Fields are evaluated in the order they are written.
+        let mut values=Vec::with_capacity(fields.len());
+        for field in fields {
+            let ty=object.and_then(|i|self.shared.types.objects[i].fields.iter().find(|f|f.name==field.name).map(|f|f.ty.clone()));
+            let value=match ty {
+                Some(ty)=>self.value_as(&field.data,&ty)?,
+                None=>self.value(&field.data)?,
+            };
+            values.push((value,self.lines.len()));
+        }
+        let values=self.settle(values);
+        let object=match object {
+            Some(object)=>object,
+            None=>{
+                let ctypes=fields.iter()
+                    .zip(values.iter())
+                    .map(|(field,value)|(field.name,value.ty.clone(),field_flags(field.public,field.mutable)))
+                    .collect();
+                self.shared.object_type(ctypes,None)
+            },
+        };
+        let order=self.shared.types.objects[object].fields.iter().map(|f|f.name).collect::<Vec<_>>();
+        let mut args=Vec::with_capacity(order.len());
+        for name in order {
+            let Some(i)=fields.iter().position(|f|f.name==name) else {
+                return self.shared.error(span,format!("the field `{}` is missing",name));
+            };
+            args.push(bare(&values[i].code).to_string());
+        }
+        let constructor=self.shared.constructor(object);
+        return Ok(Value::new(format!("{}({})",constructor,args.join(", ")),CType::Object(object)));
+    }
+    /// A new slice. `ty` is the type to make, otherwise the items are made the same type.
+    fn list(&mut self,expr:&Expr<'input>,ty:Option<&CType>)->TranslateResult<'input,Value> {
+        let span=expr.span();
+        let Expr::List{items,..}=expr else {
+            return self.shared.error(span,"Internal compiler error: not a list");
+        };
+        let item=match ty {
+            Some(CType::Slice(item))=>Some((**item).clone()),
+            _=>None,
+        };
+        let mut values=Vec::with_capacity(items.len());
+        for expr in items {
+            let value=match &item {
+                Some(item)=>self.value_as(expr,item)?,
+                None=>self.value(expr)?,
+            };
+            values.push((value,self.lines.len()));
+        }
+        let values=self.settle(values);
+        let item=match item {
+            Some(item)=>item,
+            None=>match values.iter().find(|v|v.literal.is_none()).or(values.first()) {
+                Some(first) if first.literal.is_some()&&values.iter().any(|v|v.ty.is_float())=>CType::DoubleFloat,
+                Some(first)=>first.ty.clone(),
+                None=>{
+                    let ty=expr_type(self.shared.filename,self.shared.scopes,expr);
+                    match self.shared.ctype(span,&ty)? {
+                        CType::Slice(item)=>*item,
+                        _=>return self.shared.error(span,"Internal compiler error: not a slice"),
+                    }
+                },
+            },
+        };
+        let ty=self.shared.slice_type(item.clone());
+        let new=self.shared.slice_new(&ty);
+        let list=self.temp("list",ty.clone(),&format!("{}({})",new,values.len()));
+        for (i,value) in values.into_iter().enumerate() {
+            let value=self.convert(span,value,&item)?;
+            self.line(format!("{}->items[{}] = {};",list.code,i,bare(&value.code)));
+        }
+        return Ok(list);
+    }
+    /// An anonymous function, which is its environment: the function to call followed by
+    /// pointers to the variables it captures.
+    fn closure(&mut self,span:(Location,Location),function:Scope)->TranslateResult<'input,Value> {
+        let scopes=self.shared.scopes;
+        let Some(VarScopeItem::AnonFunction{params,ret_type,statements,..})=scopes.get(function) else {
+            return self.shared.error(span,"Internal compiler error: not an anonymous function");
+        };
+        let mut param_types=Vec::with_capacity(params.len());
+        for param in params {
+            let param=scopes.get(*param);
+            let param_span=param.and_then(|p|p.def_span()).unwrap_or(span);
+            param_types.push(self.shared.ctype(param_span,param.and_then(|p|p.var_type()).unwrap_or(&Type::Unknown))?);
+        }
+        let ret=match ret_type {
+            Some(ty)=>Some(self.shared.ctype(span,ty)?),
+            None=>None,
+        };
+        let ty=self.shared.function_type(param_types.clone(),ret.clone());
+        let CType::Function(i)=ty else {
+            return self.shared.error(span,"Internal compiler error: not a function type");
+        };
+        let type_name=self.shared.types.functions[i].name.clone();
+        let mut captured=Vec::new();
+        let mut members=Names::new(["base"]);
+        for var in self.shared.captures.get(&function).cloned().unwrap_or_default() {
+            let name=scopes.get(var).and_then(|v|v.name()).unwrap_or("var");
+            let member=members.unique(&identifier(name));
+            // Variables defined after the function can't be used by it yet.
+            let cell=self.vars.get(&var).map(|local|(address_of(&local.code),local.ty.clone()));
+            captured.push((var,member,cell));
+        }
+        let env=self.shared.names.unique("cppl_closure");
+        let call=self.shared.names.unique(&format!("{}_call",env));
+        let mut typedef=format!("typedef struct {{\n    {} base;\n",type_name);
+        for (_,member,cell) in captured.iter() {
+            if let Some((_,ty))=cell {
+                let pointer=CType::Ref{inner:Box::new(ty.clone()),mutable:true};
+                typedef.push_str(&format!("    {};\n",self.shared.declare(&pointer,member)));
+            }
+        }
+        typedef.push_str(&format!("}} {};",env));
+        self.shared.typedefs.push(typedef);
+        let mut translator=FunctionTranslator::new(self.shared,ret.clone(),false);
+        if captured.iter().any(|(_,_,cell)|cell.is_some()) {
+            translator.line(format!("{0} *env = ({0} *)self;",env));
+        } else {
+            translator.line("(void)self;");
+        }
+        for (var,member,cell) in captured.iter() {
+            if let Some((_,ty))=cell {
+                translator.vars.insert(*var,Local{code:format!("(*env->{})",member),ty:ty.clone()});
+            }
+        }
+        let mut c_params=vec![format!("{} *self",type_name)];
+        for (param,ty) in params.iter().zip(param_types.iter()) {
+            let name=scopes.get(*param).and_then(|p|p.name()).unwrap_or("arg");
+            c_params.push(translator.param(*param,name,ty));
+        }
+        translator.statements(statements)?;
+        translator.ret_zero();
+        let body=translator.finish();
+        let declarator=format!("{}({})",call,c_params.join(", "));
+        let signature=format!("static {}",self.shared.declare_function(ret.as_ref(),&declarator));
+        self.shared.define_function(signature,&body);
+        let closure=self.names.unique("closure");
+        self.line(format!("{0} *{1} = cppl_allocate(sizeof *{1});",env,closure));
+        self.line(format!("{}->base.call = {};",closure,call));
+        for (_,member,cell) in captured {
+            if let Some((address,_))=cell {
+                self.line(format!("{}->{} = {};",closure,member,address));
+            }
+        }
+        return Ok(Value::temp(format!("&{}->base",closure),ty));
+    }
+    fn translate_match(&mut self,expr:&Expr<'input>,block:&Match<'input>,want:Want)->TranslateResult<'input,Option<Value>> {
+        let span=expr.span();
+        let scopes=self.shared.scopes;
+        let value=self.value(&block.to_match)?;
+        let value=self.stable(value,"value");
+        let static_ty=expr_type(self.shared.filename,scopes,&block.to_match);
+        let mut joined=match &want {
+            Want::Nothing=>None,
+            _=>{
+                let name=self.names.unique("match");
+                let declaration=self.hole();
+                let ty=match &want {
+                    Want::As(ty)=>Some(ty.clone()),
+                    _=>None,
+                };
+                Some(Joined{name,declaration,arms:Vec::new(),ty})
+            },
+        };
+        let mut chain=Chain::default();
+        let mut exhaustive=false;
+        for (pattern,leaf) in block.leafs.iter() {
+            let (condition,lines)=self.detached(|t|t.pattern(pattern,&value,&static_ty))?;
+            let condition=match condition.as_deref() {
+                Some("true")=>None,
+                _=>condition,
+            };
+            exhaustive=condition.is_none();
+            self.branch(&mut chain,condition,lines);
+            self.bind(pattern,&value)?;
+            match &mut joined {
+                Some(joined)=>{
+                    let value=match joined.ty.clone() {
+                        Some(ty)=>Some(self.value_as(leaf,&ty)?),
+                        None=>self.expr(leaf,Want::Value)?.map(|v|self.own(v)),
+                    };
+                    if let Some(value)=value {
+                        let hole=self.hole();
+                        joined.arms.push((hole,value));
+                    }
+                },
+                None=>{
+                    self.expr(leaf,Want::Nothing)?;
+                },
+            }
+            // Arms after one that matches everything never run.
+            if exhaustive {
+                break;
+            }
+        }
+        if !exhaustive {
+            self.branch(&mut chain,None,Vec::new());
+            self.fail(span,"no pattern matched the value");
+        }
+        self.end_chain(chain);
+        return match joined {
+            Some(joined)=>self.join(span,joined),
+            None=>Ok(None),
+        };
+    }
+    /// The condition for `value` matching `pattern`, `None` if it always does.
+    fn pattern(&mut self,pattern:&MatchPattern<'input>,value:&Value,static_ty:&Type<'input>)->TranslateResult<'input,Option<String>> {
+        let span=pattern.span();
+        let cc=match pattern {
+            MatchPattern::Data{inner,..}=>{
+                let other=self.literal(inner);
+                return Ok(Some(self.compare(Comparison::Equal,value.clone(),other,span)?));
+            },
+            MatchPattern::Var{..}=>return Ok(None),
+            MatchPattern::MethodCall{name,args,..}=>{
+                let Some(method)=self.shared.scopes.find_method(static_ty,name) else {
+                    return self.shared.error(span,format!("no method `{}` found",name));
+                };
+                let signature=self.shared.function(method)?;
+                let value=self.deref(value.clone());
+                let this=match &signature.this {
+                    Some((ty,true)) if !matches!(ty,CType::Interface(_))=>{
+                        // The method gets a copy to change.
+                        let this=self.own(Value{place:true,..value});
+                        let this=self.temp("this",this.ty.clone(),&this.code);
+                        Some(Value::new(format!("&{}",this.code),CType::Ref{inner:Box::new(this.ty),mutable:true}))
+                    },
+                    Some((ty,_))=>{
+                        let this=self.own(Value{place:true,..value});
+                        Some(self.convert(span,this,ty)?)
+                    },
+                    None=>None,
+                };
+                let result=self.call(span,&signature,name,this,args,Want::Value)?;
+                return Ok(Some(match result {
+                    Some(result) if result.ty==CType::Bool=>result.code,
+                    _=>"false".into(),
+                }));
+            },
+            MatchPattern::Structure{structure,..}=>return self.structure(structure,value,span),
+            MatchPattern::IsType{inner,..}=>return Ok(Some(self.is_type(span,value,static_ty,inner)?)),
+            MatchPattern::Equal{..}=>Comparison::Equal,
+            MatchPattern::NotEqual{..}=>Comparison::NotEqual,
+            MatchPattern::GreaterEqual{..}=>Comparison::GreaterEqual,
+            MatchPattern::LessEqual{..}=>Comparison::LessEqual,
+            MatchPattern::Greater{..}=>Comparison::Greater,
+            MatchPattern::Less{..}=>Comparison::Less,
+        };
+        let Some(inner)=pattern.exprs().first().copied() else {
+            return self.shared.error(span,"Internal compiler error: comparison pattern without a value");
+        };
+        let other=self.value(inner)?;
+        return Ok(Some(self.compare(cc,value.clone(),other,span)?));
+    }
+    /// The condition for `value` having the fields of `structure`.
+    fn structure(&mut self,structure:&MatchPatternStructure<'input>,value:&Value,span:(Location,Location))->TranslateResult<'input,Option<String>> {
+        let value=self.deref(value.clone());
+        let mut conditions=Vec::new();
+        let (exact,items)=match structure {
+            MatchPatternStructure::Block{exact,block,..}=>(*exact,block),
+            MatchPatternStructure::TypedBlock{exact,ty,block,..}=>{
+                let ctype=self.shared.ctype(span,ty)?;
+                if ctype!=value.ty {
+                    return Ok(Some("false".into()));
+                }
+                (*exact,block)
+            },
+        };
+        let CType::Object(i)=value.ty else {
+            return self.shared.unsupported(span,format_args!("structure patterns on `{}`",self.shared.type_name(&value.ty)));
+        };
+        if exact&&self.shared.types.objects[i].fields.len()!=items.len() {
+            return Ok(Some("false".into()));
+        }
+        for item in items {
+            let name=match item {
+                MatchPatternStructureItem::Field{name,..}|
+                    MatchPatternStructureItem::NamedField{name,..}|
+                    MatchPatternStructureItem::NamedBlock{name,..}=>*name,
+            };
+            if !self.shared.types.objects[i].fields.iter().any(|f|f.name==name) {
+                return Ok(Some("false".into()));
+            }
+            if let MatchPatternStructureItem::NamedBlock{block,..}=item {
+                let field=self.field(span,value.clone(),name)?;
+                match self.structure(block,&field,span)? {
+                    Some(condition) if condition=="false"=>return Ok(Some(condition)),
+                    Some(condition)=>conditions.push(condition),
+                    None=>{},
+                }
+            }
+        }
+        if conditions.is_empty() {
+            return Ok(None);
+        }
+        return Ok(Some(conditions.join(" && ")));
+    }
+    /// Defines the variables `pattern` binds.
+    fn bind(&mut self,pattern:&MatchPattern<'input>,value:&Value)->TranslateResult<'input> {
+        match pattern {
+            // `_` matches anything without binding it.
+            MatchPattern::Var{name:"_",..}=>{},
+            MatchPattern::Var{name,var,..}=>{
+                let value=self.own(Value{place:true,..value.clone()});
+                self.define(*var,name,value.ty.clone(),&value.code);
+            },
+            MatchPattern::Structure{structure,..}=>self.bind_structure(structure,value,pattern.span())?,
+            _=>{},
+        }
+        return Ok(());
+    }
+    fn bind_structure(&mut self,structure:&MatchPatternStructure<'input>,value:&Value,span:(Location,Location))->TranslateResult<'input> {
+        let value=self.deref(value.clone());
+        let items=match structure {
+            MatchPatternStructure::Block{block,..}|MatchPatternStructure::TypedBlock{block,..}=>block,
+        };
+        for item in items {
+            match item {
+                MatchPatternStructureItem::Field{name,var,..}|MatchPatternStructureItem::NamedField{name,var,..}=>{
+                    let var_name=match item {
+                        MatchPatternStructureItem::NamedField{rename,..}=>*rename,
+                        _=>*name,
+                    };
+                    let field=self.field(span,value.clone(),name)?;
+                    let field=self.own(Value{place:true,..field});
+                    self.define(*var,var_name,field.ty.clone(),&field.code);
+                },
+                MatchPatternStructureItem::NamedBlock{name,block,..}=>{
+                    let field=self.field(span,value.clone(),name)?;
+                    self.bind_structure(block,&field,span)?;
+                },
+            }
+        }
+        return Ok(());
+    }
+}
+/// An `if`/`else` chain being written.
+#[derive(Default)]
+struct Chain {
+    started:bool,
+    /// How many `else` blocks were opened for conditions that need statements.
+    nested:usize,
+}
+
+
+/// The variable a narrowed variable narrows.
+fn original(scopes:&Scopes,mut var:Scope)->Scope {
+    while let Some(&VarScopeItem::Narrowed{original,..})=scopes.get(var) {
+        var=original;
+    }
+    return var;
+}
+/// The variable a place is in, if it is in one.
+fn place_root(expr:&Expr)->Option<Scope> {
+    match expr {
+        Expr::Var{path,..}=>Some(*path),
+        Expr::FieldAccess{from,..}|Expr::Index{from,..}=>place_root(from),
+        _=>None,
+    }
+}
+/// The type two numbers are converted to, like `unify` in the interpreter. Literals are an `Int`
+/// or a `DoubleFloat` there.
+fn unify(a:&Value,b:&Value)->CType {
+    let runtime=|value:&Value|match value.literal {
+        Some(Number::Int(_)) if value.ty==CType::Int=>CType::Int,
+        _=>value.ty.clone(),
+    };
+    let (a,b)=(runtime(a),runtime(b));
+    match (&a,&b) {
+        _ if a==b=>a,
+        (CType::Float,CType::DoubleFloat)|(CType::DoubleFloat,CType::Float)=>CType::Float,
+        (_,CType::Int)=>a,
+        (CType::Int,_)=>b,
+        (CType::Float|CType::DoubleFloat,_)=>a,
+        _=>b,
+    }
+}
+/// A literal that isn't a string.
+fn literal(data:&Data)->Option<Value> {
+    let (number,ty)=match data {
+        Data::GenericNumber{negative,data,..}=>{
+            let digits=data.replace('_',"");
+            match digits.parse::<i64>() {
+                Ok(n)=>(Number::Int(if *negative {-(n as i128)} else {n as i128}),CType::Int),
+                Err(_)=>(Number::Int(digits.parse::<u64>().unwrap_or(u64::MAX) as i128),CType::Uint),
+            }
+        },
+        Data::GenericFloat{negative,data,..}=>{
+            let n=data.replace('_',"").parse::<f64>().unwrap_or_default();
+            (Number::Float(if *negative {-n} else {n}),CType::DoubleFloat)
+        },
+        Data::UInt{data,..}=>(Number::Int(*data as i128),CType::Uint),
+        Data::Int{data,..}=>(Number::Int(*data as i128),CType::Int),
+        Data::Float{data,..}=>(Number::Float(*data as f64),CType::Float),
+        Data::LargeFloat{data,..}=>(Number::Float(*data),CType::DoubleFloat),
+        Data::Char{data,..}=>{
+            let code=match data {
+                '\''|'\\'=>(*data as u32).to_string(),
+                c if c.is_ascii_graphic()||*c==' '=>format!("'{}'",c),
+                c=>(*c as u32).to_string(),
+            };
+            return Some(Value::temp(code,CType::Char));
+        },
+        Data::Bool{data,..}=>return Some(Value::temp(data.to_string(),CType::Bool)),
+        Data::String{..}=>return None,
+    };
+    let code=convert_literal(&number,&ty)?;
+    return Some(Value{code,ty,literal:Some(number),stable:true,place:false});
+}
+/// A number literal written as type `ty`.
+fn convert_literal(number:&Number,ty:&CType)->Option<String> {
+    let float=match *number {
+        Number::Int(n)=>match ty {
+            CType::Int=>return Some(match n as i64 {
+                i64::MIN=>"INT64_MIN".into(),
+                n=>n.to_string(),
+            }),
+            CType::Uint=>return Some(match n as u64 {
+                n if n>i64::MAX as u64=>format!("{}u",n),
+                n=>n.to_string(),
+            }),
+            CType::Byte=>return Some((n as u8).to_string()),
+            _=>n as f64,
+        },
+        Number::Float(f)=>f,
+    };
+    return Some(match ty {
+        CType::Int=>match float as i64 {
+            i64::MIN=>"INT64_MIN".into(),
+            n=>n.to_string(),
+        },
+        CType::Uint=>format!("{}u",float as u64),
+        CType::Byte=>(float as u8).to_string(),
+        CType::Float=>float_code(float as f32 as f64,"f"),
+        CType::DoubleFloat=>float_code(float,""),
+        _=>return None,
+    });
+}
+/// A float literal, with `suffix` after it if it is finite.
+fn float_code(f:f64,suffix:&str)->String {
+    if f.is_nan() {
+        return "NAN".into();
+    }
+    if f.is_infinite() {
+        return if f>0.0 {"INFINITY".into()} else {"(-INFINITY)".into()};
+    }
+    if suffix.is_empty() {
+        return format!("{:?}",f);
+    }
+    return format!("{:?}{}",f as f32,suffix);
+}
+/// `s` as a C string literal. Other than control characters, text is written as it is so the
+/// output stays readable.
+fn c_string(s:&str)->String {
+    let mut out=String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"'=>out.push_str("\\\""),
+            '\\'=>out.push_str("\\\\"),
+            '\n'=>out.push_str("\\n"),
+            '\t'=>out.push_str("\\t"),
+            '\r'=>out.push_str("\\r"),
+            // `??` starts a trigraph.
+            '?'=>out.push_str("\\?"),
+            c if c.is_control()=>for byte in c.to_string().bytes() {
+                out.push_str(&format!("\\{:03o}",byte));
+            },
+            c=>out.push(c),
+        }
+    }
+    out.push('"');
+    return out;
+}
+/// The index of the parenthesis closing the one `code` starts with.
+fn closing_paren(code:&str)->Option<usize> {
+    let mut depth=0;
+    let mut quoted=false;
+    for (i,c) in code.char_indices() {
+        match c {
+            '\''=>quoted^=true,
+            _ if quoted=>{},
+            '('|'['=>depth+=1,
+            ')'|']'=>{
+                depth-=1;
+                if depth==0 {
+                    return Some(i);
+                }
+            },
+            _=>{},
+        }
+    }
+    return None;
+}
+/// `code` without the parentheses around all of it, for where it stands alone.
+fn bare(code:&str)->&str {
+    if code.starts_with('(')&&closing_paren(code)==Some(code.len()-1) {
+        return &code[1..code.len()-1];
+    }
+    return code;
+}
+/// `code` in parentheses if an operator in front of it would bind to only part of it.
+fn paren(code:&str)->String {
+    let mut depth=0;
+    let mut quoted=false;
+    let mut top_level=false;
+    for c in code.chars() {
+        match c {
+            '\''=>quoted^=true,
+            _ if quoted=>{},
+            '('|'['=>depth+=1,
+            ')'|']'=>depth-=1,
+            ' ' if depth==0=>top_level=true,
+            _=>{},
+        }
+    }
+    let cast=code.starts_with('(')&&closing_paren(code)!=Some(code.len()-1);
+    if top_level||cast||code.starts_with(['-','!','*','&']) {
+        return format!("({})",code);
+    }
+    return code.to_string();
+}
+/// What `(*pointer)` dereferences.
+fn strip_deref(code:&str)->Option<&str> {
+    if code.starts_with("(*")&&closing_paren(code)==Some(code.len()-1) {
+        return Some(&code[2..code.len()-1]);
+    }
+    return None;
+}
+/// A pointer to the place `code`.
+fn address_of(code:&str)->String {
+    match strip_deref(code) {
+        Some(pointer)=>pointer.to_string(),
+        None=>format!("&{}",paren(code)),
+    }
+}
+/// The member `name` of the struct `code`.
+fn member(code:&str,name:&str)->String {
+    match strip_deref(code) {
+        Some(pointer)=>format!("{}->{}",paren(pointer),name),
+        None=>format!("{}.{}",paren(code),name),
+    }
+}
+/// Whether `code` only reads something, so it can be used more than once.
+fn is_simple(code:&str)->bool {
+    if let Some(pointer)=strip_deref(code) {
+        return is_simple(pointer);
+    }
+    let code=code.strip_prefix('&').or_else(||code.strip_prefix('-')).unwrap_or(code);
+    if code.starts_with('\'') {
+        return true;
+    }
+    !code.is_empty()&&code.split("->")
+        .flat_map(|part|part.split('.'))
+        .all(|part|!part.is_empty()&&part.chars().all(|c|c.is_ascii_alphanumeric()||c=='_'))
+        ||code.parse::<f64>().is_ok()
+}
+fn stmt_span(stmt:&Statement)->(Location,Location) {
+    match stmt {
+        Statement::FunctionDef{start,end,..}|
+            Statement::FunctionSig{start,end,..}|
+            Statement::InterfaceDef{start,end,..}|
+            Statement::TypeDef{start,end,..}|
+            Statement::VarDef{start,end,..}|
+            Statement::StaticVarDef{start,end,..}|
+            Statement::ConstVarDef{start,end,..}|
+            Statement::VarAssign{start,end,..}|
+            Statement::UnknownVarAssign{start,end,..}|
+            Statement::PlaceAssign{start,end,..}|
+            Statement::CompoundAssign{start,end,..}|
+            Statement::Expr{start,end,..}|
+            Statement::Return{start,end,..}|
+            Statement::Continue{start,end,..}|
+            Statement::Break{start,end,..}|
+            Statement::Enum{start,end,..}|
+            Statement::Impl{start,end,..}=>(*start,*end),
+    }
+}
@@ -0,0 +1,1042 @@
+//! How cppl types are represented in C, and the definitions and helper functions each of them
+//! needs.
+//!
+//! Numbers, `Bool`s and `Char`s are C's fixed size types and objects are structs laid out like
+//! `compiled_idea.md` describes. Everything else is a pointer: strings and slices point to their
+//! length and contents, interfaces to a box starting with a vtable, functions to an environment
+//! starting with the function to call and enums to a box starting with which variant it is.
+//!
+//! Definitions and helpers are only written out the first time a type needs them.
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+use cppl_error::Location;
+use cppl_ast::refined::*;
+use crate::translate::*;
+
+
+/// Every C keyword and name the prelude or the C library has that a variable could hide.
+const RESERVED:&[&str]=&[
+    "auto","break","case","char","const","continue","default","do","double","else","enum",
+    "extern","float","for","goto","if","inline","int","long","register","restrict","return",
+    "short","signed","sizeof","static","struct","switch","typedef","union","unsigned","void",
+    "volatile","while","bool","true","false","NULL","main","stdin","stdout","stderr","errno",
+    "EOF","fmod","fmodf","isnan","memcpy","malloc","free","exit","size_t","int8_t","int16_t",
+    "int32_t","int64_t","uint8_t","uint16_t","uint32_t","uint64_t","INT64_MIN","INT64_MAX",
+    "UINT64_MAX","self","env","object",
+];
+
+
+/// The C type of a value.
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+pub(crate) enum CType {
+    Int,
+    Uint,
+    Byte,
+    Float,
+    DoubleFloat,
+    Bool,
+    Char,
+    String,
+    Slice(Box<CType>),
+    /// An index into `Types::objects`.
+    Object(usize),
+    /// The scope of the interface.
+    Interface(Scope),
+    /// An index into `Types::functions`.
+    Function(usize),
+    /// The scope of the enum.
+    Enum(Scope),
+    /// References and pointers, which are the same thing in C.
+    Ref {
+        inner:Box<CType>,
+        mutable:bool,
+    },
+}
+impl CType {
+    pub fn is_integer(&self)->bool {
+        matches!(self,CType::Int|CType::Uint|CType::Byte)
+    }
+    pub fn is_float(&self)->bool {
+        matches!(self,CType::Float|CType::DoubleFloat)
+    }
+    pub fn is_number(&self)->bool {
+        self.is_integer()||self.is_float()
+    }
+    /// The item type of a field holding one of these, see `compiled_idea.md`.
+    fn item_type(&self)->u8 {
+        match self {
+            CType::Object(_)=>0,
+            CType::Bool=>1,
+            CType::Char=>2,
+            CType::Byte=>3,
+            CType::Int=>4,
+            CType::Uint=>5,
+            CType::Float=>6,
+            CType::DoubleFloat=>7,
+            CType::Ref{mutable:false,..}=>8,
+            CType::Ref{mutable:true,..}=>9,
+            CType::String|CType::Slice(_)|CType::Interface(_)|CType::Function(_)|CType::Enum(_)=>14,
+        }
+    }
+}
+/// A struct for objects with these fields, in this order.
+pub(crate) struct Object<'input> {
+    pub name:String,
+    pub fields:Vec<Field<'input>>,
+}
+pub(crate) struct Field<'input> {
+    pub name:&'input str,
+    /// The member holding the value.
+    pub member:String,
+    /// The member holding the header in front of the value.
+    pub header:String,
+    pub ty:CType,
+    pub flags:u8,
+}
+pub(crate) struct FunctionType {
+    pub name:String,
+    pub params:Vec<CType>,
+    pub ret:Option<CType>,
+}
+pub(crate) struct Interface<'input> {
+    pub name:String,
+    pub vtable:String,
+    /// The methods taking `this`, which are the rest of the vtable.
+    pub methods:Vec<Method<'input>>,
+    /// Whether the vtable starts with a function copying the value, which is needed when methods
+    /// can change it.
+    pub copy:bool,
+}
+pub(crate) struct Method<'input> {
+    pub name:&'input str,
+    pub member:String,
+    pub mutable:bool,
+    pub params:Vec<CType>,
+    pub ret:Option<CType>,
+}
+pub(crate) struct Enum {
+    pub name:String,
+    pub variants:Vec<Variant>,
+}
+pub(crate) struct Variant {
+    pub scope:Scope,
+    /// The struct of the variant, or the only value of it if it has no fields.
+    pub name:String,
+    /// The constant saying a value is this variant.
+    pub id:String,
+    pub fields:Vec<CType>,
+    /// The function making one, `None` if there are no fields.
+    pub constructor:Option<String>,
+}
+/// A value boxed into an interface.
+#[derive(Clone)]
+pub(crate) struct Boxed {
+    /// The struct holding the interface's header followed by the value.
+    pub name:String,
+    /// The function boxing a value.
+    pub function:String,
+    pub vtable:String,
+}
+/// Every type the program needs and the helpers made for them.
+#[derive(Default)]
+pub(crate) struct Types<'input> {
+    pub objects:Vec<Object<'input>>,
+    object_ids:HashMap<Vec<(&'input str,CType,u8)>,usize>,
+    pub functions:Vec<FunctionType>,
+    function_ids:HashMap<(Vec<CType>,Option<CType>),usize>,
+    pub interfaces:HashMap<Scope,Interface<'input>>,
+    pub enums:HashMap<Scope,Enum>,
+    slices:HashMap<CType,String>,
+    helpers:HashMap<(&'static str,CType),String>,
+    /// Functions converting objects and slices to other ones, by the type they convert from and
+    /// the one they convert to.
+    conversions:HashMap<(CType,CType),String>,
+    /// How values are boxed into interfaces, by interface and type of the value.
+    pub boxes:HashMap<(Scope,CType),Boxed>,
+    /// Named types being defined, to catch types that contain themselves.
+    defining:Vec<Scope>,
+}
+
+
+/// Names that are taken in one C scope, so generated ones never clash.
+#[derive(Default)]
+pub(crate) struct Names(HashSet<String>);
+impl Names {
+    pub fn new<'a>(taken:impl IntoIterator<Item=&'a str>)->Self {
+        Names(taken.into_iter().map(|name|name.to_string()).collect())
+    }
+    /// Names for variables, which have to stay clear of keywords and everything in the prelude.
+    pub fn locals()->Self {
+        Self::new(RESERVED.iter().copied())
+    }
+    /// `name` if it is free, otherwise `name` followed by the first number that makes it free.
+    pub fn unique(&mut self,name:&str)->String {
+        let mut candidate=name.to_string();
+        let mut n=2;
+        while self.0.contains(&candidate) {
+            candidate=format!("{}_{}",name,n);
+            n+=1;
+        }
+        self.0.insert(candidate.clone());
+        return candidate;
+    }
+}
+/// `name` made into a C identifier. Names starting like the prelude's don't stay that way.
+pub(crate) fn identifier(name:&str)->String {
+    let mut out=name.chars()
+        .map(|c|if c.is_ascii_alphanumeric()||c=='_' {c} else {'_'})
+        .collect::<String>();
+    if out.is_empty()||out.starts_with('_')||out.starts_with(|c:char|c.is_ascii_digit())||out.starts_with("cppl_") {
+        out.insert_str(0,"v_");
+    }
+    return out;
+}
+/// The 7 byte id of a field, the low 56 bits of the 64 bit FNV-1a hash of its name like the VM
+/// makes them.
+fn field_id(name:&str)->[u8;7] {
+    let mut hash:u64=0xcbf29ce484222325;
+    for byte in name.bytes() {
+        hash^=byte as u64;
+        hash=hash.wrapping_mul(0x100000001b3);
+    }
+    let mut id=[0;7];
+    id.copy_from_slice(&hash.to_le_bytes()[..7]);
+    return id;
+}
+/// Who can read and change a field, the same flags the VM uses.
+pub(crate) fn field_flags(public:Option<Visibility>,mutable:Option<Visibility>)->u8 {
+    match (public,mutable) {
+        (None,None)=>0,
+        (None,Some(_))=>1,
+        (Some(_),None)=>2,
+        // Public, but only mutable inside of the object.
+        (Some(_),Some(Visibility::Local))=>4,
+        (Some(_),Some(_))=>3,
+    }
+}
+/// `ty name` as C writes it, `name` can also be a function declarator.
+pub(crate) fn declare(ty:&str,name:&str)->String {
+    if ty.ends_with('*') {
+        format!("{}{}",ty,name)
+    } else {
+        format!("{} {}",ty,name)
+    }
+}
+
+
+impl<'a,'input> Translator<'a,'input> {
+    /// The C type values of type `ty` have. `span` is blamed if they can't be compiled.
+    pub fn ctype(&mut self,span:(Location,Location),ty:&Type<'input>)->TranslateResult<'input,CType> {
+        let scopes=self.scopes;
+        Ok(match ty {
+            Type::Int|Type::GenericNumber=>CType::Int,
+            Type::Uint=>CType::Uint,
+            Type::Byte=>CType::Byte,
+            Type::Float=>CType::Float,
+            Type::DoubleFloat|Type::GenericFloat=>CType::DoubleFloat,
+            Type::Bool=>CType::Bool,
+            Type::Char=>CType::Char,
+            Type::String=>CType::String,
+            Type::Slice{inner,..}=>{
+                let item=self.ctype(span,inner)?;
+                self.slice_type(item)
+            },
+            Type::Ref{inner,..}|Type::Pointer{inner,..}=>CType::Ref{inner:Box::new(self.ctype(span,inner)?),mutable:false},
+            Type::RefMut{inner,..}|Type::PointerMut{inner,..}=>CType::Ref{inner:Box::new(self.ctype(span,inner)?),mutable:true},
+            Type::Object{fields,..}=>{
+                let mut ctypes=Vec::with_capacity(fields.len());
+                for field in fields {
+                    let ty=self.ctype(span,&field.ty)?;
+                    ctypes.push((field.name,ty,field_flags(field.public,field.mutable)));
+                }
+                CType::Object(self.object_type(ctypes,None))
+            },
+            Type::FunctionSig{inner,..}=>{
+                let mut params=Vec::with_capacity(inner.params.normal.len());
+                for param in inner.params.normal.iter() {
+                    params.push(self.ctype(span,&param.ty)?);
+                }
+                let ret=match &inner.ret_type {
+                    Some(ty)=>Some(self.ctype(span,ty)?),
+                    None=>None,
+                };
+                self.function_type(params,ret)
+            },
+            Type::Named{generics,..} if !generics.is_empty()=>return self.unsupported(span,"generic types"),
+            Type::Named{path,..}=>match scopes.get(*path) {
+                Some(VarScopeItem::Type{params:Some(_),..})=>return self.unsupported(span,"generic types"),
+                Some(VarScopeItem::Type{ty,name,..})=>{
+                    if self.types.defining.contains(path) {
+                        return self.unsupported(span,format_args!("`{}`, which contains itself,",name));
+                    }
+                    self.types.defining.push(*path);
+                    let ctype=match ty {
+                        Type::Object{fields,..}=>{
+                            let mut ctypes=Vec::with_capacity(fields.len());
+                            let mut result=Ok(());
+                            for field in fields {
+                                match self.ctype(span,&field.ty) {
+                                    Ok(ty)=>ctypes.push((field.name,ty,field_flags(field.public,field.mutable))),
+                                    Err(err)=>{
+                                        result=Err(err);
+                                        break;
+                                    },
+                                }
+                            }
+                            result.map(|()|CType::Object(self.object_type(ctypes,Some(name))))
+                        },
+                        ty=>self.ctype(span,ty),
+                    };
+                    self.types.defining.pop();
+                    ctype?
+                },
+                Some(VarScopeItem::Interface{params:Some(_),..}|VarScopeItem::Enum{params:Some(_),..})=>return self.unsupported(span,"generic types"),
+                Some(VarScopeItem::Interface{..})=>self.interface_type(span,*path)?,
+                Some(VarScopeItem::Enum{..})=>self.enum_type(span,*path)?,
+                Some(VarScopeItem::EnumVariant{parent_scope,..})=>self.enum_type(span,*parent_scope)?,
+                _=>return self.unsupported(span,format_args!("values of type `{}`",scopes.type_name(ty))),
+            },
+            _=>return self.unsupported(span,format_args!("values of type `{}`",scopes.type_name(ty))),
+        })
+    }
+    /// The C type of a value of type `ty`.
+    pub fn c_type(&self,ty:&CType)->String {
+        match ty {
+            CType::Int=>"int64_t".into(),
+            CType::Uint=>"uint64_t".into(),
+            CType::Byte=>"uint8_t".into(),
+            CType::Float=>"float".into(),
+            CType::DoubleFloat=>"double".into(),
+            CType::Bool=>"bool".into(),
+            CType::Char=>"uint32_t".into(),
+            CType::String=>"cppl_string *".into(),
+            CType::Slice(item)=>format!("{} *",self.types.slices[&**item]),
+            CType::Object(i)=>self.types.objects[*i].name.clone(),
+            CType::Interface(scope)=>format!("{} *",self.types.interfaces[scope].name),
+            CType::Function(i)=>format!("{} *",self.types.functions[*i].name),
+            CType::Enum(scope)=>format!("{} *",self.types.enums[scope].name),
+            CType::Ref{inner,..}=>{
+                let inner=self.c_type(inner);
+                if inner.ends_with('*') {
+                    format!("{}*",inner)
+                } else {
+                    format!("{} *",inner)
+                }
+            },
+        }
+    }
+    /// `ty name` in C.
+    pub fn declare(&self,ty:&CType,name:&str)->String {
+        declare(&self.c_type(ty),name)
+    }
+    /// A function returning `ret`, or nothing.
+    pub fn declare_function(&self,ret:Option<&CType>,declarator:&str)->String {
+        match ret {
+            Some(ret)=>self.declare(ret,declarator),
+            None=>format!("void {}",declarator),
+        }
+    }
+    /// The name of a type as cppl would write it, for comments and errors.
+    pub fn type_name(&self,ty:&CType)->String {
+        match ty {
+            CType::Int=>"Int".into(),
+            CType::Uint=>"Uint".into(),
+            CType::Byte=>"Byte".into(),
+            CType::Float=>"Float".into(),
+            CType::DoubleFloat=>"DoubleFloat".into(),
+            CType::Bool=>"Bool".into(),
+            CType::Char=>"Char".into(),
+            CType::String=>"String".into(),
+            CType::Slice(item)=>format!("[{}]",self.type_name(item)),
+            CType::Object(i)=>{
+                let fields=self.types.objects[*i].fields.iter()
+                    .map(|field|{
+                        let flags=match field.flags {
+                            0=>"",
+                            1=>"mut ",
+                            2=>"pub ",
+                            4=>"pub mut(local) ",
+                            _=>"pub mut ",
+                        };
+                        format!("{}{}:{}",flags,field.name,self.type_name(&field.ty))
+                    })
+                    .collect::<Vec<_>>();
+                format!("{{{}}}",fields.join(","))
+            },
+            CType::Interface(scope)|CType::Enum(scope)=>self.scopes.get(*scope).and_then(|i|i.name()).unwrap_or("?").into(),
+            CType::Function(i)=>{
+                let function=&self.types.functions[*i];
+                let params=function.params.iter().map(|p|self.type_name(p)).collect::<Vec<_>>().join(",");
+                match &function.ret {
+                    Some(ret)=>format!("fn[{}]:{}",params,self.type_name(ret)),
+                    None=>format!("fn[{}]",params),
+                }
+            },
+            CType::Ref{inner,mutable:false}=>format!("&{}",self.type_name(inner)),
+            CType::Ref{inner,mutable:true}=>format!("&mut {}",self.type_name(inner)),
+        }
+    }
+    /// A short name for a type, used to name the types and helpers made for it.
+    pub fn short_name(&self,ty:&CType)->String {
+        match ty {
+            CType::Int=>"int".into(),
+            CType::Uint=>"uint".into(),
+            CType::Byte=>"byte".into(),
+            CType::Float=>"float".into(),
+            CType::DoubleFloat=>"double".into(),
+            CType::Bool=>"bool".into(),
+            CType::Char=>"char".into(),
+            CType::String=>"string".into(),
+            CType::Slice(item)=>format!("slice_{}",self.short_name(item)),
+            CType::Ref{inner,..}=>format!("ref_{}",self.short_name(inner)),
+            _=>self.base_name(ty).trim_start_matches("cppl_").into(),
+        }
+    }
+    /// The name of the struct behind a type, which helpers for it are named after.
+    fn base_name(&self,ty:&CType)->String {
+        match ty {
+            CType::Slice(item)=>self.types.slices[&**item].clone(),
+            CType::Object(i)=>self.types.objects[*i].name.clone(),
+            CType::Interface(scope)=>self.types.interfaces[scope].name.clone(),
+            CType::Function(i)=>self.types.functions[*i].name.clone(),
+            CType::Enum(scope)=>self.types.enums[scope].name.clone(),
+            _=>format!("cppl_{}",self.short_name(ty)),
+        }
+    }
+    /// A value of type `ty` that is all zeros, for variables that are set later.
+    pub fn zero(&self,ty:&CType)->String {
+        match ty {
+            CType::Bool=>"false".into(),
+            CType::Float=>"0.0f".into(),
+            CType::DoubleFloat=>"0.0".into(),
+            CType::Object(_)=>"{0}".into(),
+            ty if ty.is_integer()||*ty==CType::Char=>"0".into(),
+            _=>"NULL".into(),
+        }
+    }
+    /// Adds a function to the output, with its prototype.
+    pub fn define_function(&mut self,signature:String,body:&[String]) {
+        self.prototypes.push(format!("{};",signature));
+        let mut out=format!("{} {{\n",signature);
+        for line in body {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push('}');
+        self.definitions.push(out);
+    }
+    /// The struct for objects with these fields, made if there isn't one yet. `name` is the name
+    /// of the type if it has one.
+    pub fn object_type(&mut self,fields:Vec<(&'input str,CType,u8)>,name:Option<&str>)->usize {
+        if let Some(i)=self.types.object_ids.get(&fields) {
+            return *i;
+        }
+        let name=match name {
+            Some(name)=>self.names.unique(&format!("cppl_{}",identifier(name))),
+            None=>self.names.unique("cppl_object"),
+        };
+        let mut members=Names::new(["object_size","field_count","object"]);
+        let fields=fields.iter()
+            .map(|(field,ty,flags)|{
+                let member=members.unique(&identifier(field));
+                Field {
+                    name:field,
+                    header:members.unique(&format!("{}_field",member)),
+                    member,
+                    ty:ty.clone(),
+                    flags:*flags,
+                }
+            })
+            .collect::<Vec<_>>();
+        let i=self.types.objects.len();
+        self.types.objects.push(Object{name:name.clone(),fields});
+        self.types.object_ids.insert(fields_key(&self.types.objects[i]),i);
+        let mut out=format!("/* {} */\ntypedef struct {{\n    uint64_t object_size;\n    uint16_t field_count;\n",self.type_name(&CType::Object(i)));
+        for field in self.types.objects[i].fields.iter() {
+            out.push_str(&format!("    cppl_field {};\n    {};\n",field.header,self.declare(&field.ty,&field.member)));
+        }
+        out.push_str(&format!("}} {};",name));
+        self.typedefs.push(out);
+        return i;
+    }
+    /// The slice of `item`s, defining it if it isn't yet.
+    pub fn slice_type(&mut self,item:CType)->CType {
+        if !self.types.slices.contains_key(&item) {
+            if item==CType::String {
+                // The prelude has the slice of strings for the arguments.
+                self.types.slices.insert(item.clone(),"cppl_slice_string".into());
+            } else {
+                let name=self.names.unique(&format!("cppl_slice_{}",self.short_name(&item)));
+                self.typedefs.push(format!("typedef struct {{\n    uint64_t len;\n    {};\n}} {};",self.declare(&item,"items[]"),name));
+                self.types.slices.insert(item.clone(),name);
+            }
+        }
+        return CType::Slice(Box::new(item));
+    }
+    /// The function type with these parameters and return type, defining it if it isn't yet.
+    pub fn function_type(&mut self,params:Vec<CType>,ret:Option<CType>)->CType {
+        let key=(params,ret);
+        if let Some(i)=self.types.function_ids.get(&key) {
+            return CType::Function(*i);
+        }
+        let (params,ret)=key;
+        let mut name=String::from("cppl_fn");
+        for param in params.iter() {
+            name.push('_');
+            name.push_str(&self.short_name(param));
+        }
+        if let Some(ret)=&ret {
+            name.push_str("_to_");
+            name.push_str(&self.short_name(ret));
+        }
+        let name=self.names.unique(&name);
+        let mut c_params=vec![format!("{} *",name)];
+        c_params.extend(params.iter().map(|p|self.c_type(p)));
+        let call=self.declare_function(ret.as_ref(),&format!("(*call)({})",c_params.join(", ")));
+        self.typedefs.push(format!("typedef struct {0} {0};\nstruct {0} {{\n    {1};\n}};",name,call));
+        let i=self.types.functions.len();
+        self.types.functions.push(FunctionType{name,params:params.clone(),ret:ret.clone()});
+        self.types.function_ids.insert((params,ret),i);
+        return CType::Function(i);
+    }
+    /// The parameter types and return type of a method, without `this`. Whether it takes `this`
+    /// and whether it is `mut this` comes first.
+    #[allow(clippy::type_complexity)]
+    pub fn method_signature(&mut self,function:Scope)->TranslateResult<'input,(Option<bool>,Vec<CType>,Option<CType>)> {
+        let scopes=self.scopes;
+        let Some(item@VarScopeItem::Function{params,ret_type,..})=scopes.get(function) else {
+            return self.error(Default::default(),"Internal compiler error: not a function");
+        };
+        let span=item.def_span().unwrap_or_default();
+        let mut this=None;
+        let mut ctypes=Vec::with_capacity(params.len());
+        for (i,param) in params.iter().enumerate() {
+            match scopes.get(*param) {
+                Some(VarScopeItem::Parameter{name:"this",mutable,..}) if i==0=>this=Some(*mutable),
+                Some(param)=>ctypes.push(self.ctype(param.def_span().unwrap_or(span),param.var_type().unwrap_or(&Type::Unknown))?),
+                None=>{},
+            }
+        }
+        let ret=match ret_type {
+            Some(ty)=>Some(self.ctype(span,ty)?),
+            None=>None,
+        };
+        return Ok((this,ctypes,ret));
+    }
+    /// The type of values of the interface `scope`, defining it and its vtable if they aren't yet.
+    fn interface_type(&mut self,span:(Location,Location),scope:Scope)->TranslateResult<'input,CType> {
+        if self.types.interfaces.contains_key(&scope) {
+            return Ok(CType::Interface(scope));
+        }
+        let scopes=self.scopes;
+        let Some(VarScopeItem::Interface{name:interface_name,statements,..})=scopes.get(scope) else {
+            return self.error(span,"Internal compiler error: not an interface");
+        };
+        let name=self.names.unique(&format!("cppl_{}",identifier(interface_name)));
+        let vtable=self.names.unique(&format!("{}_vtable",name));
+        // Methods can take the interface, so it is declared before they are.
+        self.typedefs.push(format!("typedef struct {0} {0};",name));
+        self.types.interfaces.insert(scope,Interface{name:name.clone(),vtable:vtable.clone(),methods:Vec::new(),copy:false});
+        let mut members=Names::new(["copy"]);
+        let mut methods=Vec::new();
+        for stmt in statements.iter() {
+            let method=match stmt {
+                Statement::FunctionSig{sig,start,end} if !matches!(sig.params.method_type,MethodType::None)=>{
+                    let mut params=Vec::with_capacity(sig.params.normal.len());
+                    for param in sig.params.normal.iter() {
+                        params.push(self.ctype((param.start,param.end),&param.ty)?);
+                    }
+                    let ret=match &sig.ret_type {
+                        Some(ty)=>Some(self.ctype((*start,*end),ty)?),
+                        None=>None,
+                    };
+                    Method {
+                        name:sig.name,
+                        member:members.unique(&identifier(sig.name)),
+                        mutable:matches!(sig.params.method_type,MethodType::ThisMut),
+                        params,
+                        ret,
+                    }
+                },
+                Statement::FunctionDef{def,..}=>{
+                    let (Some(mutable),params,ret)=self.method_signature(*def)? else {continue};
+                    let method_name=scopes.get(*def).and_then(|f|f.name()).unwrap_or("?");
+                    Method {
+                        name:method_name,
+                        member:members.unique(&identifier(method_name)),
+                        mutable,
+                        params,
+                        ret,
+                    }
+                },
+                _=>continue,
+            };
+            methods.push(method);
+        }
+        let copy=methods.iter().any(|m|m.mutable);
+        let mut out=String::from("typedef struct {\n");
+        if copy {
+            out.push_str(&format!("    {0} *(*copy)({0} *this);\n",name));
+        }
+        for method in methods.iter() {
+            let mut params=vec![format!("{} *this",name)];
+            params.extend(method.params.iter().map(|p|self.c_type(p)));
+            out.push_str(&format!("    {};\n",self.declare_function(method.ret.as_ref(),&format!("(*{})({})",method.member,params.join(", ")))));
+        }
+        out.push_str(&format!("}} {};\n/* interface {} */\nstruct {} {{\n    const {} *vtable;\n}};",vtable,interface_name,name,vtable));
+        self.typedefs.push(out);
+        if let Some(interface)=self.types.interfaces.get_mut(&scope) {
+            interface.methods=methods;
+            interface.copy=copy;
+        }
+        return Ok(CType::Interface(scope));
+    }
+    /// The type of values of the enum `scope`, defining it and its variants if they aren't yet.
+    fn enum_type(&mut self,span:(Location,Location),scope:Scope)->TranslateResult<'input,CType> {
+        if self.types.enums.contains_key(&scope) {
+            return Ok(CType::Enum(scope));
+        }
+        let scopes=self.scopes;
+        let Some(VarScopeItem::Enum{name:enum_name,variants:variant_types,..})=scopes.get(scope) else {
+            return self.error(span,"Internal compiler error: not an enum");
+        };
+        let name=self.names.unique(&format!("cppl_{}",identifier(enum_name)));
+        // Variants can hold the enum, so the header is defined before they are.
+        self.typedefs.push(format!("/* enum {} */\ntypedef struct {{\n    uint64_t variant;\n}} {};",enum_name,name));
+        self.types.enums.insert(scope,Enum{name:name.clone(),variants:Vec::new()});
+        let mut variants=Vec::with_capacity(variant_types.len());
+        for ty in variant_types {
+            let Type::Named{path,..}=ty else {
+                return self.unsupported(ty.span().unwrap_or(span),"enum variants that aren't named");
+            };
+            let Some(VarScopeItem::EnumVariant{name:variant_name,fields:field_types,..})=scopes.get(*path) else {
+                return self.unsupported(ty.span().unwrap_or(span),"enum variants that aren't named");
+            };
+            let mut fields=Vec::with_capacity(field_types.len());
+            for field in field_types {
+                fields.push(self.ctype(field.span().unwrap_or(span),field)?);
+            }
+            let variant=self.names.unique(&format!("{}_{}",name,identifier(variant_name)));
+            variants.push(Variant {
+                scope:*path,
+                id:self.names.unique(&format!("{}_variant",variant)),
+                constructor:if fields.is_empty() {None} else {Some(self.names.unique(&format!("{}_new",variant)))},
+                name:variant,
+                fields,
+            });
+        }
+        let ids=variants.iter().map(|v|format!("    {}",v.id)).collect::<Vec<_>>();
+        self.typedefs.push(format!("enum {{\n{}\n}};",ids.join(",\n")));
+        for variant in variants.iter() {
+            let Some(constructor)=&variant.constructor else {
+                // Variants without fields are all the same, so they share one value.
+                self.data.push(format!("static {} {} = {{{}}};",name,variant.name,variant.id));
+                continue;
+            };
+            let mut out=format!("typedef struct {{\n    {} base;\n",name);
+            let mut params=Vec::with_capacity(variant.fields.len());
+            let mut body=vec![
+                format!("    {0} *variant = cppl_allocate(sizeof *variant);",variant.name),
+                format!("    variant->base.variant = {};",variant.id),
+            ];
+            for (i,field) in variant.fields.iter().enumerate() {
+                out.push_str(&format!("    {};\n",self.declare(field,&format!("field_{}",i))));
+                params.push(self.declare(field,&format!("field_{}",i)));
+                body.push(format!("    variant->field_{0} = field_{0};",i));
+            }
+            out.push_str(&format!("}} {};",variant.name));
+            self.typedefs.push(out);
+            body.push("    return &variant->base;".into());
+            let signature=format!("static {} *{}({})",name,constructor,params.join(", "));
+            self.define_function(signature,&body);
+        }
+        if let Some(e)=self.types.enums.get_mut(&scope) {
+            e.variants=variants;
+        }
+        return Ok(CType::Enum(scope));
+    }
+    /// A helper that was already made, or a new name for it.
+    fn helper(&mut self,kind:&'static str,ty:&CType)->Result<String,String> {
+        if let Some(name)=self.types.helpers.get(&(kind,ty.clone())) {
+            return Ok(name.clone());
+        }
+        let name=self.names.unique(&format!("{}_{}",self.base_name(ty),kind));
+        self.types.helpers.insert((kind,ty.clone()),name.clone());
+        return Err(name);
+    }
+    /// Whether copying a value has to copy more than what C copies, because it can be changed
+    /// through a pointer.
+    pub fn needs_copy(&self,ty:&CType)->bool {
+        match ty {
+            CType::Slice(_)=>true,
+            CType::Object(i)=>self.types.objects[*i].fields.iter().any(|f|self.needs_copy(&f.ty)),
+            CType::Interface(scope)=>self.types.interfaces[scope].copy,
+            _=>false,
+        }
+    }
+    /// A copy of `value` that doesn't share anything that can be changed with it, since every
+    /// value is its own in cppl.
+    pub fn copy(&mut self,ty:&CType,value:&str)->String {
+        if !self.needs_copy(ty) {
+            return value.to_string();
+        }
+        if let CType::Interface(_)=ty {
+            return format!("{0}->vtable->copy({0})",value);
+        }
+        let name=match self.helper("copy",ty) {
+            Ok(name)=>name,
+            Err(name)=>{
+                let c_type=self.c_type(ty);
+                let body=match ty {
+                    CType::Slice(item)=>{
+                        let item=(**item).clone();
+                        let new=self.slice_new(ty);
+                        let mut body=vec![format!("    {} = {}(from->len);",declare(&c_type,"slice"),new)];
+                        if self.needs_copy(&item) {
+                            let copy=self.copy(&item,"from->items[i]");
+                            body.push("    for (uint64_t i = 0; i < from->len; i++) {".into());
+                            body.push(format!("        slice->items[i] = {};",copy));
+                            body.push("    }".into());
+                        } else {
+                            body.push("    memcpy(slice->items, from->items, from->len * sizeof from->items[0]);".into());
+                        }
+                        body.push("    return slice;".into());
+                        self.define_function(format!("static {}({})",declare(&c_type,&name),declare(&c_type,"from")),&body);
+                        return format!("{}({})",name,value);
+                    },
+                    CType::Object(i)=>{
+                        let fields=self.types.objects[*i].fields.iter()
+                            .filter(|f|self.needs_copy(&f.ty))
+                            .map(|f|(f.member.clone(),f.ty.clone()))
+                            .collect::<Vec<_>>();
+                        let mut body=Vec::with_capacity(fields.len()+1);
+                        for (member,ty) in fields {
+                            let copy=self.copy(&ty,&format!("object.{}",member));
+                            body.push(format!("    object.{} = {};",member,copy));
+                        }
+                        body.push("    return object;".into());
+                        body
+                    },
+                    _=>Vec::new(),
+                };
+                self.define_function(format!("static {}({})",declare(&c_type,&name),declare(&c_type,"object")),&body);
+                name
+            },
+        };
+        return format!("{}({})",name,value);
+    }
+    /// Whether two values of type `ty` are equal, like `=`. The error says what can't be compared.
+    pub fn equal(&mut self,ty:&CType,a:&str,b:&str)->Result<String,String> {
+        let helper_body=match ty {
+            CType::String=>return Ok(format!("cppl_string_equal({}, {})",a,b)),
+            CType::Ref{inner,..}=>return self.equal(inner,&format!("*{}",a),&format!("*{}",b)),
+            CType::Interface(_)=>return Err("comparing interfaces".into()),
+            CType::Function(_)=>return Err("comparing functions".into()),
+            CType::Object(_)|CType::Slice(_)|CType::Enum(_)=>match self.helper("equal",ty) {
+                Ok(name)=>return Ok(format!("{}({}, {})",name,a,b)),
+                Err(name)=>name,
+            },
+            _=>return Ok(format!("{} == {}",a,b)),
+        };
+        let name=helper_body;
+        let c_type=self.c_type(ty);
+        let body=match ty {
+            CType::Object(i)=>{
+                let fields=self.types.objects[*i].fields.iter().map(|f|(f.member.clone(),f.ty.clone())).collect::<Vec<_>>();
+                let mut checks=Vec::with_capacity(fields.len());
+                for (member,ty) in fields {
+                    checks.push(self.equal(&ty,&format!("a.{}",member),&format!("b.{}",member))?);
+                }
+                if checks.is_empty() {
+                    checks.push("true".into());
+                }
+                vec![format!("    return {};",checks.join(" && "))]
+            },
+            CType::Slice(item)=>{
+                let item=self.equal(item,"a->items[i]","b->items[i]")?;
+                vec![
+                    "    if (a->len != b->len) {".into(),
+                    "        return false;".into(),
+                    "    }".into(),
+                    "    for (uint64_t i = 0; i < a->len; i++) {".into(),
+                    format!("        if (!({})) {{",item),
+                    "            return false;".into(),
+                    "        }".into(),
+                    "    }".into(),
+                    "    return true;".into(),
+                ]
+            },
+            CType::Enum(scope)=>{
+                let variants=self.types.enums[scope].variants.iter()
+                    .filter(|v|!v.fields.is_empty())
+                    .map(|v|(v.name.clone(),v.id.clone(),v.fields.clone()))
+                    .collect::<Vec<_>>();
+                let mut body=vec![
+                    "    if (a->variant != b->variant) {".into(),
+                    "        return false;".into(),
+                    "    }".into(),
+                    "    switch (a->variant) {".into(),
+                ];
+                for (variant,id,fields) in variants {
+                    let mut checks=Vec::with_capacity(fields.len());
+                    for (i,field) in fields.iter().enumerate() {
+                        checks.push(self.equal(field,&format!("(({} *)a)->field_{}",variant,i),&format!("(({} *)b)->field_{}",variant,i))?);
+                    }
+                    body.push(format!("    case {}:",id));
+                    body.push(format!("        return {};",checks.join(" && ")));
+                }
+                body.push("    default:".into());
+                body.push("        return true;".into());
+                body.push("    }".into());
+                body
+            },
+            _=>Vec::new(),
+        };
+        self.define_function(format!("static bool {}({}, {})",name,declare(&c_type,"a"),declare(&c_type,"b")),&body);
+        return Ok(format!("{}({}, {})",name,a,b));
+    }
+    /// The function making a new object of type `object` from the values of its fields.
+    pub fn constructor(&mut self,object:usize)->String {
+        let ty=CType::Object(object);
+        let name=match self.helper("new",&ty) {
+            Ok(name)=>return name,
+            Err(name)=>name,
+        };
+        let Object{name:struct_name,fields}=&self.types.objects[object];
+        let mut params=Vec::with_capacity(fields.len());
+        let mut body=vec![
+            format!("    {} object;",struct_name),
+            "    object.object_size = sizeof object;".into(),
+            format!("    object.field_count = {};",fields.len()),
+        ];
+        for field in fields.iter() {
+            params.push(self.declare(&field.ty,&field.member));
+            let id=field_id(field.name).iter().map(|b|format!("0x{:02x}",b)).collect::<Vec<_>>().join(", ");
+            body.push(format!("    object.{} = (cppl_field){{{{{}}}, 0x{:02x}}};",field.header,id,field.ty.item_type()|field.flags<<5));
+            body.push(format!("    object.{0} = {0};",field.member));
+        }
+        body.push("    return object;".into());
+        let params=if params.is_empty() {"void".to_string()} else {params.join(", ")};
+        let signature=format!("static {} {}({})",struct_name,name,params);
+        self.define_function(signature,&body);
+        return name;
+    }
+    /// The function making a slice of type `ty` with room for `len` items.
+    pub fn slice_new(&mut self,ty:&CType)->String {
+        let name=match self.helper("new",ty) {
+            Ok(name)=>return name,
+            Err(name)=>name,
+        };
+        let c_type=self.c_type(ty);
+        let body=[
+            format!("    {} = cppl_allocate(sizeof *slice + len * sizeof slice->items[0]);",declare(&c_type,"slice")),
+            "    slice->len = len;".into(),
+            "    return slice;".into(),
+        ];
+        self.define_function(format!("static {}(uint64_t len)",declare(&c_type,&name)),&body);
+        return name;
+    }
+    /// The function copying the items `start..end` of a slice of type `ty` into a new one. The
+    /// range has to be checked before.
+    pub fn slice_range(&mut self,ty:&CType)->String {
+        let name=match self.helper("range",ty) {
+            Ok(name)=>return name,
+            Err(name)=>name,
+        };
+        let CType::Slice(item)=ty else {return name};
+        let item=(**item).clone();
+        let c_type=self.c_type(ty);
+        let new=self.slice_new(ty);
+        let mut body=vec![format!("    {} = {}(end - start);",declare(&c_type,"slice"),new)];
+        if self.needs_copy(&item) {
+            let copy=self.copy(&item,"from->items[i]");
+            body.push("    for (uint64_t i = start; i < end; i++) {".into());
+            body.push(format!("        slice->items[i - start] = {};",copy));
+            body.push("    }".into());
+        } else {
+            body.push("    memcpy(slice->items, from->items + start, (end - start) * sizeof from->items[0]);".into());
+        }
+        body.push("    return slice;".into());
+        self.define_function(format!("static {}({}, uint64_t start, uint64_t end)",declare(&c_type,&name),declare(&c_type,"from")),&body);
+        return name;
+    }
+    /// `value` of type `from` converted to `to` without any statements, like the interpreter
+    /// converts numbers and like values are given to interfaces. `None` if it can't be.
+    pub fn convert(&mut self,span:(Location,Location),value:&str,from:&CType,to:&CType)->TranslateResult<'input,Option<String>> {
+        Ok(Some(match (from,to) {
+            _ if from==to=>value.to_string(),
+            (CType::Ref{inner:a,..},CType::Ref{inner:b,..}) if a==b=>value.to_string(),
+            (from,CType::Int|CType::Uint|CType::Byte) if from.is_float()=>{
+                let function=match to {
+                    CType::Int=>"cppl_float_to_int",
+                    CType::Uint=>"cppl_float_to_uint",
+                    _=>"cppl_float_to_byte",
+                };
+                format!("{}({})",function,value)
+            },
+            (from,to) if from.is_number()&&to.is_number()=>format!("({}){}",self.c_type(to),value),
+            (_,CType::Interface(scope))=>{
+                let from_value=self.boxing(span,*scope,from)?;
+                format!("{}({})",from_value,value)
+            },
+            (CType::Object(a),CType::Object(b))=>return self.convert_object(span,value,*a,*b),
+            (CType::Slice(_),CType::Slice(_))=>return self.convert_slice(span,value,from,to),
+            _=>return Ok(None),
+        }))
+    }
+    /// `value` converted to another object type with the same fields, by a function made for it.
+    fn convert_object(&mut self,span:(Location,Location),value:&str,from:usize,to:usize)->TranslateResult<'input,Option<String>> {
+        let key=(CType::Object(from),CType::Object(to));
+        if let Some(name)=self.types.conversions.get(&key) {
+            return Ok(Some(format!("{}({})",name,value)));
+        }
+        let targets=self.types.objects[to].fields.iter().map(|f|(f.name,f.ty.clone())).collect::<Vec<_>>();
+        let mut args=Vec::with_capacity(targets.len());
+        for (field,ty) in targets {
+            let Some(source)=self.types.objects[from].fields.iter().find(|f|f.name==field) else {
+                return Ok(None);
+            };
+            let (member,source_ty)=(source.member.clone(),source.ty.clone());
+            let Some(arg)=self.convert(span,&format!("from.{}",member),&source_ty,&ty)? else {
+                return Ok(None);
+            };
+            args.push(arg);
+        }
+        let constructor=self.constructor(to);
+        let name=self.names.unique(&format!("{}_from_{}",self.types.objects[to].name,self.short_name(&key.0)));
+        let signature=format!("static {} {}({})",self.types.objects[to].name,name,self.declare(&key.0,"from"));
+        self.define_function(signature,&[format!("    return {}({});",constructor,args.join(", "))]);
+        self.types.conversions.insert(key,name.clone());
+        return Ok(Some(format!("{}({})",name,value)));
+    }
+    /// `value` converted to a slice of other items, by a function made for it.
+    fn convert_slice(&mut self,span:(Location,Location),value:&str,from:&CType,to:&CType)->TranslateResult<'input,Option<String>> {
+        let key=(from.clone(),to.clone());
+        if let Some(name)=self.types.conversions.get(&key) {
+            return Ok(Some(format!("{}({})",name,value)));
+        }
+        let (CType::Slice(a),CType::Slice(b))=(from,to) else {return Ok(None)};
+        let Some(item)=self.convert(span,"from->items[i]",a,b)? else {
+            return Ok(None);
+        };
+        let new=self.slice_new(to);
+        let c_type=self.c_type(to);
+        let name=self.names.unique(&format!("{}_from_{}",self.base_name(to),self.short_name(from)));
+        let body=[
+            format!("    {} = {}(from->len);",declare(&c_type,"slice"),new),
+            "    for (uint64_t i = 0; i < from->len; i++) {".into(),
+            format!("        slice->items[i] = {};",item),
+            "    }".into(),
+            "    return slice;".into(),
+        ];
+        self.define_function(format!("static {}({})",declare(&c_type,&name),self.declare(from,"from")),&body);
+        self.types.conversions.insert(key,name.clone());
+        return Ok(Some(format!("{}({})",name,value)));
+    }
+    /// The function boxing values of type `ty` into the interface `interface`. The box, its
+    /// vtable and the functions in it are made the first time.
+    pub fn boxing(&mut self,span:(Location,Location),interface:Scope,ty:&CType)->TranslateResult<'input,String> {
+        Ok(self.boxed(span,interface,ty)?.function)
+    }
+    /// How values of type `ty` are boxed into the interface `interface`.
+    pub fn boxed(&mut self,span:(Location,Location),interface:Scope,ty:&CType)->TranslateResult<'input,Boxed> {
+        if let Some(boxed)=self.types.boxes.get(&(interface,ty.clone())) {
+            return Ok(boxed.clone());
+        }
+        let scopes=self.scopes;
+        let mut statements=None;
+        for scope in scopes.impls() {
+            let Some(VarScopeItem::Impl{interface:Some(Type::Named{path,..}),for_ty,params:None,statements:impl_statements,..})=scopes.get(scope) else {
+                continue;
+            };
+            if *path==interface&&self.ctype(span,for_ty).ok().as_ref()==Some(ty) {
+                statements=Some(impl_statements);
+                break;
+            }
+        }
+        let Some(statements)=statements else {
+            return self.unsupported(span,format_args!("converting `{}` to `{}`",self.type_name(ty),self.type_name(&CType::Interface(interface))));
+        };
+        let (interface_name,vtable_type,copy)={
+            let i=&self.types.interfaces[&interface];
+            (i.name.clone(),i.vtable.clone(),i.copy)
+        };
+        let methods=self.types.interfaces[&interface].methods.iter()
+            .map(|m|(m.name,m.member.clone(),m.params.clone(),m.ret.clone()))
+            .collect::<Vec<_>>();
+        let name=self.names.unique(&format!("{}_{}",interface_name,self.short_name(ty)));
+        let boxed=Boxed {
+            function:self.names.unique(&format!("{}_from_{}",interface_name,self.short_name(ty))),
+            vtable:self.names.unique(&format!("{}_vtable",name)),
+            name:name.clone(),
+        };
+        // Methods can box values again, so it is known before they are made.
+        self.types.boxes.insert((interface,ty.clone()),boxed.clone());
+        self.typedefs.push(format!("typedef struct {{\n    {} base;\n    {};\n}} {};",interface_name,self.declare(ty,"value"),name));
+        let mut entries=Vec::with_capacity(methods.len()+1);
+        if copy {
+            let thunk=self.names.unique(&format!("{}_copy",name));
+            let copied=self.copy(ty,&format!("(({} *)this)->value",name));
+            self.define_function(format!("static {0} *{1}({0} *this)",interface_name,thunk),&[format!("    return {}({});",boxed.function,copied)]);
+            entries.push(format!(".copy = {}",thunk));
+        }
+        for (method,member,params,ret) in methods {
+            let function=match scopes.find_function(statements,method) {
+                Some(function)=>function,
+                None=>{
+                    // Methods the impl doesn't have are the interface's default ones, which
+                    // already take the interface.
+                    let Some(VarScopeItem::Interface{statements,..})=scopes.get(interface) else {continue};
+                    let Some(function)=scopes.find_function(statements,method) else {continue};
+                    let signature=self.function(function)?;
+                    entries.push(format!(".{} = {}",member,signature.name));
+                    continue;
+                },
+            };
+            let signature=self.function(function)?;
+            let thunk=self.names.unique(&format!("{}_{}",name,member));
+            let mut c_params=vec![format!("{} *this",interface_name)];
+            let mut args=Vec::with_capacity(params.len()+1);
+            match &signature.this {
+                Some((_,true))=>args.push(format!("&(({} *)this)->value",name)),
+                Some((_,false))=>args.push(format!("(({} *)this)->value",name)),
+                None=>{},
+            }
+            for (i,(param,to)) in params.iter().zip(signature.params.iter()).enumerate() {
+                let arg=format!("arg_{}",i);
+                c_params.push(self.declare(param,&arg));
+                match self.convert(span,&arg,param,to)? {
+                    Some(arg)=>args.push(arg),
+                    None=>return self.unsupported(span,format_args!("implementing `{}` with these types",method)),
+                }
+            }
+            let call=format!("{}({})",signature.name,args.join(", "));
+            let body=match (&ret,&signature.ret) {
+                (Some(ret),Some(from))=>match self.convert(span,&call,from,ret)? {
+                    Some(value)=>format!("    return {};",value),
+                    None=>return self.unsupported(span,format_args!("implementing `{}` with these types",method)),
+                },
+                _=>format!("    {};",call),
+            };
+            self.define_function(format!("static {}",self.declare_function(ret.as_ref(),&format!("{}({})",thunk,c_params.join(", ")))),&[body]);
+            entries.push(format!(".{} = {}",member,thunk));
+        }
+        self.data.push(format!("static const {} {} = {{{}}};",vtable_type,boxed.vtable,entries.join(", ")));
+        let body=[
+            format!("    {0} *boxed = cppl_allocate(sizeof *boxed);",name),
+            format!("    boxed->base.vtable = &{};",boxed.vtable),
+            "    boxed->value = value;".into(),
+            "    return &boxed->base;".into(),
+        ];
+        self.define_function(format!("static {} *{}({})",interface_name,boxed.function,self.declare(ty,"value")),&body);
+        return Ok(boxed);
+    }
+}
+
+
+/// The key objects are found by.
+fn fields_key<'input>(object:&Object<'input>)->Vec<(&'input str,CType,u8)> {
+    object.fields.iter().map(|f|(f.name,f.ty.clone(),f.flags)).collect()
+}
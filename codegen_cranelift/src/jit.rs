@@ -0,0 +1,210 @@
+//! Compiling to memory and running the result right away, either the whole program or the
+//! functions the interpreter can hand off.
+use std::collections::HashMap;
+use cppl_error::{
+    Location,
+    Error,
+    ErrorLevel,
+};
+use cppl_ast::refined::{
+    Scopes,
+    Scope,
+    VarScopeItem,
+};
+use cppl_interp::{
+    Natives,
+    Value,
+};
+use cranelift_codegen::settings::{
+    self,
+    Configurable,
+};
+use cranelift_jit::{
+    JITBuilder,
+    JITModule,
+};
+use cranelift_module::default_libcall_names;
+use crate::{
+    translate::*,
+    runtime,
+};
+
+
+/// A program compiled to native code in memory.
+pub struct Program<'input> {
+    filename:&'input str,
+    /// Only `None` while it is being freed.
+    module:Option<JITModule>,
+    entry:*const u8,
+    errors:Vec<(Location,Location,String)>,
+}
+impl Drop for Program<'_> {
+    fn drop(&mut self) {
+        if let Some(module)=self.module.take() {
+            // SAFETY: `entry` is the only pointer into the module and it is never given out.
+            unsafe {module.free_memory()};
+        }
+    }
+}
+/// A module for compiled code to be put in, which can call the runtime.
+fn new_module<'input>(filename:&'input str)->Result<JITModule,Error<'input,String>> {
+    let error=|reason:String|Error::new_compile(filename,Location::default(),Location::default(),reason);
+    let mut flags=settings::builder();
+    // The code and the functions it calls can be anywhere in memory.
+    flags.set("use_colocated_libcalls","false").map_err(|e|error(format!("Internal compiler error: {}",e)))?;
+    flags.set("is_pic","false").map_err(|e|error(format!("Internal compiler error: {}",e)))?;
+    flags.set("opt_level","speed").map_err(|e|error(format!("Internal compiler error: {}",e)))?;
+    let isa=cranelift_native::builder()
+        .map_err(|e|error(format!("this machine isn't supported by the JIT: {}",e)))?
+        .finish(settings::Flags::new(flags))
+        .map_err(|e|error(format!("this machine isn't supported by the JIT: {}",e)))?;
+    let mut builder=JITBuilder::with_isa(isa,default_libcall_names());
+    for (name,function) in runtime::symbols() {
+        builder.symbol(name,function);
+    }
+    return Ok(JITModule::new(builder));
+}
+/// JIT compiles `main` and every function it calls. Programs using anything that can't be
+/// compiled yet give a compile error, so they can be run some other way.
+pub fn compile<'input>(filename:&'input str,scopes:&Scopes<'input>)->Result<Program<'input>,Error<'input,String>> {
+    let error=|reason:String|Error::new_compile(filename,Location::default(),Location::default(),reason);
+    let mut translator=Translator::new(filename,scopes,new_module(filename)?);
+    let entry=translator.translate_main()?;
+    let Translator{mut module,errors,..}=translator;
+    module.finalize_definitions().map_err(|e|error(format!("Internal compiler error: {}",e)))?;
+    let entry=module.get_finalized_function(entry);
+    return Ok(Program{filename,module:Some(module),entry,errors});
+}
+/// Runs a compiled program with the program's arguments. The exit code is what `main` returns.
+pub fn run<'input>(program:&Program<'input>,args:&[String])->Result<i32,Error<'input,String>> {
+    let args=args.iter().map(|arg|runtime::new_string(arg)).collect::<Vec<_>>();
+    // `main` itself is the first call.
+    let mut context=Context{error:0,depth:1,args:runtime::new_slice(&args)};
+    // SAFETY: `cppl_entry` was compiled to take the context and return the exit code, and the
+    // module it is in lives as long as `program`.
+    let code=unsafe {
+        let entry=std::mem::transmute::<*const u8,extern "C" fn(*mut Context)->i64>(program.entry);
+        entry(&mut context)
+    };
+    if context.error!=0 {
+        let (start,end,reason)=&program.errors[context.error as usize-1];
+        return Err(Error::new_runtime(program.filename,*start,*end,reason.clone()));
+    }
+    return Ok(code as i32);
+}
+/// A function compiled on its own, called through its wrapper.
+struct Compiled {
+    wrapper:extern "C" fn(*mut Context,*const u64,*mut u64),
+    params:Vec<Prim>,
+    ret:Option<Prim>,
+}
+/// The functions of a program that could be compiled on their own, for the interpreter to call
+/// while it runs the rest.
+pub struct Functions<'input> {
+    filename:&'input str,
+    /// Only `None` while it is being freed.
+    module:Option<JITModule>,
+    compiled:HashMap<Scope,Compiled>,
+    errors:Vec<(Location,Location,String)>,
+    /// The program's arguments, a slice of strings.
+    args:*const u8,
+}
+// SAFETY: the compiled code and the arguments are never changed after they are made, and the
+// context every call gets is its own.
+unsafe impl Send for Functions<'_> {}
+unsafe impl Sync for Functions<'_> {}
+impl Drop for Functions<'_> {
+    fn drop(&mut self) {
+        if let Some(module)=self.module.take() {
+            // SAFETY: the wrappers are the only pointers into the module and they are never given
+            // out.
+            unsafe {module.free_memory()};
+        }
+    }
+}
+impl Functions<'_> {
+    /// How many functions were compiled.
+    pub fn len(&self)->usize {
+        self.compiled.len()
+    }
+    pub fn is_empty(&self)->bool {
+        self.compiled.is_empty()
+    }
+}
+impl<'input> Natives<'input> for Functions<'input> {
+    fn call(&self,function:Scope,args:&[Value<'input>],depth:usize)->Option<Result<Value<'input>,Error<'input,String>>> {
+        let compiled=self.compiled.get(&function)?;
+        let mut words=Vec::with_capacity(args.len());
+        for (arg,prim) in args.iter().zip(compiled.params.iter()) {
+            words.push(match (prim,arg) {
+                (Prim::Int,Value::Int(n))=>*n as u64,
+                (Prim::Uint,Value::Uint(n))=>*n,
+                (Prim::Byte,Value::Byte(n))=>*n as u64,
+                (Prim::Float,Value::Float(n))=>n.to_bits() as u64,
+                (Prim::DoubleFloat,Value::DoubleFloat(n))=>n.to_bits(),
+                (Prim::Bool,Value::Bool(b))=>*b as u64,
+                (Prim::Char,Value::Char(c))=>*c as u64,
+                // Anything else is left to the interpreter.
+                _=>return None,
+            });
+        }
+        let mut context=Context{error:0,depth:depth as u32,args:self.args};
+        let mut ret=0u64;
+        (compiled.wrapper)(&mut context,words.as_ptr(),&mut ret);
+        if context.error!=0 {
+            let (start,end,reason)=&self.errors[context.error as usize-1];
+            return Some(Err(Error::new_runtime(self.filename,*start,*end,reason.clone())));
+        }
+        return Some(Ok(match compiled.ret {
+            None=>Value::None,
+            Some(Prim::Int)=>Value::Int(ret as i64),
+            Some(Prim::Uint)=>Value::Uint(ret),
+            Some(Prim::Byte)=>Value::Byte(ret as u8),
+            Some(Prim::Float)=>Value::Float(f32::from_bits(ret as u32)),
+            Some(Prim::DoubleFloat)=>Value::DoubleFloat(f64::from_bits(ret)),
+            Some(Prim::Bool)=>Value::Bool(ret as u8!=0),
+            Some(Prim::Char)=>Value::Char(char::from_u32(ret as u32).unwrap_or_default()),
+            Some(Prim::String|Prim::Slice)=>return None,
+        }));
+    }
+}
+/// JIT compiles every function that can be compiled on its own, along with the functions it
+/// calls. The errors say why the others can't be, and they are run by the interpreter instead.
+pub fn compile_functions<'input>(filename:&'input str,scopes:&Scopes<'input>,args:&[String])->Result<(Functions<'input>,Vec<Error<'input,String>>),Error<'input,String>> {
+    let error=|reason:String|Error::new_compile(filename,Location::default(),Location::default(),reason);
+    let mut candidates=scopes.var_scopes.iter()
+        .filter(|(_,item)|matches!(item,VarScopeItem::Function{..}))
+        .map(|(scope,_)|*scope)
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|s|s.0);
+    let mut skipped=Vec::new();
+    // A function that fails leaves the module half done, so it is started over without it.
+    'retry: loop {
+        let mut translator=Translator::new(filename,scopes,new_module(filename)?);
+        let mut wrappers=Vec::with_capacity(candidates.len());
+        for (i,function) in candidates.iter().enumerate() {
+            match translator.translate_function(*function) {
+                Ok(compiled)=>wrappers.push((*function,compiled)),
+                Err(err) if matches!(err.level,ErrorLevel::Compile)=>{
+                    candidates.remove(i);
+                    skipped.push(err);
+                    continue 'retry;
+                },
+                Err(err)=>return Err(err),
+            }
+        }
+        let Translator{mut module,errors,..}=translator;
+        module.finalize_definitions().map_err(|e|error(format!("Internal compiler error: {}",e)))?;
+        let compiled=wrappers.into_iter()
+            .map(|(function,(wrapper,params,ret))|{
+                let pointer=module.get_finalized_function(wrapper);
+                // SAFETY: every wrapper was compiled with this signature.
+                let wrapper=unsafe {std::mem::transmute::<*const u8,extern "C" fn(*mut Context,*const u64,*mut u64)>(pointer)};
+                (function,Compiled{wrapper,params,ret})
+            })
+            .collect();
+        let args=args.iter().map(|arg|runtime::new_string(arg)).collect::<Vec<_>>();
+        let functions=Functions{filename,module:Some(module),compiled,errors,args:runtime::new_slice(&args)};
+        return Ok((functions,skipped));
+    }
+}
@@ -0,0 +1,30 @@
+//! Native code for the refined AST through Cranelift.
+//!
+//! `compile` JIT compiles `main` and every function it calls and `run` runs them. `build` compiles
+//! them to an object file instead and links it with the runtime into an executable. Only functions
+//! working on numbers, `Bool`s, `Char`s, `String`s and slices can be compiled so far. Anything else
+//! is a compile error saying what isn't supported, so the program can be run with the interpreter
+//! instead. `compile_functions` compiles every function that can be compiled on its own, for the
+//! interpreter to call while it runs the rest.
+pub use translate::{
+    Context,
+    Prim,
+};
+pub use jit::{
+    compile,
+    run,
+    Program,
+    compile_functions,
+    Functions,
+};
+pub use object::{
+    compile_object,
+    build,
+    RUNTIME,
+};
+
+
+mod translate;
+mod jit;
+mod object;
+mod runtime;
@@ -0,0 +1,91 @@
+//! Compiling ahead of time to object files and executables.
+use std::{
+    env,
+    fs,
+    path::Path,
+    process::Command,
+};
+use cppl_error::{
+    Location,
+    Error,
+    ContextualError,
+};
+use cppl_ast::refined::Scopes;
+use cranelift_codegen::settings::{
+    self,
+    Configurable,
+};
+use cranelift_module::{
+    default_libcall_names,
+    DataDescription,
+    Linkage,
+    Module,
+};
+use cranelift_object::{
+    ObjectBuilder,
+    ObjectModule,
+};
+use crate::translate::*;
+
+
+/// The runtime executables are linked with. It has the C `main`, which calls `cppl_entry`.
+pub const RUNTIME:&str=include_str!("runtime.c");
+
+
+/// Compiles `main` and every function it calls to a relocatable object file for this machine.
+/// Linked with `RUNTIME`, it is a program.
+///
+/// The runtime errors are stored in it as they are printed, which is why it needs the source.
+pub fn compile_object<'input>(filename:&'input str,source:&str,scopes:&Scopes<'input>)->Result<Vec<u8>,Error<'input,String>> {
+    let error=|reason:String|Error::new_compile(filename,Location::default(),Location::default(),reason);
+    let mut flags=settings::builder();
+    // Executables are position independent by default.
+    flags.set("is_pic","true").map_err(|e|error(format!("Internal compiler error: {}",e)))?;
+    flags.set("opt_level","speed").map_err(|e|error(format!("Internal compiler error: {}",e)))?;
+    let isa=cranelift_native::builder()
+        .map_err(|e|error(format!("this machine isn't supported: {}",e)))?
+        .finish(settings::Flags::new(flags))
+        .map_err(|e|error(format!("this machine isn't supported: {}",e)))?;
+    let builder=ObjectBuilder::new(isa,filename,default_libcall_names())
+        .map_err(|e|error(format!("Internal compiler error: {}",e)))?;
+    let mut translator=Translator::new(filename,scopes,ObjectModule::new(builder));
+    translator.translate_main()?;
+    let Translator{mut module,errors,..}=translator;
+    // `cppl_errors` is every error, each ending with a 0 byte.
+    let mut messages=Vec::new();
+    for (start,end,reason) in errors {
+        let err=Error::<String>::new_runtime(filename,start,end,reason);
+        messages.extend_from_slice(ContextualError::from((source,err)).to_string().as_bytes());
+        messages.push(0);
+    }
+    let id=module.declare_data("cppl_errors",Linkage::Export,false,false)
+        .map_err(|e|error(format!("Internal compiler error: {}",e)))?;
+    let mut data=DataDescription::new();
+    data.define(messages.into_boxed_slice());
+    module.define_data(id,&data).map_err(|e|error(format!("Internal compiler error: {}",e)))?;
+    return module.finish().emit().map_err(|e|error(format!("Internal compiler error: {}",e)));
+}
+/// Compiles a program to an object file at `object`, then links it with the runtime into an
+/// executable at `output` using the C compiler in `CC`, or `cc`.
+pub fn build<'input>(filename:&'input str,source:&str,scopes:&Scopes<'input>,object:&Path,output:&Path)->Result<(),Error<'input,String>> {
+    let error=|reason:String|Error::new_compile(filename,Location::default(),Location::default(),reason);
+    let code=compile_object(filename,source,scopes)?;
+    fs::write(object,code).map_err(|e|error(format!("could not write `{}`: {}",object.display(),e)))?;
+    let runtime=env::temp_dir().join(format!("cppl_runtime_{}.c",std::process::id()));
+    fs::write(&runtime,RUNTIME).map_err(|e|error(format!("could not write `{}`: {}",runtime.display(),e)))?;
+    let cc=env::var("CC").unwrap_or_else(|_|"cc".to_string());
+    let linked=Command::new(&cc)
+        .arg("-O2")
+        .arg("-o")
+        .arg(output)
+        .arg(object)
+        .arg(&runtime)
+        .arg("-lm")
+        .output();
+    let _=fs::remove_file(&runtime);
+    let linked=linked.map_err(|e|error(format!("could not run the C compiler `{}`: {}",cc,e)))?;
+    if !linked.status.success() {
+        return Err(error(format!("linking failed: {}",String::from_utf8_lossy(&linked.stderr).trim_end())));
+    }
+    return Ok(());
+}
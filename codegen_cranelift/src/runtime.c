@@ -0,0 +1,121 @@
+/* The runtime compiled cppl programs are linked with. `runtime.rs` has the same functions for the
+ * JIT.
+ *
+ * Strings and slices are pointers to their length, followed by the bytes of the string or 8 bytes
+ * for every item. Nothing is freed yet, programs only keep what they allocate until they end. */
+#define _POSIX_C_SOURCE 200809L
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+
+typedef struct {
+    uint64_t len;
+    char bytes[];
+} String;
+typedef struct {
+    uint64_t len;
+    uint64_t items[];
+} Slice;
+/* What compiled code shares with the runtime, `Context` in `translate.rs`. */
+typedef struct {
+    /* One more than the index of the runtime error that stopped the program, 0 if none did. */
+    uint32_t error;
+    /* How many calls are nested. */
+    uint32_t depth;
+    /* The program's arguments, a slice of strings. */
+    const Slice *args;
+} Context;
+
+
+/* Both come from the compiled program. */
+extern int64_t cppl_entry(Context *context);
+/* Every runtime error as it is printed, each ending with a 0 byte. */
+extern const char cppl_errors[];
+
+
+/* Allocates a string or slice of `len` items of `size` bytes each, with the length set. */
+static void *allocate(uint64_t len, uint64_t size) {
+    uint64_t *ptr = malloc(8 + len * size);
+    if (ptr == NULL) {
+        fputs("out of memory\n", stderr);
+        exit(101);
+    }
+    *ptr = len;
+    return ptr;
+}
+static String *new_string(const char *bytes, uint64_t len) {
+    String *s = allocate(len, 1);
+    memcpy(s->bytes, bytes, len);
+    return s;
+}
+void cppl_print(const String *s) {
+    fwrite(s->bytes, 1, s->len, stdout);
+    fflush(stdout);
+}
+void cppl_println(const String *s) {
+    fwrite(s->bytes, 1, s->len, stdout);
+    fputc('\n', stdout);
+}
+void cppl_eprint(const String *s) {
+    fwrite(s->bytes, 1, s->len, stderr);
+    fflush(stderr);
+}
+void cppl_eprintln(const String *s) {
+    fwrite(s->bytes, 1, s->len, stderr);
+    fputc('\n', stderr);
+}
+String *cppl_read_line(void) {
+    char *line = NULL;
+    size_t cap = 0;
+    /* `stdout` is flushed first, like Rust's `stdin` does. */
+    fflush(stdout);
+    ssize_t len = getline(&line, &cap, stdin);
+    if (len < 0) {
+        len = 0;
+    }
+    while (len > 0 && (line[len - 1] == '\n' || line[len - 1] == '\r')) {
+        len--;
+    }
+    String *s = new_string(line == NULL ? "" : line, len);
+    free(line);
+    return s;
+}
+String *cppl_concat(const String *a, const String *b) {
+    String *s = allocate(a->len + b->len, 1);
+    memcpy(s->bytes, a->bytes, a->len);
+    memcpy(s->bytes + a->len, b->bytes, b->len);
+    return s;
+}
+/* -1, 0 or 1 like `Ordering`. */
+int64_t cppl_string_compare(const String *a, const String *b) {
+    uint64_t len = a->len < b->len ? a->len : b->len;
+    int order = memcmp(a->bytes, b->bytes, len);
+    if (order == 0) {
+        order = (a->len > b->len) - (a->len < b->len);
+    }
+    return (order > 0) - (order < 0);
+}
+int main(int argc, char **argv) {
+    /* Lines are written right away, like Rust's `stdout`. */
+    setvbuf(stdout, NULL, _IOLBF, 0);
+    /* The program's name isn't one of its arguments. */
+    Slice *args = allocate(argc > 0 ? argc - 1 : 0, 8);
+    for (int i = 1; i < argc; i++) {
+        args->items[i - 1] = (uint64_t)(uintptr_t)new_string(argv[i], strlen(argv[i]));
+    }
+    /* `main` itself is the first call. */
+    Context context = {0, 1, args};
+    int64_t code = cppl_entry(&context);
+    fflush(stdout);
+    if (context.error != 0) {
+        const char *message = cppl_errors;
+        for (uint32_t i = 1; i < context.error; i++) {
+            message += strlen(message) + 1;
+        }
+        fprintf(stderr, "%s\n", message);
+        return 101;
+    }
+    return (int)code;
+}
@@ -0,0 +1,118 @@
+//! The runtime JIT compiled code calls. Executables are linked with `runtime.c` instead, which
+//! has the same functions.
+//!
+//! Nothing is freed yet, programs only keep what they allocate until they end.
+use std::{
+    alloc::{
+        alloc,
+        handle_alloc_error,
+        Layout,
+    },
+    cmp::Ordering,
+    io::{
+        stderr,
+        stdin,
+        stdout,
+        Write,
+    },
+    ptr::copy_nonoverlapping,
+    slice,
+};
+
+
+/// Allocates a string or slice of `len` items of `size` bytes each, with the length set.
+fn allocate(len:usize,size:usize)->*mut u8 {
+    let Ok(layout)=Layout::from_size_align(8+len*size,8) else {
+        panic!("Internal compiler error: can't allocate {} items",len);
+    };
+    // SAFETY: the layout is never empty, it has room for the length.
+    unsafe {
+        let ptr=alloc(layout);
+        if ptr.is_null() {
+            handle_alloc_error(layout);
+        }
+        (ptr as *mut u64).write(len as u64);
+        return ptr;
+    }
+}
+pub(crate) fn new_string(s:&str)->*mut u8 {
+    let ptr=allocate(s.len(),1);
+    // SAFETY: `allocate` made room for the bytes after the length.
+    unsafe {copy_nonoverlapping(s.as_ptr(),ptr.add(8),s.len())};
+    return ptr;
+}
+pub(crate) fn new_slice(items:&[*mut u8])->*mut u8 {
+    let ptr=allocate(items.len(),8);
+    // SAFETY: `allocate` made room for the items after the length.
+    unsafe {copy_nonoverlapping(items.as_ptr(),ptr.add(8) as *mut *mut u8,items.len())};
+    return ptr;
+}
+/// The bytes of a string made by compiled code or this runtime.
+///
+/// # Safety
+/// `s` has to point to a length followed by that many bytes that live for `'a`.
+unsafe fn bytes<'a>(s:*const u8)->&'a [u8] {
+    let len=*(s as *const u64) as usize;
+    return slice::from_raw_parts(s.add(8),len);
+}
+fn write(mut to:impl Write,s:*const u8,newline:bool) {
+    // SAFETY: compiled code only passes strings.
+    let s=unsafe {bytes(s)};
+    // The interpreter can report failed writes, compiled code can't yet.
+    let _=to.write_all(s);
+    let _=if newline {to.write_all(b"\n")} else {to.flush()};
+}
+extern "C" fn print(s:*const u8) {
+    write(stdout(),s,false);
+}
+extern "C" fn println(s:*const u8) {
+    write(stdout(),s,true);
+}
+extern "C" fn eprint(s:*const u8) {
+    write(stderr(),s,false);
+}
+extern "C" fn eprintln(s:*const u8) {
+    write(stderr(),s,true);
+}
+extern "C" fn read_line()->*mut u8 {
+    let mut line=String::new();
+    let _=stdin().read_line(&mut line);
+    return new_string(line.trim_end_matches(['\n','\r']));
+}
+extern "C" fn concat(a:*const u8,b:*const u8)->*mut u8 {
+    // SAFETY: compiled code only passes strings, which are always UTF-8.
+    let (a,b)=unsafe {(bytes(a),bytes(b))};
+    let ptr=allocate(a.len()+b.len(),1);
+    // SAFETY: `allocate` made room for both.
+    unsafe {
+        copy_nonoverlapping(a.as_ptr(),ptr.add(8),a.len());
+        copy_nonoverlapping(b.as_ptr(),ptr.add(8+a.len()),b.len());
+    }
+    return ptr;
+}
+extern "C" fn string_compare(a:*const u8,b:*const u8)->i64 {
+    // SAFETY: compiled code only passes strings.
+    let (a,b)=unsafe {(bytes(a),bytes(b))};
+    match a.cmp(b) {
+        Ordering::Less=>-1,
+        Ordering::Equal=>0,
+        Ordering::Greater=>1,
+    }
+}
+/// `%` on floats, which isn't always linked in from the C library.
+extern "C" fn fmod(a:f64,b:f64)->f64 {
+    a%b
+}
+/// The functions compiled code can call and their names.
+pub(crate) fn symbols()->[(&'static str,*const u8);8] {
+    [
+        ("cppl_print",print as *const u8),
+        ("cppl_println",println as *const u8),
+        ("cppl_eprint",eprint as *const u8),
+        ("cppl_eprintln",eprintln as *const u8),
+        ("cppl_read_line",read_line as *const u8),
+        ("cppl_concat",concat as *const u8),
+        ("cppl_string_compare",string_compare as *const u8),
+        ("fmod",fmod as *const u8),
+    ]
+}
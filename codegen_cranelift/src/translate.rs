@@ -0,0 +1,1349 @@
+//! Translates the refined AST to Cranelift IR.
+//!
+//! Every function takes a pointer to a `Context` before its parameters. A runtime error stores
+//! which error happened in it and returns, and every call checks it afterwards, so errors leave
+//! the native stack without any help from the code running it.
+//!
+//! `String`s and slices are pointers to their length as a `u64`, followed by the bytes of the
+//! string or 8 bytes for every item. Anything that needs more than that, like printing or joining
+//! strings, calls a function of the runtime the code is linked with.
+use std::collections::HashMap;
+use cppl_error::{
+    Location,
+    Error,
+};
+use cppl_ast::{
+    refined::*,
+    expr_type,
+};
+use cranelift_codegen::ir::{
+    self,
+    condcodes::{
+        IntCC,
+        FloatCC,
+    },
+    types,
+    AbiParam,
+    InstBuilder,
+    MemFlags,
+};
+use cranelift_frontend::{
+    FunctionBuilder,
+    FunctionBuilderContext,
+    Variable,
+};
+use cranelift_module::{
+    DataDescription,
+    DataId,
+    FuncId,
+    Linkage,
+    Module,
+};
+
+
+/// How many calls can be nested before the program is stopped, the same as the interpreter.
+const MAX_CALL_DEPTH:i64=1_000;
+const ERROR_OFFSET:i32=0;
+const DEPTH_OFFSET:i32=4;
+const ARGS_OFFSET:i32=8;
+/// Strings and slices are pointers, which only 64 bit machines are supported for.
+const POINTER:ir::Type=types::I64;
+
+
+pub(crate) type TranslateResult<'input,T=()>=Result<T,Error<'input,String>>;
+/// What compiled code shares with the code running it.
+#[repr(C)]
+#[derive(Debug)]
+pub struct Context {
+    /// One more than the index of the runtime error that stopped the program, 0 if none did.
+    pub error:u32,
+    /// How many calls are nested.
+    pub depth:u32,
+    /// The program's arguments, a slice of strings.
+    pub args:*const u8,
+}
+/// A type whose values fit in a register. These are the only types that can be compiled so far.
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum Prim {
+    Int,
+    Uint,
+    Byte,
+    Float,
+    DoubleFloat,
+    Bool,
+    Char,
+    String,
+    /// A slice of any of these. The type of the items is only known from the static type.
+    Slice,
+}
+impl Prim {
+    fn of(scopes:&Scopes,ty:&Type)->Option<Self> {
+        Some(match scopes.expand_alias(ty) {
+            Type::Int=>Prim::Int,
+            Type::Uint=>Prim::Uint,
+            Type::Byte=>Prim::Byte,
+            Type::Float=>Prim::Float,
+            Type::DoubleFloat=>Prim::DoubleFloat,
+            Type::Bool=>Prim::Bool,
+            Type::Char=>Prim::Char,
+            Type::String=>Prim::String,
+            Type::Slice{inner,..} if Prim::of(scopes,inner).is_some()=>Prim::Slice,
+            _=>return None,
+        })
+    }
+    pub fn ir(self)->ir::Type {
+        match self {
+            Prim::Int|Prim::Uint=>types::I64,
+            Prim::Byte|Prim::Bool=>types::I8,
+            Prim::Float=>types::F32,
+            Prim::DoubleFloat=>types::F64,
+            Prim::Char=>types::I32,
+            Prim::String|Prim::Slice=>POINTER,
+        }
+    }
+    fn is_float(self)->bool {
+        matches!(self,Prim::Float|Prim::DoubleFloat)
+    }
+    fn is_integer(self)->bool {
+        matches!(self,Prim::Int|Prim::Uint|Prim::Byte)
+    }
+    /// The type of the values, `None` for slices since the type of their items isn't known.
+    fn ty<'input>(self)->Option<Type<'input>> {
+        Some(match self {
+            Prim::Int=>Type::Int,
+            Prim::Uint=>Type::Uint,
+            Prim::Byte=>Type::Byte,
+            Prim::Float=>Type::Float,
+            Prim::DoubleFloat=>Type::DoubleFloat,
+            Prim::Bool=>Type::Bool,
+            Prim::Char=>Type::Char,
+            Prim::String=>Type::String,
+            Prim::Slice=>return None,
+        })
+    }
+}
+#[derive(Copy,Clone)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+impl Op {
+    fn symbol(&self)->&'static str {
+        match self {
+            Op::Add=>"+",
+            Op::Sub=>"-",
+            Op::Mul=>"*",
+            Op::Div=>"/",
+            Op::Mod=>"%",
+            Op::BitAnd=>"&",
+            Op::BitOr=>"|",
+            Op::BitXor=>"^",
+            Op::ShiftLeft=>"<<",
+            Op::ShiftRight=>">>",
+        }
+    }
+}
+#[derive(Copy,Clone)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    LessEqual,
+    Greater,
+    Less,
+}
+/// A function that can be called from compiled code.
+#[derive(Clone)]
+struct Signature {
+    id:FuncId,
+    params:Vec<Prim>,
+    ret:Option<Prim>,
+}
+/// Translates functions into a Cranelift module. Functions are translated the first time they are
+/// called.
+pub(crate) struct Translator<'a,'input,M:Module> {
+    filename:&'input str,
+    scopes:&'a Scopes<'input>,
+    pub module:M,
+    /// The runtime errors compiled code can stop with. `Context::error` is one more than the index
+    /// of the error.
+    pub errors:Vec<(Location,Location,String)>,
+    functions:HashMap<Scope,Signature>,
+    pending:Vec<Scope>,
+    runtime:HashMap<&'static str,FuncId>,
+    strings:usize,
+}
+impl<'a,'input,M:Module> Translator<'a,'input,M> {
+    pub fn new(filename:&'input str,scopes:&'a Scopes<'input>,module:M)->Self {
+        Translator {
+            filename,
+            scopes,
+            module,
+            errors:Vec::new(),
+            functions:HashMap::new(),
+            pending:Vec::new(),
+            runtime:HashMap::new(),
+            strings:0,
+        }
+    }
+    fn error<T>(&self,(start,end):(Location,Location),reason:impl Into<String>)->TranslateResult<'input,T> {
+        Err(Error::new_compile(self.filename,start,end,reason.into()))
+    }
+    fn unsupported<T>(&self,span:(Location,Location),what:impl std::fmt::Display)->TranslateResult<'input,T> {
+        self.error(span,format!("{} can't be compiled to native code yet",what))
+    }
+    fn module_error<T>(&self,err:cranelift_module::ModuleError)->TranslateResult<'input,T> {
+        self.error(Default::default(),format!("Internal compiler error: {}",err))
+    }
+    fn prim(&self,span:(Location,Location),ty:&Type<'input>)->TranslateResult<'input,Prim> {
+        match Prim::of(self.scopes,ty) {
+            Some(prim)=>Ok(prim),
+            None=>self.unsupported(span,format_args!("values of type `{}`",self.scopes.type_name(ty))),
+        }
+    }
+    /// Declares `function` if it hasn't been, and queues it to be translated.
+    fn function(&mut self,function:Scope)->TranslateResult<'input,Signature> {
+        if let Some(signature)=self.functions.get(&function) {
+            return Ok(signature.clone());
+        }
+        let Some(VarScopeItem::Function{name,params,ret_type,..})=self.scopes.get(function) else {
+            return self.error(Default::default(),"Internal compiler error: not a function");
+        };
+        let span=self.scopes.get(function).and_then(|f|f.def_span()).unwrap_or_default();
+        let mut prims=Vec::with_capacity(params.len());
+        for param in params {
+            let param=self.scopes.get(*param);
+            let param_span=param.and_then(|p|p.def_span()).unwrap_or(span);
+            prims.push(self.prim(param_span,param.and_then(|p|p.var_type()).unwrap_or(&Type::Unknown))?);
+        }
+        let ret=match ret_type {
+            Some(ty)=>Some(self.prim(span,ty)?),
+            None=>None,
+        };
+        let mut signature=self.module.make_signature();
+        signature.params.push(AbiParam::new(self.module.target_config().pointer_type()));
+        signature.params.extend(prims.iter().map(|p|AbiParam::new(p.ir())));
+        signature.returns.extend(ret.map(|p|AbiParam::new(p.ir())));
+        // Methods of different types can have the same name.
+        let symbol=format!("cppl_{}_{}",name,function.0);
+        let id=match self.module.declare_function(&symbol,Linkage::Local,&signature) {
+            Ok(id)=>id,
+            Err(err)=>return self.module_error(err),
+        };
+        let signature=Signature{id,params:prims,ret};
+        self.functions.insert(function,signature.clone());
+        self.pending.push(function);
+        return Ok(signature);
+    }
+    /// A function of the runtime or the C library, which compiled code is linked with.
+    fn runtime(&mut self,name:&'static str,params:&[ir::Type],ret:Option<ir::Type>)->TranslateResult<'input,FuncId> {
+        if let Some(id)=self.runtime.get(name) {
+            return Ok(*id);
+        }
+        let mut signature=self.module.make_signature();
+        signature.params.extend(params.iter().map(|p|AbiParam::new(*p)));
+        signature.returns.extend(ret.map(AbiParam::new));
+        let id=match self.module.declare_function(name,Linkage::Import,&signature) {
+            Ok(id)=>id,
+            Err(err)=>return self.module_error(err),
+        };
+        self.runtime.insert(name,id);
+        return Ok(id);
+    }
+    /// Stores a string literal with the program.
+    fn string(&mut self,s:&str)->TranslateResult<'input,DataId> {
+        let name=format!("cppl_string_{}",self.strings);
+        self.strings+=1;
+        let id=match self.module.declare_data(&name,Linkage::Local,false,false) {
+            Ok(id)=>id,
+            Err(err)=>return self.module_error(err),
+        };
+        let mut bytes=(s.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(s.as_bytes());
+        let mut data=DataDescription::new();
+        data.define(bytes.into_boxed_slice());
+        data.set_align(8);
+        if let Err(err)=self.module.define_data(id,&data) {
+            return self.module_error(err);
+        }
+        return Ok(id);
+    }
+    /// Translates `main` and every function it calls. Returns `cppl_entry`, which takes the
+    /// context and returns the exit code.
+    pub fn translate_main(&mut self)->TranslateResult<'input,FuncId> {
+        if self.module.target_config().pointer_type()!=POINTER {
+            return self.error(Default::default(),"only 64 bit machines are supported");
+        }
+        let root=Scope(0);
+        let scopes=self.scopes;
+        let statements=scopes.get(root).map(|r|r.statements()).unwrap_or_default();
+        // The root scope can only define things, running anything there needs the interpreter.
+        for stmt in statements {
+            match stmt {
+                Statement::FunctionDef{..}|
+                    Statement::FunctionSig{..}|
+                    Statement::InterfaceDef{..}|
+                    Statement::TypeDef{..}|
+                    Statement::StaticVarDef{..}|
+                    Statement::ConstVarDef{..}|
+                    Statement::Enum{..}|
+                    Statement::Impl{..}=>{},
+                _=>{
+                    let span=stmt_span(stmt);
+                    return self.unsupported(span,"statements in the root scope");
+                },
+            }
+        }
+        let Some(main)=scopes.find_function(statements,"main") else {
+            return Err(Error::new_runtime(self.filename,Location::default(),Location::default(),"there is no `main` function to run"));
+        };
+        let signature=self.function(main)?;
+        if !matches!(signature.params[..],[]|[Prim::Slice]) {
+            let span=scopes.get(main).and_then(|m|m.def_span()).unwrap_or_default();
+            return self.unsupported(span,"a `main` function with these parameters");
+        }
+        let entry=self.entry(&signature)?;
+        while let Some(function)=self.pending.pop() {
+            self.translate(function)?;
+        }
+        return Ok(entry);
+    }
+    /// Translates `function` and every function it calls on their own, for code that doesn't know
+    /// its signature. Returns a wrapper taking the context, a pointer to the arguments and a
+    /// pointer to store the returned value at, each 8 bytes, along with the types of the
+    /// parameters and the returned value. Strings and slices can't be passed this way.
+    pub fn translate_function(&mut self,function:Scope)->TranslateResult<'input,(FuncId,Vec<Prim>,Option<Prim>)> {
+        if self.module.target_config().pointer_type()!=POINTER {
+            return self.error(Default::default(),"only 64 bit machines are supported");
+        }
+        let signature=self.function(function)?;
+        if signature.params.iter().chain(signature.ret.iter()).any(|p|matches!(p,Prim::String|Prim::Slice)) {
+            let span=self.scopes.get(function).and_then(|f|f.def_span()).unwrap_or_default();
+            return self.unsupported(span,"functions taking or returning strings and slices");
+        }
+        let wrapper=self.wrapper(function,&signature)?;
+        while let Some(function)=self.pending.pop() {
+            self.translate(function)?;
+        }
+        return Ok((wrapper,signature.params,signature.ret));
+    }
+    /// Defines the wrapper of `translate_function`. Values smaller than 8 bytes are in the low
+    /// bytes, which only works on little endian machines like every one Cranelift supports.
+    fn wrapper(&mut self,function:Scope,signature:&Signature)->TranslateResult<'input,FuncId> {
+        let mut wrapper=self.module.make_signature();
+        wrapper.params.extend([AbiParam::new(POINTER),AbiParam::new(POINTER),AbiParam::new(POINTER)]);
+        let id=match self.module.declare_function(&format!("cppl_call_{}",function.0),Linkage::Local,&wrapper) {
+            Ok(id)=>id,
+            Err(err)=>return self.module_error(err),
+        };
+        let mut context=self.module.make_context();
+        context.func.signature=wrapper;
+        let mut builder_context=FunctionBuilderContext::new();
+        let mut builder=FunctionBuilder::new(&mut context.func,&mut builder_context);
+        let block=builder.create_block();
+        builder.append_block_params_for_function_params(block);
+        builder.switch_to_block(block);
+        let [ctx,args,ret]=builder.block_params(block)[..] else {
+            return self.error(Default::default(),"Internal compiler error: the wrapper has the wrong parameters");
+        };
+        let mut values=vec![ctx];
+        for (i,prim) in signature.params.iter().enumerate() {
+            values.push(builder.ins().load(prim.ir(),MemFlags::trusted(),args,i as i32*8));
+        }
+        let callee=self.module.declare_func_in_func(signature.id,builder.func);
+        let call=builder.ins().call(callee,&values);
+        if let Some(value)=builder.inst_results(call).first().copied() {
+            builder.ins().store(MemFlags::trusted(),value,ret,0);
+        }
+        builder.ins().return_(&[]);
+        builder.seal_all_blocks();
+        builder.finalize();
+        if let Err(err)=self.module.define_function(id,&mut context) {
+            return self.module_error(err);
+        }
+        return Ok(id);
+    }
+    /// Defines `cppl_entry`, which calls `main` with the program's arguments if it takes them.
+    /// Returning anything but an integer gives the exit code 0.
+    fn entry(&mut self,main:&Signature)->TranslateResult<'input,FuncId> {
+        let mut signature=self.module.make_signature();
+        signature.params.push(AbiParam::new(POINTER));
+        signature.returns.push(AbiParam::new(types::I64));
+        let id=match self.module.declare_function("cppl_entry",Linkage::Export,&signature) {
+            Ok(id)=>id,
+            Err(err)=>return self.module_error(err),
+        };
+        let mut context=self.module.make_context();
+        context.func.signature=signature;
+        let mut builder_context=FunctionBuilderContext::new();
+        let mut builder=FunctionBuilder::new(&mut context.func,&mut builder_context);
+        let block=builder.create_block();
+        builder.append_block_params_for_function_params(block);
+        builder.switch_to_block(block);
+        let ctx=builder.block_params(block)[0];
+        let mut args=vec![ctx];
+        if !main.params.is_empty() {
+            args.push(builder.ins().load(POINTER,MemFlags::trusted(),ctx,ARGS_OFFSET));
+        }
+        let function=self.module.declare_func_in_func(main.id,builder.func);
+        let call=builder.ins().call(function,&args);
+        let code=match (main.ret,builder.inst_results(call).first().copied()) {
+            (Some(Prim::Int|Prim::Uint),Some(value))=>value,
+            (Some(Prim::Byte),Some(value))=>builder.ins().uextend(types::I64,value),
+            _=>builder.ins().iconst(types::I64,0),
+        };
+        builder.ins().return_(&[code]);
+        builder.seal_all_blocks();
+        builder.finalize();
+        if let Err(err)=self.module.define_function(id,&mut context) {
+            return self.module_error(err);
+        }
+        return Ok(id);
+    }
+    fn translate(&mut self,function:Scope)->TranslateResult<'input> {
+        let signature=self.functions[&function].clone();
+        let scopes=self.scopes;
+        let Some(item@VarScopeItem::Function{params,..})=scopes.get(function) else {
+            return self.error(Default::default(),"Internal compiler error: not a function");
+        };
+        let mut context=self.module.make_context();
+        context.func.signature=self.module.declarations().get_function_decl(signature.id).signature.clone();
+        let mut builder_context=FunctionBuilderContext::new();
+        let mut builder=FunctionBuilder::new(&mut context.func,&mut builder_context);
+        let entry=builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        let exit=builder.create_block();
+        if let Some(ret)=signature.ret {
+            builder.append_block_param(exit,ret.ir());
+        }
+        let args=builder.block_params(entry).to_vec();
+        let mut translator=FunctionTranslator {
+            shared:self,
+            builder,
+            context:args[0],
+            vars:HashMap::new(),
+            var_count:0,
+            loops:Vec::new(),
+            exit,
+            ret:signature.ret,
+        };
+        for ((param,prim),arg) in params.iter().zip(signature.params.iter()).zip(&args[1..]) {
+            translator.define(*param,*prim,*arg);
+        }
+        for stmt in item.statements() {
+            translator.stmt(stmt)?;
+        }
+        translator.leave();
+        let mut builder=translator.builder;
+        builder.seal_all_blocks();
+        builder.finalize();
+        if let Err(err)=self.module.define_function(signature.id,&mut context) {
+            return self.module_error(err);
+        }
+        return Ok(());
+    }
+}
+struct Loop {
+    block:Scope,
+    /// Where `continue` jumps to.
+    header:ir::Block,
+    /// Where `break` jumps to, with the value of the loop as its parameter if it has one.
+    exit:ir::Block,
+    ty:Option<Prim>,
+}
+/// Translates the body of one function.
+struct FunctionTranslator<'t,'a,'input,M:Module> {
+    shared:&'t mut Translator<'a,'input,M>,
+    builder:FunctionBuilder<'t>,
+    /// The pointer to the `Context`.
+    context:ir::Value,
+    vars:HashMap<Scope,(Variable,Prim)>,
+    /// How many Cranelift variables there are, including the ones that aren't cppl variables.
+    var_count:u32,
+    loops:Vec<Loop>,
+    /// The block every return jumps to, with the returned value as its parameter.
+    exit:ir::Block,
+    ret:Option<Prim>,
+}
+impl<'t,'a,'input,M:Module> FunctionTranslator<'t,'a,'input,M> {
+    fn new_var(&mut self,ty:ir::Type)->Variable {
+        let variable=Variable::from_u32(self.var_count);
+        self.var_count+=1;
+        self.builder.declare_var(variable,ty);
+        return variable;
+    }
+    fn define(&mut self,var:Scope,prim:Prim,value:ir::Value) {
+        let variable=self.new_var(prim.ir());
+        self.builder.def_var(variable,value);
+        self.vars.insert(var,(variable,prim));
+    }
+    /// Continues in a new block after a jump, for the code after a `return`, `break` or
+    /// `continue` that can never run.
+    fn dead_block(&mut self) {
+        let block=self.builder.create_block();
+        self.builder.switch_to_block(block);
+    }
+    fn zero(&mut self,prim:Prim)->ir::Value {
+        match prim {
+            Prim::Float=>self.builder.ins().f32const(0.0),
+            Prim::DoubleFloat=>self.builder.ins().f64const(0.0),
+            _=>self.builder.ins().iconst(prim.ir(),0),
+        }
+    }
+    /// Returns from the function with `value`, or a zero if it doesn't return anything.
+    fn ret(&mut self,value:Option<ir::Value>) {
+        let args=match (self.ret,value) {
+            (Some(_),Some(value))=>vec![value],
+            (Some(prim),None)=>vec![self.zero(prim)],
+            (None,_)=>Vec::new(),
+        };
+        self.builder.ins().jump(self.exit,&args);
+        self.dead_block();
+    }
+    /// Stops the program with a runtime error.
+    fn raise(&mut self,(start,end):(Location,Location),reason:impl Into<String>) {
+        self.shared.errors.push((start,end,reason.into()));
+        let code=self.builder.ins().iconst(types::I32,self.shared.errors.len() as i64);
+        self.builder.ins().store(MemFlags::trusted(),code,self.context,ERROR_OFFSET);
+        self.ret(None);
+    }
+    /// Ends the function, falling off the end of it returns nothing.
+    fn leave(&mut self) {
+        self.ret(None);
+        self.builder.switch_to_block(self.exit);
+        let values=self.builder.block_params(self.exit).to_vec();
+        self.builder.ins().return_(&values);
+    }
+    /// Returns right away if the function that was just called stopped with an error.
+    fn check_error(&mut self) {
+        let error=self.builder.ins().load(types::I32,MemFlags::trusted(),self.context,ERROR_OFFSET);
+        let failed=self.builder.create_block();
+        let next=self.builder.create_block();
+        self.builder.ins().brif(error,failed,&[],next,&[]);
+        self.builder.switch_to_block(failed);
+        self.ret(None);
+        self.builder.switch_to_block(next);
+    }
+    /// Calls a function of the runtime.
+    fn call_runtime(&mut self,name:&'static str,args:&[ir::Value],ret:Option<ir::Type>)->TranslateResult<'input,Option<ir::Value>> {
+        let params=args.iter().map(|a|self.builder.func.dfg.value_type(*a)).collect::<Vec<_>>();
+        let id=self.shared.runtime(name,&params,ret)?;
+        let function=self.shared.module.declare_func_in_func(id,self.builder.func);
+        let call=self.builder.ins().call(function,args);
+        return Ok(self.builder.inst_results(call).first().copied());
+    }
+    fn string(&mut self,s:&str)->TranslateResult<'input,ir::Value> {
+        let id=self.shared.string(s)?;
+        let data=self.shared.module.declare_data_in_func(id,self.builder.func);
+        return Ok(self.builder.ins().symbol_value(POINTER,data));
+    }
+    /// The type of the items of `expr` if it is a slice that can be compiled.
+    fn item_prim(&self,expr:&Expr<'input>)->Option<Prim> {
+        let scopes=self.shared.scopes;
+        let ty=expr_type(self.shared.filename,scopes,expr);
+        return scopes.item_type(&ty).and_then(|item|Prim::of(scopes,item));
+    }
+    /// Loads item `index` of `items`, which has to be in bounds.
+    fn load_item(&mut self,items:ir::Value,index:ir::Value,prim:Prim)->ir::Value {
+        let offset=self.builder.ins().imul_imm(index,8);
+        let address=self.builder.ins().iadd(items,offset);
+        return self.builder.ins().load(prim.ir(),MemFlags::trusted(),address,8);
+    }
+    /// Converts a number to another number type like the interpreter does. Other types are never
+    /// converted, so a mismatch between them is a bug in the translation.
+    fn convert(&mut self,value:ir::Value,from:Prim,to:Prim,span:(Location,Location))->TranslateResult<'input,ir::Value> {
+        if from==to {
+            return Ok(value);
+        }
+        let is_number=|prim:Prim|prim.is_integer()||prim.is_float();
+        if !is_number(from)||!is_number(to) {
+            return self.shared.error(span,format!("Internal compiler error: can't convert `{:?}` to `{:?}`",from,to));
+        }
+        let (from_ty,to_ty)=(from.ir(),to.ir());
+        let ins=self.builder.ins();
+        return Ok(match (from.is_float(),to.is_float()) {
+            (true,true) if to_ty==types::F64=>ins.fpromote(to_ty,value),
+            (true,true)=>ins.fdemote(to_ty,value),
+            (true,false)=>match to {
+                Prim::Int=>ins.fcvt_to_sint_sat(to_ty,value),
+                _=>ins.fcvt_to_uint_sat(to_ty,value),
+            },
+            (false,true)=>match from {
+                Prim::Int=>ins.fcvt_from_sint(to_ty,value),
+                _=>ins.fcvt_from_uint(to_ty,value),
+            },
+            _ if from_ty==to_ty=>value,
+            _ if from_ty.bits()>to_ty.bits()=>ins.ireduce(to_ty,value),
+            _ if from==Prim::Int=>ins.sextend(to_ty,value),
+            _=>ins.uextend(to_ty,value),
+        });
+    }
+    fn block(&mut self,block:Scope)->TranslateResult<'input> {
+        let scopes=self.shared.scopes;
+        if let Some(item)=scopes.get(block) {
+            for stmt in item.statements() {
+                self.stmt(stmt)?;
+            }
+        }
+        return Ok(());
+    }
+    /// Translates a branch of an `if` and returns the arguments for the block after the `if`: the
+    /// value the branch ends with converted to `ty`. Branches without a value never finish, so they
+    /// pass a zero that is never used.
+    fn branch(&mut self,block:Scope,ty:Option<Prim>)->TranslateResult<'input,Vec<ir::Value>> {
+        let (statements,value)=self.shared.scopes.branch_value(block);
+        for stmt in statements {
+            self.stmt(stmt)?;
+        }
+        return Ok(match (value,ty) {
+            (Some(value),Some(prim))=>vec![self.expect(value,prim)?],
+            (None,Some(prim))=>vec![self.zero(prim)],
+            (Some(value),None)=>{
+                self.expr(value)?;
+                Vec::new()
+            },
+            (None,None)=>Vec::new(),
+        });
+    }
+    /// The variable `var` is stored in.
+    fn var(&self,span:(Location,Location),var:Scope)->TranslateResult<'input,(Variable,Prim)> {
+        let var=original(self.shared.scopes,var);
+        if let Some(var)=self.vars.get(&var) {
+            return Ok(*var);
+        }
+        match self.shared.scopes.get(var) {
+            Some(VarScopeItem::Static{..})=>self.shared.unsupported(span,"`static`s"),
+            Some(VarScopeItem::Const{..})=>self.shared.unsupported(span,"`const`s"),
+            Some(VarScopeItem::Function{..}|VarScopeItem::BuiltinFunction{..}|VarScopeItem::AnonFunction{..})=>self.shared.unsupported(span,"functions used as values"),
+            Some(VarScopeItem::EnumVariant{..})=>self.shared.unsupported(span,"enum variants"),
+            _=>self.shared.unsupported(span,"variables of the root scope"),
+        }
+    }
+    fn stmt(&mut self,stmt:&Statement<'input>)->TranslateResult<'input> {
+        let scopes=self.shared.scopes;
+        match stmt {
+            Statement::VarDef{start,end,def}=>{
+                if let Some(VarScopeItem::Var{ty,data,..})=scopes.get(*def) {
+                    // Analysis doesn't find the type of everything yet, like loops and matches.
+                    let (value,prim)=match ty {
+                        Type::Unknown=>self.value(data)?,
+                        _=>{
+                            let prim=self.shared.prim((*start,*end),ty)?;
+                            (self.expect(data,prim)?,prim)
+                        },
+                    };
+                    self.define(*def,prim,value);
+                }
+            },
+            Statement::VarAssign{start,end,loc,data}=>{
+                let (var,prim)=self.var((*start,*end),*loc)?;
+                let value=self.expect(data,prim)?;
+                self.builder.def_var(var,value);
+            },
+            Statement::CompoundAssign{start,end,op,place,data}=>{
+                let Expr::Var{path,..}=place else {
+                    return self.shared.unsupported((*start,*end),"assignments to fields and references");
+                };
+                let (var,prim)=self.var((*start,*end),*path)?;
+                let op=match op {
+                    AssignOp::Add=>Op::Add,
+                    AssignOp::Sub=>Op::Sub,
+                    AssignOp::Mul=>Op::Mul,
+                    AssignOp::Div=>Op::Div,
+                    AssignOp::Mod=>Op::Mod,
+                };
+                let left=self.builder.use_var(var);
+                let right=self.expect(data,prim)?;
+                let value=self.arithmetic(op,left,right,prim,(*start,*end))?;
+                self.builder.def_var(var,value);
+            },
+            Statement::PlaceAssign{start,end,..}=>return self.shared.unsupported((*start,*end),"assignments to fields and references"),
+            Statement::Expr{expr,..}=>{
+                self.expr(expr)?;
+            },
+            Statement::Return{val,..}=>{
+                let value=match (self.ret,val) {
+                    (Some(prim),Some(val))=>Some(self.expect(val,prim)?),
+                    (None,Some(val))=>{
+                        self.expr(val)?;
+                        None
+                    },
+                    (_,None)=>None,
+                };
+                self.ret(value);
+            },
+            Statement::Continue{start,end,target,..}=>{
+                let Some(lp)=self.loops.iter().rev().find(|l|l.block==*target) else {
+                    return self.shared.error((*start,*end),"Internal compiler error: `continue` outside of its loop");
+                };
+                let header=lp.header;
+                self.builder.ins().jump(header,&[]);
+                self.dead_block();
+            },
+            Statement::Break{start,end,target,val,..}=>{
+                let Some(lp)=self.loops.iter().rev().find(|l|l.block==*target) else {
+                    return self.shared.error((*start,*end),"Internal compiler error: `break` outside of its loop");
+                };
+                let (exit,ty)=(lp.exit,lp.ty);
+                let args=match (ty,val) {
+                    (Some(prim),Some(val))=>vec![self.expect(val,prim)?],
+                    (Some(prim),None)=>vec![self.zero(prim)],
+                    (None,Some(val))=>{
+                        self.expr(val)?;
+                        Vec::new()
+                    },
+                    (None,None)=>Vec::new(),
+                };
+                self.builder.ins().jump(exit,&args);
+                self.dead_block();
+            },
+            Statement::UnknownVarAssign{start,end,name,..}=>return self.shared.error((*start,*end),format!("variable `{}` is not defined",name)),
+            // Everything else is a definition, which is translated when it is used.
+            _=>{},
+        }
+        return Ok(());
+    }
+    /// The type `expr` has, and whether it is still a number literal that takes the type of
+    /// what it is used with. `None` if it doesn't have a value that can be compiled.
+    fn static_prim(&self,expr:&Expr<'input>)->Option<(Prim,bool)> {
+        use Expr::*;
+        match expr {
+            Data{data:self::Data::GenericNumber{data,..},..}=>match data.replace('_',"").parse::<i64>() {
+                Ok(_)=>Some((Prim::Int,true)),
+                Err(_)=>Some((Prim::Uint,true)),
+            },
+            Add{inner,..}|
+                Sub{inner,..}|
+                Mul{inner,..}|
+                Div{inner,..}|
+                Mod{inner,..}|
+                BitAnd{inner,..}|
+                BitOr{inner,..}|
+                BitXor{inner,..}=>self.operand_prim(inner),
+            ShiftLeft{inner,..}|ShiftRight{inner,..}=>self.static_prim(&inner[0]),
+            Negate{inner,..}=>self.static_prim(inner),
+            Equal{..}|
+                NotEqual{..}|
+                GreaterEqual{..}|
+                LessEqual{..}|
+                Greater{..}|
+                Less{..}|
+                And{..}|
+                Or{..}|
+                Not{..}|
+                IsType{..}=>Some((Prim::Bool,false)),
+            Match{block,..}=>{
+                let mut leafs=block.leafs.iter().map(|(_,leaf)|self.static_prim(leaf));
+                let first=leafs.next()??;
+                leafs.try_fold(first,|a,b|unify(a,b?))
+            },
+            // Branches without a value never finish.
+            If{branches,else_block:Some(else_block),..}=>{
+                let scopes=self.shared.scopes;
+                let mut values=branches.iter().map(|(_,block)|*block).chain([*else_block])
+                    .filter_map(|block|scopes.branch_value(block).1)
+                    .map(|value|self.static_prim(value));
+                let first=values.next()??;
+                values.try_fold(first,|a,b|unify(a,b?))
+            },
+            _=>{
+                let scopes=self.shared.scopes;
+                match expr_type(self.shared.filename,scopes,expr) {
+                    Type::GenericNumber=>Some((Prim::Int,true)),
+                    Type::GenericFloat=>Some((Prim::DoubleFloat,true)),
+                    ty=>Prim::of(scopes,&ty).map(|p|(p,false)),
+                }
+            },
+        }
+    }
+    /// The type both sides of an arithmetic operation are converted to. Number literals take the
+    /// type of the other side.
+    fn operand_prim(&self,inner:&[Expr<'input>;2])->Option<(Prim,bool)> {
+        match (self.static_prim(&inner[0]),self.static_prim(&inner[1])) {
+            (Some(a),Some(b))=>unify(a,b),
+            (Some(a),None)|(None,Some(a))=>Some(a),
+            (None,None)=>None,
+        }
+    }
+    /// Translates an expression that has to have a value.
+    fn value(&mut self,expr:&Expr<'input>)->TranslateResult<'input,(ir::Value,Prim)> {
+        match self.expr(expr)? {
+            Some(value)=>Ok(value),
+            None=>self.shared.unsupported(expr.span(),"using this as a value"),
+        }
+    }
+    /// Translates an expression and converts its value to `prim`.
+    fn expect(&mut self,expr:&Expr<'input>,prim:Prim)->TranslateResult<'input,ir::Value> {
+        let (value,from)=self.value(expr)?;
+        return self.convert(value,from,prim,expr.span());
+    }
+    fn operands(&mut self,inner:&[Expr<'input>;2],span:(Location,Location))->TranslateResult<'input,(ir::Value,ir::Value,Prim)> {
+        let Some((prim,_))=self.operand_prim(inner) else {
+            return self.shared.unsupported(span,"this operation");
+        };
+        let left=self.expect(&inner[0],prim)?;
+        let right=self.expect(&inner[1],prim)?;
+        return Ok((left,right,prim));
+    }
+    fn binary(&mut self,op:Op,inner:&[Expr<'input>;2],span:(Location,Location))->TranslateResult<'input,(ir::Value,Prim)> {
+        if let Op::ShiftLeft|Op::ShiftRight=op {
+            let (left,prim)=self.value(&inner[0])?;
+            let (right,amount)=self.value(&inner[1])?;
+            if !prim.is_integer()||!amount.is_integer() {
+                self.raise(span,format!("`{}` can only be used on integers",op.symbol()));
+                return Ok((self.zero(prim),prim));
+            }
+            return Ok((self.arithmetic(op,left,right,prim,span)?,prim));
+        }
+        let (left,right,prim)=self.operands(inner,span)?;
+        return Ok((self.arithmetic(op,left,right,prim,span)?,prim));
+    }
+    fn arithmetic(&mut self,op:Op,left:ir::Value,right:ir::Value,prim:Prim,span:(Location,Location))->TranslateResult<'input,ir::Value> {
+        if prim.is_float() {
+            return Ok(match op {
+                Op::Add=>self.builder.ins().fadd(left,right),
+                Op::Sub=>self.builder.ins().fsub(left,right),
+                Op::Mul=>self.builder.ins().fmul(left,right),
+                Op::Div=>self.builder.ins().fdiv(left,right),
+                Op::Mod=>{
+                    let left=self.convert(left,prim,Prim::DoubleFloat,span)?;
+                    let right=self.convert(right,prim,Prim::DoubleFloat,span)?;
+                    let Some(result)=self.call_runtime("fmod",&[left,right],Some(types::F64))? else {
+                        return self.shared.error(span,"Internal compiler error: `fmod` didn't return anything");
+                    };
+                    self.convert(result,Prim::DoubleFloat,prim,span)?
+                },
+                _=>{
+                    self.raise(span,format!("`{}` can only be used on integers",op.symbol()));
+                    self.zero(prim)
+                },
+            });
+        }
+        if let (Op::Add,Prim::String)=(op,prim) {
+            let Some(joined)=self.call_runtime("cppl_concat",&[left,right],Some(POINTER))? else {
+                return self.shared.error(span,"Internal compiler error: `cppl_concat` didn't return anything");
+            };
+            return Ok(joined);
+        }
+        if !prim.is_integer() {
+            self.raise(span,format!("`{}` can not be used on these values",op.symbol()));
+            return Ok(self.zero(prim));
+        }
+        if let Op::Div|Op::Mod=op {
+            let divide=self.builder.create_block();
+            let by_zero=self.builder.create_block();
+            self.builder.ins().brif(right,divide,&[],by_zero,&[]);
+            self.builder.switch_to_block(by_zero);
+            self.raise(span,"attempt to divide by zero");
+            self.builder.switch_to_block(divide);
+        }
+        // Dividing the smallest `Int` by -1 overflows, which wraps instead of trapping.
+        if let (Op::Div|Op::Mod,Prim::Int)=(op,prim) {
+            let minus_one=self.builder.ins().icmp_imm(IntCC::Equal,right,-1);
+            let one=self.builder.ins().iconst(types::I64,1);
+            let divisor=self.builder.ins().select(minus_one,one,right);
+            return Ok(if let Op::Div=op {
+                let quotient=self.builder.ins().sdiv(left,divisor);
+                let negated=self.builder.ins().ineg(left);
+                self.builder.ins().select(minus_one,negated,quotient)
+            } else {
+                let remainder=self.builder.ins().srem(left,divisor);
+                let zero=self.builder.ins().iconst(types::I64,0);
+                self.builder.ins().select(minus_one,zero,remainder)
+            });
+        }
+        let ins=self.builder.ins();
+        return Ok(match op {
+            Op::Add=>ins.iadd(left,right),
+            Op::Sub=>ins.isub(left,right),
+            Op::Mul=>ins.imul(left,right),
+            Op::BitAnd=>ins.band(left,right),
+            Op::BitOr=>ins.bor(left,right),
+            Op::BitXor=>ins.bxor(left,right),
+            // The amount is wrapped to the size of the integer.
+            Op::ShiftLeft=>ins.ishl(left,right),
+            Op::ShiftRight if prim==Prim::Int=>ins.sshr(left,right),
+            Op::ShiftRight=>ins.ushr(left,right),
+            Op::Div=>ins.udiv(left,right),
+            Op::Mod=>ins.urem(left,right),
+        });
+    }
+    fn compare(&mut self,expr:&Expr<'input>,inner:&[Expr<'input>;2])->TranslateResult<'input,ir::Value> {
+        use Expr::*;
+        let span=expr.span();
+        let cc=match expr {
+            Equal{..}=>Comparison::Equal,
+            NotEqual{..}=>Comparison::NotEqual,
+            GreaterEqual{..}=>Comparison::GreaterEqual,
+            LessEqual{..}=>Comparison::LessEqual,
+            Greater{..}=>Comparison::Greater,
+            _=>Comparison::Less,
+        };
+        let (left,right,prim)=self.operands(inner,span)?;
+        if prim==Prim::Slice {
+            return self.shared.unsupported(span,"comparing slices");
+        }
+        // Floats that aren't ordered, like NaN, can only be checked for equality.
+        if prim.is_float()&&!matches!(cc,Comparison::Equal|Comparison::NotEqual) {
+            let unordered=self.builder.ins().fcmp(FloatCC::Unordered,left,right);
+            let compare=self.builder.create_block();
+            let fail=self.builder.create_block();
+            self.builder.ins().brif(unordered,fail,&[],compare,&[]);
+            self.builder.switch_to_block(fail);
+            self.raise(span,"these values can not be compared");
+            self.builder.switch_to_block(compare);
+        }
+        return self.compare_values(cc,left,right,prim);
+    }
+    /// Calls a function, stopping if too many calls are nested.
+    fn call(&mut self,signature:Signature,args:Vec<ir::Value>,span:(Location,Location))->Option<(ir::Value,Prim)> {
+        let depth=self.builder.ins().load(types::I32,MemFlags::trusted(),self.context,DEPTH_OFFSET);
+        let too_deep=self.builder.ins().icmp_imm(IntCC::UnsignedGreaterThanOrEqual,depth,MAX_CALL_DEPTH);
+        let overflow=self.builder.create_block();
+        let call=self.builder.create_block();
+        self.builder.ins().brif(too_deep,overflow,&[],call,&[]);
+        self.builder.switch_to_block(overflow);
+        self.raise(span,format!("stack overflow: more than {} calls are nested",MAX_CALL_DEPTH));
+        self.builder.switch_to_block(call);
+        let deeper=self.builder.ins().iadd_imm(depth,1);
+        self.builder.ins().store(MemFlags::trusted(),deeper,self.context,DEPTH_OFFSET);
+        let function=self.shared.module.declare_func_in_func(signature.id,self.builder.func);
+        let mut values=Vec::with_capacity(args.len()+1);
+        values.push(self.context);
+        values.extend(args);
+        let call=self.builder.ins().call(function,&values);
+        let result=self.builder.inst_results(call).first().copied();
+        self.builder.ins().store(MemFlags::trusted(),depth,self.context,DEPTH_OFFSET);
+        self.check_error();
+        return result.zip(signature.ret);
+    }
+    /// Translates the arguments of a call to `signature`. `this` is the value the method is
+    /// called on.
+    fn args(&mut self,signature:&Signature,this:Option<&Expr<'input>>,args:&[Expr<'input>])->TranslateResult<'input,Vec<ir::Value>> {
+        let mut values=Vec::with_capacity(signature.params.len());
+        for (arg,prim) in this.into_iter().chain(args).zip(signature.params.iter()) {
+            values.push(self.expect(arg,*prim)?);
+        }
+        return Ok(values);
+    }
+    /// Whether the first parameter of `function` is `this`, and whether it is `mut this`.
+    fn this_param(&self,function:Scope)->Option<bool> {
+        let scopes=self.shared.scopes;
+        let Some(VarScopeItem::Function{params,..})=scopes.get(function) else {return None};
+        match params.first().and_then(|p|scopes.get(*p)) {
+            Some(VarScopeItem::Parameter{name:"this",mutable,..})=>Some(*mutable),
+            _=>None,
+        }
+    }
+    /// Translates an expression. Expressions without a value, like blocks, give `None`.
+    fn expr(&mut self,expr:&Expr<'input>)->TranslateResult<'input,Option<(ir::Value,Prim)>> {
+        use Expr::*;
+        let span=expr.span();
+        let scopes=self.shared.scopes;
+        Ok(Some(match expr {
+            Data{data,..}=>self.literal(data)?,
+            Var{path,..}|AssociatedPath{path,..}=>{
+                let (var,prim)=self.var(span,*path)?;
+                (self.builder.use_var(var),prim)
+            },
+            FunctionCall{path,args,..}=>match scopes.get(*path) {
+                Some(VarScopeItem::Function{..})=>{
+                    let signature=self.shared.function(*path)?;
+                    let args=self.args(&signature,None,args)?;
+                    return Ok(self.call(signature,args,span));
+                },
+                Some(VarScopeItem::BuiltinFunction{name,..})=>return self.builtin(name,args,span),
+                Some(VarScopeItem::EnumVariant{..})=>return self.shared.unsupported(span,"enum variants"),
+                _=>return self.shared.unsupported(span,"calls to functions stored in variables"),
+            },
+            MethodCall{from,args,method:Some(method),..}=>{
+                let this=match self.this_param(*method) {
+                    Some(true)=>return self.shared.unsupported(span,"methods taking `mut this`"),
+                    Some(false)=>Some(&**from),
+                    None=>None,
+                };
+                let signature=self.shared.function(*method)?;
+                let args=self.args(&signature,this,args)?;
+                return Ok(self.call(signature,args,span));
+            },
+            MethodCall{..}=>return self.shared.unsupported(span,"calls to functions stored in fields"),
+            FieldAccess{..}|ObjectCreation{..}=>return self.shared.unsupported(span,"objects"),
+            Add{inner,..}=>self.binary(Op::Add,inner,span)?,
+            Sub{inner,..}=>self.binary(Op::Sub,inner,span)?,
+            Mul{inner,..}=>self.binary(Op::Mul,inner,span)?,
+            Div{inner,..}=>self.binary(Op::Div,inner,span)?,
+            Mod{inner,..}=>self.binary(Op::Mod,inner,span)?,
+            BitAnd{inner,..}=>self.binary(Op::BitAnd,inner,span)?,
+            BitOr{inner,..}=>self.binary(Op::BitOr,inner,span)?,
+            BitXor{inner,..}=>self.binary(Op::BitXor,inner,span)?,
+            ShiftLeft{inner,..}=>self.binary(Op::ShiftLeft,inner,span)?,
+            ShiftRight{inner,..}=>self.binary(Op::ShiftRight,inner,span)?,
+            Negate{inner,..}=>{
+                let (value,prim)=self.value(inner)?;
+                if prim.is_float() {
+                    (self.builder.ins().fneg(value),prim)
+                } else if prim.is_integer() {
+                    (self.builder.ins().ineg(value),prim)
+                } else {
+                    self.raise(span,"only numbers can be negated");
+                    (self.zero(prim),prim)
+                }
+            },
+            Equal{inner,..}|
+                NotEqual{inner,..}|
+                GreaterEqual{inner,..}|
+                LessEqual{inner,..}|
+                Greater{inner,..}|
+                Less{inner,..}=>(self.compare(expr,inner)?,Prim::Bool),
+            And{inner,..}|Or{inner,..}=>{
+                // `a and b` is `if a {b} else {false}` and `a or b` is `if a {true} else {b}`.
+                let first=self.expect(&inner[0],Prim::Bool)?;
+                let second=self.builder.create_block();
+                let end=self.builder.create_block();
+                let result=self.builder.append_block_param(end,types::I8);
+                if let And{..}=expr {
+                    let no=self.builder.ins().iconst(types::I8,0);
+                    self.builder.ins().brif(first,second,&[],end,&[no]);
+                } else {
+                    let yes=self.builder.ins().iconst(types::I8,1);
+                    self.builder.ins().brif(first,end,&[yes],second,&[]);
+                }
+                self.builder.switch_to_block(second);
+                let value=self.expect(&inner[1],Prim::Bool)?;
+                self.builder.ins().jump(end,&[value]);
+                self.builder.switch_to_block(end);
+                (result,Prim::Bool)
+            },
+            Not{inner,..}=>{
+                let value=self.expect(inner,Prim::Bool)?;
+                (self.builder.ins().icmp_imm(IntCC::Equal,value,0),Prim::Bool)
+            },
+            // Every value that can be compiled has one type known before running it.
+            IsType{to_test,ty,..}=>{
+                let (_,prim)=self.value(to_test)?;
+                let prim_ty=prim.ty().unwrap_or_else(||expr_type(self.shared.filename,scopes,to_test));
+                let is=scopes.check_assignable(&prim_ty,ty).is_ok();
+                (self.builder.ins().iconst(types::I8,is as i64),Prim::Bool)
+            },
+            AnonFunction{..}=>return self.shared.unsupported(span,"anonymous functions"),
+            Ref{..}|RefMut{..}|Deref{..}=>return self.shared.unsupported(span,"references and pointers"),
+            List{..}=>return self.shared.unsupported(span,"slice literals"),
+            Slice{..}=>return self.shared.unsupported(span,"slicing"),
+            Index{from,index,..}=>{
+                let Some(prim)=self.item_prim(from) else {
+                    return self.shared.unsupported(span,"indexing this");
+                };
+                let items=self.expect(from,Prim::Slice)?;
+                let index_span=index.span();
+                let (index,index_prim)=self.value(index)?;
+                if !index_prim.is_integer() {
+                    self.raise(index_span,"indexes have to be integers");
+                    return Ok(Some((self.zero(prim),prim)));
+                }
+                // Negative indexes are huge as unsigned numbers, so one check catches both.
+                let index=self.convert(index,index_prim,Prim::Int,index_span)?;
+                let len=self.builder.ins().load(types::I64,MemFlags::trusted(),items,0);
+                let out_of_bounds=self.builder.ins().icmp(IntCC::UnsignedGreaterThanOrEqual,index,len);
+                let fail=self.builder.create_block();
+                let load=self.builder.create_block();
+                self.builder.ins().brif(out_of_bounds,fail,&[],load,&[]);
+                self.builder.switch_to_block(fail);
+                self.raise(index_span,"index out of bounds");
+                self.builder.switch_to_block(load);
+                (self.load_item(items,index,prim),prim)
+            },
+            ForLoop{var,iterator,block,..}=>{
+                let Some(prim)=self.item_prim(iterator) else {
+                    return self.shared.unsupported(span,"iterating over this");
+                };
+                let items=self.expect(iterator,Prim::Slice)?;
+                let len=self.builder.ins().load(types::I64,MemFlags::trusted(),items,0);
+                let index=self.new_var(types::I64);
+                let zero=self.builder.ins().iconst(types::I64,0);
+                self.builder.def_var(index,zero);
+                let header=self.builder.create_block();
+                let body=self.builder.create_block();
+                let step=self.builder.create_block();
+                let exit=self.builder.create_block();
+                self.builder.ins().jump(header,&[]);
+                self.builder.switch_to_block(header);
+                let i=self.builder.use_var(index);
+                let more=self.builder.ins().icmp(IntCC::UnsignedLessThan,i,len);
+                self.builder.ins().brif(more,body,&[],exit,&[]);
+                self.builder.switch_to_block(body);
+                let item=self.load_item(items,i,prim);
+                if let Some(param)=scopes.get(*block).and_then(|b|b.get_var(var)) {
+                    self.define(param,prim,item);
+                }
+                self.loops.push(Loop{block:*block,header:step,exit,ty:None});
+                self.block(*block)?;
+                self.loops.pop();
+                self.builder.ins().jump(step,&[]);
+                self.builder.switch_to_block(step);
+                let i=self.builder.use_var(index);
+                let next=self.builder.ins().iadd_imm(i,1);
+                self.builder.def_var(index,next);
+                self.builder.ins().jump(header,&[]);
+                self.builder.switch_to_block(exit);
+                return Ok(None);
+            },
+            Block{block,..}|Unsafe{block,..}=>{
+                self.block(*block)?;
+                return Ok(None);
+            },
+            ForeverLoop{block,..}=>{
+                let ty=match expr_type(self.shared.filename,scopes,expr) {
+                    Type::Unknown|Type::Never=>None,
+                    _=>match self.static_prim(expr) {
+                        Some((prim,_))=>Some(prim),
+                        None=>return self.shared.unsupported(span,"loops with this value"),
+                    },
+                };
+                let header=self.builder.create_block();
+                let exit=self.builder.create_block();
+                let result=ty.map(|prim|self.builder.append_block_param(exit,prim.ir()));
+                self.builder.ins().jump(header,&[]);
+                self.builder.switch_to_block(header);
+                self.loops.push(Loop{block:*block,header,exit,ty});
+                self.block(*block)?;
+                self.loops.pop();
+                self.builder.ins().jump(header,&[]);
+                self.builder.switch_to_block(exit);
+                return Ok(result.zip(ty));
+            },
+            WhileLoop{condition,block,..}=>{
+                let header=self.builder.create_block();
+                let body=self.builder.create_block();
+                let exit=self.builder.create_block();
+                self.builder.ins().jump(header,&[]);
+                self.builder.switch_to_block(header);
+                let condition=self.expect(condition,Prim::Bool)?;
+                self.builder.ins().brif(condition,body,&[],exit,&[]);
+                self.builder.switch_to_block(body);
+                self.loops.push(Loop{block:*block,header,exit,ty:None});
+                self.block(*block)?;
+                self.loops.pop();
+                self.builder.ins().jump(header,&[]);
+                self.builder.switch_to_block(exit);
+                return Ok(None);
+            },
+            Match{block,..}=>return self.translate_match(expr,block),
+            If{branches,else_block,..}=>{
+                // Only an `if` with an `else` has a value.
+                let ty=else_block.and_then(|_|self.static_prim(expr)).map(|(prim,_)|prim);
+                let end=self.builder.create_block();
+                let result=ty.map(|prim|self.builder.append_block_param(end,prim.ir()));
+                for (condition,block) in branches.iter() {
+                    let condition=self.expect(condition,Prim::Bool)?;
+                    let then=self.builder.create_block();
+                    let next=self.builder.create_block();
+                    self.builder.ins().brif(condition,then,&[],next,&[]);
+                    self.builder.switch_to_block(then);
+                    let args=self.branch(*block,ty)?;
+                    self.builder.ins().jump(end,&args);
+                    self.builder.switch_to_block(next);
+                }
+                let args=match else_block {
+                    Some(block)=>self.branch(*block,ty)?,
+                    None=>Vec::new(),
+                };
+                self.builder.ins().jump(end,&args);
+                self.builder.switch_to_block(end);
+                return Ok(result.zip(ty));
+            },
+            UnknownFunctionCall{path,..}|UnknownAssociatedPath{path,..}=>return self.shared.error(span,format!("`{}` is not defined",path.join("::"))),
+            UnknownVar{name,..}=>return self.shared.error(span,format!("`{}` is not defined",name)),
+        }))
+    }
+    /// Calls a builtin function. The runtime does most of the work.
+    fn builtin(&mut self,name:&str,args:&[Expr<'input>],span:(Location,Location))->TranslateResult<'input,Option<(ir::Value,Prim)>> {
+        let function=match name {
+            "print"=>"cppl_print",
+            "println"=>"cppl_println",
+            "eprint"=>"cppl_eprint",
+            "eprintln"=>"cppl_eprintln",
+            "read_line"=>{
+                let Some(line)=self.call_runtime("cppl_read_line",&[],Some(POINTER))? else {
+                    return self.shared.error(span,"Internal compiler error: `cppl_read_line` didn't return anything");
+                };
+                return Ok(Some((line,Prim::String)));
+            },
+            "Arguments"=>{
+                let args=self.builder.ins().load(POINTER,MemFlags::trusted(),self.context,ARGS_OFFSET);
+                return Ok(Some((args,Prim::Slice)));
+            },
+            _=>return self.shared.unsupported(span,format_args!("the builtin function `{}`",name)),
+        };
+        let text=match args.first() {
+            Some(arg)=>self.expect(arg,Prim::String)?,
+            None=>self.string("")?,
+        };
+        self.call_runtime(function,&[text],None)?;
+        return Ok(None);
+    }
+    fn literal(&mut self,data:&Data<'input>)->TranslateResult<'input,(ir::Value,Prim)> {
+        if let Data::String{s,..}=data {
+            return Ok((self.string(s)?,Prim::String));
+        }
+        let ins=self.builder.ins();
+        Ok(match data {
+            Data::GenericNumber{negative,data,..}=>{
+                let digits=data.replace('_',"");
+                match digits.parse::<i64>() {
+                    Ok(n)=>(ins.iconst(types::I64,if *negative {-n} else {n}),Prim::Int),
+                    Err(_)=>(ins.iconst(types::I64,digits.parse::<u64>().unwrap_or(u64::MAX) as i64),Prim::Uint),
+                }
+            },
+            Data::GenericFloat{negative,data,..}=>{
+                let n=data.replace('_',"").parse::<f64>().unwrap_or_default();
+                (ins.f64const(if *negative {-n} else {n}),Prim::DoubleFloat)
+            },
+            Data::UInt{data,..}=>(ins.iconst(types::I64,*data as i64),Prim::Uint),
+            Data::Int{data,..}=>(ins.iconst(types::I64,*data),Prim::Int),
+            Data::Float{data,..}=>(ins.f32const(*data),Prim::Float),
+            Data::LargeFloat{data,..}=>(ins.f64const(*data),Prim::DoubleFloat),
+            Data::Char{data,..}=>(ins.iconst(types::I32,*data as i64),Prim::Char),
+            Data::Bool{data,..}=>(ins.iconst(types::I8,*data as i64),Prim::Bool),
+            Data::String{..}=>unreachable!(),
+        })
+    }
+    /// Matches are only translated on values that fit in a register, so the patterns are all
+    /// comparisons and variables.
+    fn translate_match(&mut self,expr:&Expr<'input>,block:&Match<'input>)->TranslateResult<'input,Option<(ir::Value,Prim)>> {
+        let (value,prim)=self.value(&block.to_match)?;
+        let ty=self.static_prim(expr).map(|(prim,_)|prim);
+        let end=self.builder.create_block();
+        let result=ty.map(|prim|self.builder.append_block_param(end,prim.ir()));
+        for (pattern,leaf) in block.leafs.iter() {
+            let span=pattern.span();
+            let cc=match pattern {
+                MatchPattern::Data{..}|MatchPattern::Equal{..}=>Some(Comparison::Equal),
+                MatchPattern::NotEqual{..}=>Some(Comparison::NotEqual),
+                MatchPattern::GreaterEqual{..}=>Some(Comparison::GreaterEqual),
+                MatchPattern::LessEqual{..}=>Some(Comparison::LessEqual),
+                MatchPattern::Greater{..}=>Some(Comparison::Greater),
+                MatchPattern::Less{..}=>Some(Comparison::Less),
+                MatchPattern::Var{var,..}=>{
+                    self.define(*var,prim,value);
+                    None
+                },
+                MatchPattern::MethodCall{..}=>return self.shared.unsupported(span,"method patterns"),
+                MatchPattern::Structure{..}=>return self.shared.unsupported(span,"structure patterns"),
+                MatchPattern::IsType{..}=>return self.shared.unsupported(span,"type patterns"),
+            };
+            let next=self.builder.create_block();
+            if let Some(cc)=cc {
+                let other=match pattern {
+                    MatchPattern::Data{inner,..}=>{
+                        let (other,from)=self.literal(inner)?;
+                        self.convert(other,from,prim,span)?
+                    },
+                    _=>match pattern.exprs().first() {
+                        Some(inner)=>self.expect(inner,prim)?,
+                        None=>return self.shared.error(span,"Internal compiler error: comparison pattern without a value"),
+                    },
+                };
+                let matched=self.compare_values(cc,value,other,prim)?;
+                let arm=self.builder.create_block();
+                self.builder.ins().brif(matched,arm,&[],next,&[]);
+                self.builder.switch_to_block(arm);
+            }
+            let args=match ty {
+                Some(ty)=>vec![self.expect(leaf,ty)?],
+                None=>{
+                    self.expr(leaf)?;
+                    Vec::new()
+                },
+            };
+            self.builder.ins().jump(end,&args);
+            self.builder.switch_to_block(next);
+        }
+        self.raise(expr.span(),"no pattern matched the value");
+        self.builder.switch_to_block(end);
+        return Ok(result.zip(ty));
+    }
+    fn compare_values(&mut self,cc:Comparison,mut left:ir::Value,mut right:ir::Value,mut prim:Prim)->TranslateResult<'input,ir::Value> {
+        // Strings are compared by the runtime, which gives -1, 0 or 1 like `Ordering`.
+        if prim==Prim::String {
+            let Some(ordering)=self.call_runtime("cppl_string_compare",&[left,right],Some(types::I64))? else {
+                return self.shared.error(Default::default(),"Internal compiler error: `cppl_string_compare` didn't return anything");
+            };
+            left=ordering;
+            right=self.builder.ins().iconst(types::I64,0);
+            prim=Prim::Int;
+        }
+        if prim.is_float() {
+            let cc=match cc {
+                Comparison::Equal=>FloatCC::Equal,
+                Comparison::NotEqual=>FloatCC::NotEqual,
+                Comparison::GreaterEqual=>FloatCC::GreaterThanOrEqual,
+                Comparison::LessEqual=>FloatCC::LessThanOrEqual,
+                Comparison::Greater=>FloatCC::GreaterThan,
+                Comparison::Less=>FloatCC::LessThan,
+            };
+            return Ok(self.builder.ins().fcmp(cc,left,right));
+        }
+        let signed=prim==Prim::Int;
+        let cc=match cc {
+            Comparison::Equal=>IntCC::Equal,
+            Comparison::NotEqual=>IntCC::NotEqual,
+            Comparison::GreaterEqual if signed=>IntCC::SignedGreaterThanOrEqual,
+            Comparison::LessEqual if signed=>IntCC::SignedLessThanOrEqual,
+            Comparison::Greater if signed=>IntCC::SignedGreaterThan,
+            Comparison::Less if signed=>IntCC::SignedLessThan,
+            Comparison::GreaterEqual=>IntCC::UnsignedGreaterThanOrEqual,
+            Comparison::LessEqual=>IntCC::UnsignedLessThanOrEqual,
+            Comparison::Greater=>IntCC::UnsignedGreaterThan,
+            Comparison::Less=>IntCC::UnsignedLessThan,
+        };
+        return Ok(self.builder.ins().icmp(cc,left,right));
+    }
+}
+
+
+/// The variable a narrowed variable narrows.
+fn original(scopes:&Scopes,mut var:Scope)->Scope {
+    while let Some(&VarScopeItem::Narrowed{original,..})=scopes.get(var) {
+        var=original;
+    }
+    return var;
+}
+/// The type two operands are converted to, like `unify` in the interpreter.
+fn unify(a:(Prim,bool),b:(Prim,bool))->Option<(Prim,bool)> {
+    Some(match (a,b) {
+        ((prim,false),_)|(_,(prim,false))=>(prim,false),
+        ((a,true),(b,true)) if a==b=>(a,true),
+        ((a,true),(b,true)) if a.is_float()||b.is_float()=>(Prim::DoubleFloat,true),
+        _=>(Prim::Uint,true),
+    })
+}
+fn stmt_span(stmt:&Statement)->(Location,Location) {
+    match stmt {
+        Statement::FunctionDef{start,end,..}|
+            Statement::FunctionSig{start,end,..}|
+            Statement::InterfaceDef{start,end,..}|
+            Statement::TypeDef{start,end,..}|
+            Statement::VarDef{start,end,..}|
+            Statement::StaticVarDef{start,end,..}|
+            Statement::ConstVarDef{start,end,..}|
+            Statement::VarAssign{start,end,..}|
+            Statement::UnknownVarAssign{start,end,..}|
+            Statement::PlaceAssign{start,end,..}|
+            Statement::CompoundAssign{start,end,..}|
+            Statement::Expr{start,end,..}|
+            Statement::Return{start,end,..}|
+            Statement::Continue{start,end,..}|
+            Statement::Break{start,end,..}|
+            Statement::Enum{start,end,..}|
+            Statement::Impl{start,end,..}=>(*start,*end),
+    }
+}
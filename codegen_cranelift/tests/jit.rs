@@ -0,0 +1,90 @@
+//! Programs JIT compiled as a whole, and programs that can only have some of their functions
+//! compiled.
+use cppl_parser::parse;
+use cppl_ast::{
+    analyze,
+    refined::Scopes,
+};
+use cppl_codegen_cranelift::{
+    compile,
+    compile_functions,
+    run,
+};
+use cppl_interp::run_with_natives;
+
+
+/// The scopes of `source`, which has to pass analysis.
+fn analyzed(source:&str)->Scopes<'_> {
+    let (parsed,parse_errors)=parse("test.cppl",source);
+    assert!(parse_errors.is_empty(),"{:?}",parse_errors);
+    return match analyze("test.cppl",parsed) {
+        Ok((scopes,_))=>scopes,
+        Err(results)=>panic!("{:?}",results.errors.into_iter().map(|e|e.reason).collect::<Vec<_>>()),
+    };
+}
+const FIB:&str="fn fib[n:Int]:Int {
+    if n<2 {
+        return n
+    }
+    return fib(n-1)+fib(n-2)
+}
+";
+
+
+#[test]
+fn whole_programs() {
+    let source=format!("{}fn main[]:Int {{
+    mut total:=0
+    mut i:=0
+    while i<10 {{
+        total+<-fib(i)
+        i+<-1
+    }}
+    return total
+}}
+",FIB);
+    let scopes=analyzed(&source);
+    let program=compile("test.cppl",&scopes).map_err(|e|e.reason).unwrap();
+    assert_eq!(run(&program,&[]).map_err(|e|e.reason),Ok(88));
+}
+#[test]
+fn functions_on_their_own() {
+    let source=format!("{}type Pt<-{{pub v:Int}}
+fn get[p:Pt]:Int {{
+    return p.v
+}}
+fn div[a:Int,b:Int]:Int {{
+    return a/b
+}}
+fn main[]:Int {{
+    p:Pt={{pub v<-20}}
+    return fib(get(p))%200+div(10,p.v-19)
+}}
+",FIB);
+    let scopes=analyzed(&source);
+    let Err(err)=compile("test.cppl",&scopes) else {panic!("objects were compiled")};
+    assert_eq!(err.reason,"values of type `Pt` can't be compiled to native code yet");
+    let (functions,skipped)=compile_functions("test.cppl",&scopes,&[]).map_err(|e|e.reason).unwrap();
+    // `fib` and `div` are compiled, `get` and `main` use `Pt`.
+    assert_eq!(functions.len(),2);
+    assert_eq!(skipped.len(),2);
+    assert_eq!(run_with_natives("test.cppl",&scopes,&[],&functions).map_err(|e|e.reason),Ok(6765%200+10));
+}
+#[test]
+fn errors_in_compiled_functions() {
+    let source="type Pt<-{pub v:Int}
+fn div[a:Int,b:Int]:Int {
+    return a/b
+}
+fn main[]:Int {
+    p:Pt={pub v<-0}
+    return div(1,p.v)
+}
+";
+    let scopes=analyzed(source);
+    let (functions,_)=compile_functions("test.cppl",&scopes,&[]).map_err(|e|e.reason).unwrap();
+    assert_eq!(functions.len(),1);
+    let err=run_with_natives("test.cppl",&scopes,&[],&functions).unwrap_err();
+    assert_eq!(err.reason,"attempt to divide by zero");
+    assert_eq!(err.start.line,2);
+}
@@ -1,5 +1,6 @@
 use std::{
     fs::read_to_string,
+    path::Path,
     time::Instant,
 };
 use cppl_parser::parse;
@@ -7,40 +8,166 @@ use cppl_error::*;
 use cppl_ast::{
     analyze,
     AnalysisResults,
+    refined::Scopes,
 };
 
 
-fn main() {
-    let start=Instant::now();
-    let filename="example2.cppl";
-    let source=read_to_string(filename).unwrap();
-    match parse(filename,&source) {
-        Ok(parsed)=>{
-            match analyze(filename,parsed) {
-                Ok((refined,AnalysisResults{warnings,..}))=>{
-                    let warn_count=warnings.len();
-                    for warning in warnings {
-                        println!("{}",ContextualError::from((source.as_str(),warning)));
-                    }
-                    println!("{} generated {} warnings",filename,warn_count);
-                    println!("Refined scopes: {:#?}",refined);
+/// What runs the program once it passes analysis.
+#[derive(Copy,Clone,PartialEq)]
+enum Backend {
+    Interpreter,
+    Vm,
+    /// Native code from Cranelift, falling back to the interpreter for what it can't compile yet.
+    Jit,
+    /// An object file and an executable next to the source instead of running it.
+    Build,
+    /// C99 next to the source instead of running it.
+    EmitC,
+    /// C99 and an executable built from it with the system's C compiler.
+    BuildC,
+}
+struct Options {
+    backend:Backend,
+    /// Print the bytecode before running it.
+    disassemble:bool,
+}
+impl Options {
+    /// Reads the flags before the file name.
+    fn parse(args:&mut std::iter::Peekable<impl Iterator<Item=String>>)->Self {
+        let mut options=Options{backend:Backend::Interpreter,disassemble:false};
+        while let Some(flag)=args.next_if(|a|a.starts_with("--")) {
+            match flag.as_str() {
+                "--vm"=>options.backend=Backend::Vm,
+                "--jit"=>options.backend=Backend::Jit,
+                "--build"=>options.backend=Backend::Build,
+                "--emit-c"=>options.backend=Backend::EmitC,
+                "--build-c"=>options.backend=Backend::BuildC,
+                "--disassemble"=>{
+                    options.backend=Backend::Vm;
+                    options.disassemble=true;
                 },
-                Err(AnalysisResults{errors,warnings})=>{
-                    let warn_count=warnings.len();
-                    let err_count=errors.len();
-                    for warning in warnings {
-                        println!("{}",ContextualError::from((source.as_str(),warning)));
-                    }
-                    for err in errors {
-                        println!("{}",ContextualError::from((source.as_str(),err)));
-                    }
-                    println!("{} generated {} warnings",filename,warn_count);
-                    println!("{} generated {} errors",filename,err_count);
+                _=>{
+                    eprintln!("Unknown flag `{}`",flag);
+                    std::process::exit(2);
                 },
             }
+        }
+        return options;
+    }
+}
+
+
+fn run<'input>(filename:&'input str,source:&str,scopes:&Scopes<'input>,args:&[String],options:&Options)->Result<i32,Error<'input,String>> {
+    match options.backend {
+        Backend::Interpreter=>cppl_interp::run(filename,scopes,args),
+        Backend::Vm=>{
+            let program=cppl_vm::compile(filename,scopes)?;
+            if options.disassemble {
+                println!("{}",program);
+            }
+            cppl_vm::run(&program,args)
+        },
+        Backend::Jit=>match cppl_codegen_cranelift::compile(filename,scopes) {
+            Ok(program)=>cppl_codegen_cranelift::run(&program,args),
+            Err(mut err) if matches!(err.level,ErrorLevel::Compile)=>{
+                // Functions that can be compiled on their own still are, the rest is interpreted.
+                let (functions,skipped)=cppl_codegen_cranelift::compile_functions(filename,scopes,args)?;
+                if !skipped.iter().any(|s|s.start.index==err.start.index&&s.reason==err.reason) {
+                    err.level=ErrorLevel::Warning;
+                    println!("{}",ContextualError::from((source,err)));
+                }
+                for mut err in skipped {
+                    err.level=ErrorLevel::Warning;
+                    println!("{}",ContextualError::from((source,err)));
+                }
+                println!("{} can't be JIT compiled as a whole, running it with the interpreter and {} JIT compiled functions",filename,functions.len());
+                cppl_interp::run_with_natives(filename,scopes,args,&functions)
+            },
+            Err(err)=>Err(err),
         },
-        Err(e)=>println!("{}",ContextualError::from((source.as_str(),e))),
+        Backend::Build=>{
+            let path=Path::new(filename);
+            let object=path.with_extension("o");
+            let mut output=path.with_extension("");
+            // Never overwrite the source.
+            if output==path {
+                output.set_extension("out");
+            }
+            cppl_codegen_cranelift::build(filename,source,scopes,&object,&output)?;
+            println!("Built {}",output.display());
+            Ok(0)
+        },
+        Backend::EmitC=>{
+            let c_file=Path::new(filename).with_extension("c");
+            let code=cppl_codegen_c::translate(filename,source,scopes)?;
+            std::fs::write(&c_file,code).map_err(|e|Error::new_compile(filename,Location::default(),Location::default(),format!("could not write `{}`: {}",c_file.display(),e)))?;
+            println!("Wrote {}",c_file.display());
+            Ok(0)
+        },
+        Backend::BuildC=>{
+            let path=Path::new(filename);
+            let c_file=path.with_extension("c");
+            let mut output=path.with_extension("");
+            // Never overwrite the source.
+            if output==path {
+                output.set_extension("out");
+            }
+            cppl_codegen_c::build(filename,source,scopes,&c_file,&output)?;
+            println!("Built {}",output.display());
+            Ok(0)
+        },
+    }
+}
+fn main() {
+    let start=Instant::now();
+    let mut args=std::env::args().skip(1).peekable();
+    let options=Options::parse(&mut args);
+    let filename=args.next().unwrap_or("example2.cppl".into());
+    let filename=filename.as_str();
+    // Everything after the file name is passed to the program.
+    let program_args=args.collect::<Vec<_>>();
+    let exit_code;
+    let source=read_to_string(filename).unwrap();
+    let (parsed,parse_errors)=parse(filename,&source);
+    if !parse_errors.is_empty() {
+        let err_count=parse_errors.len();
+        for err in parse_errors {
+            println!("{}",ContextualError::from((source.as_str(),err)));
+        }
+        println!("{} generated {} errors",filename,err_count);
+        exit_code=1;
+    } else {
+        match analyze(filename,parsed) {
+            Ok((refined,AnalysisResults{warnings,..}))=>{
+                let warn_count=warnings.len();
+                for warning in warnings {
+                    println!("{}",ContextualError::from((source.as_str(),warning)));
+                }
+                println!("{} generated {} warnings",filename,warn_count);
+                match run(filename,&source,&refined,&program_args,&options) {
+                    Ok(code)=>exit_code=code,
+                    Err(err)=>{
+                        println!("{}",ContextualError::from((source.as_str(),err)));
+                        exit_code=101;
+                    },
+                }
+            },
+            Err(AnalysisResults{errors,warnings})=>{
+                let warn_count=warnings.len();
+                let err_count=errors.len();
+                for warning in warnings {
+                    println!("{}",ContextualError::from((source.as_str(),warning)));
+                }
+                for err in errors {
+                    println!("{}",ContextualError::from((source.as_str(),err)));
+                }
+                println!("{} generated {} warnings",filename,warn_count);
+                println!("{} generated {} errors",filename,err_count);
+                exit_code=1;
+            },
+        }
     }
     let elapsed=start.elapsed();
     println!("Elapsed time: {:?}",elapsed);
+    std::process::exit(exit_code);
 }
@@ -0,0 +1,413 @@
+//! Programs that have to fail analysis, and the errors they have to fail with.
+use cppl_parser::parse;
+use cppl_ast::analyze;
+
+
+/// The messages of every analysis error in `source`, which has to parse.
+fn analysis_errors(source:&str)->Vec<String> {
+    let (parsed,parse_errors)=parse("test.cppl",source);
+    assert!(parse_errors.is_empty(),"{:?}",parse_errors);
+    return match analyze("test.cppl",parsed) {
+        Ok(_)=>Vec::new(),
+        Err(results)=>results.errors.into_iter().map(|e|e.reason).collect(),
+    };
+}
+fn assert_error(source:&str,expected:&str) {
+    let errors=analysis_errors(source);
+    assert!(errors.iter().any(|e|e==expected),"expected `{}`, found {:?}",expected,errors);
+}
+fn assert_ok(source:&str) {
+    let errors=analysis_errors(source);
+    assert!(errors.is_empty(),"{:?}",errors);
+}
+
+
+#[test]
+fn if_values() {
+    assert_error(
+        "fn main[]:Int {\n    c:=true\n    return 1+if c {\n        1\n    }\n}\n",
+        "`if` without `else` does not have a value",
+    );
+    assert_error(
+        "fn main[]:Int {\n    c:=true\n    z:=if c {\n        1\n    } else {\n        \"a\"\n    }\n    return 0\n}\n",
+        "mismatched types: expected `{number}`, found `String`",
+    );
+    assert_error(
+        "fn main[]:Int {\n    c:=true\n    z:Int=if c {\n        1\n    } else {\n        x:=2\n    }\n    return z\n}\n",
+        "expected this branch to end with a value",
+    );
+    assert_error(
+        "fn main[]:Int {\n    c:=true\n    return if c {\n        1\n    } else {\n        false\n    }\n}\n",
+        "mismatched types: expected `Int`, found `Bool`",
+    );
+    assert_ok("fn main[]:Int {\n    c:=true\n    z:=if c {\n        x:=1\n        x+1\n    } else if false {\n        return 3\n    } else {\n        4\n    }\n    s:String=if z>2 {\n        \"big\"\n    } else {\n        \"small\"\n    }\n    return z\n}\n");
+}
+#[test]
+fn if_statements() {
+    assert_ok("fn main[]:Int {\n    c:=true\n    if c {\n        return 1\n    }\n    match c {\n        true=>if c {\n            return 2\n        },\n        false=>0,\n    }\n    return 0\n}\n");
+}
+/// An interface with a required and a default function, and two types implementing it.
+const SHAPES:&str="interface Shape {
+    fn area[this]:Int
+    fn scaled[this,by:Int]:Int {
+        return this.area()*by
+    }
+}
+type Square<-{pub side:Int}
+type Rect<-{pub w:Int,pub h:Int}
+impl Shape for Square {
+    fn area[this]:Int {
+        s:=this.side
+        return s*s
+    }
+}
+impl Shape for Rect {
+    fn area[this]:Int {
+        w:=this.w
+        h:=this.h
+        return w*h
+    }
+}
+";
+#[test]
+fn methods_on_any_receiver() {
+    assert_ok("type P<-{pub v:Int}
+impl P {
+    fn get[this]:Int {
+        return this.v
+    }
+    fn me[this]:P {
+        return {pub v<-this.v}
+    }
+}
+type Q<-{pub p:P}
+fn mk[]:P {
+    return {pub v<-4}
+}
+fn main[]:Int {
+    p:P={pub v<-1}
+    q:Q={pub p<-p}
+    t:=mk()
+    return q.p.get()+mk().get()+p.me().get()+t.get()
+}
+");
+}
+#[test]
+fn interface_methods() {
+    assert_ok(&format!("{}fn total[s:Shape]:Int {{
+    return s.area()+s.scaled(2)
+}}
+",SHAPES));
+    assert_error(
+        &format!("{}fn total[s:Shape]:Int {{\n    return s.bogus(1,2,3)\n}}\n",SHAPES),
+        "no method named `bogus` found for `Shape`",
+    );
+    assert_error(
+        &format!("{}fn total[s:Shape]:Int {{\n    return s.area(1)\n}}\n",SHAPES),
+        "expected 0 arguments, found 1",
+    );
+    assert_error(
+        &format!("{}fn total[s:Shape]:Int {{\n    return s.scaled()\n}}\n",SHAPES),
+        "expected 1 arguments, found 0",
+    );
+    assert_error(
+        &format!("{}fn total[s:Shape]:Int {{\n    return s.area()+s.scaled(true)\n}}\n",SHAPES),
+        "mismatched types: expected `Int`, found `Bool`",
+    );
+}
+#[test]
+fn unknown_methods() {
+    assert_error(
+        "type P<-{pub v:Int}
+impl P {
+    fn get[this]:Int {
+        return this.v
+    }
+}
+fn mk[]:P {
+    return {pub v<-4}
+}
+fn main[]:Int {
+    return mk().get(1)
+}
+",
+        "expected 0 arguments, found 1",
+    );
+    assert_error(
+        "fn main[]:Int {\n    x:=true\n    return x.bogus()\n}\n",
+        "no method named `bogus` found for `Bool`",
+    );
+}
+#[test]
+fn numbers_inside_other_types() {
+    assert_ok("fn take[r:&mut Int]:Int {
+    return *r
+}
+fn look[r:&Int]:Int {
+    return *r
+}
+fn main[]:Int {
+    mut x:=1
+    a:=&mut x
+    b:=a
+    z:=2
+    r:=&z
+    w:=&5
+    l:=[[1,2],[3]]
+    return take(b)+look(r)+look(w)+l[0][1]
+}
+");
+}
+#[test]
+fn object_types_are_exact() {
+    assert_error(
+        "type P<-{pub v:Int}
+fn main[]:Int {
+    p:P={pub v<-1,pub w<-2}
+    return 0
+}
+",
+        "mismatched types: `{pub v:{number},pub w:{number}}` is not a `P`: `{pub v:{number},pub w:{number}}` has the field `w` which the exact type `{pub v:Int}` does not",
+    );
+    assert_ok("type P<-{pub v:Int,...}
+fn get[p:P]:Int {
+    return p.v
+}
+fn main[]:Int {
+    return get({pub v<-1,pub w<-2})
+}
+");
+}
+/// A generic type with an impl, and a generic enum.
+const GENERICS:&str="type Box[T]<-{pub mut v:T}
+impl [T] Box(T) {
+    fn get[this]:T {
+        return this.v
+    }
+    fn set[mut this,v:T] {
+        this.v<-v
+    }
+}
+enum Opt[T] {
+    Some(T),
+    None,
+}
+";
+#[test]
+fn generics() {
+    assert_ok(&format!("{}fn main[]:Int {{
+    mut b:Box(Int)={{pub mut v<-3}}
+    b.set(4)
+    o:Opt(Int)=Some(5)
+    n:Opt(Int)=None
+    xs:[Int]=[]
+    return b.get()+b.v
+}}
+",GENERICS));
+    assert_error(
+        &format!("{}fn main[]:Int {{\n    b:Box(Int)={{pub mut v<-3}}\n    s:String=b.get()\n    return 0\n}}\n",GENERICS),
+        "mismatched types: expected `String`, found `Int`",
+    );
+    assert_error(
+        &format!("{}fn main[]:Int {{\n    b:Box(Int)={{pub mut v<-3}}\n    s:String=b.v\n    return 0\n}}\n",GENERICS),
+        "mismatched types: expected `String`, found `Int`",
+    );
+    assert_error(
+        &format!("{}fn main[]:Int {{\n    mut b:Box(Int)={{pub mut v<-3}}\n    b.set(true)\n    return 0\n}}\n",GENERICS),
+        "mismatched types: expected `Int`, found `Bool`",
+    );
+    assert_error(
+        &format!("{}fn main[]:Int {{\n    o:Opt(Int)=Some(\"a\")\n    return 0\n}}\n",GENERICS),
+        "mismatched types: expected `Opt(Int)`, found `Some(String)`",
+    );
+}
+#[test]
+fn values_are_required() {
+    assert_error(
+        "fn f[] {\n}\nfn main[]:Int {\n    x:Int=f()\n    return 0\n}\n",
+        "expected a value of type `Int`",
+    );
+    assert_error(
+        "fn f[] {\n}\nfn main[]:Int {\n    return 1+f()\n}\n",
+        "expected a value",
+    );
+    assert_error(
+        "fn main[]:Int {\n    b:=true\n    return match b {\n        true=>1,\n        false=>{\n            x:=2\n        },\n    }\n}\n",
+        "expected a value of type `Int`",
+    );
+}
+#[test]
+fn returned_references_point_at_the_local() {
+    let source="fn f[]:&Int {\n    x:=5\n    r:=&x\n    return r\n}\nfn main[]:Int {\n    return 0\n}\n";
+    let (parsed,_)=parse("test.cppl",source);
+    let Err(results)=analyze("test.cppl",parsed) else {panic!("the reference to `x` was returned")};
+    let error=results.errors.iter()
+        .find(|e|e.reason=="can not return a reference to the local variable `x`")
+        .expect("no error for returning a reference to `x`");
+    let note=error.note.as_ref().expect("no note on the error");
+    assert_eq!(note.reason,"`x` is declared here and does not live after the function returns");
+    assert_eq!(note.start.line,1);
+}
+#[test]
+fn unreachable_patterns_cite_every_covering_pattern() {
+    let source="fn main[]:Int {\n    b:=true\n    return match b {\n        true=>1,\n        false=>2,\n        x=>3,\n    }\n}\n";
+    let (parsed,_)=parse("test.cppl",source);
+    let Ok((_,results))=analyze("test.cppl",parsed) else {panic!("the match did not pass analysis")};
+    let warning=results.warnings.iter()
+        .find(|w|w.reason=="unreachable pattern")
+        .expect("no warning for the unreachable pattern");
+    let mut lines=Vec::new();
+    let mut note=warning.note.as_ref();
+    while let Some(n)=note {
+        lines.push(n.start.line);
+        note=n.note.as_ref();
+    }
+    assert_eq!(lines,[3,4]);
+}
+#[test]
+fn undefined_names() {
+    assert_error("fn main[]:Int {\n    return y\n}\n","variable `y` is not defined");
+    assert_error("fn main[]:Int {\n    x:Foo=1\n    return 0\n}\n","type `Foo` is not defined");
+    assert_error(
+        "fn f[a:Int]:Int {\n    return a\n}\nfn main[]:Int {\n    return f(1,2)\n}\n",
+        "expected 1 arguments, found 2",
+    );
+}
+#[test]
+fn immutable_fields() {
+    assert_error(
+        "type Point<-{pub v:Int}\nfn main[]:Int {\n    mut p:Point={pub v<-1}\n    p.v<-2\n    return 0\n}\n",
+        "field `v` is not mutable",
+    );
+}
+#[test]
+fn private_fields() {
+    assert_error(
+        "type Point<-{v:Int}\nfn main[]:Int {\n    p:Point={v<-1}\n    return p.v\n}\n",
+        "field `v` of `Point` is private",
+    );
+}
+#[test]
+fn borrows_and_moves() {
+    assert_error(
+        "fn main[]:Int {\n    mut x:=1\n    a:=&mut x\n    b:=&x\n    c:=*a\n    return 0\n}\n",
+        "can not borrow `x` as immutable because it is also borrowed as mutable",
+    );
+    assert_error(
+        "fn take[r:&mut Int] {\n}\nfn main[]:Int {\n    mut x:=1\n    a:=&mut x\n    take(a)\n    take(a)\n    return 0\n}\n",
+        "use of moved value `a`",
+    );
+}
+#[test]
+fn non_exhaustive_matches() {
+    assert_error(
+        "enum Letter {\n    A,\n    B,\n}\nfn main[]:Int {\n    e:Letter=A\n    return match e {\n        A=>1,\n    }\n}\n",
+        "non-exhaustive match: `B` is not matched",
+    );
+}
+#[test]
+fn item_visibility() {
+    let program=|vis:&str|format!("type T<-{{}}\nimpl T {{\n    {}fn f[]:Int {{\n        return 1\n    }}\n}}\nfn main[]:Int {{\n    return T::f()\n}}\n",vis);
+    assert_error(&program(""),"`f` is private in `T`");
+    assert_error(&program("pub(local) "),"`f` is only visible inside of `T`");
+    assert_ok(&program("pub(lib) "));
+    assert_ok(&program("pub "));
+}
+#[test]
+fn every_path_returns() {
+    assert_error("fn f[]:String {\n}\nfn main[]:Int {\n    f()\n    return 0\n}\n","not every path returns a `String`");
+    assert_error(
+        "fn f[c:Bool]:Int {\n    if c {\n        return 1\n    }\n}\nfn main[]:Int {\n    return f(true)\n}\n",
+        "not every path returns a `Int`",
+    );
+    assert_error(
+        "fn f[c:Bool]:Int {\n    while c {\n        return 1\n    }\n}\nfn main[]:Int {\n    return f(true)\n}\n",
+        "not every path returns a `Int`",
+    );
+    assert_error("fn main[]:Int {\n    g:=fn[]:Int {\n    }\n    return g()\n}\n","not every path returns a `Int`");
+    assert_ok("fn f[c:Bool]:Int {\n    if c {\n        return 1\n    } else {\n        return 2\n    }\n}\nfn main[]:Int {\n    return f(true)\n}\n");
+    assert_ok("fn f[c:Bool]:Int {\n    match c {\n        true=>{\n            return 1\n        },\n        false=>{\n            return 2\n        },\n    }\n}\nfn main[]:Int {\n    return f(true)\n}\n");
+    assert_ok("fn f[]:Int {\n    mut i:=0\n    loop {\n        i+<-1\n        if i>5 {\n            return i\n        }\n    }\n}\nfn main[]:Int {\n    return f()\n}\n");
+}
+#[test]
+fn operand_types() {
+    assert_error("fn main[]:Int {\n    b:=1=\"a\"\n    return 0\n}\n","mismatched types: `{number}` and `String`");
+    assert_error("fn main[]:Int {\n    b:='a'<\"b\"\n    return 0\n}\n","mismatched types: `Char` and `String`");
+    assert_error("fn main[]:Int {\n    b:=true+false\n    return 0\n}\n","`+` can only be used on number types and `String`, found `Bool`");
+    assert_error("fn main[]:Int {\n    s:=\"a\"*\"b\"\n    return 0\n}\n","`*` can only be used on number types, found `String`");
+    assert_ok("fn main[]:Int {\n    s:=\"a\"+\"b\"\n    b:=s=\"ab\" and 1.5<2 and 'a'!='b'\n    x:Byte=1\n    y:Byte=x+2\n    return 0\n}\n");
+}
+#[test]
+fn immutable_bindings() {
+    assert_error("fn main[]:Int {\n    x:Int=1\n    x<-2\n    return x\n}\n","variable `x` is not mutable");
+    assert_error("fn main[]:Int {\n    x:Int=1\n    x+<-2\n    return x\n}\n","variable `x` is not mutable");
+    assert_error(
+        "type Point<-{pub mut v:Int}\nfn main[]:Int {\n    p:Point={pub mut v<-1}\n    p.v<-2\n    return p.v\n}\n",
+        "variable `p` is not mutable",
+    );
+    assert_error("fn main[]:Int {\n    xs:=[1,2]\n    xs[0]<-9\n    return xs[0]\n}\n","variable `xs` is not mutable");
+    assert_error("fn f[x:Int]:Int {\n    x<-2\n    return x\n}\nfn main[]:Int {\n    return f(1)\n}\n","parameter `x` is not mutable");
+    assert_ok("type Point<-{pub mut v:Int}\nfn main[]:Int {\n    mut x:Int=1\n    x<-2\n    mut p:Point={pub mut v<-1}\n    p.v<-x\n    mut xs:=[1,2]\n    xs[0]<-p.v\n    return xs[0]\n}\n");
+    assert_ok("fn set[r:&mut Int] {\n    *r<-2\n}\nfn main[]:Int {\n    mut x:=1\n    set(&mut x)\n    return x\n}\n");
+}
+#[test]
+fn mutable_borrows_of_immutable_bindings() {
+    assert_error(
+        "fn set[r:&mut Int] {\n    *r<-2\n}\nfn main[]:Int {\n    x:=1\n    set(&mut x)\n    return x\n}\n",
+        "can not borrow `x` as mutable because it is not mutable",
+    );
+    assert_error(
+        "type Tally<-{pub mut n:Int}\nimpl Tally {\n    fn bump[mut this] {\n        this.n<-this.n+1\n    }\n}\nfn main[]:Int {\n    t:Tally={pub mut n<-0}\n    t.bump()\n    return t.n\n}\n",
+        "can not borrow `t` as mutable because it is not mutable",
+    );
+    assert_ok("fn set[r:&mut Int] {\n    *r<-2\n}\nfn again[r:&mut Int] {\n    set(&mut *r)\n}\nfn main[]:Int {\n    mut x:=1\n    again(&mut x)\n    return x\n}\n");
+}
+#[test]
+fn imports() {
+    assert_ok("import std::{io::println,env::Arguments}\nfn main[]:Int {\n    for arg in Arguments() {\n        println(arg)\n    }\n    return 0\n}\n");
+    assert_ok("import std::io\nfn main[]:Int {\n    io::println(\"hi\")\n    return 0\n}\n");
+    assert_error("import std::io::shout\nfn main[]:Int {\n    return 0\n}\n","`shout` is not defined in `std::io`");
+    assert_error("import other::thing\nfn main[]:Int {\n    return 0\n}\n","`other` is not defined");
+}
+#[test]
+fn every_refine_error_is_reported() {
+    let errors=analysis_errors("fn f[a:Int,a:Int]:Int {\n    return a\n}\nfn main[]:Int {\n    x:=y\n    break\n    return z\n}\n");
+    for expected in ["parameter a is specified twice","`break` outside of a loop","variable `y` is not defined","variable `z` is not defined"] {
+        assert!(errors.iter().any(|e|e==expected),"expected `{}`, found {:?}",expected,errors);
+    }
+}
+#[test]
+fn loop_labels() {
+    assert_error("fn main[]:Int {\n    loop {\n        break 'nowhere\n    }\n    return 0\n}\n","unknown label `'nowhere`");
+    assert_error("fn main[]:Int {\n    continue\n    return 0\n}\n","`continue` outside of a loop");
+    assert_error("fn main[]:Int {\n    'l: while true {\n        break 'l 1\n    }\n    return 0\n}\n","only `loop` can be left with a value");
+    assert_error("fn main[]:Int {\n    x:Int='l: loop {\n        break 'l \"a\"\n    }\n    return x\n}\n","mismatched types: expected `Int`, found `String`");
+}
+#[test]
+fn compound_assignment() {
+    assert_ok("fn main[]:Int {\n    mut x:=1\n    x+<-2\n    x%<-2\n    mut s:=\"a\"\n    s+<-\"b\"\n    return x\n}\n");
+    assert_error("fn main[]:Int {\n    mut s:=\"a\"\n    s-<-\"b\"\n    return 0\n}\n","`-` can only be used on number types, found `String`");
+    assert_error("fn main[]:Int {\n    mut x:=1\n    x+<-\"b\"\n    return x\n}\n","mismatched types: expected `Int`, found `String`");
+}
+#[test]
+fn bitwise_operators() {
+    assert_ok("fn main[]:Int {\n    b:Byte=3&1|4\n    u:Uint=1<<b\n    return 1^2>>1\n}\n");
+    assert_error("fn main[]:Int {\n    f:=1.5\n    x:=f&1\n    return 0\n}\n","`&` can only be used on integer types, found `Float`");
+    assert_error("fn main[]:Int {\n    x:=true|false\n    return 0\n}\n","`|` can only be used on integer types, found `Bool`");
+    assert_error("fn main[]:Int {\n    b:Byte=1\n    x:Int=2\n    return x^b\n}\n","mismatched types: `Int` and `Byte`");
+}
+#[test]
+fn slices() {
+    assert_ok("fn main[]:Int {\n    xs:[[Int]]=[[1],[2,3],[]]\n    ys:=xs[1][..1]\n    return ys[0]\n}\n");
+    assert_error("fn main[]:Int {\n    xs:=[1,\"a\"]\n    return 0\n}\n","mismatched types: expected `{number}`, found `String`");
+    assert_error("fn main[]:Int {\n    xs:=[1,2]\n    return xs[true]\n}\n","indexes have to be integers, found `Bool`");
+    assert_error("fn main[]:Int {\n    x:=1\n    return x[0]\n}\n","`Int` can not be indexed");
+}
+#[test]
+fn pattern_bindings() {
+    let program=|pattern:&str,arm:&str|format!("type P<-{{pub x:Int,pub y:Int}}\nfn main[]:Int {{\n    p:P={{pub x<-1,pub y<-2}}\n    n:=match p {{\n        {}=>{},\n    }}\n    return n\n}}\n",pattern,arm);
+    assert_ok(&program("{x,y:other}","x+other"));
+    assert_error(&program("{x,y:x}","x"),"`x` is bound more than once in the same pattern");
+    assert_error(&program("{x,z,...}","x"),"no field `z` on type `P`");
+    assert_error("fn main[]:Int {\n    n:=match 1 {\n        v=>v,\n    }\n    return v\n}\n","variable `v` is not defined");
+}
@@ -0,0 +1,616 @@
+//! The same programs run on every backend, which all have to end the same way.
+use std::{
+    path::PathBuf,
+    process::Command,
+};
+use cppl_error::{
+    Error,
+    ErrorLevel,
+};
+use cppl_parser::parse;
+use cppl_ast::{
+    analyze,
+    refined::Scopes,
+};
+
+
+/// A program and how it has to end.
+struct Program {
+    name:&'static str,
+    source:&'static str,
+    args:&'static [&'static str],
+    /// The exit code, or the start of the reason of the runtime error that stops the program.
+    /// Compiled code can't put values in its errors, so some of them say less than the
+    /// interpreter's.
+    ends:Result<i32,&'static str>,
+    /// Whether the AOT compiler can build it yet. The other backends run every program.
+    native:bool,
+}
+const PROGRAMS:&[Program]=&[
+    Program {
+        name:"recursion_and_loops",
+        source:"fn fib[n:Int]:Int {
+    if n<2 {
+        return n
+    }
+    return fib(n-1)+fib(n-2)
+}
+fn collatz[start:Int]:Int {
+    mut n:=start
+    mut steps:Int=0
+    while n!=1 {
+        if n%2=0 {
+            n<-n/2
+        } else {
+            n<-3*n+1
+        }
+        steps+<-1
+    }
+    return steps
+}
+fn main[]:Int {
+    mut total:=fib(20)
+    total+<-collatz(27)
+    return total%256
+}
+",
+        args:&[],
+        ends:Ok(220),
+        native:true,
+    },
+    Program {
+        name:"number_types",
+        source:"fn half[x:Float]:Float {
+    return x/2
+}
+fn wrap[b:Byte]:Byte {
+    return b+200
+}
+fn main[]:Int {
+    mut acc:Int=0
+    f:=half(7.0)
+    if f>3.4 and f<3.6 {
+        acc+<-1
+    }
+    d:DoubleFloat=10.5%4
+    if d=2.5 {
+        acc+<-2
+    }
+    b:=wrap(100)
+    if b=44 {
+        acc+<-4
+    }
+    c:='a'
+    if c<'b' {
+        acc+<-8
+    }
+    n:=-17
+    if n/5=-3 and n%5=-2 and n>>1=-9 and 1<<65=2 {
+        acc+<-16
+    }
+    u:Uint=0
+    if u-1>u {
+        acc+<-32
+    }
+    min:=-9223372036854775807-1
+    if min/-1=min and min%-1=0 {
+        acc+<-64
+    }
+    return acc
+}
+",
+        args:&[],
+        ends:Ok(127),
+        native:true,
+    },
+    Program {
+        name:"bitwise_operators",
+        source:"fn main[]:Int {
+    b:Byte=240
+    low:Byte=b>>4|1
+    mask:Int=12&10
+    flip:Int=12^10
+    u:Uint=1
+    wide:=u<<40>>38
+    mut total:=mask+flip*10
+    if low=15 {
+        total+<-100
+    }
+    if wide=4 and 1|2^3&1=3 {
+        total+<-1000
+    }
+    return total
+}
+",
+        args:&[],
+        ends:Ok(1168%256),
+        native:true,
+    },
+    Program {
+        name:"control_flow",
+        source:"fn main[]:Int {
+    mut i:=0
+    mut sum:=0
+    while i<10 {
+        i+<-1
+        if i%2=0 {
+            continue
+        }
+        sum+<-i*2
+    }
+    x:=loop {
+        if sum>100 {
+            break sum-sum
+        }
+        sum+<-sum
+        if sum>40 {
+            break sum
+        }
+    }
+    r:=match x {
+        0=>1,
+        >50=>2,
+        v=>v,
+    }
+    return r+sum
+}
+",
+        args:&[],
+        ends:Ok(102),
+        native:true,
+    },
+    Program {
+        name:"labelled_loops",
+        source:"fn main[]:Int {
+    mut i:=0
+    found:='outer: loop {
+        i+<-1
+        mut j:=0
+        while j<10 {
+            if i*j=12 {
+                break 'outer i*10+j
+            }
+            if j>i {
+                continue 'outer
+            }
+            j+<-1
+        }
+    }
+    mut skipped:=0
+    mut row:=0
+    'rows: while row<3 {
+        row+<-1
+        mut col:=0
+        'cols: while col<3 {
+            col+<-1
+            if col>row {
+                continue 'rows
+            }
+            if row=3 {
+                break 'rows
+            }
+            skipped+<-1
+        }
+    }
+    return found+skipped*100
+}
+",
+        args:&[],
+        ends:Ok(334%256),
+        native:true,
+    },
+    Program {
+        name:"if_values",
+        source:"fn pick[c:Bool,x:Int]:Int {
+    return if c {
+        y:=x*2
+        y+1
+    } else if x>100 {
+        return 7
+    } else {
+        x-1
+    }
+}
+fn main[]:Int {
+    b:Byte=if pick(true,3)=7 {
+        200
+    } else {
+        1
+    }
+    s:=if b>100 {
+        \"big\"
+    } else {
+        \"small\"
+    }
+    mut total:=pick(true,3)+pick(false,5)*10+pick(false,500)*100
+    if s=\"big\" {
+        total+<-1000
+    }
+    f:Float=if total>0 {
+        1.5
+    } else {
+        2
+    }
+    if f>1.0 {
+        total+<-100
+    }
+    return total%256
+}
+",
+        args:&[],
+        ends:Ok(55),
+        native:true,
+    },
+    Program {
+        name:"enums",
+        source:"enum Shape {
+    Circle(Int),
+    Square(Int),
+    Empty,
+}
+fn kind[s:Shape]:Int {
+    return match s {
+        is Circle=>1,
+        is Square=>2,
+        Empty=>3,
+    }
+}
+fn main[]:Int {
+    c:Shape=Circle(2)
+    mut total:=kind(c)+kind(Square(3))*10+kind(Empty)*100
+    if c is Circle {
+        total+<-1000
+    }
+    e:Shape=Empty
+    if e is Circle {
+        total+<-2000
+    }
+    return total%256
+}
+",
+        args:&[],
+        ends:Ok(41),
+        native:false,
+    },
+    Program {
+        name:"compound_assignment",
+        source:"fn main[]:Int {
+    mut x:=10
+    x+<-5
+    x-<-3
+    x*<-4
+    x/<-6
+    x%<-5
+    mut calls:=0
+    mut xs:=[1,2,3]
+    next:=fn[]:Int {
+        calls+<-1
+        return calls
+    }
+    xs[next()]*<-10
+    mut s:=\"a\"
+    s+<-\"b\"
+    if s=\"ab\" {
+        x+<-100
+    }
+    return x+xs[1]*1000+calls*10
+}
+",
+        args:&[],
+        ends:Ok(20113%256),
+        native:false,
+    },
+    Program {
+        name:"pattern_bindings",
+        source:"type Point<-{pub x:Int,pub y:Int}
+fn pick[p:Point]:Int {
+    return match p {
+        {x,y:other}=>x*100+other,
+    }
+}
+fn main[]:Int {
+    p:Point={pub x<-1,pub y<-2}
+    n:=match pick(p) {
+        <0=>0,
+        big=>big+1000,
+    }
+    return n
+}
+",
+        args:&[],
+        ends:Ok(1102%256),
+        native:false,
+    },
+    Program {
+        name:"strings_and_arguments",
+        source:"import std::env::Arguments
+fn greet[name:String]:String {
+    return \"Hello, \"+name+\"!\"
+}
+fn main[args:[String]]:Int {
+    mut count:=0
+    for arg in Arguments() {
+        if greet(arg)=\"Hello, x!\" {
+            count+<-10
+        }
+        count+<-1
+    }
+    mut m:Int=match args[1] {
+        \"y\"=>7,
+        other=>8,
+    }
+    if \"abc\"<\"abd\" {
+        m+<-100
+    }
+    return count+m
+}
+",
+        args:&["x","y"],
+        ends:Ok(119),
+        native:true,
+    },
+    Program {
+        name:"deep_recursion",
+        source:"fn deep[n:Int]:Int {
+    if n=0 {
+        return 0
+    }
+    return deep(n-1)+1
+}
+fn main[]:Int {
+    return deep(900)+deep(2000)
+}
+",
+        args:&[],
+        ends:Err("stack overflow: more than 1000 calls are nested"),
+        native:true,
+    },
+    Program {
+        name:"slices",
+        source:"fn sum[xs:[Int]]:Int {
+    mut total:=0
+    for x in xs {
+        total+<-x
+    }
+    return total
+}
+fn main[]:Int {
+    xs:[Int]=[1,2,3,4,5]
+    words:=[[\"a\",\"b\"],[\"c\"]]
+    mut total:=sum(xs[1..3])+sum(xs[..2])*10+sum(xs[3..])*100
+    if words[1][0]=\"c\" {
+        total+<-1
+    }
+    empty:[Int]=[]
+    return total+sum(empty)+sum(xs[5..])
+}
+",
+        args:&[],
+        ends:Ok(936%256),
+        native:false,
+    },
+    Program {
+        name:"divide_by_zero",
+        source:"fn div[a:Int,b:Int]:Int {
+    return a/b
+}
+fn main[]:Int {
+    return div(1,0)+1
+}
+",
+        args:&[],
+        ends:Err("attempt to divide by zero"),
+        native:true,
+    },
+    Program {
+        name:"index_out_of_bounds",
+        source:"fn main[args:[String]]:Int {
+    s:=args[5]
+    return 0
+}
+",
+        args:&["x","y"],
+        ends:Err("index out of bounds"),
+        native:true,
+    },
+    Program {
+        name:"slice_out_of_bounds",
+        source:"fn main[]:Int {
+    xs:=[1,2,3]
+    ys:=xs[2..9]
+    return 0
+}
+",
+        args:&[],
+        ends:Err("range"),
+        native:false,
+    },
+    Program {
+        name:"objects_interfaces_and_closures",
+        source:"interface Counter {
+    fn bump[mut this]:Int
+    fn get[this]:Int
+}
+type Tally<-{pub mut n:Int}
+type Other<-{pub mut m:Int}
+impl Counter for Tally {
+    fn bump[mut this]:Int {
+        this.n<-this.n+1
+        return this.n
+    }
+    fn get[this]:Int {
+        return this.n
+    }
+}
+impl Counter for Other {
+    fn bump[mut this]:Int {
+        this.m<-this.m+10
+        return this.m
+    }
+    fn get[this]:Int {
+        return this.m
+    }
+}
+fn make_adder[k:Int]:fn[a:Int]:Int {
+    return fn[a:Int]:Int {
+        return a+k
+    }
+}
+fn kind[c:Counter]:Int {
+    return match c {
+        is Tally=>1,
+        is Other=>2,
+        _=>3,
+    }
+}
+fn main[]:Int {
+    mut count:=0
+    inc:=fn[] {
+        count<-count+1
+    }
+    inc()
+    inc()
+    t0:Tally={pub mut n<-0}
+    mut c:Counter=t0
+    c.bump()
+    c.bump()
+    o0:Other={pub mut m<-1}
+    mut o:Counter=o0
+    o.bump()
+    add3:=make_adder(3)
+    return count+c.get()+o.get()+add3(7)+kind(c)*10+kind(o)*100
+}
+",
+        args:&[],
+        ends:Ok(235),
+        native:false,
+    },
+    Program {
+        name:"evaluation_order",
+        source:"fn main[]:Int {
+    mut x:=1
+    set:=fn[]:Int {
+        x<-100
+        return 0
+    }
+    y:=x+set()
+    mut xs:=[1,2]
+    mut i:=0
+    grow:=fn[]:Int {
+        i<-1
+        return 5
+    }
+    xs[i]<-grow()
+    return y+xs[0]*10+xs[1]*100
+}
+",
+        args:&[],
+        ends:Ok(511%256),
+        native:false,
+    },
+];
+
+
+fn analyzed<'input>(program:&Program,source:&'input str)->Scopes<'input> {
+    let filename=program.name;
+    let (parsed,parse_errors)=parse(filename,source);
+    assert!(parse_errors.is_empty(),"{}: {:?}",filename,parse_errors);
+    return match analyze(filename,parsed) {
+        Ok((scopes,_))=>scopes,
+        Err(results)=>panic!("{}: {:?}",filename,results.errors.into_iter().map(|e|e.reason).collect::<Vec<_>>()),
+    };
+}
+fn args(program:&Program)->Vec<String> {
+    program.args.iter().map(|a|a.to_string()).collect()
+}
+/// Checks how a backend that runs in this process ended `program`. Exit codes are cut to a byte
+/// like they are for executables.
+fn check(program:&Program,backend:&str,result:Result<i32,Error<String>>) {
+    match (result,program.ends) {
+        (Ok(code),Ok(expected))=>assert_eq!(code&0xff,expected,"`{}` on the {}",program.name,backend),
+        (Err(err),Err(reason))=>assert!(err.reason.starts_with(reason),"`{}` on the {}: {}",program.name,backend,err.reason),
+        (result,_)=>panic!("`{}` on the {} ended with {:?}",program.name,backend,result.map_err(|e|e.reason)),
+    }
+}
+/// Runs an executable built from `program` and checks how it ended.
+fn check_executable(program:&Program,backend:&str,executable:&PathBuf) {
+    let output=Command::new(executable).args(program.args).output().expect("could not run the executable");
+    let code=output.status.code().expect("the executable was stopped by a signal");
+    match program.ends {
+        Ok(expected)=>assert_eq!(code,expected,"`{}` built by the {}",program.name,backend),
+        Err(reason)=>{
+            let stderr=String::from_utf8_lossy(&output.stderr);
+            assert_eq!(code,101,"`{}` built by the {}",program.name,backend);
+            assert!(stderr.starts_with(&format!("Runtime error: {}",reason)),"`{}` built by the {}: {}",program.name,backend,stderr);
+        },
+    }
+}
+/// A directory for the files the backend `backend` builds.
+fn build_dir(backend:&str)->PathBuf {
+    let dir=PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("backends").join(backend);
+    std::fs::create_dir_all(&dir).expect("could not make the build directory");
+    return dir;
+}
+
+
+#[test]
+fn interpreter() {
+    for program in PROGRAMS {
+        let scopes=analyzed(program,program.source);
+        check(program,"interpreter",cppl_interp::run(program.name,&scopes,&args(program)));
+    }
+}
+#[test]
+fn vm() {
+    for program in PROGRAMS {
+        let scopes=analyzed(program,program.source);
+        let compiled=cppl_vm::compile(program.name,&scopes).map_err(|e|e.reason).unwrap();
+        check(program,"VM",cppl_vm::run(&compiled,&args(program)));
+    }
+}
+/// Programs that can't be JIT compiled as a whole run with the interpreter and the functions that
+/// can be.
+#[test]
+fn jit() {
+    for program in PROGRAMS {
+        let scopes=analyzed(program,program.source);
+        let args=args(program);
+        let result=match cppl_codegen_cranelift::compile(program.name,&scopes) {
+            Ok(compiled)=>cppl_codegen_cranelift::run(&compiled,&args),
+            Err(err) if matches!(err.level,ErrorLevel::Compile)=>{
+                let (functions,_)=cppl_codegen_cranelift::compile_functions(program.name,&scopes,&args).map_err(|e|e.reason).unwrap();
+                cppl_interp::run_with_natives(program.name,&scopes,&args,&functions)
+            },
+            Err(err)=>Err(err),
+        };
+        check(program,"JIT",result);
+    }
+}
+#[test]
+fn aot() {
+    let dir=build_dir("aot");
+    for program in PROGRAMS {
+        let scopes=analyzed(program,program.source);
+        let object=dir.join(program.name).with_extension("o");
+        let executable=dir.join(program.name);
+        let built=cppl_codegen_cranelift::build(program.name,program.source,&scopes,&object,&executable);
+        if !program.native {
+            let Err(err)=built else {panic!("`{}` can be built by the AOT compiler now",program.name)};
+            assert!(matches!(err.level,ErrorLevel::Compile),"`{}`: {}",program.name,err.reason);
+            continue;
+        }
+        built.map_err(|e|e.reason).unwrap();
+        check_executable(program,"AOT compiler",&executable);
+    }
+}
+#[test]
+fn c() {
+    let dir=build_dir("c");
+    for program in PROGRAMS {
+        let scopes=analyzed(program,program.source);
+        let c_file=dir.join(program.name).with_extension("c");
+        let executable=dir.join(program.name);
+        cppl_codegen_c::build(program.name,program.source,&scopes,&c_file,&executable).map_err(|e|e.reason).unwrap();
+        check_executable(program,"C backend",&executable);
+    }
+}
@@ -14,6 +14,12 @@ pub enum ErrorLevel {
     LexError,
     ParseError,
     Verification,
+    /// Something that went wrong while running the program, like indexing out of bounds.
+    Runtime,
+    /// Something in a program that passed analysis that a backend can't compile.
+    Compile,
+    /// Extra information attached to another error.
+    Note,
 }
 impl Display for ErrorLevel {
     fn fmt(&self,f:&mut Formatter)->FmtResult {
@@ -23,6 +29,9 @@ impl Display for ErrorLevel {
             LexError=>write!(f,"Lex error"),
             ParseError=>write!(f,"Parse error"),
             Verification=>write!(f,"Verification error"),
+            Runtime=>write!(f,"Runtime error"),
+            Compile=>write!(f,"Compile error"),
+            Note=>write!(f,"Note"),
         }
     }
 }
@@ -36,10 +45,11 @@ pub struct ContextualError<'source,T:Display+Debug> {
     pub reason:T,
     pub level:ErrorLevel,
     pub source:&'source str,
+    pub note:Option<Box<Self>>,
 }
 impl<'source,T:Display+Debug> From<(&'source str,Error<'source,T>)> for ContextualError<'source,T> {
     fn from((source,error):(&'source str,Error<'source,T>))->Self {
-        let Error{filename,start,end,reason,level}=error;
+        let Error{filename,start,end,reason,level,note}=error;
         return ContextualError {
             filename,
             start,
@@ -47,6 +57,7 @@ impl<'source,T:Display+Debug> From<(&'source str,Error<'source,T>)> for Contextu
             reason,
             level,
             source,
+            note:note.map(|note|Box::new((source,*note).into())),
         };
     }
 }
@@ -106,6 +117,9 @@ impl<'source,T:Display+Debug> Display for ContextualError<'source,T> {
             }
             write!(f,"╯")?;
         }
+        if let Some(note)=&self.note {
+            write!(f,"\n{}",note)?;
+        }
         return Ok(());
     }
 }
@@ -115,13 +129,32 @@ pub struct Error<'source,T:Display> {
     pub end:Location,
     pub reason:T,
     pub level:ErrorLevel,
+    /// A second location related to this error, like the definition of something that conflicts.
+    /// Further notes are chained onto this one.
+    pub note:Option<Box<Self>>,
 }
 impl<'source,T:Display> Error<'source,T> {
     pub fn new_verif<R:Into<T>>(filename:&'source str,start:Location,end:Location,reason:R)->Self {
-        Error{filename,start,end,reason:reason.into(),level:ErrorLevel::Verification}
+        Error{filename,start,end,reason:reason.into(),level:ErrorLevel::Verification,note:None}
     }
     pub fn new_warning<R:Into<T>>(filename:&'source str,start:Location,end:Location,reason:R)->Self {
-        Error{filename,start,end,reason:reason.into(),level:ErrorLevel::Warning}
+        Error{filename,start,end,reason:reason.into(),level:ErrorLevel::Warning,note:None}
+    }
+    pub fn new_runtime<R:Into<T>>(filename:&'source str,start:Location,end:Location,reason:R)->Self {
+        Error{filename,start,end,reason:reason.into(),level:ErrorLevel::Runtime,note:None}
+    }
+    pub fn new_compile<R:Into<T>>(filename:&'source str,start:Location,end:Location,reason:R)->Self {
+        Error{filename,start,end,reason:reason.into(),level:ErrorLevel::Compile,note:None}
+    }
+    /// Attaches a note pointing at another location in the same file, after any notes already
+    /// attached.
+    pub fn with_note<R:Into<T>>(mut self,start:Location,end:Location,reason:R)->Self {
+        let mut last=&mut self.note;
+        while let Some(note)=last {
+            last=&mut note.note;
+        }
+        *last=Some(Box::new(Error{filename:self.filename,start,end,reason:reason.into(),level:ErrorLevel::Note,note:None}));
+        self
     }
 }
 impl<'source,T:Display> Debug for Error<'source,T> {
@@ -137,6 +170,9 @@ impl<'source,T:Display> Debug for Error<'source,T> {
             }
         }
         write!(f,"`")?;
+        if let Some(note)=&self.note {
+            write!(f,"\n{:?}",note)?;
+        }
         return Ok(());
     }
 }
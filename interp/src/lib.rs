@@ -0,0 +1,915 @@
+//! A tree-walking interpreter that runs the refined AST directly.
+//!
+//! Variables are cells keyed by the scope they are declared in. Every call gets a new frame of
+//! cells, anonymous functions keep the cells they can see when they are created and variables of
+//! the root scope, `static`s and `const`s live in the first frame. `static`s and `const`s are
+//! initialized the first time they are used.
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    rc::Rc,
+};
+use cppl_error::{
+    Location,
+    Error,
+};
+use cppl_ast::{
+    refined::*,
+    expr_type,
+    call_builtin,
+};
+pub use value::*;
+
+
+mod value;
+
+
+/// How many calls can be nested before the program is stopped.
+const MAX_CALL_DEPTH:usize=1_000;
+/// The stack size of the thread the interpreter runs on. Every call nests a few functions of the
+/// interpreter for each level of the expressions it is in. That is about 48KiB for a simple
+/// recursive function in a debug build, so `MAX_CALL_DEPTH` calls need more than the default.
+const STACK_SIZE:usize=64*1024*1024;
+/// How much of the stack has to be left to start another call. Calls in deeply nested expressions
+/// use more of the stack than `STACK_SIZE` plans for, so running out is checked as well.
+const STACK_RESERVE:usize=8*1024*1024;
+
+
+/// Why evaluation stopped before the end of an expression.
+enum Flow<'input> {
+    Return(Value<'input>),
+    /// Leaves the loop with the block `.0`.
+    Break(Scope,Value<'input>),
+    /// Goes to the next iteration of the loop with the block `.0`.
+    Continue(Scope),
+    Error(Error<'input,String>),
+}
+type Eval<'input,T=Value<'input>>=Result<T,Flow<'input>>;
+/// An operator that takes two numbers.
+#[derive(Copy,Clone)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+impl Op {
+    fn symbol(self)->&'static str {
+        match self {
+            Op::Add=>"+",
+            Op::Sub=>"-",
+            Op::Mul=>"*",
+            Op::Div=>"/",
+            Op::Mod=>"%",
+            Op::BitAnd=>"&",
+            Op::BitOr=>"|",
+            Op::BitXor=>"^",
+            Op::ShiftLeft=>"<<",
+            Op::ShiftRight=>">>",
+        }
+    }
+}
+impl From<AssignOp> for Op {
+    fn from(op:AssignOp)->Self {
+        match op {
+            AssignOp::Add=>Op::Add,
+            AssignOp::Sub=>Op::Sub,
+            AssignOp::Mul=>Op::Mul,
+            AssignOp::Div=>Op::Div,
+            AssignOp::Mod=>Op::Mod,
+        }
+    }
+}
+/// Functions compiled to native code, which calls are handed to instead of being interpreted.
+pub trait Natives<'input> {
+    /// Runs `function` with `args` if it was compiled, `None` if it has to be interpreted. `depth`
+    /// is how many calls are nested, counting this one.
+    fn call(&self,function:Scope,args:&[Value<'input>],depth:usize)->Option<Result<Value<'input>,Error<'input,String>>>;
+}
+#[derive(Default)]
+struct Frame<'input> {
+    vars:Env<'input>,
+    /// The variables an anonymous function was created with.
+    captured:Option<Rc<Env<'input>>>,
+}
+struct Interpreter<'a,'input> {
+    filename:&'input str,
+    scopes:&'a Scopes<'input>,
+    /// The arguments passed to the program.
+    args:&'a [String],
+    frames:Vec<Frame<'input>>,
+    /// The address of the stack when the interpreter started.
+    stack_start:usize,
+    natives:Option<&'a (dyn Natives<'input>+'a)>,
+}
+impl<'a,'input> Interpreter<'a,'input> {
+    fn error<T>(&self,(start,end):(Location,Location),reason:impl Into<String>)->Eval<'input,T> {
+        Err(Flow::Error(Error::new_runtime(self.filename,start,end,reason.into())))
+    }
+    fn frame(&mut self)->&mut Frame<'input> {
+        self.frames.last_mut().expect("Internal compiler error: no frame to run in")
+    }
+    /// The cell of the variable `var`.
+    fn cell(&mut self,var:Scope,span:(Location,Location))->Eval<'input,Cell<'input>> {
+        let var=original(self.scopes,var);
+        let frame=self.frames.last().expect("Internal compiler error: no frame to run in");
+        let found=frame.vars.get(&var)
+            .or_else(||frame.captured.as_ref().and_then(|c|c.get(&var)))
+            .or_else(||self.frames[0].vars.get(&var));
+        if let Some(cell)=found {
+            return Ok(cell.clone());
+        }
+        match self.scopes.get(var) {
+            Some(VarScopeItem::Static{data,ty,..}|VarScopeItem::Const{data,ty,..})=>{
+                self.frames.push(Frame::default());
+                let value=self.eval(data);
+                self.frames.pop();
+                let value=self.coerce(value?,ty);
+                let cell=Rc::new(RefCell::new(value));
+                self.frames[0].vars.insert(var,cell.clone());
+                Ok(cell)
+            },
+            item=>{
+                let name=item.and_then(|i|i.name()).unwrap_or("?");
+                self.error(span,format!("`{}` is used before it is defined",name))
+            },
+        }
+    }
+    fn define(&mut self,var:Scope,value:Value<'input>) {
+        self.frame().vars.insert(var,Rc::new(RefCell::new(value)));
+    }
+    fn read_var(&mut self,path:Scope,span:(Location,Location))->Eval<'input> {
+        match self.scopes.get(path) {
+            Some(VarScopeItem::Function{..}|VarScopeItem::BuiltinFunction{..})=>Ok(Value::Function{function:path,captured:None}),
+            Some(VarScopeItem::EnumVariant{fields,..}) if fields.is_empty()=>Ok(Value::Variant{variant:path,fields:Vec::new()}),
+            Some(VarScopeItem::EnumVariant{..})=>Ok(Value::Function{function:path,captured:None}),
+            _=>Ok(self.cell(path,span)?.borrow().clone()),
+        }
+    }
+    fn read(&self,place:&Place<'input>,span:(Location,Location))->Eval<'input> {
+        match place.read() {
+            Some(value)=>Ok(value),
+            None=>self.error(span,"the value this refers to does not exist anymore"),
+        }
+    }
+    fn write(&self,place:&Place<'input>,value:Value<'input>,span:(Location,Location))->Eval<'input,()> {
+        // Number literals take the type of what they are assigned to.
+        let value=match place.read() {
+            Some(old) if old.is_number()&&value.is_number()=>value.convert_number(&old),
+            _=>value,
+        };
+        if !place.write(value) {
+            return self.error(span,"the value this refers to does not exist anymore");
+        }
+        return Ok(());
+    }
+    /// Follows references until a value that isn't one is found.
+    fn deref(&self,mut value:Value<'input>,span:(Location,Location))->Eval<'input> {
+        while let Value::Ref(place)=value {
+            value=self.read(&place,span)?;
+        }
+        return Ok(value);
+    }
+    /// Follows references stored at `place` until a place that doesn't hold one is found.
+    fn deref_place(&self,mut place:Place<'input>)->Place<'input> {
+        while let Some(Value::Ref(inner))=place.read() {
+            place=inner;
+        }
+        return place;
+    }
+    /// Where the value of `expr` is stored. Expressions that aren't stored anywhere are put in a
+    /// new cell.
+    fn place(&mut self,expr:&Expr<'input>)->Eval<'input,Place<'input>> {
+        let span=expr.span();
+        match expr {
+            Expr::Var{path,..}=>match self.scopes.get(*path) {
+                Some(VarScopeItem::Function{..}|VarScopeItem::BuiltinFunction{..}|VarScopeItem::EnumVariant{..})=>{
+                    Ok(Place::new(self.read_var(*path,span)?))
+                },
+                _=>Ok(Place{cell:self.cell(*path,span)?,path:Vec::new()}),
+            },
+            Expr::FieldAccess{from,name,..}=>{
+                let from=self.place(from)?;
+                let mut place=self.deref_place(from);
+                place.path.push(Projection::Field(name));
+                Ok(place)
+            },
+            Expr::Index{from,index,..}=>{
+                let from=self.place(from)?;
+                let mut place=self.deref_place(from);
+                let len=match self.read(&place,span)? {
+                    Value::Slice(items)=>items.len(),
+                    _=>return self.error(span,"only slices can be indexed"),
+                };
+                let index=self.index(index,len)?;
+                place.path.push(Projection::Index(index));
+                Ok(place)
+            },
+            Expr::Deref{val,..}=>match self.eval(val)? {
+                Value::Ref(place)=>Ok(place),
+                value=>Ok(Place::new(value)),
+            },
+            _=>Ok(Place::new(self.eval(expr)?)),
+        }
+    }
+    /// Evaluates an index into something with `len` items.
+    fn index(&mut self,index:&Expr<'input>,len:usize)->Eval<'input,usize> {
+        let span=index.span();
+        let value=self.eval(index)?;
+        let Some(i)=value.as_integer() else {
+            return self.error(span,"indexes have to be integers");
+        };
+        if i<0||i>=len as i128 {
+            return self.error(span,format!("index out of bounds: the length is {} but the index is {}",len,i));
+        }
+        return Ok(i as usize);
+    }
+    /// Converts numbers in `value` to the number types `ty` says they are. Number literals are
+    /// `Int`s or `DoubleFloat`s until they are used as something.
+    fn coerce(&self,value:Value<'input>,ty:&Type<'input>)->Value<'input> {
+        match (self.scopes.expand_alias(ty),value) {
+            (Type::Uint,v) if v.is_number()=>v.convert_number(&Value::Uint(0)),
+            (Type::Int,v) if v.is_number()=>v.convert_number(&Value::Int(0)),
+            (Type::Byte,v) if v.is_number()=>v.convert_number(&Value::Byte(0)),
+            (Type::Float,v) if v.is_number()=>v.convert_number(&Value::Float(0.0)),
+            (Type::DoubleFloat,v) if v.is_number()=>v.convert_number(&Value::DoubleFloat(0.0)),
+            (Type::Object{fields,..},Value::Object(values))=>Value::Object(values.into_iter()
+                .map(|(name,v)|match fields.iter().find(|f|f.name==name) {
+                    Some(field)=>(name,self.coerce(v,&field.ty)),
+                    None=>(name,v),
+                })
+                .collect()
+            ),
+            (Type::Slice{inner,..},Value::Slice(items))=>Value::Slice(items.into_iter().map(|i|self.coerce(i,inner)).collect()),
+            (_,Value::Variant{variant,fields})=>{
+                let fields=match self.scopes.get(variant) {
+                    Some(VarScopeItem::EnumVariant{fields:types,..})=>fields.into_iter()
+                        .zip(types.iter())
+                        .map(|(v,t)|self.coerce(v,t))
+                        .collect(),
+                    _=>fields,
+                };
+                Value::Variant{variant,fields}
+            },
+            (_,v)=>v,
+        }
+    }
+    fn is_type(&self,value:&Value<'input>,ty:&Type<'input>)->bool {
+        self.scopes.check_assignable(&value.ty(),ty).is_ok()
+    }
+    /// Whether the first parameter of `function` is `this`, and whether it is `mut this`.
+    fn this_param(&self,function:Scope)->Option<bool> {
+        let Some(VarScopeItem::Function{params,..})=self.scopes.get(function) else {return None};
+        match params.first().and_then(|p|self.scopes.get(*p)) {
+            Some(VarScopeItem::Parameter{name:"this",mutable,..})=>Some(*mutable),
+            _=>None,
+        }
+    }
+    fn call_value(&mut self,function:Value<'input>,args:Vec<Value<'input>>,span:(Location,Location))->Eval<'input> {
+        match self.deref(function,span)? {
+            Value::Function{function,captured}=>self.call(function,captured,args,span),
+            _=>self.error(span,"only functions can be called"),
+        }
+    }
+    /// How much of the stack is in use.
+    fn stack_used(&self)->usize {
+        let here=0u8;
+        return self.stack_start.abs_diff(&here as *const u8 as usize);
+    }
+    fn call(&mut self,function:Scope,captured:Option<Rc<Env<'input>>>,args:Vec<Value<'input>>,span:(Location,Location))->Eval<'input> {
+        match self.scopes.get(function) {
+            Some(VarScopeItem::Function{params,ret_type,..}|VarScopeItem::AnonFunction{params,ret_type,..})=>{
+                if self.frames.len()>MAX_CALL_DEPTH {
+                    return self.error(span,format!("stack overflow: more than {} calls are nested",MAX_CALL_DEPTH));
+                }
+                if self.stack_used()>STACK_SIZE-STACK_RESERVE {
+                    return self.error(span,format!("stack overflow: the interpreter ran out of stack after {} nested calls",self.frames.len()));
+                }
+                let args=params.iter().zip(args)
+                    .map(|(param,arg)|match self.scopes.get(*param).and_then(|p|p.var_type()) {
+                        Some(ty)=>self.coerce(arg,ty),
+                        None=>arg,
+                    })
+                    .collect::<Vec<_>>();
+                if let Some(result)=self.natives.and_then(|n|n.call(function,&args,self.frames.len())) {
+                    return result.map_err(Flow::Error);
+                }
+                let mut frame=Frame{vars:HashMap::new(),captured};
+                for (param,arg) in params.iter().zip(args) {
+                    frame.vars.insert(*param,Rc::new(RefCell::new(arg)));
+                }
+                self.frames.push(frame);
+                let result=self.run_block(function);
+                self.frames.pop();
+                let value=match result {
+                    Ok(())=>Value::None,
+                    Err(Flow::Return(value))=>value,
+                    Err(flow)=>return Err(flow),
+                };
+                Ok(match ret_type {
+                    Some(ty)=>self.coerce(value,ty),
+                    None=>value,
+                })
+            },
+            Some(VarScopeItem::BuiltinFunction{name,..})=>match call_builtin(name,args,self.args) {
+                Ok(value)=>Ok(value),
+                Err(reason)=>self.error(span,reason),
+            },
+            Some(VarScopeItem::EnumVariant{..})=>Ok(self.coerce(Value::Variant{variant:function,fields:args},&Type::Unknown)),
+            _=>self.error(span,"only functions can be called"),
+        }
+    }
+    /// The arguments of a call to the method `method` on `from`. Methods with `mut this` get a
+    /// reference to `from` so they can change it.
+    fn method_args(&mut self,method:Scope,from:&Expr<'input>,args:&[Expr<'input>])->Eval<'input,Vec<Value<'input>>> {
+        let span=from.span();
+        let mut values=Vec::with_capacity(args.len()+1);
+        match self.this_param(method) {
+            Some(true)=>{
+                let place=self.place(from)?;
+                values.push(Value::Ref(self.deref_place(place)));
+            },
+            Some(false)=>{
+                let value=self.eval(from)?;
+                values.push(self.deref(value,span)?);
+            },
+            None=>{},
+        }
+        for arg in args {
+            values.push(self.eval(arg)?);
+        }
+        return Ok(values);
+    }
+    /// Calls the function `name` of the impl of `interface` for the type of the value of `from`.
+    fn interface_call(&mut self,interface:Scope,from:&Expr<'input>,name:&str,args:&[Expr<'input>],span:(Location,Location))->Eval<'input> {
+        let place=self.place(from)?;
+        let place=self.deref_place(place);
+        let value=self.read(&place,span)?;
+        let Some(method)=self.scopes.dispatch(interface,&value.ty(),name) else {
+            return self.error(span,format!("no impl has `{}` for this value",name));
+        };
+        let mut values=Vec::with_capacity(args.len()+1);
+        match self.this_param(method) {
+            Some(true)=>values.push(Value::Ref(place)),
+            Some(false)=>values.push(value),
+            None=>{},
+        }
+        for arg in args {
+            values.push(self.eval(arg)?);
+        }
+        return self.call(method,None,values,span);
+    }
+    fn field(&self,from:Value<'input>,name:&str,span:(Location,Location))->Eval<'input> {
+        match self.deref(from,span)?.field(name) {
+            Some(value)=>Ok(value.clone()),
+            None=>self.error(span,format!("no field `{}`",name)),
+        }
+    }
+    /// Runs the statements of `scope`.
+    fn run_block(&mut self,scope:Scope)->Eval<'input,()> {
+        let Some(item)=self.scopes.get(scope) else {return Ok(())};
+        for stmt in item.statements() {
+            self.exec(stmt)?;
+        }
+        return Ok(());
+    }
+    /// Runs one iteration of the loop with the block `block`. `Some` is the value the loop is left
+    /// with.
+    fn iteration(&mut self,block:Scope)->Eval<'input,Option<Value<'input>>> {
+        match self.run_block(block) {
+            Ok(())=>Ok(None),
+            Err(Flow::Continue(target)) if target==block=>Ok(None),
+            Err(Flow::Break(target,value)) if target==block=>Ok(Some(value)),
+            Err(flow)=>Err(flow),
+        }
+    }
+    fn exec(&mut self,stmt:&Statement<'input>)->Eval<'input,()> {
+        match stmt {
+            Statement::VarDef{def,..}=>{
+                if let Some(VarScopeItem::Var{ty,data,..})=self.scopes.get(*def) {
+                    let value=self.eval(data)?;
+                    let value=self.coerce(value,ty);
+                    self.define(*def,value);
+                }
+            },
+            Statement::VarAssign{start,end,loc,data}=>{
+                let value=self.eval(data)?;
+                let value=match self.scopes.get(original(self.scopes,*loc)).and_then(|v|v.var_type()) {
+                    Some(ty)=>self.coerce(value,ty),
+                    None=>value,
+                };
+                let cell=self.cell(*loc,(*start,*end))?;
+                *cell.borrow_mut()=value;
+            },
+            Statement::PlaceAssign{start,end,place,data}=>{
+                let value=self.eval(data)?;
+                let place=self.place(place)?;
+                self.write(&place,value,(*start,*end))?;
+            },
+            Statement::CompoundAssign{start,end,op,place,data}=>{
+                let place=self.place(place)?;
+                let old=self.read(&place,(*start,*end))?;
+                let data=self.eval(data)?;
+                let new=self.arithmetic((*op).into(),old,data,(*start,*end))?;
+                self.write(&place,new,(*start,*end))?;
+            },
+            Statement::Expr{expr,..}=>{
+                self.eval(expr)?;
+            },
+            Statement::Return{val,..}=>{
+                let value=match val {
+                    Some(val)=>self.eval(val)?,
+                    None=>Value::None,
+                };
+                return Err(Flow::Return(value));
+            },
+            Statement::Continue{target,..}=>return Err(Flow::Continue(*target)),
+            Statement::Break{target,val,..}=>{
+                let value=match val {
+                    Some(val)=>self.eval(val)?,
+                    None=>Value::None,
+                };
+                return Err(Flow::Break(*target,value));
+            },
+            Statement::UnknownVarAssign{start,end,name,..}=>return self.error((*start,*end),format!("variable `{}` is not defined",name)),
+            // Everything else is a definition, which is looked up when it is used.
+            _=>{},
+        }
+        return Ok(());
+    }
+    fn eval_all(&mut self,exprs:&[Expr<'input>])->Eval<'input,Vec<Value<'input>>> {
+        exprs.iter().map(|e|self.eval(e)).collect()
+    }
+    fn condition(&mut self,expr:&Expr<'input>)->Eval<'input,bool> {
+        let value=self.eval(expr)?;
+        return Ok(matches!(self.deref(value,expr.span())?,Value::Bool(true)));
+    }
+    /// Evaluates `expr`. This is nested for every level of an expression and every call, so the
+    /// bigger kinds of expressions have their own functions to keep its share of the stack small.
+    fn eval(&mut self,expr:&Expr<'input>)->Eval<'input> {
+        use Expr::*;
+        let span=expr.span();
+        match expr {
+            Data{data,..}=>Ok(Value::from_literal(data)),
+            Var{path,..}|AssociatedPath{path,..}=>self.read_var(*path,span),
+            FunctionCall{path,args,..}=>{
+                let function=self.read_var(*path,span)?;
+                let args=self.eval_all(args)?;
+                self.call_value(function,args,span)
+            },
+            MethodCall{from,args,method:Some(method),..}=>{
+                let args=self.method_args(*method,from,args)?;
+                self.call(*method,None,args,span)
+            },
+            MethodCall{from,name,args,method:None,..}=>self.field_call(from,name,args,span),
+            FieldAccess{from,name,..}=>{
+                let from=self.eval(from)?;
+                self.field(from,name,span)
+            },
+            Add{inner,..}=>self.binary(Op::Add,inner,span),
+            Sub{inner,..}=>self.binary(Op::Sub,inner,span),
+            Mul{inner,..}=>self.binary(Op::Mul,inner,span),
+            Div{inner,..}=>self.binary(Op::Div,inner,span),
+            Mod{inner,..}=>self.binary(Op::Mod,inner,span),
+            BitAnd{inner,..}=>self.binary(Op::BitAnd,inner,span),
+            BitOr{inner,..}=>self.binary(Op::BitOr,inner,span),
+            BitXor{inner,..}=>self.binary(Op::BitXor,inner,span),
+            ShiftLeft{inner,..}=>self.binary(Op::ShiftLeft,inner,span),
+            ShiftRight{inner,..}=>self.binary(Op::ShiftRight,inner,span),
+            Negate{inner,..}=>self.negate(inner,span),
+            Equal{..}|NotEqual{..}|GreaterEqual{..}|LessEqual{..}|Greater{..}|Less{..}=>self.comparison(expr,span),
+            And{inner,..}=>Ok(Value::Bool(self.condition(&inner[0])?&&self.condition(&inner[1])?)),
+            Or{inner,..}=>Ok(Value::Bool(self.condition(&inner[0])?||self.condition(&inner[1])?)),
+            Not{inner,..}=>Ok(Value::Bool(!self.condition(inner)?)),
+            IsType{to_test,ty,..}=>{
+                let value=self.eval(to_test)?;
+                Ok(Value::Bool(self.is_type(&value,ty)))
+            },
+            ObjectCreation{fields,..}=>self.object(fields),
+            AnonFunction{function,..}=>Ok(self.anon_function(*function)),
+            Ref{val,..}|RefMut{val,..}=>Ok(Value::Ref(self.place(val)?)),
+            Deref{val,..}=>match self.eval(val)? {
+                Value::Ref(place)=>self.read(&place,span),
+                _=>self.error(span,"only references and pointers can be dereferenced"),
+            },
+            Block{block,..}|Unsafe{block,..}=>{
+                self.run_block(*block)?;
+                Ok(Value::None)
+            },
+            ForeverLoop{..}|WhileLoop{..}|ForLoop{..}=>self.run_loop(expr,span),
+            Match{block,..}=>self.run_match(block,span),
+            If{branches,else_block,..}=>self.run_if(branches,*else_block),
+            List{items,..}=>Ok(Value::Slice(self.eval_all(items)?)),
+            Index{from,index,..}=>self.index_value(from,index,span),
+            Slice{from,range_start,range_end,..}=>self.slice_value(from,[range_start.as_deref(),range_end.as_deref()],span),
+            UnknownFunctionCall{..}|UnknownAssociatedPath{..}|UnknownVar{..}=>self.error(span,"this was never defined"),
+        }
+    }
+    /// Calls a method that wasn't resolved, which is either a method of an interface or a function
+    /// stored in a field.
+    fn field_call(&mut self,from:&Expr<'input>,name:&str,args:&[Expr<'input>],span:(Location,Location))->Eval<'input> {
+        if let Some(interface)=self.scopes.interface(&expr_type(self.filename,self.scopes,from)) {
+            return self.interface_call(interface,from,name,args,span);
+        }
+        let from=self.eval(from)?;
+        let function=self.field(from,name,span)?;
+        let args=self.eval_all(args)?;
+        return self.call_value(function,args,span);
+    }
+    fn negate(&mut self,inner:&Expr<'input>,span:(Location,Location))->Eval<'input> {
+        match self.eval(inner)? {
+            Value::Int(n)=>Ok(Value::Int(n.wrapping_neg())),
+            Value::Uint(n)=>Ok(Value::Uint(n.wrapping_neg())),
+            Value::Byte(n)=>Ok(Value::Byte(n.wrapping_neg())),
+            Value::Float(n)=>Ok(Value::Float(-n)),
+            Value::DoubleFloat(n)=>Ok(Value::DoubleFloat(-n)),
+            _=>self.error(span,"only numbers can be negated"),
+        }
+    }
+    /// Evaluates `==`, `!=` and the comparisons of order.
+    fn comparison(&mut self,expr:&Expr<'input>,span:(Location,Location))->Eval<'input> {
+        use Expr::*;
+        let (Equal{inner,..}|NotEqual{inner,..}|GreaterEqual{inner,..}|LessEqual{inner,..}|Greater{inner,..}|Less{inner,..})=expr else {
+            return self.error(span,"Internal compiler error: not a comparison");
+        };
+        let left=self.eval(&inner[0])?;
+        let right=self.eval(&inner[1])?;
+        if let Equal{..}|NotEqual{..}=expr {
+            let equal=self.equal(left,right,span)?;
+            return Ok(Value::Bool(equal==matches!(expr,Equal{..})));
+        }
+        let ordering=self.compare(left,right,span)?;
+        return Ok(Value::Bool(match expr {
+            GreaterEqual{..}=>ordering!=Ordering::Less,
+            LessEqual{..}=>ordering!=Ordering::Greater,
+            Greater{..}=>ordering==Ordering::Greater,
+            _=>ordering==Ordering::Less,
+        }));
+    }
+    fn object(&mut self,fields:&[ObjectField<'input>])->Eval<'input> {
+        let mut values=Vec::with_capacity(fields.len());
+        for field in fields {
+            values.push((field.name,self.eval(&field.data)?));
+        }
+        return Ok(Value::Object(values));
+    }
+    /// An anonymous function, which keeps the variables it can see.
+    fn anon_function(&self,function:Scope)->Value<'input> {
+        let frame=self.frames.last().expect("Internal compiler error: no frame to run in");
+        let mut captured=frame.captured.as_deref().cloned().unwrap_or_default();
+        captured.extend(frame.vars.iter().map(|(k,v)|(*k,v.clone())));
+        return Value::Function{function,captured:Some(Rc::new(captured))};
+    }
+    fn run_loop(&mut self,expr:&Expr<'input>,span:(Location,Location))->Eval<'input> {
+        match expr {
+            Expr::ForeverLoop{block,..}=>loop {
+                if let Some(value)=self.iteration(*block)? {
+                    return Ok(value);
+                }
+            },
+            Expr::WhileLoop{condition,block,..}=>{
+                while self.condition(condition)? {
+                    if let Some(value)=self.iteration(*block)? {
+                        return Ok(value);
+                    }
+                }
+                Ok(Value::None)
+            },
+            Expr::ForLoop{var,iterator,block,..}=>{
+                let items=match self.eval(iterator)? {
+                    Value::Slice(items)=>items,
+                    Value::Ref(place)=>match self.deref(Value::Ref(place),span)? {
+                        Value::Slice(items)=>items,
+                        _=>return self.error(span,"only slices can be iterated over"),
+                    },
+                    _=>return self.error(span,"only slices can be iterated over"),
+                };
+                let param=self.scopes.get(*block).and_then(|b|b.get_var(var));
+                for item in items {
+                    if let Some(param)=param {
+                        self.define(param,item);
+                    }
+                    if let Some(value)=self.iteration(*block)? {
+                        return Ok(value);
+                    }
+                }
+                Ok(Value::None)
+            },
+            _=>self.error(span,"Internal compiler error: not a loop"),
+        }
+    }
+    fn run_match(&mut self,block:&Match<'input>,span:(Location,Location))->Eval<'input> {
+        let value=self.eval(&block.to_match)?;
+        let ty=expr_type(self.filename,self.scopes,&block.to_match);
+        for (pattern,leaf) in block.leafs.iter() {
+            if self.matches(pattern,&value,&ty)? {
+                return self.eval(leaf);
+            }
+        }
+        return self.error(span,"no pattern matched the value");
+    }
+    fn run_if(&mut self,branches:&[(Expr<'input>,Scope)],else_block:Option<Scope>)->Eval<'input> {
+        for (condition,block) in branches.iter() {
+            if self.condition(condition)? {
+                return self.run_branch(*block);
+            }
+        }
+        return match else_block {
+            Some(block)=>self.run_branch(block),
+            None=>Ok(Value::None),
+        };
+    }
+    /// Runs a branch of an `if`. Its value is the value of the expression it ends with.
+    fn run_branch(&mut self,block:Scope)->Eval<'input> {
+        let (statements,value)=self.scopes.branch_value(block);
+        for stmt in statements {
+            self.exec(stmt)?;
+        }
+        return match value {
+            Some(value)=>self.eval(value),
+            None=>Ok(Value::None),
+        };
+    }
+    fn index_value(&mut self,from:&Expr<'input>,index:&Expr<'input>,span:(Location,Location))->Eval<'input> {
+        let from=self.eval(from)?;
+        let Value::Slice(mut items)=self.deref(from,span)? else {
+            return self.error(span,"only slices can be indexed");
+        };
+        let index=self.index(index,items.len())?;
+        return Ok(items.swap_remove(index));
+    }
+    /// The items of `from` from the first bound up to the second. Bounds that are left out are the
+    /// ends of the slice.
+    fn slice_value(&mut self,from:&Expr<'input>,range:[Option<&Expr<'input>>;2],span:(Location,Location))->Eval<'input> {
+        let from=self.eval(from)?;
+        let Value::Slice(items)=self.deref(from,span)? else {
+            return self.error(span,"only slices can be sliced");
+        };
+        let mut bounds=[0,items.len() as i128];
+        for (bound,expr) in bounds.iter_mut().zip(range) {
+            if let Some(expr)=expr {
+                match self.eval(expr)?.as_integer() {
+                    Some(n)=>*bound=n,
+                    None=>return self.error(expr.span(),"indexes have to be integers"),
+                }
+            }
+        }
+        let [start,end]=bounds;
+        if start<0||start>end||end>items.len() as i128 {
+            return self.error(span,format!("range {}..{} is out of bounds for a slice of length {}",start,end,items.len()));
+        }
+        return Ok(Value::Slice(items[start as usize..end as usize].to_vec()));
+    }
+    fn binary(&mut self,op:Op,inner:&[Expr<'input>;2],span:(Location,Location))->Eval<'input> {
+        let left=self.eval(&inner[0])?;
+        let right=self.eval(&inner[1])?;
+        return self.arithmetic(op,left,right,span);
+    }
+    fn arithmetic(&self,op:Op,left:Value<'input>,right:Value<'input>,span:(Location,Location))->Eval<'input> {
+        let left=self.deref(left,span)?;
+        let right=self.deref(right,span)?;
+        // The amount to shift by doesn't have to be the same type as what is shifted.
+        if let Op::ShiftLeft|Op::ShiftRight=op {
+            let (Some(n),Some(amount))=(left.as_integer(),right.as_integer()) else {
+                return self.error(span,format!("`{}` can only be used on integers",op.symbol()));
+            };
+            let bits=if let Value::Byte(_)=left {8} else {64};
+            let amount=amount.rem_euclid(bits) as u32;
+            let n=if let Op::ShiftLeft=op {n<<amount} else {n>>amount};
+            return Ok(integer(&left,n));
+        }
+        let (left,right)=unify(left,right);
+        if let (Op::Add,Value::String(a),Value::String(b))=(op,&left,&right) {
+            return Ok(Value::String(format!("{}{}",a,b)));
+        }
+        if let (Some(a),Some(b))=(left.as_integer(),right.as_integer()) {
+            if matches!(op,Op::Div|Op::Mod)&&b==0 {
+                return self.error(span,"attempt to divide by zero");
+            }
+            // Every integer fits in an `i128`, and truncating the result back wraps it.
+            let n=match op {
+                Op::Add=>a+b,
+                Op::Sub=>a-b,
+                Op::Mul=>a.wrapping_mul(b),
+                Op::Div=>a/b,
+                Op::Mod=>a%b,
+                Op::BitAnd=>a&b,
+                Op::BitOr=>a|b,
+                Op::BitXor=>a^b,
+                Op::ShiftLeft|Op::ShiftRight=>unreachable!(),
+            };
+            return Ok(integer(&left,n));
+        }
+        if let (Value::Float(_)|Value::DoubleFloat(_),Some(a),Some(b))=(&left,left.as_float(),right.as_float()) {
+            let n=match op {
+                Op::Add=>a+b,
+                Op::Sub=>a-b,
+                Op::Mul=>a*b,
+                Op::Div=>a/b,
+                Op::Mod=>a%b,
+                _=>return self.error(span,format!("`{}` can only be used on integers",op.symbol())),
+            };
+            return Ok(Value::DoubleFloat(n).convert_number(&left));
+        }
+        return self.error(span,format!("`{}` can not be used on these values",op.symbol()));
+    }
+    fn equal(&self,left:Value<'input>,right:Value<'input>,span:(Location,Location))->Eval<'input,bool> {
+        let (left,right)=unify(self.deref(left,span)?,self.deref(right,span)?);
+        return Ok(left==right);
+    }
+    fn compare(&self,left:Value<'input>,right:Value<'input>,span:(Location,Location))->Eval<'input,Ordering> {
+        let (left,right)=unify(self.deref(left,span)?,self.deref(right,span)?);
+        let ordering=match (&left,&right) {
+            (Value::Char(a),Value::Char(b))=>Some(a.cmp(b)),
+            (Value::String(a),Value::String(b))=>Some(a.cmp(b)),
+            (Value::Bool(a),Value::Bool(b))=>Some(a.cmp(b)),
+            _=>match (left.as_integer(),right.as_integer()) {
+                (Some(a),Some(b))=>Some(a.cmp(&b)),
+                _=>left.as_float().zip(right.as_float()).and_then(|(a,b)|a.partial_cmp(&b)),
+            },
+        };
+        match ordering {
+            Some(ordering)=>Ok(ordering),
+            None=>self.error(span,"these values can not be compared"),
+        }
+    }
+    /// Whether `value` matches `pattern`. Variables the pattern binds are defined as it is
+    /// matched. Method patterns call the method of `ty`, the static type of what is matched.
+    fn matches(&mut self,pattern:&MatchPattern<'input>,value:&Value<'input>,ty:&Type<'input>)->Eval<'input,bool> {
+        let span=pattern.span();
+        match pattern {
+            MatchPattern::Data{inner,..}=>self.equal(value.clone(),Value::from_literal(inner),span),
+            MatchPattern::MethodCall{name,args,..}=>{
+                let value=self.deref(value.clone(),span)?;
+                let Some(method)=self.scopes.find_method(ty,name) else {
+                    return self.error(span,format!("no method `{}` found",name));
+                };
+                let mut values=Vec::with_capacity(args.len()+1);
+                match self.this_param(method) {
+                    Some(true)=>values.push(Value::Ref(Place::new(value))),
+                    Some(false)=>values.push(value),
+                    None=>{},
+                }
+                values.extend(self.eval_all(args)?);
+                let result=self.call(method,None,values,span)?;
+                Ok(matches!(result,Value::Bool(true)))
+            },
+            MatchPattern::Structure{structure,..}=>self.matches_structure(structure,value,span),
+            MatchPattern::Var{var,..}=>{
+                self.define(*var,value.clone());
+                Ok(true)
+            },
+            MatchPattern::Equal{inner,..}|MatchPattern::NotEqual{inner,..}=>{
+                let other=self.eval(inner)?;
+                let equal=self.equal(value.clone(),other,span)?;
+                Ok(equal==matches!(pattern,MatchPattern::Equal{..}))
+            },
+            MatchPattern::GreaterEqual{inner,..}|
+                MatchPattern::LessEqual{inner,..}|
+                MatchPattern::Greater{inner,..}|
+                MatchPattern::Less{inner,..}=>{
+                let other=self.eval(inner)?;
+                let ordering=self.compare(value.clone(),other,span)?;
+                Ok(match pattern {
+                    MatchPattern::GreaterEqual{..}=>ordering!=Ordering::Less,
+                    MatchPattern::LessEqual{..}=>ordering!=Ordering::Greater,
+                    MatchPattern::Greater{..}=>ordering==Ordering::Greater,
+                    _=>ordering==Ordering::Less,
+                })
+            },
+            MatchPattern::IsType{inner,..}=>Ok(self.is_type(value,inner)),
+        }
+    }
+    fn matches_structure(&mut self,structure:&MatchPatternStructure<'input>,value:&Value<'input>,span:(Location,Location))->Eval<'input,bool> {
+        let value=self.deref(value.clone(),span)?;
+        let (exact,items)=match structure {
+            MatchPatternStructure::Block{exact,block,..}=>(*exact,block),
+            MatchPatternStructure::TypedBlock{exact,ty,block,..}=>{
+                if !self.is_type(&value,ty) {
+                    return Ok(false);
+                }
+                (*exact,block)
+            },
+        };
+        let Value::Object(fields)=&value else {return Ok(false)};
+        if exact&&fields.len()!=items.len() {
+            return Ok(false);
+        }
+        for item in items {
+            match item {
+                MatchPatternStructureItem::Field{name,var,..}|MatchPatternStructureItem::NamedField{name,var,..}=>match value.field(name) {
+                    Some(field)=>self.define(*var,field.clone()),
+                    None=>return Ok(false),
+                },
+                MatchPatternStructureItem::NamedBlock{name,block,..}=>match value.field(name) {
+                    Some(field)=>if !self.matches_structure(block,field,span)? {
+                        return Ok(false);
+                    },
+                    None=>return Ok(false),
+                },
+            }
+        }
+        return Ok(true);
+    }
+}
+
+
+/// The variable a narrowed variable narrows.
+fn original(scopes:&Scopes,mut var:Scope)->Scope {
+    while let Some(&VarScopeItem::Narrowed{original,..})=scopes.get(var) {
+        var=original;
+    }
+    return var;
+}
+/// An integer of the same type as `like`, wrapping if it doesn't fit.
+fn integer<'input>(like:&Value<'input>,n:i128)->Value<'input> {
+    match like {
+        Value::Uint(_)=>Value::Uint(n as u64),
+        Value::Byte(_)=>Value::Byte(n as u8),
+        _=>Value::Int(n as i64),
+    }
+}
+/// Converts two numbers to the same type. They can only be different when one of them is a number
+/// literal, which is an `Int` or a `DoubleFloat` until it is used as something else.
+fn unify<'input>(left:Value<'input>,right:Value<'input>)->(Value<'input>,Value<'input>) {
+    if !left.is_number()||!right.is_number()||std::mem::discriminant(&left)==std::mem::discriminant(&right) {
+        return (left,right);
+    }
+    let to_left=match (&left,&right) {
+        (Value::Float(_),Value::DoubleFloat(_))=>true,
+        (Value::DoubleFloat(_),Value::Float(_))=>false,
+        (_,Value::Int(_))=>true,
+        (Value::Int(_),_)=>false,
+        (Value::Float(_)|Value::DoubleFloat(_),_)=>true,
+        _=>false,
+    };
+    if to_left {
+        let right=right.convert_number(&left);
+        return (left,right);
+    } else {
+        return (left.convert_number(&right),right);
+    }
+}
+fn exit_code(value:&Value)->i32 {
+    value.as_integer().map_or(0,|n|n as i32)
+}
+fn run_main<'input>(filename:&'input str,scopes:&Scopes<'input>,args:&[String],natives:Option<&dyn Natives<'input>>)->Result<i32,Error<'input,String>> {
+    let root=Scope(0);
+    let stack_start=0u8;
+    let mut interpreter=Interpreter {
+        filename,
+        scopes,
+        args,
+        frames:vec![Frame::default()],
+        stack_start:&stack_start as *const u8 as usize,
+        natives,
+    };
+    if let Err(Flow::Error(err))=interpreter.run_block(root) {
+        return Err(err);
+    }
+    let main=scopes.get(root).and_then(|r|scopes.find_function(r.statements(),"main"));
+    let Some(main)=main else {
+        return Err(Error::new_runtime(filename,Location::default(),Location::default(),"there is no `main` function to run"));
+    };
+    let span=scopes.get(main).and_then(|m|m.def_span()).unwrap_or_default();
+    let main_args=match scopes.get(main) {
+        Some(VarScopeItem::Function{params,..}) if !params.is_empty()=>{
+            vec![Value::Slice(args.iter().map(|a|Value::String(a.clone())).collect())]
+        },
+        _=>Vec::new(),
+    };
+    return match interpreter.call(main,None,main_args,span) {
+        Ok(value)|Err(Flow::Return(value))=>Ok(exit_code(&value)),
+        Err(Flow::Error(err))=>Err(err),
+        Err(_)=>Ok(0),
+    };
+}
+/// Runs the program on a thread with a big enough stack.
+fn run_on_thread<'input>(filename:&'input str,scopes:&Scopes<'input>,args:&[String],natives:Option<&(dyn Natives<'input>+Sync)>)->Result<i32,Error<'input,String>> {
+    std::thread::scope(|s|{
+        let thread=std::thread::Builder::new()
+            .name("cppl_interp".into())
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(s,||run_main(filename,scopes,args,natives.map(|n|n as &dyn Natives<'input>)));
+        match thread {
+            Ok(thread)=>thread.join().unwrap_or_else(|e|std::panic::resume_unwind(e)),
+            Err(err)=>Err(Error::new_runtime(filename,Location::default(),Location::default(),format!("could not start the interpreter: {}",err))),
+        }
+    })
+}
+/// Runs the `main` function of a program that passed analysis, after the statements in the root
+/// scope. `args` are passed to `main` and the exit code is what it returns.
+pub fn run<'input>(filename:&'input str,scopes:&Scopes<'input>,args:&[String])->Result<i32,Error<'input,String>> {
+    return run_on_thread(filename,scopes,args,None);
+}
+/// Runs a program like `run`, but calls to the functions in `natives` run their native code.
+pub fn run_with_natives<'input>(filename:&'input str,scopes:&Scopes<'input>,args:&[String],natives:&(dyn Natives<'input>+Sync))->Result<i32,Error<'input,String>> {
+    return run_on_thread(filename,scopes,args,Some(natives));
+}
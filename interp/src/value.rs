@@ -0,0 +1,240 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+};
+use cppl_ast::{
+    refined::*,
+    BuiltinValue,
+};
+
+
+/// Where a variable's value is stored. References point into these.
+pub type Cell<'input>=Rc<RefCell<Value<'input>>>;
+/// The variables an anonymous function can see from where it was created.
+pub type Env<'input>=HashMap<Scope,Cell<'input>>;
+
+
+#[derive(Debug,Clone)]
+pub enum Value<'input> {
+    /// What a function without a return type returns, and the value of blocks and loops that don't
+    /// have one.
+    None,
+    Uint(u64),
+    Int(i64),
+    Float(f32),
+    DoubleFloat(f64),
+    Byte(u8),
+    Bool(bool),
+    Char(char),
+    String(String),
+    Object(Vec<(&'input str,Value<'input>)>),
+    /// A value of a named enum variant. `variant` is the `EnumVariant` scope.
+    Variant {
+        variant:Scope,
+        fields:Vec<Value<'input>>,
+    },
+    Slice(Vec<Value<'input>>),
+    /// References and pointers.
+    Ref(Place<'input>),
+    /// A function, builtin function or enum variant that can be called. Anonymous functions keep
+    /// the variables they can see in `captured`.
+    Function {
+        function:Scope,
+        captured:Option<Rc<Env<'input>>>,
+    },
+}
+impl<'input> Value<'input> {
+    pub fn from_literal(data:&Data<'input>)->Self {
+        match data {
+            Data::String{s,..}=>Value::String(s.to_string()),
+            Data::GenericNumber{negative,data,..}=>{
+                let digits=data.replace('_',"");
+                match digits.parse::<i64>() {
+                    Ok(n) if *negative=>Value::Int(-n),
+                    Ok(n)=>Value::Int(n),
+                    // Too large for an `Int`, so it can only be a `Uint`.
+                    Err(_)=>Value::Uint(digits.parse().unwrap_or(u64::MAX)),
+                }
+            },
+            // Float literals are kept at full precision until we know what type they are used as.
+            Data::GenericFloat{negative,data,..}=>{
+                let n=data.replace('_',"").parse::<f64>().unwrap_or_default();
+                Value::DoubleFloat(if *negative {-n} else {n})
+            },
+            Data::UInt{data,..}=>Value::Uint(*data),
+            Data::Int{data,..}=>Value::Int(*data),
+            Data::Float{data,..}=>Value::Float(*data),
+            Data::LargeFloat{data,..}=>Value::DoubleFloat(*data),
+            Data::Char{data,..}=>Value::Char(*data),
+            Data::Bool{data,..}=>Value::Bool(*data),
+        }
+    }
+    /// The value of an integer as an `i128` so any integer type fits.
+    pub fn as_integer(&self)->Option<i128> {
+        match self {
+            Value::Uint(n)=>Some(*n as i128),
+            Value::Int(n)=>Some(*n as i128),
+            Value::Byte(n)=>Some(*n as i128),
+            _=>None,
+        }
+    }
+    pub fn as_float(&self)->Option<f64> {
+        match self {
+            Value::Float(n)=>Some(*n as f64),
+            Value::DoubleFloat(n)=>Some(*n),
+            _=>self.as_integer().map(|n|n as f64),
+        }
+    }
+    pub fn is_number(&self)->bool {
+        matches!(self,Value::Uint(_)|Value::Int(_)|Value::Float(_)|Value::DoubleFloat(_)|Value::Byte(_))
+    }
+    /// Converts a number to the number type `like` is.
+    pub fn convert_number(&self,like:&Self)->Self {
+        match (self,like) {
+            (Value::Float(_),Value::Float(_))|(Value::DoubleFloat(_),Value::DoubleFloat(_))=>self.clone(),
+            (_,Value::Float(_))=>Value::Float(self.as_float().unwrap_or_default() as f32),
+            (_,Value::DoubleFloat(_))=>Value::DoubleFloat(self.as_float().unwrap_or_default()),
+            (_,Value::Uint(_))=>Value::Uint(self.as_integer().unwrap_or_default() as u64),
+            (_,Value::Int(_))=>Value::Int(self.as_integer().unwrap_or_default() as i64),
+            (_,Value::Byte(_))=>Value::Byte(self.as_integer().unwrap_or_default() as u8),
+            _=>self.clone(),
+        }
+    }
+    /// Gets the field `name` of an object.
+    pub fn field(&self,name:&str)->Option<&Self> {
+        match self {
+            Value::Object(fields)=>fields.iter().find(|(n,_)|*n==name).map(|(_,v)|v),
+            _=>None,
+        }
+    }
+    /// The type of this value, as precise as the value can tell.
+    pub fn ty(&self)->Type<'input> {
+        match self {
+            Value::None=>Type::Unknown,
+            Value::Uint(_)=>Type::Uint,
+            Value::Int(_)=>Type::Int,
+            Value::Float(_)=>Type::Float,
+            Value::DoubleFloat(_)=>Type::DoubleFloat,
+            Value::Byte(_)=>Type::Byte,
+            Value::Bool(_)=>Type::Bool,
+            Value::Char(_)=>Type::Char,
+            Value::String(_)=>Type::String,
+            Value::Object(fields)=>Type::Object {
+                start:Default::default(),
+                end:Default::default(),
+                fields:fields.iter()
+                    .map(|(name,value)|TypeObjectField {
+                        start:Default::default(),
+                        end:Default::default(),
+                        public:Some(Visibility::Full),
+                        mutable:Some(Visibility::Full),
+                        name,
+                        ty:value.ty(),
+                    })
+                    .collect(),
+                exact:true,
+            },
+            Value::Variant{variant,..}=>Type::Named{path:*variant,start:Default::default(),end:Default::default(),generics:Vec::new()},
+            Value::Slice(items)=>Type::Slice {
+                start:Default::default(),
+                end:Default::default(),
+                inner:Box::new(items.first().map_or(Type::Never,|i|i.ty())),
+            },
+            Value::Ref(place)=>Type::Ref {
+                start:Default::default(),
+                end:Default::default(),
+                inner:Box::new(place.read().map_or(Type::Unknown,|v|v.ty())),
+            },
+            Value::Function{..}=>Type::Unknown,
+        }
+    }
+}
+impl BuiltinValue for Value<'_> {
+    fn none()->Self {
+        Value::None
+    }
+    fn string(s:String)->Self {
+        Value::String(s)
+    }
+    fn slice(items:Vec<Self>)->Self {
+        Value::Slice(items)
+    }
+    fn as_str(&self)->Option<&str> {
+        match self {
+            Value::String(s)=>Some(s),
+            _=>None,
+        }
+    }
+}
+impl PartialEq for Value<'_> {
+    fn eq(&self,other:&Self)->bool {
+        match (self,other) {
+            (Value::None,Value::None)=>true,
+            (Value::Bool(a),Value::Bool(b))=>a==b,
+            (Value::Char(a),Value::Char(b))=>a==b,
+            (Value::String(a),Value::String(b))=>a==b,
+            (Value::Object(a),Value::Object(b))=>a.len()==b.len()&&a.iter().all(|(name,v)|other.field(name)==Some(v)),
+            (Value::Variant{variant:a,fields:a_fields},Value::Variant{variant:b,fields:b_fields})=>a==b&&a_fields==b_fields,
+            (Value::Slice(a),Value::Slice(b))=>a==b,
+            (Value::Ref(a),Value::Ref(b))=>a.read()==b.read(),
+            (Value::Function{function:a,..},Value::Function{function:b,..})=>a==b,
+            (a,b) if a.is_number()&&b.is_number()=>match (a.as_integer(),b.as_integer()) {
+                (Some(a),Some(b))=>a==b,
+                _=>a.as_float()==b.as_float(),
+            },
+            _=>false,
+        }
+    }
+}
+/// A step from a value to a part of it.
+#[derive(Debug,Clone)]
+pub enum Projection<'input> {
+    Field(&'input str),
+    Index(usize),
+}
+/// A place a value is stored: a variable and the fields and items to follow inside of it.
+#[derive(Debug,Clone)]
+pub struct Place<'input> {
+    pub cell:Cell<'input>,
+    pub path:Vec<Projection<'input>>,
+}
+impl<'input> Place<'input> {
+    pub fn new(value:Value<'input>)->Self {
+        Place{cell:Rc::new(RefCell::new(value)),path:Vec::new()}
+    }
+    /// The value at this place. `None` if a field or item it goes through doesn't exist.
+    pub fn read(&self)->Option<Value<'input>> {
+        let value=self.cell.borrow();
+        let mut current=&*value;
+        for step in self.path.iter() {
+            current=match (step,current) {
+                (Projection::Field(name),_)=>current.field(name)?,
+                (Projection::Index(i),Value::Slice(items))=>items.get(*i)?,
+                _=>return None,
+            };
+        }
+        return Some(current.clone());
+    }
+    /// Changes the value at this place. Returns `false` if a field or item it goes through doesn't
+    /// exist.
+    pub fn write(&self,new:Value<'input>)->bool {
+        let mut value=self.cell.borrow_mut();
+        let mut current=&mut *value;
+        for step in self.path.iter() {
+            current=match (step,current) {
+                (Projection::Field(name),Value::Object(fields))=>match fields.iter_mut().find(|(n,_)|n==name) {
+                    Some((_,v))=>v,
+                    None=>return false,
+                },
+                (Projection::Index(i),Value::Slice(items))=>match items.get_mut(*i) {
+                    Some(v)=>v,
+                    None=>return false,
+                },
+                _=>return false,
+            };
+        }
+        *current=new;
+        return true;
+    }
+}
@@ -0,0 +1,146 @@
+//! Programs run with the interpreter, and the exit codes or errors they have to end with.
+use cppl_parser::parse;
+use cppl_ast::analyze;
+use cppl_interp::run;
+
+
+/// Runs `source`, which has to pass analysis. The error is the reason of the runtime error.
+fn run_source(source:&str)->Result<i32,String> {
+    let (parsed,parse_errors)=parse("test.cppl",source);
+    assert!(parse_errors.is_empty(),"{:?}",parse_errors);
+    let (scopes,_)=match analyze("test.cppl",parsed) {
+        Ok(analyzed)=>analyzed,
+        Err(results)=>panic!("{:?}",results.errors.into_iter().map(|e|e.reason).collect::<Vec<_>>()),
+    };
+    return run("test.cppl",&scopes,&[]).map_err(|e|e.reason);
+}
+/// A function that recurses `n` times, with its call `depth` levels deep in an expression.
+fn deep(n:usize,depth:usize)->String {
+    let ones="+1".repeat(depth);
+    return format!("fn deep[n:Int]:Int {{
+    if n=0 {{
+        return 0
+    }}
+    return deep(n-1){}-{}+1
+}}
+fn main[]:Int {{
+    return deep({})-{}
+}}
+",ones,depth,n,n-10);
+}
+
+
+#[test]
+fn control_flow() {
+    assert_eq!(run_source("fn main[]:Int {
+    mut total:=0
+    for x in [1,2,3,4] {
+        if x=3 {
+            continue
+        }
+        total+<-x
+    }
+    mut i:=0
+    while i<5 {
+        i+<-1
+    }
+    found:=loop {
+        break 6
+    }
+    size:=match total {
+        7=>1,
+        _=>2,
+    }
+    return total+i+found+size
+}
+"),Ok(19));
+}
+#[test]
+fn functions_and_objects() {
+    assert_eq!(run_source("type P<-{pub mut v:Int}
+fn adder[k:Int]:fn[a:Int]:Int {
+    return fn[a:Int]:Int {
+        return a+k
+    }
+}
+fn bump[p:&mut P] {
+    p.v<-p.v+1
+}
+fn main[]:Int {
+    mut p:P={pub mut v<-1}
+    bump(&mut p)
+    add:=adder(10)
+    return add(p.v)
+}
+"),Ok(12));
+}
+#[test]
+fn interface_dispatch() {
+    assert_eq!(run_source("interface Shape {
+    fn area[this]:Int
+    fn scaled[this,by:Int]:Int {
+        return this.area()*by
+    }
+}
+type Square<-{pub side:Int}
+type Rect<-{pub w:Int,pub h:Int}
+impl Shape for Square {
+    fn area[this]:Int {
+        s:=this.side
+        return s*s
+    }
+}
+impl Shape for Rect {
+    fn area[this]:Int {
+        w:=this.w
+        h:=this.h
+        return w*h
+    }
+    fn scaled[this,by:Int]:Int {
+        return 0
+    }
+}
+fn total[s:Shape]:Int {
+    return s.area()+s.scaled(2)
+}
+fn main[]:Int {
+    sq:Square={pub side<-3}
+    r:Rect={pub w<-2,pub h<-5}
+    return total(sq)+total(r)
+}
+"),Ok(37));
+}
+#[test]
+fn runtime_errors() {
+    assert_eq!(
+        run_source("fn main[]:Int {\n    xs:=[1,2]\n    return xs[2]\n}\n"),
+        Err("index out of bounds: the length is 2 but the index is 2".to_string()),
+    );
+    assert_eq!(
+        run_source("fn main[]:Int {\n    z:=0\n    return 1/z\n}\n"),
+        Err("attempt to divide by zero".to_string()),
+    );
+}
+#[test]
+fn deep_recursion() {
+    assert_eq!(run_source(&deep(900,0)),Ok(10));
+    assert_eq!(run_source(&deep(2000,0)),Err("stack overflow: more than 1000 calls are nested".to_string()));
+}
+/// Calls deep inside of expressions use more of the interpreter's stack for each call, which has
+/// to end in an error instead of overflowing the real stack.
+#[test]
+fn deep_recursion_in_expressions() {
+    let result=run_source(&deep(900,6));
+    assert!(matches!(&result,Ok(10))||result.as_ref().is_err_and(|e|e.starts_with("stack overflow")),"{:?}",result);
+}
+#[test]
+fn derefs_in_operators() {
+    assert_eq!(run_source("fn twice[r:&Int]:Int {
+    return 2 * *r + *r
+}
+fn main[]:Int {
+    x:=7
+    return twice(&x)
+}
+"),Ok(21));
+}
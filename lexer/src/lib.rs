@@ -24,7 +24,10 @@ pub enum Token<'input> {
     #[token("loop", |_|Keyword::Loop)]
     #[token("return", |_|Keyword::Return)]
     #[token("continue", |_|Keyword::Continue)]
+    #[token("break", |_|Keyword::Break)]
     #[token("match", |_|Keyword::Match)]
+    #[token("if", |_|Keyword::If)]
+    #[token("else", |_|Keyword::Else)]
     #[token("enum", |_|Keyword::Enum)]
     #[token("module", |_|Keyword::Module)]
     #[token("this", |_|Keyword::This)]
@@ -36,6 +39,7 @@ pub enum Token<'input> {
     #[token("or", |_|Keyword::Or)]
     #[token("in", |_|Keyword::In)]
     #[token("is", |_|Keyword::Is)]
+    #[token("unsafe", |_|Keyword::Unsafe)]
     Keyword(Keyword),
     #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*",slice)]
     Word(&'input str),
@@ -75,14 +79,32 @@ pub enum Token<'input> {
     NotEqual,
     #[token("<-")]
     Assign,
+    #[token("+<-")]
+    AddAssign,
+    #[token("-<-")]
+    SubAssign,
+    #[token("*<-")]
+    MulAssign,
+    #[token("/<-")]
+    DivAssign,
+    #[token("%<-")]
+    ModAssign,
     #[token("-")]
     Dash,
     #[token(".")]
     Dot,
+    #[token("..")]
+    Range,
     #[token("...")]
     Etc,
     #[token("|")]
     Union,
+    #[token("^")]
+    Caret,
+    #[token("<<")]
+    ShiftLeft,
+    #[token(">>")]
+    ShiftRight,
     #[token("+")]
     Add,
     #[token("*")]
@@ -97,6 +119,13 @@ pub enum Token<'input> {
     Not,
     #[token("'")]
     SingleQuote,
+    /// A character literal that is just a letter, like `'a'`. Lexed as one token so it isn't
+    /// mistaken for a label.
+    #[regex("'[a-zA-Z_][a-zA-Z0-9_]*'",fix_string)]
+    CharWord(&'input str),
+    /// A loop label, like `'outer`.
+    #[regex("'[a-zA-Z_][a-zA-Z0-9_]*",label)]
+    Label(&'input str),
     #[token("\\")]
     Backslash,
     #[token("=>")]
@@ -110,7 +139,6 @@ pub enum Token<'input> {
     Number(&'input str),
     #[regex("[0-9]+\\.[0-9]+",slice)]
     #[regex("\\.[0-9]+",slice)]
-    #[regex("[0-9]+\\.",slice)]
     Float(&'input str),
     #[regex("///[^\n]*",line_doc_comment_fix)]
     /// Disable block comments for now. We can't do syntax highlighting on them properly in
@@ -150,10 +178,19 @@ impl<'source> Display for Token<'source> {
             Equal=>write!(f,"token: `==`"),
             NotEqual=>write!(f,"token: `!="),
             Assign=>write!(f,"token: `<-`"),
+            AddAssign=>write!(f,"token: `+<-`"),
+            SubAssign=>write!(f,"token: `-<-`"),
+            MulAssign=>write!(f,"token: `*<-`"),
+            DivAssign=>write!(f,"token: `/<-`"),
+            ModAssign=>write!(f,"token: `%<-`"),
             Dash=>write!(f,"token: `-`"),
             Dot=>write!(f,"token: `.`"),
+            Range=>write!(f,"token: `..`"),
             Etc=>write!(f,"token: `...`"),
             Union=>write!(f,"token: `|`"),
+            Caret=>write!(f,"token: `^`"),
+            ShiftLeft=>write!(f,"token: `<<`"),
+            ShiftRight=>write!(f,"token: `>>`"),
             Add=>write!(f,"token: `+`"),
             Mul=>write!(f,"token: `*`"),
             Div=>write!(f,"token: `/`"),
@@ -161,6 +198,8 @@ impl<'source> Display for Token<'source> {
             And=>write!(f,"token: `&`"),
             Not=>write!(f,"token: `!`"),
             SingleQuote=>write!(f,"token: `'`"),
+            CharWord(s)=>write!(f,"char: `'{}'`",s),
+            Label(s)=>write!(f,"label: `'{}`",s),
             Backslash=>write!(f,"token: `\\`"),
             MatchSeparator=>write!(f,"token: `=>`"),
             String(_)=>write!(f,"String"),
@@ -187,7 +226,10 @@ pub enum Keyword {
     Loop,
     Return,
     Continue,
+    Break,
     Match,
+    If,
+    Else,
     Enum,
     Module,
     This,
@@ -199,6 +241,7 @@ pub enum Keyword {
     Or,
     In,
     Is,
+    Unsafe,
 }
 impl Display for Keyword {
     fn fmt(&self,f:&mut Formatter)->FmtResult {
@@ -216,7 +259,10 @@ impl Display for Keyword {
             Loop=>write!(f,"loop"),
             Return=>write!(f,"return"),
             Continue=>write!(f,"continue"),
+            Break=>write!(f,"break"),
             Match=>write!(f,"match"),
+            If=>write!(f,"if"),
+            Else=>write!(f,"else"),
             Enum=>write!(f,"enum"),
             Module=>write!(f,"module"),
             This=>write!(f,"this"),
@@ -228,6 +274,7 @@ impl Display for Keyword {
             Or=>write!(f,"or"),
             In=>write!(f,"in"),
             Is=>write!(f,"is"),
+            Unsafe=>write!(f,"unsafe"),
         }
     }
 }
@@ -300,6 +347,7 @@ impl<'input> Iterator for TokenIterator<'input> {
                     reason:"Invalid token",
                     level:ErrorLevel::LexError,
                     filename:self.filename,
+                    note:None,
                     start,
                     end:Location {
                         line_start_index:self.line_start,
@@ -350,6 +398,9 @@ fn line_doc_comment_fix<'input>(lex:&mut Lexer<'input,Token<'input>>)->&'input s
 fn slice<'input>(lex:&mut Lexer<'input,Token<'input>>)->&'input str {
     return lex.slice();
 }
+fn label<'input>(lexer:&mut Lexer<'input,Token<'input>>)->&'input str {
+    &lexer.slice()[1..]
+}
 fn parse_raw_string<'input>(lexer:&mut Lexer<'input,Token<'input>>)->Option<&'input str> {
     const CHAR_SLICE:&[char]=&['r','"'];
     let closing=lexer.slice().trim_matches(CHAR_SLICE);
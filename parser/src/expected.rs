@@ -0,0 +1,211 @@
+//! Turns the `expected` lists from LALRPOP into something readable. Groups of terminals are
+//! described by what they start, and the tokens before the error are used to say what the parser
+//! was in the middle of.
+use cppl_lexer::{
+    Keyword,
+    Token,
+    TokenIterator,
+};
+
+
+/// Groups of terminals that are described together. A group is used when every terminal in
+/// `required` is expected, and then every expected terminal in `members` is covered by it.
+struct Category {
+    name:&'static str,
+    required:&'static [&'static str],
+    members:&'static [&'static str],
+}
+const CATEGORIES:&[Category]=&[
+    Category {
+        name:"an expression",
+        required:&["\"Number\"","\"String\"","\"Word\""],
+        members:&[
+            "\"Number\"",
+            "\"Float\"",
+            "\"String\"",
+            "\"Word\"",
+            "\"'\"",
+            "\"CharWord\"",
+            "\"Label\"",
+            "\"(\"",
+            "\"[\"",
+            "\"{\"",
+            "\"-\"",
+            "\"!\"",
+            "\"&\"",
+            "\"*\"",
+            "\"true\"",
+            "\"false\"",
+            "\"this\"",
+            "\"fn\"",
+            "\"match\"",
+            "\"if\"",
+            "\"unsafe\"",
+            "\"loop\"",
+            "\"while\"",
+            "\"for\"",
+        ],
+    },
+    Category {
+        name:"a statement",
+        required:&["\"type\"","\"import\""],
+        members:&[
+            "\"type\"",
+            "\"interface\"",
+            "\"pub\"",
+            "\"mut\"",
+            "\"impl\"",
+            "\"import\"",
+            "\"enum\"",
+            "\"module\"",
+            "\"const\"",
+            "\"static\"",
+        ],
+    },
+    Category {
+        name:"a type",
+        required:&["\"Word\"","\"fn\"","\"{\""],
+        members:&["\"Word\"","\"fn\"","\"{\"","\"(\"","\"[\"","\"&\"","\"*\""],
+    },
+    Category {
+        name:"an operator",
+        required:&["\"+\"","\"*\""],
+        members:&[
+            "\"+\"",
+            "\"-\"",
+            "\"*\"",
+            "\"/\"",
+            "\"%\"",
+            "\"=\"",
+            "\"!=\"",
+            "\"<\"",
+            "\"<=\"",
+            "\">\"",
+            "\">=\"",
+            "\"and\"",
+            "\"or\"",
+            "\"is\"",
+            "\"|\"",
+            "\"^\"",
+            "\"<<\"",
+            "\">>\"",
+            "\".\"",
+            "\"..\"",
+        ],
+    },
+    Category {
+        name:"the end of the statement",
+        required:&["\"Newline\"","\";\""],
+        members:&["\"Newline\"","\";\""],
+    },
+];
+/// Terminals that don't read well when quoted.
+const TERMINAL_NAMES:&[(&str,&str)]=&[
+    ("\"Word\"","a name"),
+    ("\"Number\"","a number"),
+    ("\"Float\"","a float"),
+    ("\"String\"","a string"),
+    ("\"CharWord\"","a character"),
+    ("\"Label\"","a label"),
+    ("\"Newline\"","a new line"),
+];
+fn describe_terminal(terminal:&str)->String {
+    if let Some((_,name))=TERMINAL_NAMES.iter().find(|(t,_)|*t==terminal) {
+        return name.to_string();
+    }
+    return format!("`{}`",terminal.trim_matches('"'));
+}
+/// The text of the last token, if it is one that tells us what has to follow it.
+fn previous_token(tokens:&[Token])->Option<&'static str> {
+    match tokens.iter().rev().find(|t|**t!=Token::Newline)? {
+        Token::Decl=>Some(":="),
+        Token::Assign=>Some("<-"),
+        Token::MatchSeparator=>Some("=>"),
+        Token::Associated=>Some("::"),
+        Token::Colon=>Some(":"),
+        Token::Equal=>Some("="),
+        Token::Comma=>Some(","),
+        Token::Range=>Some(".."),
+        Token::Dot=>Some("."),
+        _=>None,
+    }
+}
+/// Finds the bracket that is still open at the end of `tokens` and what it is for.
+fn open_bracket(tokens:&[Token])->Option<(char,&'static str)> {
+    let mut depth=0usize;
+    for (i,token) in tokens.iter().enumerate().rev() {
+        match token {
+            Token::ParenthesisEnd|Token::BracketEnd|Token::BraceEnd=>depth+=1,
+            Token::ParenthesisStart|Token::BracketStart|Token::BraceStart if depth>0=>depth-=1,
+            Token::BracketStart=>return Some((']',bracket_kind(&tokens[..i]))),
+            Token::ParenthesisStart=>return Some((')',"the parentheses")),
+            Token::BraceStart=>return Some(('}',"the block")),
+            _=>{},
+        }
+    }
+    return None;
+}
+/// What a `[` after `before` starts.
+fn bracket_kind(before:&[Token])->&'static str {
+    use Keyword as K;
+    match before {
+        [..,Token::Keyword(K::Function)]|
+            [..,Token::Keyword(K::Function),Token::Word(_)]=>"the parameter list",
+        [..,Token::Keyword(K::Impl)]|
+            [..,Token::Keyword(K::Type|K::Interface|K::Enum),Token::Word(_)]=>"the type parameters",
+        [..,Token::Word(_)|Token::ParenthesisEnd|Token::BracketEnd|Token::String(_)|Token::Keyword(K::This)]=>"the index",
+        _=>"the list",
+    }
+}
+/// Describes what the parser expected at `index` in `source`.
+pub(crate) fn describe(expected:&[String],source:&str,index:usize)->String {
+    // Only whole tokens come before the error, so lexing up to it gives the same tokens the parser
+    // saw, without anything from strings or comments.
+    let tokens=TokenIterator::new(&source[..index],"",true)
+        .filter_map(|t|t.ok())
+        .map(|(_,t,_)|t)
+        .collect::<Vec<_>>();
+    let expected=expected.iter().map(|s|s.as_str()).collect::<Vec<_>>();
+    let closer=open_bracket(&tokens).map(|(close,what)|(format!("\"{}\"",close),what))
+        .filter(|(close,_)|expected.contains(&close.as_str()));
+    let mut remaining=expected.clone();
+    let mut items=Vec::new();
+    for category in CATEGORIES {
+        if !category.required.iter().all(|r|remaining.contains(r)) {
+            continue;
+        }
+        remaining.retain(|t|!category.members.contains(t));
+        // Inside of brackets, closing them is what's most likely missing. Listing every token that
+        // could continue whatever came last is just noise.
+        if closer.is_some()&&matches!(category.name,"an operator"|"the end of the statement") {
+            continue;
+        }
+        let mut item=category.name.to_string();
+        if let Some(prev)=previous_token(&tokens) {
+            if items.is_empty()&&category.name!="an operator" {
+                item.push_str(&format!(" after `{}`",prev));
+            }
+        }
+        items.push(item);
+    }
+    if let Some((close,_))=&closer {
+        remaining.retain(|t|t==close||*t=="\",\"");
+    }
+    for terminal in remaining {
+        let mut item=describe_terminal(terminal);
+        if let Some((close,what))=&closer {
+            if terminal==close {
+                item.push_str(&format!(" to close {}",what));
+            }
+        }
+        items.push(item);
+    }
+    return match items.len() {
+        0=>"something else".to_string(),
+        1=>items.pop().unwrap(),
+        _=>{
+            let last=items.pop().unwrap();
+            format!("{} or {}",items.join(", "),last)
+        },
+    };
+}
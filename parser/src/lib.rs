@@ -8,11 +8,33 @@ use cppl_lexer::*;
 
 
 lalrpop_mod!(pub parser);
+mod expected;
 
 
-pub fn parse<'input>(filename:&'input str,source:&'input str)->Result<Vec<Statement<'input>>,Error<'input,String>> {
+/// Parses a file and recovers from syntax errors at statement boundaries. Returns every statement
+/// that parsed along with all of the errors. If the errors are empty, the statements are the whole
+/// file.
+pub fn parse<'input>(filename:&'input str,source:&'input str)->(Vec<Statement<'input>>,Vec<Error<'input,String>>) {
     let tokens=TokenIterator::new(&source,filename,true);
-    return parser::AllParser::new().parse(filename,tokens).map_err(|e|convert_error(e,filename));
+    let mut recovered=Vec::new();
+    let result=parser::AllParser::new().parse(filename,&mut recovered,tokens);
+    let mut errors=recovered.into_iter()
+        .map(|r|convert_error(r.error,filename,source))
+        .collect::<Vec<_>>();
+    let statements=match result {
+        Ok(statements)=>statements,
+        Err(e)=>{
+            let error=convert_error(e,filename,source);
+            // Parsing can't go on past this error, but everything before its line still parsed, so
+            // parse just that again to keep it. Its errors were already recorded the first time.
+            let cut=error.start.line_start_index;
+            errors.push(error);
+            let tokens=TokenIterator::new(&source,filename,true)
+                .take_while(|t|!matches!(t,Ok((start,_,_)) if start.index>=cut));
+            parser::AllParser::new().parse(filename,&mut Vec::new(),tokens).unwrap_or_default()
+        },
+    };
+    return (statements,errors);
 }
 pub(crate) fn parse_char<'input>(s:String,filename:&'input str,start:Location,end:Location)->Result<char,Error<'input,&'static str>> {
     let mut start=start;
@@ -44,6 +66,7 @@ pub(crate) fn parse_char<'input>(s:String,filename:&'input str,start:Location,en
                                 start,
                                 end,
                                 level,
+                                note:None,
                                 reason:"invalid ASCII escape sequence",
                             });
                         }
@@ -61,6 +84,7 @@ pub(crate) fn parse_char<'input>(s:String,filename:&'input str,start:Location,en
                                 start,
                                 end:start,
                                 level,
+                                note:None,
                                 reason:"expected \"{\" got hex number"
                             });
                         }
@@ -71,6 +95,7 @@ pub(crate) fn parse_char<'input>(s:String,filename:&'input str,start:Location,en
                                 start,
                                 end:start,
                                 level,
+                                note:None,
                                 reason:"expected \"}\" got token `'`",
                             });
                         }
@@ -82,6 +107,7 @@ pub(crate) fn parse_char<'input>(s:String,filename:&'input str,start:Location,en
                             start,
                             end,
                             level,
+                            note:None,
                             reason:"unicode escape sequence must be between 0x0 and 0xD7FF or 0xE000 and 0x10FFFF including these values",
                         };
                         if !hex.chars().all(|c|c.is_ascii_hexdigit()) {
@@ -90,6 +116,7 @@ pub(crate) fn parse_char<'input>(s:String,filename:&'input str,start:Location,en
                                 start,
                                 end,
                                 level,
+                                note:None,
                                 reason:"invalid hex data",
                             });
                         }
@@ -106,6 +133,7 @@ pub(crate) fn parse_char<'input>(s:String,filename:&'input str,start:Location,en
                 start,
                 end,
                 level,
+                note:None,
                 reason:"invalid escape sequence",
             });
         },
@@ -116,6 +144,7 @@ pub(crate) fn parse_char<'input>(s:String,filename:&'input str,start:Location,en
                     start,
                     end,
                     level,
+                    note:None,
                     reason:"`char` can only have one character, or a valid escape sequence",
                 });
             }
@@ -123,7 +152,7 @@ pub(crate) fn parse_char<'input>(s:String,filename:&'input str,start:Location,en
         },
     }
 }
-fn convert_error<'input>(e:ParseError<Location,Token<'input>,Error<'input,&'input str>>,filename:&'input str)->Error<'input,String> {
+fn convert_error<'input>(e:ParseError<Location,Token<'input>,Error<'input,&'input str>>,filename:&'input str,source:&'input str)->Error<'input,String> {
     use ParseError::*;
     let level=ErrorLevel::ParseError;
     match e {
@@ -132,37 +161,31 @@ fn convert_error<'input>(e:ParseError<Location,Token<'input>,Error<'input,&'inpu
             end:location,
             filename,
             level,
+            note:None,
             reason:"(internal error) unknown token".into(),
         },
-        UnrecognizedEOF{location,..}=>{
+        UnrecognizedEOF{location,expected}=>{
             Error {
                 start:location,
                 end:location,
                 filename,
                 level,
-                reason:"Unexpected EOF".into(),
+                note:None,
+                reason:format!("expected {}, found the end of the file",expected::describe(&expected,source,location.index)),
             }
         },
         UnrecognizedToken{token:(start,token,end),expected}=>{
-            let mut reason=String::from("Expected: ");
-            let final_i=expected.len()-1;
-            for (i,option) in expected.into_iter().enumerate() {
-                reason.push_str(&option);
-                if i==final_i {
-                    reason.push(' ');
-                } else {
-                    reason.push_str(", ");
-                }
-            }
-            reason.push_str("found ");
-            let token=token.to_string();
-            reason.push_str(&token);
+            let found=match token {
+                Token::Newline=>"the end of the line".to_string(),
+                token=>token.to_string(),
+            };
             Error {
                 start,
                 end,
                 filename,
                 level,
-                reason,
+                note:None,
+                reason:format!("expected {}, found {}",expected::describe(&expected,source,start.index),found),
             }
         },
         ExtraToken{token:(start,token,end)}=>Error {
@@ -170,13 +193,15 @@ fn convert_error<'input>(e:ParseError<Location,Token<'input>,Error<'input,&'inpu
             end,
             filename,
             level,
+            note:None,
             reason:format!("Unexpected {}",token),
         },
-        User{error:Error{start,end,filename,level,reason}}=>Error {
+        User{error:Error{start,end,filename,level,reason,..}}=>Error {
             start,
             end,
             filename,
             level,
+            note:None,
             reason:reason.to_string(),
         },
     }
@@ -0,0 +1,38 @@
+use cppl_parser::parse;
+
+
+/// The messages of every error in `source`.
+fn errors(source:&str)->Vec<String> {
+    let (_,errors)=parse("test.cppl",source);
+    return errors.into_iter().map(|e|e.reason).collect();
+}
+
+
+#[test]
+fn invalid_tokens() {
+    assert_eq!(errors("x:=1 $ 2\n"),["Invalid token"]);
+    assert_eq!(errors("x:=\"abc\n"),["Invalid token"]);
+}
+#[test]
+fn chars_with_more_than_one_character() {
+    assert_eq!(errors("c:='ab'\n"),["`char` can only have one character, or a valid escape sequence"]);
+}
+#[test]
+fn unclosed_blocks_and_parentheses() {
+    assert_eq!(errors("fn f[] {\n    return 1\n"),["expected an expression, a statement or `}` to close the block, found the end of the file"]);
+    assert_eq!(errors("x:=(1+2\n"),["expected `)` to close the parentheses, found the end of the line"]);
+}
+#[test]
+fn stray_closing_braces() {
+    assert_eq!(errors("x:=1\n}\n"),["expected an expression, a statement, a new line, `break`, `continue` or `return`, found token: `}`"]);
+}
+#[test]
+fn valid_programs_have_no_errors() {
+    assert_eq!(errors("fn main[]:Int {\n    x:=[1,2]\n    return x[0]\n}\n"),Vec::<String>::new());
+}
+#[test]
+fn derefs_are_operands() {
+    for source in ["x:=2 * *r\n","x:=*r * *r\n","x:=1 + *r\n","x:=**r << *r\n","x:=1 = *r and *r\n"] {
+        assert_eq!(errors(source),Vec::<String>::new(),"{}",source);
+    }
+}
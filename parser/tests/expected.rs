@@ -0,0 +1,31 @@
+use cppl_parser::parse;
+
+
+/// The message of the first error in `source`.
+fn first_error(source:&str)->String {
+    let (_,errors)=parse("test.cppl",source);
+    return errors.into_iter().next().expect("expected a syntax error").reason;
+}
+
+
+#[test]
+fn brackets_in_strings_and_comments_are_ignored() {
+    assert_eq!(first_error("x:=\"[\" +\n"),"expected an expression, found the end of the line");
+    assert_eq!(first_error("// [\nx:=1 +\n"),"expected an expression, found the end of the line");
+}
+#[test]
+fn only_whole_tokens_say_what_follows() {
+    assert_eq!(first_error("x:=1 >= \n"),"expected an expression, found the end of the line");
+    assert_eq!(first_error("x:=\n"),"expected an expression after `:=`, found the end of the line");
+}
+#[test]
+fn unclosed_brackets_only_list_how_to_close_them() {
+    assert_eq!(first_error("fn f[a:Int b:Int] {\n}\n"),"expected `,` or `]` to close the parameter list, found word: `b`");
+    assert_eq!(first_error("x:=[1,2\n"),"expected `,` or `]` to close the list, found the end of the line");
+    assert_eq!(first_error("x:=a[1\n"),"expected `]` to close the index, found the end of the line");
+    assert_eq!(first_error("f(1,\n"),"expected an expression after `,` or `)` to close the parentheses, found the end of the file");
+}
+#[test]
+fn derefs_are_expressions() {
+    assert_eq!(first_error("if x {\n"),"expected an expression, a statement or `}` to close the block, found the end of the file");
+}
@@ -0,0 +1,40 @@
+use cppl_parser::parse;
+
+
+/// The messages of every error in `source`, and how many statements parsed.
+fn parse_errors(source:&str)->(usize,Vec<String>) {
+    let (statements,errors)=parse("test.cppl",source);
+    return (statements.len(),errors.into_iter().map(|e|e.reason).collect());
+}
+
+
+#[test]
+fn recovers_at_statement_ends() {
+    let (statements,errors)=parse_errors("a:=1 +\nb:=2\nc:=)\nd:=4\n");
+    assert_eq!(errors.len(),2,"{:?}",errors);
+    assert_eq!(statements,2);
+}
+#[test]
+fn recovers_after_a_broken_block_header() {
+    let (statements,errors)=parse_errors("fn g[a:]:Int {\n    return 1\n}\nb:=2\n");
+    assert_eq!(errors,["expected a type after `:`, found token: `]`"]);
+    assert_eq!(statements,1);
+}
+#[test]
+fn recovers_before_a_closing_brace() {
+    let (statements,errors)=parse_errors("fn f[] {\n    y:=1 + }\nb:=2\n");
+    assert_eq!(errors.len(),1,"{:?}",errors);
+    assert_eq!(statements,2);
+}
+#[test]
+fn keeps_statements_before_the_end_of_the_file() {
+    let (statements,errors)=parse_errors("a:=1\nb:=[1,\n");
+    assert_eq!(errors.len(),1,"{:?}",errors);
+    assert_eq!(statements,1);
+}
+#[test]
+fn keeps_statements_before_an_unrecoverable_error() {
+    let (statements,errors)=parse_errors("a:=1\nb:=2\nc:Int(Bool)=3\nd:=4\n");
+    assert_eq!(errors,["builtin types cannot have generics"]);
+    assert_eq!(statements,2);
+}
@@ -0,0 +1,301 @@
+//! The instructions and tables the VM runs.
+use std::collections::HashMap;
+use cppl_error::Location;
+use crate::{
+    object::*,
+    Value,
+};
+
+
+/// An instruction of the stack machine. Operands are indexes into the tables of the `Program` or
+/// the current function, and every instruction fits in 8 bytes.
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum Instruction {
+    /// Pushes the constant `.0`.
+    Const(u32),
+    /// Pushes the value of blocks and functions that don't have one.
+    None,
+    Pop,
+    Dup,
+    /// Swaps the top two values.
+    Swap,
+    /// Removes `.0` values from under the top of the stack.
+    Slide(u16),
+    LoadLocal(u16),
+    /// Changes the value of the local `.0`.
+    StoreLocal(u16),
+    /// Gives the local `.0` a new cell, so functions that captured the old one keep it.
+    DefineLocal(u16),
+    /// Pushes the global `.0`, running its initializer first if it hasn't been run.
+    LoadGlobal(u32),
+    StoreGlobal(u32),
+    DefineGlobal(u32),
+    /// Pushes the function `.0`.
+    Function(u32),
+    /// Pushes the anonymous function `.0` with the cells of the locals it captures.
+    Closure(u32),
+    /// Makes a value of the enum variant `variant` out of the top `fields` values.
+    Variant {
+        variant:u32,
+        fields:u16,
+    },
+    /// Makes an object with the field ids and flags of the shape `shape` out of the top `fields`
+    /// values.
+    Object {
+        shape:u32,
+        fields:u16,
+    },
+    /// Makes a slice out of the top `.0` values.
+    List(u32),
+    /// Calls the function under the top `.0` values with them as the arguments.
+    Call(u16),
+    CallFunction {
+        function:u32,
+        args:u16,
+    },
+    Return,
+    Jump(u32),
+    /// Pops a value and jumps if it isn't `true`.
+    JumpIfFalse(u32),
+    GetField(FieldId),
+    Index,
+    /// Pops the end and start of the range if they are there, then what is sliced.
+    Slice {
+        start:bool,
+        end:bool,
+    },
+    /// Pushes the length of a slice.
+    Len,
+    /// Pushes a reference to the local `.0`.
+    RefLocal(u16),
+    RefGlobal(u32),
+    /// Replaces a reference with a reference to its field `.0`.
+    RefField(FieldId),
+    /// Pops an index and replaces the reference under it with a reference to that item.
+    RefIndex,
+    /// Replaces a value with a reference to a new cell holding it.
+    RefTemp,
+    /// Marks the reference on top as mutable.
+    RefMut,
+    /// Follows the references stored at the reference on top.
+    Follow,
+    /// Replaces a reference with the value it refers to.
+    Load,
+    /// Pops a value and the reference under it and writes the value there.
+    Store,
+    /// Dereferences a reference or pointer once.
+    Deref,
+    /// Follows references until a value that isn't one is found.
+    Unref,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Negate,
+    Not,
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    LessEqual,
+    Greater,
+    Less,
+    /// Replaces a value with whether it has the type `.0`.
+    IsType(u32),
+    /// Converts the numbers in a value to the number types of the type `.0`.
+    Coerce(u32),
+    /// Replaces a value with whether it is an object with the fields of the shape `shape`.
+    HasFields {
+        shape:u32,
+        exact:bool,
+    },
+    /// Stops with a runtime error, the message is the constant `.0`.
+    Error(u32),
+}
+const _:()=assert!(std::mem::size_of::<Instruction>()==8);
+impl Instruction {
+    /// How many values running this adds to the stack, or removes if it is negative.
+    pub fn stack_effect(&self)->i32 {
+        use Instruction::*;
+        match *self {
+            Const(_)|None|Dup|LoadLocal(_)|LoadGlobal(_)|Function(_)|Closure(_)|RefLocal(_)|RefGlobal(_)=>1,
+            Pop|StoreLocal(_)|DefineLocal(_)|StoreGlobal(_)|DefineGlobal(_)|Return|JumpIfFalse(_)|Index|RefIndex=>-1,
+            Add|Sub|Mul|Div|Mod|BitAnd|BitOr|BitXor|ShiftLeft|ShiftRight=>-1,
+            Equal|NotEqual|GreaterEqual|LessEqual|Greater|Less=>-1,
+            Slide(n)=>-(n as i32),
+            Variant{fields,..}|Object{fields,..}=>1-fields as i32,
+            List(n)=>1-n as i32,
+            Call(n)=>-(n as i32),
+            CallFunction{args,..}=>1-args as i32,
+            Slice{start,end}=>-(start as i32)-(end as i32),
+            Store=>-2,
+            Swap|Jump(_)|GetField(_)|Len|RefField(_)|RefTemp|RefMut|Follow|Load|Deref|Unref|Negate|Not|IsType(_)|Coerce(_)|HasFields{..}|Error(_)=>0,
+        }
+    }
+}
+/// A type as far as it can be checked at runtime.
+#[derive(Debug,Clone,PartialEq)]
+pub enum RuntimeType {
+    /// Types that aren't checked, like generics and interfaces.
+    Any,
+    Uint,
+    Int,
+    Float,
+    DoubleFloat,
+    Byte,
+    Bool,
+    Char,
+    String,
+    Object {
+        fields:Vec<(FieldId,RuntimeType)>,
+        exact:bool,
+    },
+    /// Any of these enum variants.
+    Variants(Vec<u32>),
+    Union(Vec<RuntimeType>),
+    Slice(Box<RuntimeType>),
+    Ref(Box<RuntimeType>),
+    Function,
+}
+impl RuntimeType {
+    /// Whether values of this type can hold numbers that have to be converted to it.
+    pub fn has_numbers(&self)->bool {
+        match self {
+            RuntimeType::Uint|RuntimeType::Int|RuntimeType::Float|RuntimeType::DoubleFloat|RuntimeType::Byte=>true,
+            RuntimeType::Object{fields,..}=>fields.iter().any(|(_,ty)|ty.has_numbers()),
+            RuntimeType::Union(items)=>items.iter().any(|ty|ty.has_numbers()),
+            RuntimeType::Slice(inner)=>inner.has_numbers(),
+            // Variants convert their own fields.
+            RuntimeType::Variants(_)=>true,
+            _=>false,
+        }
+    }
+}
+#[derive(Debug,Default)]
+pub struct Code {
+    pub params:u16,
+    /// How many locals the function uses, including its parameters and captured variables.
+    pub locals:u16,
+    /// The locals of the enclosing function an anonymous function captures, and the locals they
+    /// become in it.
+    pub captures:Vec<(u16,u16)>,
+    pub instructions:Vec<Instruction>,
+    /// Where each instruction came from, for runtime errors.
+    pub spans:Vec<(Location,Location)>,
+}
+#[derive(Debug)]
+pub enum FunctionKind<'input> {
+    Code(Code),
+    Builtin(&'input str),
+    /// Calling an enum variant makes a value of it.
+    Variant(u32),
+}
+#[derive(Debug)]
+pub struct Function<'input> {
+    pub name:String,
+    pub kind:FunctionKind<'input>,
+}
+/// A variable of the root scope, a `static` or a `const`.
+#[derive(Debug)]
+pub struct Global<'input> {
+    pub name:&'input str,
+    /// The function computing the value of a `static` or `const` the first time it is used.
+    pub init:Option<u32>,
+}
+#[derive(Debug)]
+pub struct Variant<'input> {
+    pub name:&'input str,
+    /// The types of the fields.
+    pub fields:Vec<u32>,
+}
+#[derive(Debug)]
+pub struct Program<'input> {
+    pub filename:&'input str,
+    pub functions:Vec<Function<'input>>,
+    pub globals:Vec<Global<'input>>,
+    pub variants:Vec<Variant<'input>>,
+    /// The field ids and flags of objects that are made and the fields matched by patterns.
+    pub shapes:Vec<Vec<(FieldId,Flags)>>,
+    pub types:Vec<RuntimeType>,
+    pub constants:Vec<Value>,
+    /// The names of field ids, for errors and disassembly.
+    pub field_names:HashMap<FieldId,&'input str>,
+    /// The function running the statements of the root scope.
+    pub root:u32,
+    pub main:Option<u32>,
+}
+impl<'input> Program<'input> {
+    /// The code of a function that isn't a builtin function or an enum variant.
+    pub fn code(&self,function:u32)->Option<&Code> {
+        match &self.functions.get(function as usize)?.kind {
+            FunctionKind::Code(code)=>Some(code),
+            _=>None,
+        }
+    }
+    pub fn field_name(&self,id:FieldId)->String {
+        match self.field_names.get(&id) {
+            Some(name)=>name.to_string(),
+            None=>id.to_string(),
+        }
+    }
+    pub fn is_type(&self,value:&Value,ty:&RuntimeType)->bool {
+        match (ty,value) {
+            (RuntimeType::Any,_)=>true,
+            (RuntimeType::Uint,Value::Uint(_))|
+                (RuntimeType::Int,Value::Int(_))|
+                (RuntimeType::Float,Value::Float(_))|
+                (RuntimeType::DoubleFloat,Value::DoubleFloat(_))|
+                (RuntimeType::Byte,Value::Byte(_))|
+                (RuntimeType::Bool,Value::Bool(_))|
+                (RuntimeType::Char,Value::Char(_))|
+                (RuntimeType::String,Value::String(_))|
+                (RuntimeType::Function,Value::Function{..})=>true,
+            (RuntimeType::Object{fields,exact},Value::Object(object))=>{
+                (!exact||object.fields.len()==fields.len())&&fields.iter().all(|(id,ty)|match object.field(*id) {
+                    Some(field)=>self.is_type(&field.item.to_value(),ty),
+                    None=>false,
+                })
+            },
+            (RuntimeType::Variants(variants),Value::Variant{variant,..})=>variants.contains(variant),
+            (RuntimeType::Union(items),value)=>items.iter().any(|ty|self.is_type(value,ty)),
+            (RuntimeType::Slice(inner),Value::Slice(items))=>items.iter().all(|i|self.is_type(i,inner)),
+            (RuntimeType::Ref(inner),Value::Ref{place,..})=>place.read().is_some_and(|v|self.is_type(&v,inner)),
+            _=>false,
+        }
+    }
+    /// Converts the numbers in `value` to the number types `ty` says they are. Number literals are
+    /// `Int`s or `DoubleFloat`s until they are used as something.
+    pub fn coerce(&self,value:Value,ty:&RuntimeType)->Value {
+        match (ty,value) {
+            (RuntimeType::Uint,v) if v.is_number()=>v.convert_number(&Value::Uint(0)),
+            (RuntimeType::Int,v) if v.is_number()=>v.convert_number(&Value::Int(0)),
+            (RuntimeType::Byte,v) if v.is_number()=>v.convert_number(&Value::Byte(0)),
+            (RuntimeType::Float,v) if v.is_number()=>v.convert_number(&Value::Float(0.0)),
+            (RuntimeType::DoubleFloat,v) if v.is_number()=>v.convert_number(&Value::DoubleFloat(0.0)),
+            (RuntimeType::Object{fields,..},Value::Object(mut object))=>{
+                for field in object.fields.iter_mut() {
+                    if let Some((_,ty))=fields.iter().find(|(id,_)|*id==field.field_id) {
+                        let value=self.coerce(field.item.to_value(),ty);
+                        field.set(value.into());
+                    }
+                }
+                Value::Object(object)
+            },
+            (RuntimeType::Slice(inner),Value::Slice(items))=>Value::Slice(items.into_iter().map(|i|self.coerce(i,inner)).collect()),
+            (_,Value::Variant{variant,fields})=>{
+                let types=&self.variants[variant as usize].fields;
+                let fields=fields.into_iter()
+                    .zip(types.iter())
+                    .map(|(v,t)|self.coerce(v,&self.types[*t as usize]))
+                    .collect();
+                Value::Variant{variant,fields}
+            },
+            (_,v)=>v,
+        }
+    }
+}
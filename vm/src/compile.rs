@@ -0,0 +1,1093 @@
+//! Lowers the refined AST to bytecode.
+//!
+//! Every function, anonymous function and initializer of a `static` or `const` becomes a
+//! `Function`. Variables declared inside of a function are locals of it, variables of the root
+//! scope and `static`s and `const`s are globals. Anonymous functions copy the cells of the locals
+//! they use from the function they are created in, so both see the same variables.
+use std::collections::HashMap;
+use cppl_error::{
+    Location,
+    Error,
+};
+use cppl_ast::{
+    refined::*,
+    expr_type,
+};
+use crate::{
+    bytecode::*,
+    object::*,
+    Value,
+};
+
+
+/// How deep nested types are converted to runtime types before giving up.
+const MAX_TYPE_DEPTH:usize=32;
+
+
+type CompileResult<'input,T=()>=Result<T,Error<'input,String>>;
+struct Loop {
+    block:Scope,
+    /// The stack depth when the loop started.
+    depth:i32,
+    /// Where `continue` jumps to, if it is known yet.
+    continue_target:Option<u32>,
+    continues:Vec<usize>,
+    breaks:Vec<usize>,
+}
+/// A function that is being compiled.
+struct Builder {
+    /// The function, anonymous function, root scope or global the code is for.
+    scope:Scope,
+    slots:HashMap<Scope,u16>,
+    locals:u16,
+    captures:Vec<(u16,u16)>,
+    instructions:Vec<Instruction>,
+    spans:Vec<(Location,Location)>,
+    /// The span given to new instructions.
+    span:(Location,Location),
+    /// How many values are on the stack.
+    depth:i32,
+    loops:Vec<Loop>,
+}
+impl Builder {
+    fn new(scope:Scope,span:(Location,Location))->Self {
+        Builder {
+            scope,
+            slots:HashMap::new(),
+            locals:0,
+            captures:Vec::new(),
+            instructions:Vec::new(),
+            spans:Vec::new(),
+            span,
+            depth:0,
+            loops:Vec::new(),
+        }
+    }
+}
+enum VarRef {
+    Local(u16),
+    Global(u32),
+}
+/// Something that is referred to before it is compiled.
+enum Pending {
+    Function(u32,Scope),
+    /// The initializer of a `static` or `const`.
+    Global(u32,Scope),
+}
+struct Compiler<'a,'input> {
+    filename:&'input str,
+    scopes:&'a Scopes<'input>,
+    program:Program<'input>,
+    builders:Vec<Builder>,
+    functions:HashMap<Scope,u32>,
+    globals:HashMap<Scope,u32>,
+    variants:HashMap<Scope,u32>,
+    pending:Vec<Pending>,
+}
+impl<'a,'input> Compiler<'a,'input> {
+    fn error<T>(&self,reason:impl Into<String>)->CompileResult<'input,T> {
+        let (start,end)=self.builders.last().map(|b|b.span).unwrap_or_default();
+        Err(Error::new_compile(self.filename,start,end,reason.into()))
+    }
+    fn builder(&mut self)->&mut Builder {
+        self.builders.last_mut().expect("Internal compiler error: no function is being compiled")
+    }
+    fn emit(&mut self,instruction:Instruction)->usize {
+        let builder=self.builder();
+        builder.depth+=instruction.stack_effect();
+        builder.instructions.push(instruction);
+        builder.spans.push(builder.span);
+        return builder.instructions.len()-1;
+    }
+    fn here(&mut self)->u32 {
+        self.builder().instructions.len() as u32
+    }
+    fn set_depth(&mut self,depth:i32) {
+        self.builder().depth=depth;
+    }
+    fn depth(&mut self)->i32 {
+        self.builder().depth
+    }
+    /// Points the jump at `at` to the next instruction.
+    fn patch(&mut self,at:usize) {
+        let target=self.here();
+        match &mut self.builder().instructions[at] {
+            Instruction::Jump(to)|Instruction::JumpIfFalse(to)=>*to=target,
+            _=>unreachable!("Internal compiler error: only jumps can be patched"),
+        }
+    }
+    fn constant(&mut self,value:Value)->u32 {
+        self.program.constants.push(value);
+        return self.program.constants.len() as u32-1;
+    }
+    fn field_id(&mut self,name:&'input str)->CompileResult<'input,FieldId> {
+        let id=FieldId::new(name);
+        match self.program.field_names.insert(id,name) {
+            Some(other) if other!=name=>self.error(format!("the fields `{}` and `{}` have the same id",other,name)),
+            _=>Ok(id),
+        }
+    }
+    fn shape(&mut self,fields:Vec<(&'input str,Flags)>)->CompileResult<'input,u32> {
+        let mut shape=Vec::with_capacity(fields.len());
+        for (name,flags) in fields {
+            shape.push((self.field_id(name)?,flags));
+        }
+        self.program.shapes.push(shape);
+        return Ok(self.program.shapes.len() as u32-1);
+    }
+    fn runtime_type(&mut self,ty:&Type<'input>,depth:usize)->CompileResult<'input,RuntimeType> {
+        if depth>MAX_TYPE_DEPTH {
+            return Ok(RuntimeType::Any);
+        }
+        let scopes=self.scopes;
+        Ok(match scopes.expand_alias(ty) {
+            Type::Uint=>RuntimeType::Uint,
+            Type::Int=>RuntimeType::Int,
+            Type::Float=>RuntimeType::Float,
+            Type::DoubleFloat=>RuntimeType::DoubleFloat,
+            Type::Byte=>RuntimeType::Byte,
+            Type::Bool=>RuntimeType::Bool,
+            Type::Char=>RuntimeType::Char,
+            Type::String=>RuntimeType::String,
+            Type::Never=>RuntimeType::Union(Vec::new()),
+            Type::Object{fields,exact,..}=>{
+                let mut items=Vec::with_capacity(fields.len());
+                for field in fields {
+                    items.push((self.field_id(field.name)?,self.runtime_type(&field.ty,depth+1)?));
+                }
+                RuntimeType::Object{fields:items,exact:*exact}
+            },
+            Type::Named{path,..}=>match scopes.get(*path) {
+                Some(VarScopeItem::EnumVariant{..})=>RuntimeType::Variants(vec![self.variant_index(*path)?]),
+                Some(VarScopeItem::Enum{variants,..})=>{
+                    let mut items=Vec::with_capacity(variants.len());
+                    for variant in variants {
+                        items.push(self.runtime_type(variant,depth+1)?);
+                    }
+                    RuntimeType::Union(items)
+                },
+                _=>RuntimeType::Any,
+            },
+            Type::Union{items,..}=>{
+                let mut types=Vec::with_capacity(items.len());
+                for item in items {
+                    types.push(self.runtime_type(item,depth+1)?);
+                }
+                RuntimeType::Union(types)
+            },
+            Type::Slice{inner,..}=>RuntimeType::Slice(Box::new(self.runtime_type(inner,depth+1)?)),
+            Type::Ref{inner,..}|
+                Type::RefMut{inner,..}|
+                Type::Pointer{inner,..}|
+                Type::PointerMut{inner,..}=>RuntimeType::Ref(Box::new(self.runtime_type(inner,depth+1)?)),
+            Type::FunctionSig{..}=>RuntimeType::Function,
+            _=>RuntimeType::Any,
+        })
+    }
+    fn type_index(&mut self,ty:&Type<'input>)->CompileResult<'input,u32> {
+        let ty=self.runtime_type(ty,0)?;
+        self.program.types.push(ty);
+        return Ok(self.program.types.len() as u32-1);
+    }
+    /// Converts the numbers in the value on top of the stack to the number types of `ty`.
+    fn coerce(&mut self,ty:&Type<'input>)->CompileResult<'input> {
+        let runtime=self.runtime_type(ty,0)?;
+        if runtime.has_numbers() {
+            self.program.types.push(runtime);
+            let index=self.program.types.len() as u32-1;
+            self.emit(Instruction::Coerce(index));
+        }
+        return Ok(());
+    }
+    fn variant_index(&mut self,scope:Scope)->CompileResult<'input,u32> {
+        if let Some(index)=self.variants.get(&scope) {
+            return Ok(*index);
+        }
+        let Some(VarScopeItem::EnumVariant{name,fields,..})=self.scopes.get(scope) else {
+            return self.error("Internal compiler error: not an enum variant");
+        };
+        let index=self.program.variants.len() as u32;
+        self.program.variants.push(Variant{name,fields:Vec::new()});
+        // The fields can refer to the variant, so it has to exist first.
+        self.variants.insert(scope,index);
+        let mut types=Vec::with_capacity(fields.len());
+        for field in fields {
+            types.push(self.type_index(field)?);
+        }
+        self.program.variants[index as usize].fields=types;
+        return Ok(index);
+    }
+    /// The index of a function, builtin function or enum variant that can be called.
+    fn function_index(&mut self,scope:Scope)->CompileResult<'input,u32> {
+        if let Some(index)=self.functions.get(&scope) {
+            return Ok(*index);
+        }
+        let index=self.program.functions.len() as u32;
+        let (name,kind)=match self.scopes.get(scope) {
+            Some(VarScopeItem::Function{name,..})=>{
+                self.pending.push(Pending::Function(index,scope));
+                (name.to_string(),FunctionKind::Code(Code::default()))
+            },
+            Some(VarScopeItem::BuiltinFunction{name,..})=>(name.to_string(),FunctionKind::Builtin(name)),
+            Some(VarScopeItem::EnumVariant{name,..})=>(name.to_string(),FunctionKind::Variant(self.variant_index(scope)?)),
+            _=>return self.error("Internal compiler error: not a function"),
+        };
+        self.program.functions.push(Function{name,kind});
+        self.functions.insert(scope,index);
+        return Ok(index);
+    }
+    fn global_index(&mut self,var:Scope)->u32 {
+        if let Some(index)=self.globals.get(&var) {
+            return *index;
+        }
+        let item=self.scopes.get(var);
+        let name=item.and_then(|i|i.name()).unwrap_or("?");
+        let init=match item {
+            Some(VarScopeItem::Static{..}|VarScopeItem::Const{..})=>{
+                let init=self.program.functions.len() as u32;
+                self.program.functions.push(Function{name:name.to_string(),kind:FunctionKind::Code(Code::default())});
+                self.pending.push(Pending::Global(init,var));
+                Some(init)
+            },
+            _=>None,
+        };
+        let index=self.program.globals.len() as u32;
+        self.program.globals.push(Global{name,init});
+        self.globals.insert(var,index);
+        return index;
+    }
+    /// The function, anonymous function or root scope `scope` is declared in.
+    fn owner(&self,mut scope:Scope)->Scope {
+        while let Some(item)=self.scopes.get(scope) {
+            match (item,item.parent_scope()) {
+                (VarScopeItem::Function{..}|VarScopeItem::AnonFunction{..}|VarScopeItem::Root{..},_)|(_,None)=>break,
+                (_,Some(parent))=>scope=parent,
+            }
+        }
+        return scope;
+    }
+    fn new_local(&mut self)->CompileResult<'input,u16> {
+        let builder=self.builder();
+        if builder.locals==u16::MAX {
+            return self.error("too many variables in one function");
+        }
+        builder.locals+=1;
+        return Ok(builder.locals-1);
+    }
+    fn slot_in(&mut self,builder:usize,var:Scope)->CompileResult<'input,u16> {
+        if let Some(slot)=self.builders[builder].slots.get(&var) {
+            return Ok(*slot);
+        }
+        let slot=self.builders[builder].locals;
+        if slot==u16::MAX {
+            return self.error("too many variables in one function");
+        }
+        self.builders[builder].locals+=1;
+        self.builders[builder].slots.insert(var,slot);
+        return Ok(slot);
+    }
+    /// Where the variable `var` is stored. Locals of enclosing functions are captured by every
+    /// anonymous function between them and the current one.
+    fn var_ref(&mut self,var:Scope)->CompileResult<'input,VarRef> {
+        let var=original(self.scopes,var);
+        let global=match self.scopes.get(var) {
+            Some(VarScopeItem::Static{..}|VarScopeItem::Const{..})=>true,
+            _=>matches!(self.scopes.get(self.owner(var)),Some(VarScopeItem::Root{..})),
+        };
+        if global {
+            return Ok(VarRef::Global(self.global_index(var)));
+        }
+        let owner=self.owner(var);
+        let Some(declared)=self.builders.iter().rposition(|b|b.scope==owner) else {
+            return self.error("Internal compiler error: variable of a function that isn't being compiled");
+        };
+        let mut slot=self.slot_in(declared,var)?;
+        for i in declared+1..self.builders.len() {
+            if let Some(&inner)=self.builders[i].slots.get(&var) {
+                slot=inner;
+                continue;
+            }
+            let inner=self.slot_in(i,var)?;
+            self.builders[i].captures.push((slot,inner));
+            slot=inner;
+        }
+        return Ok(VarRef::Local(slot));
+    }
+    fn define(&mut self,var:Scope)->CompileResult<'input> {
+        match self.var_ref(var)? {
+            VarRef::Local(slot)=>self.emit(Instruction::DefineLocal(slot)),
+            VarRef::Global(index)=>self.emit(Instruction::DefineGlobal(index)),
+        };
+        return Ok(());
+    }
+    fn store(&mut self,var:Scope)->CompileResult<'input> {
+        match self.var_ref(var)? {
+            VarRef::Local(slot)=>self.emit(Instruction::StoreLocal(slot)),
+            VarRef::Global(index)=>self.emit(Instruction::StoreGlobal(index)),
+        };
+        return Ok(());
+    }
+    /// Pushes the value of a variable, function or enum variant.
+    fn load(&mut self,path:Scope)->CompileResult<'input> {
+        match self.scopes.get(path) {
+            Some(VarScopeItem::Function{..}|VarScopeItem::BuiltinFunction{..})=>{
+                let index=self.function_index(path)?;
+                self.emit(Instruction::Function(index));
+            },
+            Some(VarScopeItem::EnumVariant{fields,..}) if fields.is_empty()=>{
+                let variant=self.variant_index(path)?;
+                self.emit(Instruction::Variant{variant,fields:0});
+            },
+            Some(VarScopeItem::EnumVariant{..})=>{
+                let index=self.function_index(path)?;
+                self.emit(Instruction::Function(index));
+            },
+            _=>match self.var_ref(path)? {
+                VarRef::Local(slot)=>{self.emit(Instruction::LoadLocal(slot));},
+                VarRef::Global(index)=>{self.emit(Instruction::LoadGlobal(index));},
+            },
+        }
+        return Ok(());
+    }
+    /// Whether the first parameter of `function` is `this`, and whether it is `mut this`.
+    fn this_param(&self,function:Scope)->Option<bool> {
+        let Some(VarScopeItem::Function{params,..})=self.scopes.get(function) else {return None};
+        match params.first().and_then(|p|self.scopes.get(*p)) {
+            Some(VarScopeItem::Parameter{name:"this",mutable,..})=>Some(*mutable),
+            _=>None,
+        }
+    }
+    /// Compiles the body of a function, anonymous function or the root scope into the function
+    /// `index`.
+    fn compile_function(&mut self,index:u32,scope:Scope)->CompileResult<'input> {
+        let Some(item)=self.scopes.get(scope) else {
+            return self.error("Internal compiler error: missing function");
+        };
+        self.builders.push(Builder::new(scope,item.def_span().unwrap_or_default()));
+        let params=match item {
+            VarScopeItem::Function{params,..}|VarScopeItem::AnonFunction{params,..}=>params.as_slice(),
+            _=>&[],
+        };
+        for param in params {
+            self.slot_in(self.builders.len()-1,*param)?;
+        }
+        for (slot,param) in params.iter().enumerate() {
+            if let Some(ty)=self.scopes.get(*param).and_then(|p|p.var_type()) {
+                if self.runtime_type(ty,0)?.has_numbers() {
+                    self.emit(Instruction::LoadLocal(slot as u16));
+                    self.coerce(ty)?;
+                    self.emit(Instruction::StoreLocal(slot as u16));
+                }
+            }
+        }
+        for stmt in item.statements() {
+            self.compile_stmt(stmt)?;
+        }
+        self.emit(Instruction::None);
+        self.emit(Instruction::Return);
+        let builder=self.builders.pop().expect("Internal compiler error: no function is being compiled");
+        self.program.functions[index as usize].kind=FunctionKind::Code(Code {
+            params:params.len() as u16,
+            locals:builder.locals,
+            captures:builder.captures,
+            instructions:builder.instructions,
+            spans:builder.spans,
+        });
+        return Ok(());
+    }
+    /// Compiles the function computing the value of a `static` or `const` into the function
+    /// `index`.
+    fn compile_global(&mut self,index:u32,var:Scope)->CompileResult<'input> {
+        let Some(VarScopeItem::Static{data,ty,..}|VarScopeItem::Const{data,ty,..})=self.scopes.get(var) else {
+            return self.error("Internal compiler error: not a static or const");
+        };
+        self.builders.push(Builder::new(var,data.span()));
+        self.compile_expr(data)?;
+        self.coerce(ty)?;
+        self.emit(Instruction::Return);
+        let builder=self.builders.pop().expect("Internal compiler error: no function is being compiled");
+        self.program.functions[index as usize].kind=FunctionKind::Code(Code {
+            params:0,
+            locals:builder.locals,
+            captures:builder.captures,
+            instructions:builder.instructions,
+            spans:builder.spans,
+        });
+        return Ok(());
+    }
+    fn compile_block(&mut self,block:Scope)->CompileResult<'input> {
+        let Some(item)=self.scopes.get(block) else {return Ok(())};
+        for stmt in item.statements() {
+            self.compile_stmt(stmt)?;
+        }
+        return Ok(());
+    }
+    /// Compiles a branch of an `if`, leaving the value of the expression it ends with.
+    fn compile_branch(&mut self,block:Scope)->CompileResult<'input> {
+        let (statements,value)=self.scopes.branch_value(block);
+        for stmt in statements {
+            self.compile_stmt(stmt)?;
+        }
+        match value {
+            Some(value)=>self.compile_expr(value)?,
+            None=>{self.emit(Instruction::None);},
+        }
+        return Ok(());
+    }
+    fn compile_stmt(&mut self,stmt:&Statement<'input>)->CompileResult<'input> {
+        match stmt {
+            Statement::VarDef{start,end,def}=>{
+                self.builder().span=(*start,*end);
+                if let Some(VarScopeItem::Var{ty,data,..})=self.scopes.get(*def) {
+                    self.compile_expr(data)?;
+                    self.coerce(ty)?;
+                    self.define(*def)?;
+                }
+            },
+            Statement::VarAssign{start,end,loc,data}=>{
+                self.compile_expr(data)?;
+                self.builder().span=(*start,*end);
+                if let Some(ty)=self.scopes.get(original(self.scopes,*loc)).and_then(|v|v.var_type()) {
+                    self.coerce(ty)?;
+                }
+                self.store(*loc)?;
+            },
+            Statement::PlaceAssign{start,end,place,data}=>{
+                // The value is worked out before the place, like the other backends do.
+                self.compile_expr(data)?;
+                self.compile_place(place)?;
+                self.builder().span=(*start,*end);
+                self.emit(Instruction::Swap);
+                self.emit(Instruction::Store);
+            },
+            Statement::CompoundAssign{start,end,op,place,data}=>{
+                self.compile_place(place)?;
+                self.builder().span=(*start,*end);
+                self.emit(Instruction::Dup);
+                self.emit(Instruction::Load);
+                self.compile_expr(data)?;
+                self.builder().span=(*start,*end);
+                self.emit(match op {
+                    AssignOp::Add=>Instruction::Add,
+                    AssignOp::Sub=>Instruction::Sub,
+                    AssignOp::Mul=>Instruction::Mul,
+                    AssignOp::Div=>Instruction::Div,
+                    AssignOp::Mod=>Instruction::Mod,
+                });
+                self.emit(Instruction::Store);
+            },
+            Statement::Expr{expr,..}=>{
+                self.compile_expr(expr)?;
+                self.emit(Instruction::Pop);
+            },
+            Statement::Return{start,end,val,..}=>{
+                match val {
+                    Some(val)=>self.compile_expr(val)?,
+                    None=>{self.emit(Instruction::None);},
+                }
+                self.builder().span=(*start,*end);
+                let scope=self.builder().scope;
+                if let Some(VarScopeItem::Function{ret_type:Some(ty),..}|VarScopeItem::AnonFunction{ret_type:Some(ty),..})=self.scopes.get(scope) {
+                    self.coerce(ty)?;
+                }
+                self.emit(Instruction::Return);
+            },
+            Statement::Continue{start,end,target,..}=>{
+                self.builder().span=(*start,*end);
+                let Some(index)=self.builder().loops.iter().rposition(|l|l.block==*target) else {
+                    return self.error("Internal compiler error: `continue` outside of its loop");
+                };
+                let extra=self.depth()-self.builder().loops[index].depth;
+                for _ in 0..extra {
+                    self.emit(Instruction::Pop);
+                }
+                match self.builder().loops[index].continue_target {
+                    Some(target)=>{self.emit(Instruction::Jump(target));},
+                    None=>{
+                        let jump=self.emit(Instruction::Jump(0));
+                        self.builder().loops[index].continues.push(jump);
+                    },
+                }
+            },
+            Statement::Break{start,end,target,val,..}=>{
+                match val {
+                    Some(val)=>self.compile_expr(val)?,
+                    None=>{self.emit(Instruction::None);},
+                }
+                self.builder().span=(*start,*end);
+                let Some(index)=self.builder().loops.iter().rposition(|l|l.block==*target) else {
+                    return self.error("Internal compiler error: `break` outside of its loop");
+                };
+                // Anything the loop is nested in the middle of is left on the stack.
+                let extra=self.depth()-1-self.builder().loops[index].depth;
+                if extra>0 {
+                    self.emit(Instruction::Slide(extra as u16));
+                }
+                let jump=self.emit(Instruction::Jump(0));
+                self.builder().loops[index].breaks.push(jump);
+            },
+            Statement::UnknownVarAssign{start,end,name,..}=>{
+                self.builder().span=(*start,*end);
+                return self.error(format!("variable `{}` is not defined",name));
+            },
+            // Everything else is a definition, which is compiled when it is used.
+            _=>{},
+        }
+        return Ok(());
+    }
+    /// Pushes a reference to where the value of `expr` is stored. Expressions that aren't stored
+    /// anywhere are put in a new cell.
+    fn compile_place(&mut self,expr:&Expr<'input>)->CompileResult<'input> {
+        let span=self.builder().span;
+        self.builder().span=expr.span();
+        match expr {
+            Expr::Var{path,..} if !matches!(self.scopes.get(*path),Some(VarScopeItem::Function{..}|VarScopeItem::BuiltinFunction{..}|VarScopeItem::EnumVariant{..}))=>{
+                match self.var_ref(*path)? {
+                    VarRef::Local(slot)=>self.emit(Instruction::RefLocal(slot)),
+                    VarRef::Global(index)=>self.emit(Instruction::RefGlobal(index)),
+                };
+            },
+            Expr::FieldAccess{from,name,..}=>{
+                self.compile_place(from)?;
+                let id=self.field_id(name)?;
+                self.emit(Instruction::RefField(id));
+            },
+            Expr::Index{from,index,..}=>{
+                self.compile_place(from)?;
+                self.compile_expr(index)?;
+                self.emit(Instruction::RefIndex);
+            },
+            Expr::Deref{val,..}=>self.compile_expr(val)?,
+            _=>{
+                self.compile_expr(expr)?;
+                self.emit(Instruction::RefTemp);
+            },
+        }
+        self.builder().span=span;
+        return Ok(());
+    }
+    fn compile_all(&mut self,exprs:&[Expr<'input>])->CompileResult<'input> {
+        for expr in exprs {
+            self.compile_expr(expr)?;
+        }
+        return Ok(());
+    }
+    fn compile_binary(&mut self,inner:&[Expr<'input>;2],instruction:Instruction)->CompileResult<'input> {
+        self.compile_expr(&inner[0])?;
+        self.compile_expr(&inner[1])?;
+        self.emit(instruction);
+        return Ok(());
+    }
+    /// Pushes the arguments of a call to `method`, with `from` as `this` if the method takes it.
+    fn compile_this(&mut self,method:Scope,from:&Expr<'input>)->CompileResult<'input,u16> {
+        match self.this_param(method) {
+            Some(true)=>{
+                self.compile_place(from)?;
+                self.emit(Instruction::Follow);
+                self.emit(Instruction::RefMut);
+                Ok(1)
+            },
+            Some(false)=>{
+                self.compile_expr(from)?;
+                self.emit(Instruction::Unref);
+                Ok(1)
+            },
+            None=>Ok(0),
+        }
+    }
+    /// Calls the function `name` of the impl of `interface` for the type of the value of `from`.
+    /// The impls are tried in the order they were defined.
+    fn compile_interface_call(&mut self,interface:Scope,from:&Expr<'input>,name:&str,args:&[Expr<'input>])->CompileResult<'input> {
+        let scopes=self.scopes;
+        self.compile_place(from)?;
+        self.emit(Instruction::Follow);
+        let depth=self.depth();
+        let mut ends=Vec::new();
+        for (for_ty,statements) in scopes.interface_impls(interface) {
+            let method=scopes.find_function(statements,name)
+                .or_else(||scopes.get(interface).and_then(|i|scopes.find_function(i.statements(),name)));
+            let Some(method)=method else {continue};
+            self.emit(Instruction::Dup);
+            self.emit(Instruction::Load);
+            let ty=self.type_index(for_ty)?;
+            self.emit(Instruction::IsType(ty));
+            let next=self.emit(Instruction::JumpIfFalse(0));
+            let this=match self.this_param(method) {
+                Some(true)=>{
+                    self.emit(Instruction::RefMut);
+                    1
+                },
+                Some(false)=>{
+                    self.emit(Instruction::Load);
+                    1
+                },
+                None=>{
+                    self.emit(Instruction::Pop);
+                    0
+                },
+            };
+            self.compile_all(args)?;
+            let function=self.function_index(method)?;
+            self.emit(Instruction::CallFunction{function,args:args.len() as u16+this});
+            ends.push(self.emit(Instruction::Jump(0)));
+            self.patch(next);
+            self.set_depth(depth);
+        }
+        let message=self.constant(Value::String(format!("no impl has `{}` for this value",name).into()));
+        self.emit(Instruction::Error(message));
+        for jump in ends {
+            self.patch(jump);
+        }
+        self.set_depth(depth);
+        return Ok(());
+    }
+    /// Compiles the loop body `block`. `continue_target` is where `continue` jumps, `None` if it
+    /// comes after the body. Returns the jumps of `continue`s and `break`s that still go nowhere.
+    fn compile_loop_body(&mut self,block:Scope,continue_target:Option<u32>)->CompileResult<'input,(Vec<usize>,Vec<usize>)> {
+        let depth=self.depth();
+        self.builder().loops.push(Loop{block,depth,continue_target,continues:Vec::new(),breaks:Vec::new()});
+        self.compile_block(block)?;
+        self.set_depth(depth);
+        let lp=self.builder().loops.pop().expect("Internal compiler error: no loop");
+        return Ok((lp.continues,lp.breaks));
+    }
+    fn compile_expr(&mut self,expr:&Expr<'input>)->CompileResult<'input> {
+        use Expr::*;
+        let span=self.builder().span;
+        self.builder().span=expr.span();
+        let depth=self.depth();
+        match expr {
+            Data{data,..}=>{
+                let value=literal(data);
+                let index=self.constant(value);
+                self.emit(Instruction::Const(index));
+            },
+            Var{path,..}|AssociatedPath{path,..}=>self.load(*path)?,
+            FunctionCall{path,args,..}=>match self.scopes.get(*path) {
+                Some(VarScopeItem::EnumVariant{..})=>{
+                    self.compile_all(args)?;
+                    let variant=self.variant_index(*path)?;
+                    self.emit(Instruction::Variant{variant,fields:args.len() as u16});
+                },
+                Some(VarScopeItem::Function{..}|VarScopeItem::BuiltinFunction{..})=>{
+                    self.compile_all(args)?;
+                    let function=self.function_index(*path)?;
+                    self.emit(Instruction::CallFunction{function,args:args.len() as u16});
+                },
+                _=>{
+                    self.load(*path)?;
+                    self.compile_all(args)?;
+                    self.emit(Instruction::Call(args.len() as u16));
+                },
+            },
+            MethodCall{from,args,method:Some(method),..}=>{
+                let this=self.compile_this(*method,from)?;
+                self.compile_all(args)?;
+                let function=self.function_index(*method)?;
+                self.emit(Instruction::CallFunction{function,args:args.len() as u16+this});
+            },
+            MethodCall{from,name,args,method:None,..}=>match self.scopes.interface(&expr_type(self.filename,self.scopes,from)) {
+                Some(interface)=>self.compile_interface_call(interface,from,name,args)?,
+                None=>{
+                    self.compile_expr(from)?;
+                    let id=self.field_id(name)?;
+                    self.emit(Instruction::GetField(id));
+                    self.compile_all(args)?;
+                    self.emit(Instruction::Call(args.len() as u16));
+                },
+            },
+            FieldAccess{from,name,..}=>{
+                self.compile_expr(from)?;
+                let id=self.field_id(name)?;
+                self.emit(Instruction::GetField(id));
+            },
+            Add{inner,..}=>self.compile_binary(inner,Instruction::Add)?,
+            Sub{inner,..}=>self.compile_binary(inner,Instruction::Sub)?,
+            Mul{inner,..}=>self.compile_binary(inner,Instruction::Mul)?,
+            Div{inner,..}=>self.compile_binary(inner,Instruction::Div)?,
+            Mod{inner,..}=>self.compile_binary(inner,Instruction::Mod)?,
+            BitAnd{inner,..}=>self.compile_binary(inner,Instruction::BitAnd)?,
+            BitOr{inner,..}=>self.compile_binary(inner,Instruction::BitOr)?,
+            BitXor{inner,..}=>self.compile_binary(inner,Instruction::BitXor)?,
+            ShiftLeft{inner,..}=>self.compile_binary(inner,Instruction::ShiftLeft)?,
+            ShiftRight{inner,..}=>self.compile_binary(inner,Instruction::ShiftRight)?,
+            Equal{inner,..}=>self.compile_binary(inner,Instruction::Equal)?,
+            NotEqual{inner,..}=>self.compile_binary(inner,Instruction::NotEqual)?,
+            GreaterEqual{inner,..}=>self.compile_binary(inner,Instruction::GreaterEqual)?,
+            LessEqual{inner,..}=>self.compile_binary(inner,Instruction::LessEqual)?,
+            Greater{inner,..}=>self.compile_binary(inner,Instruction::Greater)?,
+            Less{inner,..}=>self.compile_binary(inner,Instruction::Less)?,
+            Negate{inner,..}=>{
+                self.compile_expr(inner)?;
+                self.emit(Instruction::Negate);
+            },
+            Not{inner,..}=>{
+                self.compile_expr(inner)?;
+                self.emit(Instruction::Not);
+            },
+            And{inner,..}|Or{inner,..}=>{
+                // `a and b` is `if a {b} else {false}` and `a or b` is `if a {true} else {b}`.
+                let is_and=matches!(expr,And{..});
+                self.compile_expr(&inner[0])?;
+                let to_second=self.emit(Instruction::JumpIfFalse(0));
+                if is_and {
+                    self.compile_expr(&inner[1])?;
+                } else {
+                    let index=self.constant(Value::Bool(true));
+                    self.emit(Instruction::Const(index));
+                }
+                let to_end=self.emit(Instruction::Jump(0));
+                self.patch(to_second);
+                self.set_depth(depth);
+                if is_and {
+                    let index=self.constant(Value::Bool(false));
+                    self.emit(Instruction::Const(index));
+                } else {
+                    self.compile_expr(&inner[1])?;
+                }
+                self.patch(to_end);
+            },
+            IsType{to_test,ty,..}=>{
+                self.compile_expr(to_test)?;
+                let ty=self.type_index(ty)?;
+                self.emit(Instruction::IsType(ty));
+            },
+            ObjectCreation{fields,..}=>{
+                let mut shape=Vec::with_capacity(fields.len());
+                for field in fields {
+                    self.compile_expr(&field.data)?;
+                    shape.push((field.name,Flags::new(field.public,field.mutable)));
+                }
+                let shape=self.shape(shape)?;
+                self.emit(Instruction::Object{shape,fields:fields.len() as u16});
+            },
+            AnonFunction{function,..}=>{
+                let index=self.program.functions.len() as u32;
+                self.program.functions.push(Function{name:"<anonymous>".to_string(),kind:FunctionKind::Code(Code::default())});
+                self.compile_function(index,*function)?;
+                self.emit(Instruction::Closure(index));
+            },
+            Ref{val,..}=>self.compile_place(val)?,
+            RefMut{val,..}=>{
+                self.compile_place(val)?;
+                self.emit(Instruction::RefMut);
+            },
+            Deref{val,..}=>{
+                self.compile_expr(val)?;
+                self.emit(Instruction::Deref);
+            },
+            Block{block,..}|Unsafe{block,..}=>{
+                self.compile_block(*block)?;
+                self.set_depth(depth);
+                self.emit(Instruction::None);
+            },
+            ForeverLoop{block,..}=>{
+                let start=self.here();
+                let (_,breaks)=self.compile_loop_body(*block,Some(start))?;
+                self.emit(Instruction::Jump(start));
+                for jump in breaks {
+                    self.patch(jump);
+                }
+                self.set_depth(depth+1);
+            },
+            WhileLoop{condition,block,..}=>{
+                let start=self.here();
+                self.compile_expr(condition)?;
+                let exit=self.emit(Instruction::JumpIfFalse(0));
+                let (_,breaks)=self.compile_loop_body(*block,Some(start))?;
+                self.emit(Instruction::Jump(start));
+                self.patch(exit);
+                self.emit(Instruction::None);
+                for jump in breaks {
+                    self.patch(jump);
+                }
+            },
+            ForLoop{var,iterator,block,..}=>{
+                let items=self.new_local()?;
+                let index=self.new_local()?;
+                self.compile_expr(iterator)?;
+                self.emit(Instruction::Unref);
+                self.emit(Instruction::DefineLocal(items));
+                let zero=self.constant(Value::Int(0));
+                self.emit(Instruction::Const(zero));
+                self.emit(Instruction::DefineLocal(index));
+                let start=self.here();
+                self.emit(Instruction::LoadLocal(index));
+                self.emit(Instruction::LoadLocal(items));
+                self.emit(Instruction::Len);
+                self.emit(Instruction::Less);
+                let exit=self.emit(Instruction::JumpIfFalse(0));
+                self.emit(Instruction::LoadLocal(items));
+                self.emit(Instruction::LoadLocal(index));
+                self.emit(Instruction::Index);
+                match self.scopes.get(*block).and_then(|b|b.get_var(var)) {
+                    Some(param)=>self.define(param)?,
+                    None=>{self.emit(Instruction::Pop);},
+                }
+                let (continues,breaks)=self.compile_loop_body(*block,None)?;
+                for jump in continues {
+                    self.patch(jump);
+                }
+                let one=self.constant(Value::Int(1));
+                self.emit(Instruction::LoadLocal(index));
+                self.emit(Instruction::Const(one));
+                self.emit(Instruction::Add);
+                self.emit(Instruction::StoreLocal(index));
+                self.emit(Instruction::Jump(start));
+                self.patch(exit);
+                self.emit(Instruction::None);
+                for jump in breaks {
+                    self.patch(jump);
+                }
+            },
+            Match{block,..}=>{
+                let ty=expr_type(self.filename,self.scopes,&block.to_match);
+                let value=self.new_local()?;
+                self.compile_expr(&block.to_match)?;
+                self.emit(Instruction::DefineLocal(value));
+                let mut ends=Vec::new();
+                for (pattern,leaf) in block.leafs.iter() {
+                    let mut fails=Vec::new();
+                    self.compile_pattern(pattern,value,&ty,&mut fails)?;
+                    self.compile_expr(leaf)?;
+                    ends.push(self.emit(Instruction::Jump(0)));
+                    for jump in fails {
+                        self.patch(jump);
+                    }
+                    self.set_depth(depth);
+                }
+                self.builder().span=expr.span();
+                let message=self.constant(Value::String("no pattern matched the value".into()));
+                self.emit(Instruction::Error(message));
+                for jump in ends {
+                    self.patch(jump);
+                }
+                self.set_depth(depth+1);
+            },
+            If{branches,else_block,..}=>{
+                let mut ends=Vec::new();
+                for (condition,block) in branches.iter() {
+                    self.compile_expr(condition)?;
+                    let next=self.emit(Instruction::JumpIfFalse(0));
+                    self.compile_branch(*block)?;
+                    ends.push(self.emit(Instruction::Jump(0)));
+                    self.patch(next);
+                    self.set_depth(depth);
+                }
+                match else_block {
+                    Some(block)=>self.compile_branch(*block)?,
+                    None=>{self.emit(Instruction::None);},
+                }
+                for jump in ends {
+                    self.patch(jump);
+                }
+                self.set_depth(depth+1);
+            },
+            List{items,..}=>{
+                self.compile_all(items)?;
+                self.emit(Instruction::List(items.len() as u32));
+            },
+            Index{from,index,..}=>{
+                self.compile_expr(from)?;
+                self.compile_expr(index)?;
+                self.emit(Instruction::Index);
+            },
+            Slice{from,range_start,range_end,..}=>{
+                self.compile_expr(from)?;
+                if let Some(range_start)=range_start {
+                    self.compile_expr(range_start)?;
+                }
+                if let Some(range_end)=range_end {
+                    self.compile_expr(range_end)?;
+                }
+                self.emit(Instruction::Slice{start:range_start.is_some(),end:range_end.is_some()});
+            },
+            UnknownFunctionCall{path,..}|UnknownAssociatedPath{path,..}=>return self.error(format!("`{}` is not defined",path.join("::"))),
+            UnknownVar{name,..}=>return self.error(format!("`{}` is not defined",name)),
+        }
+        self.builder().span=span;
+        return Ok(());
+    }
+    /// Checks whether the value in the local `value` matches `pattern`, jumping to one of the jumps
+    /// put in `fails` if it doesn't. `ty` is the type of the value.
+    fn compile_pattern(&mut self,pattern:&MatchPattern<'input>,value:u16,ty:&Type<'input>,fails:&mut Vec<usize>)->CompileResult<'input> {
+        self.builder().span=pattern.span();
+        let compare=|pattern:&MatchPattern<'input>|match pattern {
+            MatchPattern::Equal{..}=>Instruction::Equal,
+            MatchPattern::NotEqual{..}=>Instruction::NotEqual,
+            MatchPattern::GreaterEqual{..}=>Instruction::GreaterEqual,
+            MatchPattern::LessEqual{..}=>Instruction::LessEqual,
+            MatchPattern::Greater{..}=>Instruction::Greater,
+            _=>Instruction::Less,
+        };
+        match pattern {
+            MatchPattern::Data{inner,..}=>{
+                self.emit(Instruction::LoadLocal(value));
+                let index=self.constant(literal(inner));
+                self.emit(Instruction::Const(index));
+                self.emit(Instruction::Equal);
+                fails.push(self.emit(Instruction::JumpIfFalse(0)));
+            },
+            MatchPattern::MethodCall{name,args,..}=>{
+                let Some(method)=self.scopes.find_method(ty,name) else {
+                    return self.error(format!("no method `{}` found",name));
+                };
+                let this=match self.this_param(method) {
+                    Some(true)=>{
+                        self.emit(Instruction::RefLocal(value));
+                        self.emit(Instruction::Follow);
+                        self.emit(Instruction::RefMut);
+                        1
+                    },
+                    Some(false)=>{
+                        self.emit(Instruction::LoadLocal(value));
+                        self.emit(Instruction::Unref);
+                        1
+                    },
+                    None=>0,
+                };
+                self.compile_all(args)?;
+                let function=self.function_index(method)?;
+                self.emit(Instruction::CallFunction{function,args:args.len() as u16+this});
+                fails.push(self.emit(Instruction::JumpIfFalse(0)));
+            },
+            MatchPattern::Structure{structure,..}=>self.compile_structure(structure,value,fails)?,
+            MatchPattern::Var{var,..}=>{
+                self.emit(Instruction::LoadLocal(value));
+                self.define(*var)?;
+            },
+            MatchPattern::Equal{inner,..}|
+                MatchPattern::NotEqual{inner,..}|
+                MatchPattern::GreaterEqual{inner,..}|
+                MatchPattern::LessEqual{inner,..}|
+                MatchPattern::Greater{inner,..}|
+                MatchPattern::Less{inner,..}=>{
+                self.emit(Instruction::LoadLocal(value));
+                self.compile_expr(inner)?;
+                self.emit(compare(pattern));
+                fails.push(self.emit(Instruction::JumpIfFalse(0)));
+            },
+            MatchPattern::IsType{inner,..}=>{
+                self.emit(Instruction::LoadLocal(value));
+                let ty=self.type_index(inner)?;
+                self.emit(Instruction::IsType(ty));
+                fails.push(self.emit(Instruction::JumpIfFalse(0)));
+            },
+        }
+        return Ok(());
+    }
+    fn compile_structure(&mut self,structure:&MatchPatternStructure<'input>,value:u16,fails:&mut Vec<usize>)->CompileResult<'input> {
+        let (exact,items)=match structure {
+            MatchPatternStructure::Block{exact,block,..}=>(*exact,block),
+            MatchPatternStructure::TypedBlock{exact,ty,block,..}=>{
+                self.emit(Instruction::LoadLocal(value));
+                let ty=self.type_index(ty)?;
+                self.emit(Instruction::IsType(ty));
+                fails.push(self.emit(Instruction::JumpIfFalse(0)));
+                (*exact,block)
+            },
+        };
+        let names=items.iter()
+            .map(|item|match item {
+                MatchPatternStructureItem::Field{name,..}|
+                    MatchPatternStructureItem::NamedField{name,..}|
+                    MatchPatternStructureItem::NamedBlock{name,..}=>(*name,Flags::PrivateImmutable),
+            })
+            .collect();
+        let shape=self.shape(names)?;
+        self.emit(Instruction::LoadLocal(value));
+        self.emit(Instruction::Unref);
+        self.emit(Instruction::HasFields{shape,exact});
+        fails.push(self.emit(Instruction::JumpIfFalse(0)));
+        for item in items {
+            self.emit(Instruction::LoadLocal(value));
+            self.emit(Instruction::Unref);
+            match item {
+                MatchPatternStructureItem::Field{name,var,..}|MatchPatternStructureItem::NamedField{name,var,..}=>{
+                    let id=self.field_id(name)?;
+                    self.emit(Instruction::GetField(id));
+                    self.define(*var)?;
+                },
+                MatchPatternStructureItem::NamedBlock{name,block,..}=>{
+                    let id=self.field_id(name)?;
+                    self.emit(Instruction::GetField(id));
+                    let field=self.new_local()?;
+                    self.emit(Instruction::DefineLocal(field));
+                    self.compile_structure(block,field,fails)?;
+                },
+            }
+        }
+        return Ok(());
+    }
+}
+
+
+/// The variable a narrowed variable narrows.
+fn original(scopes:&Scopes,mut var:Scope)->Scope {
+    while let Some(&VarScopeItem::Narrowed{original,..})=scopes.get(var) {
+        var=original;
+    }
+    return var;
+}
+/// The value of a literal. Number literals are `Int`s, or `Uint`s if they are too large, and float
+/// literals are `DoubleFloat`s until they are used as something else.
+fn literal(data:&Data)->Value {
+    match data {
+        Data::String{s,..}=>Value::String(s.as_ref().into()),
+        Data::GenericNumber{negative,data,..}=>{
+            let digits=data.replace('_',"");
+            match digits.parse::<i64>() {
+                Ok(n) if *negative=>Value::Int(-n),
+                Ok(n)=>Value::Int(n),
+                Err(_)=>Value::Uint(digits.parse().unwrap_or(u64::MAX)),
+            }
+        },
+        Data::GenericFloat{negative,data,..}=>{
+            let n=data.replace('_',"").parse::<f64>().unwrap_or_default();
+            Value::DoubleFloat(if *negative {-n} else {n})
+        },
+        Data::UInt{data,..}=>Value::Uint(*data),
+        Data::Int{data,..}=>Value::Int(*data),
+        Data::Float{data,..}=>Value::Float(*data),
+        Data::LargeFloat{data,..}=>Value::DoubleFloat(*data),
+        Data::Char{data,..}=>Value::Char(*data),
+        Data::Bool{data,..}=>Value::Bool(*data),
+    }
+}
+/// Compiles a program that passed analysis to bytecode.
+pub fn compile<'input>(filename:&'input str,scopes:&Scopes<'input>)->Result<Program<'input>,Error<'input,String>> {
+    let root=Scope(0);
+    let mut compiler=Compiler {
+        filename,
+        scopes,
+        program:Program {
+            filename,
+            functions:vec![Function{name:"<root>".to_string(),kind:FunctionKind::Code(Code::default())}],
+            globals:Vec::new(),
+            variants:Vec::new(),
+            shapes:Vec::new(),
+            types:Vec::new(),
+            constants:Vec::new(),
+            field_names:HashMap::new(),
+            root:0,
+            main:None,
+        },
+        builders:Vec::new(),
+        functions:HashMap::new(),
+        globals:HashMap::new(),
+        variants:HashMap::new(),
+        pending:Vec::new(),
+    };
+    compiler.compile_function(0,root)?;
+    let main=scopes.get(root).and_then(|r|scopes.find_function(r.statements(),"main"));
+    if let Some(main)=main {
+        compiler.program.main=Some(compiler.function_index(main)?);
+    }
+    while let Some(pending)=compiler.pending.pop() {
+        match pending {
+            Pending::Function(index,scope)=>compiler.compile_function(index,scope)?,
+            Pending::Global(index,var)=>compiler.compile_global(index,var)?,
+        }
+    }
+    return Ok(compiler.program);
+}
@@ -0,0 +1,98 @@
+//! A readable listing of a compiled program, for debugging the compiler.
+use std::fmt::{
+    Display,
+    Formatter,
+    Result as FmtResult,
+};
+use crate::{
+    bytecode::*,
+    Value,
+};
+
+
+impl Display for Value {
+    fn fmt(&self,f:&mut Formatter)->FmtResult {
+        match self {
+            Value::None=>write!(f,"none"),
+            Value::Uint(n)=>write!(f,"{}",n),
+            Value::Int(n)=>write!(f,"{}",n),
+            Value::Float(n)=>write!(f,"{}",n),
+            Value::DoubleFloat(n)=>write!(f,"{}",n),
+            Value::Byte(n)=>write!(f,"{}",n),
+            Value::Bool(b)=>write!(f,"{}",b),
+            Value::Char(c)=>write!(f,"{:?}",c),
+            Value::String(s)=>write!(f,"{:?}",s),
+            Value::Object(object)=>write!(f,"<object of {} bytes>",object.size()),
+            Value::Variant{variant,..}=>write!(f,"<variant {}>",variant),
+            Value::Slice(items)=>write!(f,"<slice of {} items>",items.len()),
+            Value::Ref{..}=>write!(f,"<reference>"),
+            Value::Function{function,..}=>write!(f,"<function {}>",function),
+        }
+    }
+}
+impl Program<'_> {
+    fn type_name(&self,ty:&RuntimeType)->String {
+        let list=|items:&mut dyn Iterator<Item=String>,sep:&str|items.collect::<Vec<_>>().join(sep);
+        match ty {
+            RuntimeType::Object{fields,exact}=>format!(
+                "{{{}{}}}",
+                list(&mut fields.iter().map(|(id,ty)|format!("{}:{}",self.field_name(*id),self.type_name(ty))),","),
+                if *exact {""} else {",.."},
+            ),
+            RuntimeType::Variants(variants)=>list(&mut variants.iter().map(|v|self.variants[*v as usize].name.to_string()),"|"),
+            RuntimeType::Union(items)=>list(&mut items.iter().map(|ty|self.type_name(ty)),"|"),
+            RuntimeType::Slice(inner)=>format!("[{}]",self.type_name(inner)),
+            RuntimeType::Ref(inner)=>format!("&{}",self.type_name(inner)),
+            _=>format!("{:?}",ty),
+        }
+    }
+    /// What an operand of `instruction` refers to, if it is worth showing.
+    fn describe(&self,instruction:Instruction)->Option<String> {
+        use Instruction::*;
+        let function=|index:u32|self.functions[index as usize].name.clone();
+        Some(match instruction {
+            Const(index)|Error(index)=>self.constants[index as usize].to_string(),
+            LoadGlobal(index)|StoreGlobal(index)|DefineGlobal(index)|RefGlobal(index)=>self.globals[index as usize].name.to_string(),
+            Function(index)|Closure(index)|CallFunction{function:index,..}=>function(index),
+            Variant{variant,..}=>self.variants[variant as usize].name.to_string(),
+            Object{shape,..}|HasFields{shape,..}=>self.shapes[shape as usize].iter()
+                .map(|(id,flags)|format!("{}:{:?}",self.field_name(*id),flags))
+                .collect::<Vec<_>>()
+                .join(", "),
+            GetField(id)|RefField(id)=>self.field_name(id),
+            IsType(ty)|Coerce(ty)=>self.type_name(&self.types[ty as usize]),
+            _=>return Option::None,
+        })
+    }
+}
+impl Display for Program<'_> {
+    fn fmt(&self,f:&mut Formatter)->FmtResult {
+        for (index,function) in self.functions.iter().enumerate() {
+            let code=match &function.kind {
+                FunctionKind::Code(code)=>code,
+                FunctionKind::Builtin(name)=>{
+                    writeln!(f,"{} {}: builtin `{}`\n",index,function.name,name)?;
+                    continue;
+                },
+                FunctionKind::Variant(variant)=>{
+                    writeln!(f,"{} {}: makes variant {}\n",index,function.name,variant)?;
+                    continue;
+                },
+            };
+            write!(f,"{} {}: {} params, {} locals",index,function.name,code.params,code.locals)?;
+            if !code.captures.is_empty() {
+                write!(f,", captures {:?}",code.captures)?;
+            }
+            writeln!(f)?;
+            for (pc,instruction) in code.instructions.iter().enumerate() {
+                let text=format!("{:?}",instruction);
+                match self.describe(*instruction) {
+                    Some(description)=>writeln!(f,"    {:04} {:<40} ; {}",pc,text,description)?,
+                    None=>writeln!(f,"    {:04} {}",pc,text)?,
+                }
+            }
+            writeln!(f)?;
+        }
+        return Ok(());
+    }
+}
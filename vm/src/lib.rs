@@ -0,0 +1,18 @@
+//! A bytecode compiler and stack machine for the refined AST.
+//!
+//! `compile` lowers a program to a `Program` of compact instructions and `run` runs it. Objects use
+//! the layout from `compiled_idea.md`: every field has a 7 byte id made from its name and a header
+//! byte with its item type and flags. Printing a `Program` disassembles it.
+pub use object::*;
+pub use value::*;
+pub use bytecode::*;
+pub use compile::compile;
+pub use vm::run;
+
+
+mod object;
+mod value;
+mod bytecode;
+mod compile;
+mod vm;
+mod disassemble;
@@ -0,0 +1,260 @@
+//! The runtime layout of objects from `compiled_idea.md`.
+use std::{
+    fmt::{
+        Display,
+        Debug,
+        Formatter,
+        Result as FmtResult,
+    },
+    sync::atomic::{
+        AtomicBool,
+        AtomicU8,
+        AtomicI64,
+        AtomicU64,
+        Ordering,
+    },
+};
+use cppl_ast::refined::Visibility;
+use crate::{
+    Value,
+    Place,
+};
+
+
+/// The 7 byte id of a field, generated from its name at compile time.
+#[derive(Copy,Clone,PartialEq,Eq,Hash)]
+pub struct FieldId(pub [u8;7]);
+impl FieldId {
+    /// The low 56 bits of the 64 bit FNV-1a hash of the name.
+    pub fn new(name:&str)->Self {
+        let mut hash:u64=0xcbf29ce484222325;
+        for byte in name.bytes() {
+            hash^=byte as u64;
+            hash=hash.wrapping_mul(0x100000001b3);
+        }
+        let mut id=[0;7];
+        id.copy_from_slice(&hash.to_le_bytes()[..7]);
+        return FieldId(id);
+    }
+}
+impl Debug for FieldId {
+    fn fmt(&self,f:&mut Formatter)->FmtResult {
+        for byte in self.0.iter().rev() {
+            write!(f,"{:02x}",byte)?;
+        }
+        return Ok(());
+    }
+}
+impl Display for FieldId {
+    fn fmt(&self,f:&mut Formatter)->FmtResult {
+        write!(f,"{:?}",self)
+    }
+}
+/// Who can read and change a field. Stored in the top 3 bits of the field's header byte.
+#[derive(Debug,Copy,Clone,PartialEq)]
+#[repr(u8)]
+pub enum Flags {
+    PrivateImmutable=0,
+    PrivateMutable=1,
+    PublicImmutable=2,
+    PublicMutable=3,
+    /// Public, but only mutable inside of the object.
+    PublicMutableSelf=4,
+    /// Only mutable inside of the object.
+    PrivateMutableSelf=5,
+    /// Only mutable inside of the library of the object.
+    PrivateMutableLib=6,
+    /// Public, but only mutable inside of the library of the object.
+    PublicMutableLib=7,
+}
+impl Flags {
+    pub fn new(public:Option<Visibility>,mutable:Option<Visibility>)->Self {
+        match (public,mutable) {
+            (None,None)=>Flags::PrivateImmutable,
+            (None,Some(Visibility::Local))=>Flags::PrivateMutableSelf,
+            (None,Some(Visibility::Library))=>Flags::PrivateMutableLib,
+            (None,Some(Visibility::Full))=>Flags::PrivateMutable,
+            (Some(_),None)=>Flags::PublicImmutable,
+            (Some(_),Some(Visibility::Local))=>Flags::PublicMutableSelf,
+            (Some(_),Some(Visibility::Library))=>Flags::PublicMutableLib,
+            (Some(_),Some(Visibility::Full))=>Flags::PublicMutable,
+        }
+    }
+    pub fn from_bits(bits:u8)->Option<Self> {
+        match bits {
+            0=>Some(Flags::PrivateImmutable),
+            1=>Some(Flags::PrivateMutable),
+            2=>Some(Flags::PublicImmutable),
+            3=>Some(Flags::PublicMutable),
+            4=>Some(Flags::PublicMutableSelf),
+            5=>Some(Flags::PrivateMutableSelf),
+            6=>Some(Flags::PrivateMutableLib),
+            7=>Some(Flags::PublicMutableLib),
+            _=>None,
+        }
+    }
+}
+/// What kind of data a field holds. Stored in the low 5 bits of the field's header byte.
+#[derive(Debug,Copy,Clone,PartialEq)]
+#[repr(u8)]
+pub enum ItemType {
+    Object=0,
+    Bool=1,
+    Char=2,
+    Byte=3,
+    Int=4,
+    Uint=5,
+    SingleFloat=6,
+    Float=7,
+    Pointer=8,
+    MutablePointer=9,
+    AtomicBool=10,
+    AtomicByte=11,
+    AtomicInt=12,
+    AtomicUint=13,
+    Boxed=14,
+}
+impl ItemType {
+    pub fn from_bits(bits:u8)->Option<Self> {
+        use ItemType::*;
+        const TYPES:[ItemType;15]=[Object,Bool,Char,Byte,Int,Uint,SingleFloat,Float,Pointer,MutablePointer,AtomicBool,AtomicByte,AtomicInt,AtomicUint,Boxed];
+        TYPES.get(bits as usize).copied()
+    }
+}
+/// The data of a field.
+#[derive(Debug)]
+pub enum ObjectFieldType {
+    Object(Object),
+    Bool(bool),
+    Char(char),
+    Byte(u8),
+    Int(i64),
+    Uint(u64),
+    Float(f32),
+    DoubleFloat(f64),
+    Ref(Place),
+    RefMut(Place),
+    AtomicBool(AtomicBool),
+    AtomicByte(AtomicU8),
+    AtomicInt(AtomicI64),
+    AtomicUint(AtomicU64),
+    /// Values without a fixed layout like strings, slices and functions.
+    Boxed(Box<Value>),
+}
+impl ObjectFieldType {
+    pub fn item_type(&self)->ItemType {
+        match self {
+            ObjectFieldType::Object(_)=>ItemType::Object,
+            ObjectFieldType::Bool(_)=>ItemType::Bool,
+            ObjectFieldType::Char(_)=>ItemType::Char,
+            ObjectFieldType::Byte(_)=>ItemType::Byte,
+            ObjectFieldType::Int(_)=>ItemType::Int,
+            ObjectFieldType::Uint(_)=>ItemType::Uint,
+            ObjectFieldType::Float(_)=>ItemType::SingleFloat,
+            ObjectFieldType::DoubleFloat(_)=>ItemType::Float,
+            ObjectFieldType::Ref(_)=>ItemType::Pointer,
+            ObjectFieldType::RefMut(_)=>ItemType::MutablePointer,
+            ObjectFieldType::AtomicBool(_)=>ItemType::AtomicBool,
+            ObjectFieldType::AtomicByte(_)=>ItemType::AtomicByte,
+            ObjectFieldType::AtomicInt(_)=>ItemType::AtomicInt,
+            ObjectFieldType::AtomicUint(_)=>ItemType::AtomicUint,
+            ObjectFieldType::Boxed(_)=>ItemType::Boxed,
+        }
+    }
+    /// How many bytes the data takes up.
+    pub fn size(&self)->u64 {
+        match self {
+            ObjectFieldType::Object(object)=>object.size(),
+            ObjectFieldType::Bool(_)|ObjectFieldType::Byte(_)|ObjectFieldType::AtomicBool(_)|ObjectFieldType::AtomicByte(_)=>1,
+            ObjectFieldType::Char(_)|ObjectFieldType::Float(_)=>4,
+            _=>8,
+        }
+    }
+    pub fn to_value(&self)->Value {
+        match self {
+            ObjectFieldType::Object(object)=>Value::Object(object.clone()),
+            ObjectFieldType::Bool(b)=>Value::Bool(*b),
+            ObjectFieldType::Char(c)=>Value::Char(*c),
+            ObjectFieldType::Byte(n)=>Value::Byte(*n),
+            ObjectFieldType::Int(n)=>Value::Int(*n),
+            ObjectFieldType::Uint(n)=>Value::Uint(*n),
+            ObjectFieldType::Float(n)=>Value::Float(*n),
+            ObjectFieldType::DoubleFloat(n)=>Value::DoubleFloat(*n),
+            ObjectFieldType::Ref(place)=>Value::Ref{place:place.clone(),mutable:false},
+            ObjectFieldType::RefMut(place)=>Value::Ref{place:place.clone(),mutable:true},
+            ObjectFieldType::AtomicBool(b)=>Value::Bool(b.load(Ordering::SeqCst)),
+            ObjectFieldType::AtomicByte(n)=>Value::Byte(n.load(Ordering::SeqCst)),
+            ObjectFieldType::AtomicInt(n)=>Value::Int(n.load(Ordering::SeqCst)),
+            ObjectFieldType::AtomicUint(n)=>Value::Uint(n.load(Ordering::SeqCst)),
+            ObjectFieldType::Boxed(value)=>(**value).clone(),
+        }
+    }
+}
+impl From<Value> for ObjectFieldType {
+    fn from(value:Value)->Self {
+        match value {
+            Value::Object(object)=>ObjectFieldType::Object(object),
+            Value::Bool(b)=>ObjectFieldType::Bool(b),
+            Value::Char(c)=>ObjectFieldType::Char(c),
+            Value::Byte(n)=>ObjectFieldType::Byte(n),
+            Value::Int(n)=>ObjectFieldType::Int(n),
+            Value::Uint(n)=>ObjectFieldType::Uint(n),
+            Value::Float(n)=>ObjectFieldType::Float(n),
+            Value::DoubleFloat(n)=>ObjectFieldType::DoubleFloat(n),
+            Value::Ref{place,mutable:false}=>ObjectFieldType::Ref(place),
+            Value::Ref{place,mutable:true}=>ObjectFieldType::RefMut(place),
+            value=>ObjectFieldType::Boxed(Box::new(value)),
+        }
+    }
+}
+impl Clone for ObjectFieldType {
+    fn clone(&self)->Self {
+        match self {
+            ObjectFieldType::AtomicBool(b)=>ObjectFieldType::AtomicBool(AtomicBool::new(b.load(Ordering::SeqCst))),
+            ObjectFieldType::AtomicByte(n)=>ObjectFieldType::AtomicByte(AtomicU8::new(n.load(Ordering::SeqCst))),
+            ObjectFieldType::AtomicInt(n)=>ObjectFieldType::AtomicInt(AtomicI64::new(n.load(Ordering::SeqCst))),
+            ObjectFieldType::AtomicUint(n)=>ObjectFieldType::AtomicUint(AtomicU64::new(n.load(Ordering::SeqCst))),
+            other=>other.to_value().into(),
+        }
+    }
+}
+#[derive(Debug,Clone)]
+pub struct ObjectField {
+    pub field_id:FieldId,
+    /// The item type in the low 5 bits and the flags in the high 3 bits.
+    pub header:u8,
+    pub item:ObjectFieldType,
+}
+impl ObjectField {
+    pub fn new(field_id:FieldId,flags:Flags,item:ObjectFieldType)->Self {
+        let header=item.item_type() as u8|(flags as u8)<<5;
+        return ObjectField{field_id,header,item};
+    }
+    pub fn flags(&self)->Flags {
+        Flags::from_bits(self.header>>5).expect("Internal compiler error: invalid field flags")
+    }
+    /// Replaces the data of the field, keeping its flags.
+    pub fn set(&mut self,item:ObjectFieldType) {
+        self.header=item.item_type() as u8|self.header&0b1110_0000;
+        self.item=item;
+    }
+}
+#[derive(Debug,Clone,Default)]
+pub struct Object {
+    pub fields:Vec<ObjectField>,
+}
+impl Object {
+    /// The size of the object in bytes, including the size and field count.
+    pub fn size(&self)->u64 {
+        8+2+self.fields.iter().map(|f|7+1+f.item.size()).sum::<u64>()
+    }
+    pub fn field_count(&self)->u16 {
+        self.fields.len() as u16
+    }
+    pub fn field(&self,id:FieldId)->Option<&ObjectField> {
+        self.fields.iter().find(|f|f.field_id==id)
+    }
+    pub fn field_mut(&mut self,id:FieldId)->Option<&mut ObjectField> {
+        self.fields.iter_mut().find(|f|f.field_id==id)
+    }
+}
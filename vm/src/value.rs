@@ -0,0 +1,195 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+};
+use cppl_ast::BuiltinValue;
+use crate::object::*;
+
+
+/// Where a variable's value is stored. References point into these.
+pub type Cell=Rc<RefCell<Value>>;
+
+
+#[derive(Debug,Clone)]
+pub enum Value {
+    /// What a function without a return type returns, and the value of blocks and loops that don't
+    /// have one.
+    None,
+    Uint(u64),
+    Int(i64),
+    Float(f32),
+    DoubleFloat(f64),
+    Byte(u8),
+    Bool(bool),
+    Char(char),
+    String(Rc<str>),
+    Object(Object),
+    /// A value of a named enum variant. `variant` is the index of the variant in the program.
+    Variant {
+        variant:u32,
+        fields:Vec<Value>,
+    },
+    Slice(Vec<Value>),
+    /// References and pointers.
+    Ref {
+        place:Place,
+        mutable:bool,
+    },
+    /// A function, builtin function or enum variant that can be called. Anonymous functions keep
+    /// the cells of the variables they use in `captured`.
+    Function {
+        function:u32,
+        captured:Rc<[Cell]>,
+    },
+}
+impl Value {
+    /// The value of an integer as an `i128` so any integer type fits.
+    pub fn as_integer(&self)->Option<i128> {
+        match self {
+            Value::Uint(n)=>Some(*n as i128),
+            Value::Int(n)=>Some(*n as i128),
+            Value::Byte(n)=>Some(*n as i128),
+            _=>None,
+        }
+    }
+    pub fn as_float(&self)->Option<f64> {
+        match self {
+            Value::Float(n)=>Some(*n as f64),
+            Value::DoubleFloat(n)=>Some(*n),
+            _=>self.as_integer().map(|n|n as f64),
+        }
+    }
+    pub fn is_number(&self)->bool {
+        matches!(self,Value::Uint(_)|Value::Int(_)|Value::Float(_)|Value::DoubleFloat(_)|Value::Byte(_))
+    }
+    /// Converts a number to the number type `like` is.
+    pub fn convert_number(&self,like:&Self)->Self {
+        match (self,like) {
+            (Value::Float(_),Value::Float(_))|(Value::DoubleFloat(_),Value::DoubleFloat(_))=>self.clone(),
+            (_,Value::Float(_))=>Value::Float(self.as_float().unwrap_or_default() as f32),
+            (_,Value::DoubleFloat(_))=>Value::DoubleFloat(self.as_float().unwrap_or_default()),
+            (_,Value::Uint(_))=>Value::Uint(self.as_integer().unwrap_or_default() as u64),
+            (_,Value::Int(_))=>Value::Int(self.as_integer().unwrap_or_default() as i64),
+            (_,Value::Byte(_))=>Value::Byte(self.as_integer().unwrap_or_default() as u8),
+            _=>self.clone(),
+        }
+    }
+    /// Gets the field with the id `id` of an object.
+    pub fn field(&self,id:FieldId)->Option<Value> {
+        match self {
+            Value::Object(object)=>object.field(id).map(|f|f.item.to_value()),
+            _=>None,
+        }
+    }
+}
+impl BuiltinValue for Value {
+    fn none()->Self {
+        Value::None
+    }
+    fn string(s:String)->Self {
+        Value::String(s.into())
+    }
+    fn slice(items:Vec<Self>)->Self {
+        Value::Slice(items)
+    }
+    fn as_str(&self)->Option<&str> {
+        match self {
+            Value::String(s)=>Some(s),
+            _=>None,
+        }
+    }
+}
+impl PartialEq for Value {
+    fn eq(&self,other:&Self)->bool {
+        match (self,other) {
+            (Value::None,Value::None)=>true,
+            (Value::Bool(a),Value::Bool(b))=>a==b,
+            (Value::Char(a),Value::Char(b))=>a==b,
+            (Value::String(a),Value::String(b))=>a==b,
+            (Value::Object(a),Value::Object(b))=>a.fields.len()==b.fields.len()&&a.fields.iter()
+                .all(|f|b.field(f.field_id).is_some_and(|o|f.item.to_value()==o.item.to_value())),
+            (Value::Variant{variant:a,fields:a_fields},Value::Variant{variant:b,fields:b_fields})=>a==b&&a_fields==b_fields,
+            (Value::Slice(a),Value::Slice(b))=>a==b,
+            (Value::Ref{place:a,..},Value::Ref{place:b,..})=>a.read()==b.read(),
+            (Value::Function{function:a,..},Value::Function{function:b,..})=>a==b,
+            (a,b) if a.is_number()&&b.is_number()=>match (a.as_integer(),b.as_integer()) {
+                (Some(a),Some(b))=>a==b,
+                _=>a.as_float()==b.as_float(),
+            },
+            _=>false,
+        }
+    }
+}
+/// A step from a value to a part of it.
+#[derive(Debug,Copy,Clone)]
+pub enum Projection {
+    Field(FieldId),
+    Index(usize),
+}
+/// A place a value is stored: a variable and the fields and items to follow inside of it.
+#[derive(Debug,Clone)]
+pub struct Place {
+    pub cell:Cell,
+    pub path:Vec<Projection>,
+}
+impl Place {
+    pub fn new(value:Value)->Self {
+        Place{cell:Rc::new(RefCell::new(value)),path:Vec::new()}
+    }
+    /// The value at this place. `None` if a field or item it goes through doesn't exist.
+    pub fn read(&self)->Option<Value> {
+        read_value(&self.cell.borrow(),&self.path)
+    }
+    /// Changes the value at this place. Returns `false` if a field or item it goes through doesn't
+    /// exist.
+    pub fn write(&self,new:Value)->bool {
+        write_value(&mut self.cell.borrow_mut(),&self.path,new)
+    }
+}
+
+
+// Fields holding objects are stored inline in their object, so paths through them are followed
+// without making a `Value` out of every object on the way.
+fn read_value(value:&Value,path:&[Projection])->Option<Value> {
+    match (path.split_first(),value) {
+        (None,value)=>Some(value.clone()),
+        (Some((Projection::Field(id),rest)),Value::Object(object))=>read_field(object,*id,rest),
+        (Some((Projection::Index(i),rest)),Value::Slice(items))=>read_value(items.get(*i)?,rest),
+        _=>None,
+    }
+}
+fn read_field(object:&Object,id:FieldId,path:&[Projection])->Option<Value> {
+    let field=object.field(id)?;
+    match (path.split_first(),&field.item) {
+        (None,item)=>Some(item.to_value()),
+        (Some((Projection::Field(id),rest)),ObjectFieldType::Object(object))=>read_field(object,*id,rest),
+        (Some(_),ObjectFieldType::Boxed(value))=>read_value(value,path),
+        _=>None,
+    }
+}
+fn write_value(value:&mut Value,path:&[Projection],new:Value)->bool {
+    match (path.split_first(),value) {
+        (None,value)=>{
+            *value=new;
+            true
+        },
+        (Some((Projection::Field(id),rest)),Value::Object(object))=>write_field(object,*id,rest,new),
+        (Some((Projection::Index(i),rest)),Value::Slice(items))=>match items.get_mut(*i) {
+            Some(item)=>write_value(item,rest,new),
+            None=>false,
+        },
+        _=>false,
+    }
+}
+fn write_field(object:&mut Object,id:FieldId,path:&[Projection],new:Value)->bool {
+    let Some(field)=object.field_mut(id) else {return false};
+    match (path.split_first(),&mut field.item) {
+        (None,_)=>{
+            field.set(new.into());
+            true
+        },
+        (Some((Projection::Field(id),rest)),ObjectFieldType::Object(object))=>write_field(object,*id,rest,new),
+        (Some(_),ObjectFieldType::Boxed(value))=>write_value(value,path,new),
+        _=>false,
+    }
+}
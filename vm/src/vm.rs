@@ -0,0 +1,567 @@
+//! Runs bytecode on a stack machine.
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    rc::Rc,
+};
+use cppl_error::{
+    Location,
+    Error,
+};
+use cppl_ast::call_builtin;
+use crate::{
+    bytecode::*,
+    object::*,
+    value::*,
+};
+
+
+/// How many calls can be nested before the program is stopped, the same as the interpreter.
+const MAX_CALL_DEPTH:usize=1_000;
+
+
+type VmResult<'input,T=()>=Result<T,Error<'input,String>>;
+struct Frame {
+    function:u32,
+    /// The next instruction to run.
+    pc:usize,
+    /// Where the values of this call start on the stack.
+    base:usize,
+    locals:Vec<Cell>,
+    /// The global this call computes the value of.
+    global:Option<u32>,
+}
+struct Vm<'a,'input> {
+    program:&'a Program<'input>,
+    /// The arguments passed to the program.
+    args:&'a [String],
+    stack:Vec<Value>,
+    frames:Vec<Frame>,
+    globals:Vec<Option<Cell>>,
+}
+impl<'a,'input> Vm<'a,'input> {
+    fn error<T>(&self,reason:impl Into<String>)->VmResult<'input,T> {
+        let (start,end)=self.frames.last()
+            .and_then(|f|self.program.code(f.function)?.spans.get(f.pc.saturating_sub(1)).copied())
+            .unwrap_or_default();
+        Err(Error::new_runtime(self.program.filename,start,end,reason.into()))
+    }
+    fn frame(&mut self)->&mut Frame {
+        self.frames.last_mut().expect("Internal compiler error: no frame to run in")
+    }
+    fn push(&mut self,value:Value) {
+        self.stack.push(value);
+    }
+    fn pop(&mut self)->Value {
+        self.stack.pop().expect("Internal compiler error: the stack is empty")
+    }
+    fn pop_n(&mut self,n:usize)->Vec<Value> {
+        self.stack.split_off(self.stack.len()-n)
+    }
+    /// Starts a call of `function`. Functions with code get a new frame and the others are run
+    /// right away and push their result.
+    fn call(&mut self,function:u32,captured:&[Cell],args:Vec<Value>)->VmResult<'input> {
+        match &self.program.functions[function as usize].kind {
+            FunctionKind::Code(code)=>{
+                if self.frames.len()>MAX_CALL_DEPTH {
+                    return self.error(format!("stack overflow: more than {} calls are nested",MAX_CALL_DEPTH));
+                }
+                let mut locals=args.into_iter()
+                    .map(|a|Rc::new(RefCell::new(a)))
+                    .collect::<Vec<_>>();
+                locals.resize_with(code.locals as usize,||Rc::new(RefCell::new(Value::None)));
+                for ((_,inner),cell) in code.captures.iter().zip(captured) {
+                    locals[*inner as usize]=cell.clone();
+                }
+                let base=self.stack.len();
+                self.frames.push(Frame{function,pc:0,base,locals,global:None});
+            },
+            FunctionKind::Builtin(name)=>match call_builtin(name,args,self.args) {
+                Ok(value)=>self.push(value),
+                Err(reason)=>return self.error(reason),
+            },
+            FunctionKind::Variant(variant)=>{
+                let value=self.program.coerce(Value::Variant{variant:*variant,fields:args},&RuntimeType::Any);
+                self.push(value);
+            },
+        }
+        return Ok(());
+    }
+    /// Calls `function` and runs until it returns.
+    fn run_function(&mut self,function:u32,args:Vec<Value>)->VmResult<'input,Value> {
+        let depth=self.frames.len();
+        self.call(function,&[],args)?;
+        if self.frames.len()>depth {
+            return self.execute();
+        }
+        return Ok(self.pop());
+    }
+    /// The cell of the global `index`. `None` if its initializer has to run first, in which case
+    /// the current instruction runs again after it.
+    fn global(&mut self,index:u32)->VmResult<'input,Option<Cell>> {
+        if let Some(cell)=&self.globals[index as usize] {
+            return Ok(Some(cell.clone()));
+        }
+        let global=&self.program.globals[index as usize];
+        let Some(init)=global.init else {
+            return self.error(format!("`{}` is used before it is defined",global.name));
+        };
+        self.frame().pc-=1;
+        self.call(init,&[],Vec::new())?;
+        self.frame().global=Some(index);
+        return Ok(None);
+    }
+    fn read(&self,place:&Place)->VmResult<'input,Value> {
+        match place.read() {
+            Some(value)=>Ok(value),
+            None=>self.error("the value this refers to does not exist anymore"),
+        }
+    }
+    /// Follows references until a value that isn't one is found.
+    fn unref(&self,mut value:Value)->VmResult<'input,Value> {
+        while let Value::Ref{place,..}=value {
+            value=self.read(&place)?;
+        }
+        return Ok(value);
+    }
+    /// Follows references stored at `place` until a place that doesn't hold one is found.
+    fn follow(&self,mut place:Place)->Place {
+        while let Some(Value::Ref{place:inner,..})=place.read() {
+            place=inner;
+        }
+        return place;
+    }
+    fn pop_ref(&mut self)->(Place,bool) {
+        match self.pop() {
+            Value::Ref{place,mutable}=>(place,mutable),
+            value=>(Place::new(value),false),
+        }
+    }
+    fn index(&self,index:&Value,len:usize)->VmResult<'input,usize> {
+        let Some(i)=index.as_integer() else {
+            return self.error("indexes have to be integers");
+        };
+        if i<0||i>=len as i128 {
+            return self.error(format!("index out of bounds: the length is {} but the index is {}",len,i));
+        }
+        return Ok(i as usize);
+    }
+    fn field(&self,value:Value,id:FieldId)->VmResult<'input,Value> {
+        match self.unref(value)?.field(id) {
+            Some(value)=>Ok(value),
+            None=>self.error(format!("no field `{}`",self.program.field_name(id))),
+        }
+    }
+    fn arithmetic(&self,op:Instruction,left:Value,right:Value)->VmResult<'input,Value> {
+        let left=self.unref(left)?;
+        let right=self.unref(right)?;
+        let symbol=symbol(op);
+        // The amount to shift by doesn't have to be the same type as what is shifted.
+        if let Instruction::ShiftLeft|Instruction::ShiftRight=op {
+            let (Some(n),Some(amount))=(left.as_integer(),right.as_integer()) else {
+                return self.error(format!("`{}` can only be used on integers",symbol));
+            };
+            let bits=if let Value::Byte(_)=left {8} else {64};
+            let amount=amount.rem_euclid(bits) as u32;
+            let n=if let Instruction::ShiftLeft=op {n<<amount} else {n>>amount};
+            return Ok(integer(&left,n));
+        }
+        let (left,right)=unify(left,right);
+        if let (Instruction::Add,Value::String(a),Value::String(b))=(op,&left,&right) {
+            return Ok(Value::String(format!("{}{}",a,b).into()));
+        }
+        if let (Some(a),Some(b))=(left.as_integer(),right.as_integer()) {
+            if matches!(op,Instruction::Div|Instruction::Mod)&&b==0 {
+                return self.error("attempt to divide by zero");
+            }
+            // Every integer fits in an `i128`, and truncating the result back wraps it.
+            let n=match op {
+                Instruction::Add=>a+b,
+                Instruction::Sub=>a-b,
+                Instruction::Mul=>a.wrapping_mul(b),
+                Instruction::Div=>a/b,
+                Instruction::Mod=>a%b,
+                Instruction::BitAnd=>a&b,
+                Instruction::BitOr=>a|b,
+                _=>a^b,
+            };
+            return Ok(integer(&left,n));
+        }
+        if let (Value::Float(_)|Value::DoubleFloat(_),Some(a),Some(b))=(&left,left.as_float(),right.as_float()) {
+            let n=match op {
+                Instruction::Add=>a+b,
+                Instruction::Sub=>a-b,
+                Instruction::Mul=>a*b,
+                Instruction::Div=>a/b,
+                Instruction::Mod=>a%b,
+                _=>return self.error(format!("`{}` can only be used on integers",symbol)),
+            };
+            return Ok(Value::DoubleFloat(n).convert_number(&left));
+        }
+        return self.error(format!("`{}` can not be used on these values",symbol));
+    }
+    fn compare(&self,left:Value,right:Value)->VmResult<'input,Ordering> {
+        let (left,right)=unify(self.unref(left)?,self.unref(right)?);
+        let ordering=match (&left,&right) {
+            (Value::Char(a),Value::Char(b))=>Some(a.cmp(b)),
+            (Value::String(a),Value::String(b))=>Some(a.cmp(b)),
+            (Value::Bool(a),Value::Bool(b))=>Some(a.cmp(b)),
+            _=>match (left.as_integer(),right.as_integer()) {
+                (Some(a),Some(b))=>Some(a.cmp(&b)),
+                _=>left.as_float().zip(right.as_float()).and_then(|(a,b)|a.partial_cmp(&b)),
+            },
+        };
+        match ordering {
+            Some(ordering)=>Ok(ordering),
+            None=>self.error("these values can not be compared"),
+        }
+    }
+    /// Runs instructions until the frame that is on top when this is called returns.
+    fn execute(&mut self)->VmResult<'input,Value> {
+        use Instruction::*;
+        let program=self.program;
+        let depth=self.frames.len();
+        loop {
+            let frame=self.frame();
+            let pc=frame.pc;
+            frame.pc+=1;
+            let code=program.code(frame.function).expect("Internal compiler error: running a function without code");
+            match code.instructions[pc] {
+                Const(index)=>self.push(program.constants[index as usize].clone()),
+                None=>self.push(Value::None),
+                Pop=>{self.pop();},
+                Dup=>{
+                    let value=self.stack.last().expect("Internal compiler error: the stack is empty").clone();
+                    self.push(value);
+                },
+                Swap=>{
+                    let len=self.stack.len();
+                    self.stack.swap(len-1,len-2);
+                },
+                Slide(n)=>{
+                    let top=self.pop();
+                    self.stack.truncate(self.stack.len()-n as usize);
+                    self.push(top);
+                },
+                LoadLocal(slot)=>{
+                    let value=self.frame().locals[slot as usize].borrow().clone();
+                    self.push(value);
+                },
+                StoreLocal(slot)=>{
+                    let value=self.pop();
+                    *self.frame().locals[slot as usize].borrow_mut()=value;
+                },
+                DefineLocal(slot)=>{
+                    let value=self.pop();
+                    self.frame().locals[slot as usize]=Rc::new(RefCell::new(value));
+                },
+                LoadGlobal(index)=>if let Some(cell)=self.global(index)? {
+                    let value=cell.borrow().clone();
+                    self.push(value);
+                },
+                StoreGlobal(index)=>if let Some(cell)=self.global(index)? {
+                    *cell.borrow_mut()=self.pop();
+                },
+                DefineGlobal(index)=>{
+                    let value=self.pop();
+                    self.globals[index as usize]=Some(Rc::new(RefCell::new(value)));
+                },
+                Function(function)=>self.push(Value::Function{function,captured:Rc::new([])}),
+                Closure(function)=>{
+                    let captures=&program.code(function).expect("Internal compiler error: anonymous function without code").captures;
+                    let frame=self.frame();
+                    let captured=captures.iter().map(|(outer,_)|frame.locals[*outer as usize].clone()).collect();
+                    self.push(Value::Function{function,captured});
+                },
+                Variant{variant,fields}=>{
+                    let fields=self.pop_n(fields as usize);
+                    let value=program.coerce(Value::Variant{variant,fields},&RuntimeType::Any);
+                    self.push(value);
+                },
+                Object{shape,fields}=>{
+                    let values=self.pop_n(fields as usize);
+                    let fields=program.shapes[shape as usize].iter()
+                        .zip(values)
+                        .map(|((id,flags),value)|ObjectField::new(*id,*flags,value.into()))
+                        .collect();
+                    self.push(Value::Object(crate::Object{fields}));
+                },
+                List(n)=>{
+                    let items=self.pop_n(n as usize);
+                    self.push(Value::Slice(items));
+                },
+                Call(n)=>{
+                    let args=self.pop_n(n as usize);
+                    let callee=self.pop();
+                    match self.unref(callee)? {
+                        Value::Function{function,captured}=>self.call(function,&captured,args)?,
+                        _=>return self.error("only functions can be called"),
+                    }
+                },
+                CallFunction{function,args}=>{
+                    let args=self.pop_n(args as usize);
+                    self.call(function,&[],args)?;
+                },
+                Return=>{
+                    let value=self.pop();
+                    let frame=self.frames.pop().expect("Internal compiler error: no frame to return from");
+                    self.stack.truncate(frame.base);
+                    if self.frames.len()<depth {
+                        return Ok(value);
+                    }
+                    match frame.global {
+                        Some(index)=>self.globals[index as usize]=Some(Rc::new(RefCell::new(value))),
+                        Option::None=>self.push(value),
+                    }
+                },
+                Jump(target)=>self.frame().pc=target as usize,
+                JumpIfFalse(target)=>{
+                    let value=self.pop();
+                    if !matches!(self.unref(value)?,Value::Bool(true)) {
+                        self.frame().pc=target as usize;
+                    }
+                },
+                GetField(id)=>{
+                    let from=self.pop();
+                    let value=self.field(from,id)?;
+                    self.push(value);
+                },
+                Index=>{
+                    let index=self.pop();
+                    let from=self.pop();
+                    let Value::Slice(mut items)=self.unref(from)? else {
+                        return self.error("only slices can be indexed");
+                    };
+                    let index=self.index(&index,items.len())?;
+                    self.push(items.swap_remove(index));
+                },
+                Slice{start,end}=>{
+                    let range_end=if end {Some(self.pop())} else {Option::None};
+                    let range_start=if start {Some(self.pop())} else {Option::None};
+                    let from=self.pop();
+                    let Value::Slice(items)=self.unref(from)? else {
+                        return self.error("only slices can be sliced");
+                    };
+                    let mut bounds=[0,items.len() as i128];
+                    for (bound,value) in bounds.iter_mut().zip([range_start,range_end]) {
+                        if let Some(value)=value {
+                            match value.as_integer() {
+                                Some(n)=>*bound=n,
+                                Option::None=>return self.error("indexes have to be integers"),
+                            }
+                        }
+                    }
+                    let [start,end]=bounds;
+                    if start<0||start>end||end>items.len() as i128 {
+                        return self.error(format!("range {}..{} is out of bounds for a slice of length {}",start,end,items.len()));
+                    }
+                    self.push(Value::Slice(items[start as usize..end as usize].to_vec()));
+                },
+                Len=>{
+                    let value=self.pop();
+                    match self.unref(value)? {
+                        Value::Slice(items)=>self.push(Value::Int(items.len() as i64)),
+                        _=>return self.error("only slices can be iterated over"),
+                    }
+                },
+                RefLocal(slot)=>{
+                    let cell=self.frame().locals[slot as usize].clone();
+                    self.push(Value::Ref{place:Place{cell,path:Vec::new()},mutable:false});
+                },
+                RefGlobal(index)=>if let Some(cell)=self.global(index)? {
+                    self.push(Value::Ref{place:Place{cell,path:Vec::new()},mutable:false});
+                },
+                RefField(id)=>{
+                    let (place,mutable)=self.pop_ref();
+                    let mut place=self.follow(place);
+                    place.path.push(Projection::Field(id));
+                    self.push(Value::Ref{place,mutable});
+                },
+                RefIndex=>{
+                    let index=self.pop();
+                    let (place,mutable)=self.pop_ref();
+                    let mut place=self.follow(place);
+                    let len=match self.read(&place)? {
+                        Value::Slice(items)=>items.len(),
+                        _=>return self.error("only slices can be indexed"),
+                    };
+                    let index=self.index(&index,len)?;
+                    place.path.push(Projection::Index(index));
+                    self.push(Value::Ref{place,mutable});
+                },
+                RefTemp=>{
+                    let value=self.pop();
+                    self.push(Value::Ref{place:Place::new(value),mutable:false});
+                },
+                RefMut=>{
+                    let (place,_)=self.pop_ref();
+                    self.push(Value::Ref{place,mutable:true});
+                },
+                Follow=>{
+                    let (place,mutable)=self.pop_ref();
+                    let place=self.follow(place);
+                    self.push(Value::Ref{place,mutable});
+                },
+                Load=>{
+                    let (place,_)=self.pop_ref();
+                    let value=self.read(&place)?;
+                    self.push(value);
+                },
+                Store=>{
+                    let value=self.pop();
+                    let (place,_)=self.pop_ref();
+                    // Number literals take the type of what they are assigned to.
+                    let value=match place.read() {
+                        Some(old) if old.is_number()&&value.is_number()=>value.convert_number(&old),
+                        _=>value,
+                    };
+                    if !place.write(value) {
+                        return self.error("the value this refers to does not exist anymore");
+                    }
+                },
+                Deref=>match self.pop() {
+                    Value::Ref{place,..}=>{
+                        let value=self.read(&place)?;
+                        self.push(value);
+                    },
+                    _=>return self.error("only references and pointers can be dereferenced"),
+                },
+                Unref=>{
+                    let value=self.pop();
+                    let value=self.unref(value)?;
+                    self.push(value);
+                },
+                op@(Add|Sub|Mul|Div|Mod|BitAnd|BitOr|BitXor|ShiftLeft|ShiftRight)=>{
+                    let right=self.pop();
+                    let left=self.pop();
+                    let value=self.arithmetic(op,left,right)?;
+                    self.push(value);
+                },
+                Negate=>{
+                    let value=self.pop();
+                    let value=match self.unref(value)? {
+                        Value::Int(n)=>Value::Int(n.wrapping_neg()),
+                        Value::Uint(n)=>Value::Uint(n.wrapping_neg()),
+                        Value::Byte(n)=>Value::Byte(n.wrapping_neg()),
+                        Value::Float(n)=>Value::Float(-n),
+                        Value::DoubleFloat(n)=>Value::DoubleFloat(-n),
+                        _=>return self.error("only numbers can be negated"),
+                    };
+                    self.push(value);
+                },
+                Not=>{
+                    let value=self.pop();
+                    let value=!matches!(self.unref(value)?,Value::Bool(true));
+                    self.push(Value::Bool(value));
+                },
+                op@(Equal|NotEqual)=>{
+                    let right=self.pop();
+                    let left=self.pop();
+                    let (left,right)=unify(self.unref(left)?,self.unref(right)?);
+                    self.push(Value::Bool((left==right)==(op==Equal)));
+                },
+                op@(GreaterEqual|LessEqual|Greater|Less)=>{
+                    let right=self.pop();
+                    let left=self.pop();
+                    let ordering=self.compare(left,right)?;
+                    self.push(Value::Bool(match op {
+                        GreaterEqual=>ordering!=Ordering::Less,
+                        LessEqual=>ordering!=Ordering::Greater,
+                        Greater=>ordering==Ordering::Greater,
+                        _=>ordering==Ordering::Less,
+                    }));
+                },
+                IsType(ty)=>{
+                    let value=self.pop();
+                    let is=program.is_type(&value,&program.types[ty as usize]);
+                    self.push(Value::Bool(is));
+                },
+                Coerce(ty)=>{
+                    let value=self.pop();
+                    let value=program.coerce(value,&program.types[ty as usize]);
+                    self.push(value);
+                },
+                HasFields{shape,exact}=>{
+                    let shape=&program.shapes[shape as usize];
+                    let has=match self.pop() {
+                        Value::Object(object)=>{
+                            (!exact||object.fields.len()==shape.len())&&shape.iter().all(|(id,_)|object.field(*id).is_some())
+                        },
+                        _=>false,
+                    };
+                    self.push(Value::Bool(has));
+                },
+                Error(message)=>return match &program.constants[message as usize] {
+                    Value::String(message)=>self.error(message.as_ref()),
+                    _=>self.error("the program stopped"),
+                },
+            }
+        }
+    }
+}
+
+
+fn symbol(op:Instruction)->&'static str {
+    match op {
+        Instruction::Add=>"+",
+        Instruction::Sub=>"-",
+        Instruction::Mul=>"*",
+        Instruction::Div=>"/",
+        Instruction::Mod=>"%",
+        Instruction::BitAnd=>"&",
+        Instruction::BitOr=>"|",
+        Instruction::BitXor=>"^",
+        Instruction::ShiftLeft=>"<<",
+        _=>">>",
+    }
+}
+/// An integer of the same type as `like`, wrapping if it doesn't fit.
+fn integer(like:&Value,n:i128)->Value {
+    match like {
+        Value::Uint(_)=>Value::Uint(n as u64),
+        Value::Byte(_)=>Value::Byte(n as u8),
+        _=>Value::Int(n as i64),
+    }
+}
+/// Converts two numbers to the same type. They can only be different when one of them is a number
+/// literal, which is an `Int` or a `DoubleFloat` until it is used as something else.
+fn unify(left:Value,right:Value)->(Value,Value) {
+    if !left.is_number()||!right.is_number()||std::mem::discriminant(&left)==std::mem::discriminant(&right) {
+        return (left,right);
+    }
+    let to_left=match (&left,&right) {
+        (Value::Float(_),Value::DoubleFloat(_))=>true,
+        (Value::DoubleFloat(_),Value::Float(_))=>false,
+        (_,Value::Int(_))=>true,
+        (Value::Int(_),_)=>false,
+        (Value::Float(_)|Value::DoubleFloat(_),_)=>true,
+        _=>false,
+    };
+    if to_left {
+        let right=right.convert_number(&left);
+        return (left,right);
+    } else {
+        return (left.convert_number(&right),right);
+    }
+}
+/// Runs the `main` function of a compiled program, after the statements in the root scope. `args`
+/// are passed to `main` and the exit code is what it returns.
+pub fn run<'input>(program:&Program<'input>,args:&[String])->Result<i32,Error<'input,String>> {
+    let mut vm=Vm {
+        program,
+        args,
+        stack:Vec::new(),
+        frames:Vec::new(),
+        globals:vec![Option::None;program.globals.len()],
+    };
+    vm.run_function(program.root,Vec::new())?;
+    let Some(main)=program.main else {
+        return Err(Error::new_runtime(program.filename,Location::default(),Location::default(),"there is no `main` function to run"));
+    };
+    let main_args=match program.code(main) {
+        Some(code) if code.params>0=>vec![Value::Slice(args.iter().map(|a|Value::String(a.as_str().into())).collect())],
+        _=>Vec::new(),
+    };
+    let value=vm.run_function(main,main_args)?;
+    return Ok(value.as_integer().map_or(0,|n|n as i32));
+}
@@ -0,0 +1,181 @@
+//! Programs compiled to bytecode and run with the VM, and the exit codes or errors they have to end
+//! with.
+use cppl_parser::parse;
+use cppl_ast::analyze;
+use cppl_ast::refined::Visibility;
+use cppl_vm::{
+    compile,
+    run,
+    FieldId,
+    Flags,
+    ObjectField,
+    ObjectFieldType,
+};
+
+
+/// Compiles and runs `source`, which has to pass analysis, with the program arguments `args`. The
+/// error is the reason of the runtime error.
+fn run_source(source:&str,args:&[&str])->Result<i32,String> {
+    let (parsed,parse_errors)=parse("test.cppl",source);
+    assert!(parse_errors.is_empty(),"{:?}",parse_errors);
+    let (scopes,_)=match analyze("test.cppl",parsed) {
+        Ok(analyzed)=>analyzed,
+        Err(results)=>panic!("{:?}",results.errors.into_iter().map(|e|e.reason).collect::<Vec<_>>()),
+    };
+    let program=compile("test.cppl",&scopes).map_err(|e|e.reason)?;
+    let args=args.iter().map(|a|a.to_string()).collect::<Vec<_>>();
+    return run(&program,&args).map_err(|e|e.reason);
+}
+
+
+#[test]
+fn control_flow() {
+    assert_eq!(run_source("fn main[]:Int {
+    mut total:=0
+    for x in [1,2,3,4] {
+        if x=3 {
+            continue
+        }
+        total+<-x
+    }
+    mut i:=0
+    while i<5 {
+        i+<-1
+    }
+    found:=loop {
+        break 6
+    }
+    size:=match total {
+        7=>1,
+        _=>2,
+    }
+    return total+i+found+size
+}
+",&[]),Ok(19));
+}
+#[test]
+fn functions_and_objects() {
+    assert_eq!(run_source("type P<-{pub mut v:Int}
+fn adder[k:Int]:fn[a:Int]:Int {
+    return fn[a:Int]:Int {
+        return a+k
+    }
+}
+fn bump[p:&mut P] {
+    p.v<-p.v+1
+}
+fn main[]:Int {
+    mut p:P={pub mut v<-1}
+    bump(&mut p)
+    add:=adder(10)
+    return add(p.v)
+}
+",&[]),Ok(12));
+}
+/// The value of an assignment is worked out before the place it is assigned to.
+#[test]
+fn assignment_order() {
+    assert_eq!(run_source("fn main[]:Int {
+    mut xs:=[1,2]
+    mut i:=0
+    grow:=fn[]:Int {
+        i<-1
+        return 5
+    }
+    xs[i]<-grow()
+    return xs[0]*10+xs[1]
+}
+",&[]),Ok(15));
+}
+#[test]
+fn interface_dispatch() {
+    assert_eq!(run_source("interface Shape {
+    fn area[this]:Int
+    fn scaled[this,by:Int]:Int {
+        return this.area()*by
+    }
+}
+type Square<-{pub side:Int}
+type Rect<-{pub w:Int,pub h:Int}
+impl Shape for Square {
+    fn area[this]:Int {
+        s:=this.side
+        return s*s
+    }
+}
+impl Shape for Rect {
+    fn area[this]:Int {
+        w:=this.w
+        h:=this.h
+        return w*h
+    }
+    fn scaled[this,by:Int]:Int {
+        return 0
+    }
+}
+fn total[s:Shape]:Int {
+    return s.area()+s.scaled(2)
+}
+fn main[]:Int {
+    sq:Square={pub side<-3}
+    r:Rect={pub w<-2,pub h<-5}
+    return total(sq)+total(r)
+}
+",&[]),Ok(37));
+}
+#[test]
+fn builtins() {
+    assert_eq!(run_source("import std::env::Arguments
+fn main[args:[String]]:Int {
+    mut count:=0
+    for arg in Arguments() {
+        count+<-1
+    }
+    m:Int=match args[1] {
+        \"y\"=>7,
+        other=>8,
+    }
+    return count*10+m
+}
+",&["x","y"]),Ok(27));
+}
+#[test]
+fn runtime_errors() {
+    assert_eq!(
+        run_source("fn main[]:Int {\n    xs:=[1,2]\n    return xs[2]\n}\n",&[]),
+        Err("index out of bounds: the length is 2 but the index is 2".to_string()),
+    );
+    assert_eq!(
+        run_source("fn main[]:Int {\n    z:=0\n    return 1/z\n}\n",&[]),
+        Err("attempt to divide by zero".to_string()),
+    );
+}
+#[test]
+fn deep_recursion() {
+    let deep="fn deep[n:Int]:Int {
+    if n=0 {
+        return 0
+    }
+    return deep(n-1)+1
+}
+fn main[]:Int {
+    return deep(N)-890
+}
+";
+    assert_eq!(run_source(&deep.replace("N","900"),&[]),Ok(10));
+    assert_eq!(run_source(&deep.replace("N","2000"),&[]),Err("stack overflow: more than 1000 calls are nested".to_string()));
+}
+#[test]
+fn field_flags() {
+    let visibilities=[None,Some(Visibility::Local),Some(Visibility::Library),Some(Visibility::Full)];
+    let mut seen=Vec::new();
+    for public in [None,Some(Visibility::Full)] {
+        for mutable in visibilities {
+            let flags=Flags::new(public,mutable);
+            assert!(!seen.contains(&flags),"{:?} is used twice",flags);
+            seen.push(flags);
+            let field=ObjectField::new(FieldId::new("a"),flags,ObjectFieldType::Int(-1));
+            assert_eq!(field.flags(),flags);
+        }
+    }
+}